@@ -0,0 +1,219 @@
+// What every replica does for every message: the actor's handlers for each kind of message,
+// the codecs that carry them and the quorum checks behind each promise and accept, then a
+// recorded run of 10k deliveries replayed end to end. `cargo bench --bench hot_paths`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use paxos_rs::codec::{Bincode, Cbor, Json, WireCodec};
+use paxos_rs::paxos::{Command, PaxosActor, PaxosMsg, PaxosState, RoundIdentifier};
+use paxos_rs::quorum::{Grid, Majority, QuorumSystem, Votes};
+use stateright::actor::register::RegisterMsg;
+use stateright::actor::{Actor, Command as ActorCommand, Id, Out};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, VecDeque};
+
+type Msg = RegisterMsg<u64, char, PaxosMsg<char>>;
+
+const SERVERS: usize = 3;
+const CLIENT: usize = SERVERS;
+const TRACE_LEN: usize = 10_000;
+
+fn actors() -> Vec<PaxosActor<char>> {
+    (0..SERVERS)
+        .map(|i| {
+            let peers = (0..SERVERS).filter(|j| *j != i).map(Id::from).collect();
+            PaxosActor::new(peers)
+        })
+        .collect()
+}
+
+fn start(actor: &PaxosActor<char>, id: usize) -> PaxosState<char> {
+    actor.on_start(Id::from(id), &mut Out::new())
+}
+
+// the state a message leaves the actor in, and the messages it sends on
+fn deliver(
+    actor: &PaxosActor<char>,
+    id: usize,
+    state: &PaxosState<char>,
+    src: usize,
+    msg: Msg,
+) -> (PaxosState<char>, Vec<(Id, Msg)>) {
+    let mut state = Cow::Borrowed(state);
+    let mut out = Out::new();
+    actor.on_msg(Id::from(id), &mut state, Id::from(src), msg, &mut out);
+    let sent = out
+        .into_iter()
+        .filter_map(|command| match command {
+            ActorCommand::Send(dst, msg) => Some((dst, msg)),
+            _ => None,
+        })
+        .collect();
+    (state.into_owned(), sent)
+}
+
+fn on_msg(c: &mut Criterion) {
+    let actors = actors();
+    let fresh = start(&actors[0], 0);
+    let rid = RoundIdentifier::new(1, Id::from(0));
+    let client = Id::from(CLIENT);
+    let value = Command::Put('A');
+
+    // server 0 proposing a put, then through phase 1
+    let (proposing, _) = deliver(&actors[0], 0, &fresh, CLIENT, RegisterMsg::Put(1, 'A'));
+    let promise: Msg = RegisterMsg::Internal(PaxosMsg::Promise(1, client, 0, rid, None));
+    let (promised, _) = deliver(&actors[0], 0, &proposing, 1, promise.clone());
+    let (accepting, _) = deliver(&actors[0], 0, &promised, 2, promise.clone());
+    // server 1 as the acceptor of that round
+    let acceptor = start(&actors[1], 1);
+    let prepare = RegisterMsg::Internal(PaxosMsg::Prepare(1, client, 0, rid));
+    let (prepared, _) = deliver(&actors[1], 1, &acceptor, 0, prepare.clone());
+
+    let cases: Vec<(&str, &PaxosState<char>, usize, usize, Msg)> = vec![
+        ("put", &fresh, 0, CLIENT, RegisterMsg::Put(1, 'A')),
+        ("prepare", &acceptor, 1, 0, prepare),
+        ("promise", &proposing, 0, 1, promise),
+        (
+            "accept",
+            &prepared,
+            1,
+            0,
+            RegisterMsg::Internal(PaxosMsg::Accept(1, client, 0, rid, value.clone())),
+        ),
+        (
+            "accepted",
+            &accepting,
+            0,
+            1,
+            RegisterMsg::Internal(PaxosMsg::Accepted(1, client, 0, rid, value.clone())),
+        ),
+        (
+            "decided",
+            &fresh,
+            0,
+            1,
+            RegisterMsg::Internal(PaxosMsg::Decided(0, rid, value)),
+        ),
+    ];
+    let mut group = c.benchmark_group("on_msg");
+    for (name, state, id, src, msg) in cases {
+        let actor = &actors[id];
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut state = Cow::Borrowed(state);
+                let mut out = Out::new();
+                actor.on_msg(
+                    Id::from(id),
+                    &mut state,
+                    Id::from(src),
+                    msg.clone(),
+                    &mut out,
+                );
+                black_box((state, out));
+            })
+        });
+    }
+    group.finish();
+}
+
+fn codecs(c: &mut Criterion) {
+    let rid = RoundIdentifier::new(3, Id::from(1));
+    let batch = Command::Batch(('A'..='P').collect());
+    let msg: Msg = RegisterMsg::Internal(PaxosMsg::Accept(7, Id::from(3), 12, rid, batch));
+    fn bench<C: WireCodec>(c: &mut Criterion, name: &str, msg: &Msg) {
+        let bytes = C::encode(msg).unwrap();
+        let mut group = c.benchmark_group(name);
+        group.bench_function("encode", |b| b.iter(|| C::encode(black_box(msg)).unwrap()));
+        group.bench_function("decode", |b| {
+            b.iter(|| C::decode::<Msg>(black_box(&bytes)).unwrap())
+        });
+        group.finish();
+    }
+    bench::<Json>(c, "json", &msg);
+    bench::<Bincode>(c, "bincode", &msg);
+    bench::<Cbor>(c, "cbor", &msg);
+}
+
+fn quorums(c: &mut Criterion) {
+    let acceptors: Vec<Id> = (0..9).map(Id::from).collect();
+    let answered: BTreeSet<Id> = acceptors.iter().copied().step_by(2).collect();
+    let majority = Majority::new(acceptors.len());
+    let grid = Grid::with_row_len(&acceptors, 3);
+    let mut group = c.benchmark_group("quorum");
+    group.bench_function("majority", |b| {
+        b.iter(|| majority.is_phase1_quorum(black_box(&answered)))
+    });
+    group.bench_function("grid", |b| {
+        b.iter(|| grid.is_phase1_quorum(black_box(&answered)))
+    });
+    group.bench_function("votes", |b| {
+        b.iter(|| {
+            let mut votes = Votes::default();
+            for id in &answered {
+                votes.insert(&acceptors, *id);
+            }
+            majority.is_phase1_quorum(&votes.ids(&acceptors))
+        })
+    });
+    group.finish();
+}
+
+// One client putting values through the servers in turn, each put sent once the last was
+// acknowledged, with every message delivered in the order sent. Timers never fire.
+fn record() -> Vec<(usize, usize, Msg)> {
+    let actors = actors();
+    let mut states: Vec<_> = (0..SERVERS).map(|i| start(&actors[i], i)).collect();
+    let mut queue = VecDeque::new();
+    let mut trace = Vec::with_capacity(TRACE_LEN);
+    let mut request_id = 0;
+    while trace.len() < TRACE_LEN {
+        let (src, dst, msg) = match queue.pop_front() {
+            Some(delivery) => delivery,
+            None => {
+                request_id += 1;
+                let value = (b'A' + (request_id % 26) as u8) as char;
+                let server = request_id as usize % SERVERS;
+                (CLIENT, server, RegisterMsg::Put(request_id, value))
+            }
+        };
+        trace.push((src, dst, msg.clone()));
+        if dst == CLIENT {
+            continue;
+        }
+        let (state, sent) = deliver(&actors[dst], dst, &states[dst], src, msg);
+        states[dst] = state;
+        for (to, msg) in sent {
+            queue.push_back((dst, usize::from(to), msg));
+        }
+    }
+    trace
+}
+
+fn replay(c: &mut Criterion) {
+    let trace = record();
+    let actors = actors();
+    c.bench_function("replay 10k messages", |b| {
+        b.iter(|| {
+            let mut states: Vec<_> = (0..SERVERS).map(|i| start(&actors[i], i)).collect();
+            for (src, dst, msg) in &trace {
+                if *dst == CLIENT {
+                    continue;
+                }
+                let mut state = Cow::Borrowed(&states[*dst]);
+                let mut out = Out::new();
+                actors[*dst].on_msg(
+                    Id::from(*dst),
+                    &mut state,
+                    Id::from(*src),
+                    msg.clone(),
+                    &mut out,
+                );
+                if let Cow::Owned(state) = state {
+                    states[*dst] = state;
+                }
+            }
+            black_box(states)
+        })
+    });
+}
+
+criterion_group!(benches, on_msg, codecs, quorums, replay);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+// Explores a handful of paxos configurations and prints what each takes to check: how long,
+// how many states and how large they are. Run it on either side of a change to the actor
+// state to see what the change is worth, with `cargo bench --bench state_size`.
+use paxos_rs::checking;
+use paxos_rs::model::PaxosModelConfig;
+use std::time::Instant;
+
+fn main() {
+    let configs: [(&str, PaxosModelConfig<char>); 3] = [
+        ("2 clients, 3 servers", PaxosModelConfig::new()),
+        (
+            "2 clients, 4 servers, 3 rounds",
+            PaxosModelConfig::new().server_count(4).max_rounds(Some(3)),
+        ),
+        (
+            "3 clients, 3 servers, 2 rounds",
+            PaxosModelConfig::new().client_count(3).max_rounds(Some(2)),
+        ),
+    ];
+    for (name, config) in configs {
+        let started = Instant::now();
+        let report = checking::memory_report(&config.into_model());
+        println!("{0} ({1:.2?})", name, started.elapsed());
+        println!("{}", report);
+        println!();
+    }
+}
@@ -0,0 +1,9 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // the gRPC gateway is generated from its protobuf definition, see src/grpc.rs
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/paxos.proto")?;
+    // the protobuf wire format replicas can use instead of bincode, see src/wire.rs
+    #[cfg(feature = "protobuf")]
+    prost_build::compile_protos(&["proto/wire.proto"], &["proto"])?;
+    Ok(())
+}
@@ -0,0 +1,458 @@
+use crate::metrics::PrometheusMetrics;
+use crate::paxos::{PaxosState, Slot, Value};
+use crate::transport::{Event, QueueDepths};
+use serde::Serialize;
+use stateright::actor::Id;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddrV4, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+// a client that takes longer than this to send its request is dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+// request lines and headers past this are not worth reading
+const MAX_REQUEST: usize = 8192;
+// a follower that has heard from no peer for this long is out of touch
+const STALE: Duration = Duration::from_secs(5);
+
+// what `/status` reports about a replica
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Status {
+    // leader, follower, or unknown before the replica has heard of any leader
+    role: &'static str,
+    round: u64,
+    leader: Option<String>,
+    // first slot that is not applied yet
+    applied: Slot,
+    // decided slots still in the log, the rest are in the snapshot
+    log_len: usize,
+    // decided slots past a hole in the applied prefix, which the replica is still catching up on
+    unapplied: usize,
+    // the first slot past every decision peers have reported
+    peers_decided: Slot,
+    value: Option<serde_json::Value>,
+}
+
+impl Status {
+    pub fn of<V: Value>(state: &PaxosState<V>) -> Self {
+        let leader = state.lease().map(|lease| lease.ballot().id());
+        Status {
+            role: match leader {
+                Some(leader) if leader == state.id() => "leader",
+                Some(_) => "follower",
+                None => "unknown",
+            },
+            round: state.durable().round(),
+            leader: leader.map(|leader| SocketAddrV4::from(leader).to_string()),
+            applied: state.applied().next(),
+            log_len: state.log().len(),
+            unapplied: state.log().range(state.applied().next()..).count(),
+            peers_decided: state.reported(),
+            value: serde_json::to_value(state.applied().value()).ok(),
+        }
+    }
+}
+
+// what `/healthz` and `/readyz` report about a replica
+#[derive(Clone, Debug, Serialize)]
+struct Health {
+    // starting until the actor has run, stopped once its thread has exited or panicked
+    replica: &'static str,
+    // ok, none without a write-ahead log, or why it cannot be appended to
+    wal: String,
+    // seconds since each peer was last heard from, none if not since this replica started
+    peers: BTreeMap<String, Option<u64>>,
+    caught_up: bool,
+}
+
+// Read access to a running replica, kept up to date by the runner through `observe` and served
+// over HTTP by `serve`. Each endpoint answers with JSON, apart from `/metrics`, which uses the
+// Prometheus text format.
+pub struct Admin {
+    id: SocketAddrV4,
+    peers: Vec<SocketAddrV4>,
+    status: RwLock<Status>,
+    received: AtomicU64,
+    sent: AtomicU64,
+    timeouts: AtomicU64,
+    // what the actor itself counts, shared with the other replicas in this process
+    exporter: Option<Arc<PrometheusMetrics>>,
+    // how full the replica's TCP transport is
+    queues: Option<Arc<QueueDepths>>,
+    // the replica's write-ahead log, checked by `/healthz`
+    wal: Option<PathBuf>,
+    // when each peer was last heard from
+    heard: Mutex<BTreeMap<SocketAddrV4, Instant>>,
+    started: AtomicBool,
+    stopped: AtomicBool,
+}
+
+// Marks the replica stopped when dropped, which includes its thread unwinding from a panic,
+// such as the one a failed write-ahead log write causes.
+pub struct Running<'a>(&'a Admin);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Admin {
+    pub fn new(id: SocketAddrV4, peers: Vec<SocketAddrV4>) -> Self {
+        Admin {
+            id,
+            peers,
+            status: RwLock::new(Status::default()),
+            received: AtomicU64::new(0),
+            sent: AtomicU64::new(0),
+            timeouts: AtomicU64::new(0),
+            exporter: None,
+            queues: None,
+            wal: None,
+            heard: Mutex::new(BTreeMap::new()),
+            started: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    pub fn exporter(mut self, exporter: Arc<PrometheusMetrics>) -> Self {
+        self.exporter = Some(exporter);
+        self
+    }
+
+    pub fn queues(mut self, queues: Arc<QueueDepths>) -> Self {
+        self.queues = Some(queues);
+        self
+    }
+
+    pub fn wal(mut self, path: PathBuf) -> Self {
+        self.wal = Some(path);
+        self
+    }
+
+    // held by the thread running the replica for as long as it runs
+    pub fn running(&self) -> Running<'_> {
+        Running(self)
+    }
+
+    // for `transport::run_observed`
+    pub fn observe<V: Value>(&self, event: Event, state: &PaxosState<V>, sent: usize) {
+        match event {
+            Event::Start => {
+                self.started.store(true, Ordering::Relaxed);
+            }
+            Event::Message(src) => {
+                self.received.fetch_add(1, Ordering::Relaxed);
+                let src = SocketAddrV4::from(src);
+                if src != self.id && self.peers.contains(&src) {
+                    if let Ok(mut heard) = self.heard.lock() {
+                        heard.insert(src, Instant::now());
+                    }
+                }
+            }
+            Event::Timeout => {
+                self.timeouts.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.sent.fetch_add(sent as u64, Ordering::Relaxed);
+        if let Ok(mut status) = self.status.write() {
+            *status = Status::of(state);
+        }
+    }
+
+    fn metrics(&self) -> String {
+        let mut metrics = String::new();
+        let counters = [
+            ("paxos_messages_received_total", &self.received),
+            ("paxos_messages_sent_total", &self.sent),
+            ("paxos_timeouts_total", &self.timeouts),
+        ];
+        for (name, counter) in counters {
+            metrics.push_str(&format!("# TYPE {} counter\n", name));
+            metrics.push_str(&format!(
+                "{}{{replica=\"{}\"}} {}\n",
+                name,
+                self.id,
+                counter.load(Ordering::Relaxed)
+            ));
+        }
+        if let Some(queues) = &self.queues {
+            let shed = [
+                ("requests", queues.requests_shed()),
+                ("outgoing", queues.outgoing_shed()),
+            ];
+            metrics.push_str("# TYPE paxos_messages_shed_total counter\n");
+            for (queue, count) in shed {
+                metrics.push_str(&format!(
+                    "paxos_messages_shed_total{{replica=\"{}\",queue=\"{}\"}} {}\n",
+                    self.id, queue, count
+                ));
+            }
+            let gauges = [
+                ("paxos_inbox_depth", queues.inbox()),
+                ("paxos_request_queue_depth", queues.requests()),
+            ];
+            for (name, depth) in gauges {
+                metrics.push_str(&format!("# TYPE {} gauge\n", name));
+                metrics.push_str(&format!("{}{{replica=\"{}\"}} {}\n", name, self.id, depth));
+            }
+            metrics.push_str("# TYPE paxos_outgoing_queue_depth gauge\n");
+            for (peer, depth) in queues.outgoing() {
+                metrics.push_str(&format!(
+                    "paxos_outgoing_queue_depth{{replica=\"{}\",peer=\"{}\"}} {}\n",
+                    self.id,
+                    SocketAddrV4::from(peer),
+                    depth
+                ));
+            }
+        }
+        if let Some(exporter) = &self.exporter {
+            metrics.push_str(&exporter.render(Id::from(self.id)));
+        }
+        metrics
+    }
+
+    fn wal_health(&self) -> Result<(), String> {
+        match &self.wal {
+            // opened the way the replica appends to it, so a missing directory, a read-only
+            // disk or lost permissions all show
+            Some(path) => OpenOptions::new()
+                .append(true)
+                .open(path)
+                .map(|_| ())
+                .map_err(|e| format!("{}: {}", path.display(), e)),
+            None => Ok(()),
+        }
+    }
+
+    // Healthy while the replica runs and can append to its write-ahead log. Ready once it is
+    // healthy, has applied every slot it knows to be decided and every slot its peers report
+    // decided, and is in touch with the cluster: a leader, or a follower that knows a leader
+    // and has heard from a peer within `STALE`.
+    fn health(&self) -> (bool, bool, Health) {
+        let replica = if self.stopped.load(Ordering::Relaxed) {
+            "stopped"
+        } else if self.started.load(Ordering::Relaxed) {
+            "running"
+        } else {
+            "starting"
+        };
+        let wal = self.wal_health();
+        let heard = self
+            .heard
+            .lock()
+            .map(|heard| heard.clone())
+            .unwrap_or_default();
+        let now = Instant::now();
+        let peers = self
+            .peers
+            .iter()
+            .filter(|peer| **peer != self.id)
+            .map(|peer| {
+                let age = heard.get(peer).map(|at| now.duration_since(*at).as_secs());
+                (peer.to_string(), age)
+            })
+            .collect();
+        let recent = heard.values().any(|at| now.duration_since(*at) < STALE);
+        let (caught_up, in_touch) = match self.status.read() {
+            Ok(status) => (
+                status.unapplied == 0 && status.applied >= status.peers_decided,
+                status.role == "leader" || (status.role == "follower" && recent),
+            ),
+            Err(_) => (false, false),
+        };
+        let healthy = replica == "running" && wal.is_ok();
+        let ready = healthy && caught_up && in_touch;
+        let health = Health {
+            replica,
+            wal: match (&self.wal, wal) {
+                (None, _) => "none".to_string(),
+                (Some(_), Ok(())) => "ok".to_string(),
+                (Some(_), Err(e)) => e,
+            },
+            peers,
+            caught_up,
+        };
+        (healthy, ready, health)
+    }
+
+    // the status line, content type and body for a path
+    fn respond(&self, path: &str) -> (&'static str, &'static str, String) {
+        let json = |value: serde_json::Value| ("200 OK", "application/json", value.to_string());
+        match path {
+            "/status" => match self
+                .status
+                .read()
+                .ok()
+                .map(|status| serde_json::to_value(&*status))
+            {
+                Some(Ok(status)) => json(status),
+                _ => ("500 Internal Server Error", "text/plain", String::new()),
+            },
+            "/peers" => {
+                let peers: Vec<String> = self.peers.iter().map(ToString::to_string).collect();
+                json(serde_json::json!({ "id": self.id.to_string(), "peers": peers }))
+            }
+            "/metrics" => ("200 OK", "text/plain; version=0.0.4", self.metrics()),
+            "/healthz" | "/readyz" => {
+                let (healthy, ready, health) = self.health();
+                let ok = if path == "/healthz" { healthy } else { ready };
+                let status = if ok {
+                    "200 OK"
+                } else {
+                    "503 Service Unavailable"
+                };
+                match serde_json::to_string(&health) {
+                    Ok(body) => (status, "application/json", body),
+                    Err(_) => ("500 Internal Server Error", "text/plain", String::new()),
+                }
+            }
+            _ => ("404 Not Found", "text/plain", String::new()),
+        }
+    }
+
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?.take(MAX_REQUEST as u64));
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // the headers are of no interest, but have to be read before answering
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (status, content_type, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some(path)) => self.respond(path),
+            _ => ("405 Method Not Allowed", "text/plain", String::new()),
+        };
+        let mut stream = stream;
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        )
+    }
+
+    // answers one request at a time, which is plenty for an operator
+    pub fn serve(&self, addr: SocketAddrV4) -> io::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        for stream in listener.incoming() {
+            // a misbehaving client only loses its own request
+            let _ = stream.and_then(|stream| self.handle(stream));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transport::Mailbox;
+
+    fn addr(port: u16) -> SocketAddrV4 {
+        SocketAddrV4::new([127, 0, 0, 1].into(), port)
+    }
+
+    fn admin() -> Admin {
+        Admin::new(addr(3000), vec![addr(3000), addr(3001), addr(3002)])
+    }
+
+    fn set(admin: &Admin, role: &'static str, applied: Slot, peers_decided: Slot) {
+        *admin.status.write().unwrap() = Status {
+            role,
+            applied,
+            peers_decided,
+            ..Status::default()
+        };
+    }
+
+    fn heard(admin: &Admin, ago: Duration) {
+        let at = Instant::now().checked_sub(ago).unwrap();
+        admin.heard.lock().unwrap().insert(addr(3001), at);
+    }
+
+    #[test]
+    fn healthy_while_running_with_a_log_it_can_append_to() {
+        let dir = std::env::temp_dir().join(format!("paxos-admin-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let wal = dir.join("replica.wal");
+        std::fs::write(&wal, "").unwrap();
+        let admin = admin().wal(wal.clone());
+        assert_eq!(admin.respond("/healthz").0, "503 Service Unavailable");
+        admin.started.store(true, Ordering::Relaxed);
+        let (status, _, body) = admin.respond("/healthz");
+        assert_eq!(status, "200 OK", "{}", body);
+        assert!(body.contains("\"wal\":\"ok\""), "{}", body);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        let (status, _, body) = admin.respond("/healthz");
+        assert_eq!(status, "503 Service Unavailable");
+        assert!(body.contains(&wal.display().to_string()), "{}", body);
+
+        // the replica's thread going away, unwinding or not
+        drop(admin.running());
+        let (status, _, body) = admin.respond("/healthz");
+        assert_eq!(status, "503 Service Unavailable");
+        assert!(body.contains("\"replica\":\"stopped\""), "{}", body);
+    }
+
+    #[test]
+    fn ready_once_caught_up_with_its_peers_and_in_touch() {
+        let admin = admin();
+        admin.started.store(true, Ordering::Relaxed);
+        set(&admin, "unknown", 0, 0);
+        assert_eq!(admin.respond("/readyz").0, "503 Service Unavailable");
+
+        // a follower that has applied less than its peers report decided
+        set(&admin, "follower", 3, 5);
+        heard(&admin, Duration::ZERO);
+        let (status, _, body) = admin.respond("/readyz");
+        assert_eq!(status, "503 Service Unavailable");
+        assert!(body.contains("\"caught_up\":false"), "{}", body);
+        set(&admin, "follower", 5, 5);
+        let (status, _, body) = admin.respond("/readyz");
+        assert_eq!(status, "200 OK", "{}", body);
+        assert!(body.contains("\"caught_up\":true"), "{}", body);
+
+        // having heard from a peer once is not enough
+        heard(&admin, STALE * 2);
+        assert_eq!(admin.respond("/readyz").0, "503 Service Unavailable");
+        set(&admin, "leader", 5, 5);
+        assert_eq!(admin.respond("/readyz").0, "200 OK");
+    }
+
+    #[test]
+    fn metrics_count_what_the_queues_shed() {
+        let mailbox = Mailbox::new()
+            .peers([Id::from(addr(3001))])
+            .request_depth(1);
+        let client = Id::from(addr(4000));
+        assert!(mailbox.admit(client));
+        assert!(!mailbox.admit(client));
+        let admin = Admin::new(addr(3000), vec![addr(3000), addr(3001)]).queues(mailbox.depths());
+        let (status, _, metrics) = admin.respond("/metrics");
+        assert_eq!(status, "200 OK");
+        for line in [
+            "paxos_messages_shed_total{replica=\"127.0.0.1:3000\",queue=\"requests\"} 1",
+            "paxos_messages_shed_total{replica=\"127.0.0.1:3000\",queue=\"outgoing\"} 0",
+            "paxos_request_queue_depth{replica=\"127.0.0.1:3000\"} 1",
+            "paxos_inbox_depth{replica=\"127.0.0.1:3000\"} 1",
+        ] {
+            assert!(
+                metrics.lines().any(|l| l == line),
+                "{} missing from\n{}",
+                line,
+                metrics
+            );
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use stateright::actor::Id;
+use stateright::{Rewrite, RewritePlan};
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+
+// what rounds are numbered with; acceptors only ever compare ballots, so any total order will
+// do as long as no two proposers share a ballot and a proposer's next round outranks every
+// ballot carrying a lower round number
+pub trait Ballot:
+    Copy + Debug + fmt::Display + Ord + Hash + Serialize + DeserializeOwned + Rewrite<Id>
+{
+    // the ballot `id` proposes with in its `round`th round
+    fn new(round: u64, id: Id) -> Self;
+    // a proposer skips its round counter past the ones it sees elsewhere
+    fn round_num(&self) -> u64;
+    // the proposer the ballot belongs to
+    fn id(&self) -> Id;
+}
+
+// A round number is an epoch in its high 32 bits and a count of rounds within that epoch in
+// the low 32. Rounds used to be a bare u32, which a replica retrying often enough over a long
+// enough life could run through, and a wrapped counter would hand out ballots lower than ones
+// it had already promised. A round from before the widening reads as the same round in epoch
+// 0. Moving to the next round carries into the following epoch once the count is used up, so
+// every round still outranks the ones before it, and only running out of epochs as well leaves
+// a proposer with no round to start.
+pub fn epoch(round: u64) -> u32 {
+    (round >> 32) as u32
+}
+
+// the rounds started so far within the round's epoch
+pub fn count(round: u64) -> u32 {
+    round as u32
+}
+
+pub fn round(epoch: u32, count: u32) -> u64 {
+    (u64::from(epoch) << 32) | u64::from(count)
+}
+
+// the round that follows, rolling over into the next epoch; none once both are exhausted
+pub fn next_round(round_num: u64) -> Option<u64> {
+    match count(round_num).checked_add(1) {
+        Some(count) => Some(round(epoch(round_num), count)),
+        None => Some(round(epoch(round_num).checked_add(1)?, 0)),
+    }
+}
+
+// written as `count.id` in epoch 0, so traces read as they always have, and `epoch/count.id`
+// after a rollover
+fn display(f: &mut fmt::Formatter, round_num: u64, id: Id) -> fmt::Result {
+    match epoch(round_num) {
+        0 => write!(f, "{}.{}", count(round_num), usize::from(id)),
+        epoch => write!(f, "{}/{}.{}", epoch, count(round_num), usize::from(id)),
+    }
+}
+
+// the default, lexicographic on the round number and then the proposer
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct RoundIdentifier {
+    round_num: u64,
+    id: Id,
+}
+
+impl RoundIdentifier {
+    pub fn new(round_num: u64, id: Id) -> Self {
+        RoundIdentifier { round_num, id }
+    }
+
+    pub fn round_num(&self) -> u64 {
+        self.round_num
+    }
+
+    pub fn epoch(&self) -> u32 {
+        epoch(self.round_num)
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+}
+
+// the round number, then the proposer it belongs to
+impl fmt::Display for RoundIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        display(f, self.round_num, self.id)
+    }
+}
+
+impl PartialOrd for RoundIdentifier {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl Ord for RoundIdentifier {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        if self.round_num != other.round_num {
+            return self.round_num.cmp(&other.round_num);
+        }
+        return self.id.cmp(&other.id);
+    }
+}
+
+impl Ballot for RoundIdentifier {
+    fn new(round: u64, id: Id) -> Self {
+        RoundIdentifier {
+            round_num: round,
+            id,
+        }
+    }
+
+    fn round_num(&self) -> u64 {
+        self.round_num
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+}
+
+impl Rewrite<Id> for RoundIdentifier {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        RoundIdentifier {
+            round_num: self.round_num,
+            id: plan.rewrite(&self.id),
+        }
+    }
+}
+
+// breaks ties between proposers in the same round by a scrambled id rather than the id
+// itself, so the proposer that wins a duel is no longer the one with the highest id; anything
+// that quietly relies on the usual tie-break shows up as a different counterexample
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct ScrambledBallot {
+    round_num: u64,
+    id: Id,
+}
+
+impl ScrambledBallot {
+    // a fixed permutation of the ids, splitmix64's finaliser
+    fn tie_break(&self) -> u64 {
+        let mut z = (usize::from(self.id) as u64).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl fmt::Display for ScrambledBallot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        display(f, self.round_num, self.id)
+    }
+}
+
+impl PartialOrd for ScrambledBallot {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScrambledBallot {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.round_num, self.tie_break(), self.id).cmp(&(
+            other.round_num,
+            other.tie_break(),
+            other.id,
+        ))
+    }
+}
+
+impl Ballot for ScrambledBallot {
+    fn new(round: u64, id: Id) -> Self {
+        ScrambledBallot {
+            round_num: round,
+            id,
+        }
+    }
+
+    fn round_num(&self) -> u64 {
+        self.round_num
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+}
+
+// renaming changes the scrambled order, so symmetry reduction is not sound with these
+impl Rewrite<Id> for ScrambledBallot {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        ScrambledBallot {
+            round_num: self.round_num,
+            id: plan.rewrite(&self.id),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use clap::{Parser, Subcommand};
+use paxos_rs::chunking::Chunked;
+use paxos_rs::paxos::PaxosMsg;
+use paxos_rs::transport::{self, TransportMode, WireFormat};
+use stateright::actor::register::RegisterMsg;
+use stateright::actor::Id;
+use std::net::SocketAddrV4;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type Msg = RegisterMsg<u64, char, Chunked<PaxosMsg<char>>>;
+
+#[derive(Parser)]
+#[clap(about = "Send a single request to a spawned Paxos replica")]
+struct Cli {
+    /// Replica to send the request to
+    server: SocketAddrV4,
+    #[clap(subcommand)]
+    request: Request,
+    /// Request id, replicas ignore ids they have already served
+    #[clap(long)]
+    request_id: Option<u64>,
+    /// Seconds to wait for the reply
+    #[clap(long, default_value_t = 5)]
+    timeout: u64,
+    /// One of udp or tcp, whichever the replicas were spawned with
+    #[clap(long, default_value_t = TransportMode::Udp)]
+    transport: TransportMode,
+    /// One of json, bincode, cbor or protobuf, whichever the replicas were spawned with
+    #[clap(long)]
+    wire: Option<WireFormat>,
+}
+
+#[derive(Subcommand)]
+enum Request {
+    /// Write a value to the register
+    Put { value: char },
+    /// Read the register
+    Get,
+    /// Have the leader, which the request must be sent to, hand its leadership to another
+    /// replica once its outstanding proposals are decided
+    TransferLeadership { to: SocketAddrV4 },
+}
+
+fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+    let request_id = match cli.request_id {
+        Some(request_id) => request_id,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default(),
+    };
+
+    let msg: Msg = match cli.request {
+        Request::Put { value } => RegisterMsg::Put(request_id, value),
+        Request::Get => RegisterMsg::Get(request_id),
+        Request::TransferLeadership { to } => {
+            let msg = PaxosMsg::TransferLeadership(Id::from(to));
+            RegisterMsg::Internal(Chunked::Whole(msg))
+        }
+    };
+    let timeout = Duration::from_secs(cli.timeout);
+    let wire = cli.wire.unwrap_or_else(|| cli.transport.default_wire());
+    let (_, reply) =
+        transport::call(
+            cli.transport,
+            wire,
+            cli.server,
+            &msg,
+            timeout,
+            |reply| match reply {
+                RegisterMsg::PutOk(id) | RegisterMsg::GetOk(id, _) => *id == request_id,
+                RegisterMsg::Internal(Chunked::Whole(PaxosMsg::Handoff(..))) => true,
+                _ => false,
+            },
+        )?;
+    match reply {
+        RegisterMsg::GetOk(_, value) => println!("GetOk {}", value),
+        RegisterMsg::Internal(_) => println!("Handed off"),
+        _ => println!("PutOk"),
+    }
+    Ok(())
+}
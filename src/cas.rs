@@ -0,0 +1,533 @@
+use crate::ballot;
+use crate::model::NetworkMode;
+use crate::paxos::{RoundIdentifier, Value};
+use crate::quorum::{Majority, QuorumSpec, QuorumSystem};
+use serde::{Deserialize, Serialize};
+use stateright::actor::*;
+use stateright::semantics::{LinearizabilityTester, SequentialSpec};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::time::Duration;
+
+// a change function a client submits, applied to whatever value the register currently holds
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum Change<V> {
+    Write(V),
+    // only writes the second value if the register holds the first
+    Cas(V, V),
+    Read,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum ChangeRet<V> {
+    WriteOk,
+    CasOk(bool),
+    ReadOk(V),
+}
+
+impl<V: Value> Change<V> {
+    // the register's next value, and what the client is told
+    pub fn apply(&self, current: &V) -> (V, ChangeRet<V>) {
+        match self {
+            Change::Write(value) => (value.clone(), ChangeRet::WriteOk),
+            Change::Cas(expected, value) if expected == current => {
+                (value.clone(), ChangeRet::CasOk(true))
+            }
+            Change::Cas(_, _) => (current.clone(), ChangeRet::CasOk(false)),
+            Change::Read => (current.clone(), ChangeRet::ReadOk(current.clone())),
+        }
+    }
+}
+
+// sequential reference object for a register that also supports compare-and-swap
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CasRegister<V>(pub V);
+
+impl<V: Value> SequentialSpec for CasRegister<V> {
+    type Op = Change<V>;
+    type Ret = ChangeRet<V>;
+
+    fn invoke(&mut self, op: &Self::Op) -> Self::Ret {
+        let (next, ret) = op.apply(&self.0);
+        self.0 = next;
+        ret
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum CasMsg<V> {
+    // client facing
+    Change(u64, Change<V>),
+    ChangeOk(u64, ChangeRet<V>),
+    // request_id is stateright specific while rid is the ballot
+    Prepare(u64, RoundIdentifier),
+    Promise(u64, RoundIdentifier, Option<(RoundIdentifier, V)>),
+    Accept(u64, RoundIdentifier, V),
+    Accepted(u64, RoundIdentifier),
+    Nack(u64, RoundIdentifier),
+}
+
+// a change this actor is driving through both phases
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct CasProposal<V> {
+    client: Id,
+    change: Change<V>,
+    rid: RoundIdentifier,
+    promises: BTreeSet<Id>,
+    // highest (ballot, value) reported by the promises
+    current: Option<(RoundIdentifier, V)>,
+    accepts: BTreeSet<Id>,
+    // set once phase 2 has started, so late promises are ignored
+    ret: Option<ChangeRet<V>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct CasState<V> {
+    id: Id,
+    round: u64,
+    // acceptor side, there is a single register so no slots
+    promised: Option<RoundIdentifier>,
+    accepted: Option<(RoundIdentifier, V)>,
+    // proposer side, keyed by the client's request id
+    proposals: BTreeMap<u64, CasProposal<V>>,
+    handled: BTreeSet<u64>,
+}
+
+impl<V: Value> CasState<V> {
+    pub fn accepted(&self) -> Option<&(RoundIdentifier, V)> {
+        self.accepted.as_ref()
+    }
+
+    // none once every epoch's rounds have been used
+    fn next_round(&mut self) -> Option<RoundIdentifier> {
+        self.round = ballot::next_round(self.round)?;
+        Some(RoundIdentifier::new(self.round, self.id))
+    }
+}
+
+// CASPaxos: every change runs both phases, phase 2 writes the change applied to the
+// highest accepted value of the phase 1 quorum
+pub struct CasPaxosActor {
+    // every acceptor, including this actor
+    acceptors: Vec<Id>,
+    quorum: Box<dyn QuorumSystem>,
+    timeout: Range<Duration>,
+}
+
+impl CasPaxosActor {
+    pub fn new(acceptors: Vec<Id>) -> Self {
+        CasPaxosActor {
+            quorum: Box::new(Majority::new(acceptors.len())),
+            acceptors,
+            timeout: Duration::from_millis(500)..Duration::from_millis(1000),
+        }
+    }
+
+    pub fn quorum(mut self, quorum: Box<dyn QuorumSystem>) -> Self {
+        self.quorum = quorum;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Range<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // (re)starts phase 1 for a proposal with a fresh ballot
+    fn prepare<V: Value>(
+        &self,
+        state: &mut CasState<V>,
+        request_id: u64,
+        o: &mut Out<CasActor<V>>,
+    ) {
+        let rid = match state.next_round() {
+            Some(rid) => rid,
+            None => return,
+        };
+        let proposal = match state.proposals.get_mut(&request_id) {
+            Some(proposal) => proposal,
+            None => return,
+        };
+        proposal.rid = rid;
+        proposal.promises.clear();
+        proposal.current = None;
+        proposal.accepts.clear();
+        proposal.ret = None;
+        o.broadcast(&self.acceptors, &CasMsg::Prepare(request_id, rid));
+        o.set_timer(self.timeout.clone());
+    }
+
+    fn on_msg<V: Value>(
+        &self,
+        state: &mut Cow<CasState<V>>,
+        src: Id,
+        msg: CasMsg<V>,
+        o: &mut Out<CasActor<V>>,
+    ) {
+        match msg {
+            CasMsg::Change(request_id, change) => {
+                if state.handled.contains(&request_id) {
+                    return;
+                }
+                let state = state.to_mut();
+                state.handled.insert(request_id);
+                state.proposals.insert(
+                    request_id,
+                    CasProposal {
+                        client: src,
+                        change,
+                        rid: RoundIdentifier::new(0, state.id),
+                        promises: BTreeSet::new(),
+                        current: None,
+                        accepts: BTreeSet::new(),
+                        ret: None,
+                    },
+                );
+                self.prepare(state, request_id, o);
+            }
+            CasMsg::Prepare(request_id, rid) => {
+                let greater = match state.promised {
+                    Some(promised) => rid >= promised,
+                    None => true,
+                };
+                if greater {
+                    let state = state.to_mut();
+                    state.promised = Some(rid);
+                    o.send(
+                        src,
+                        CasMsg::Promise(request_id, rid, state.accepted.clone()),
+                    );
+                } else if let Some(promised) = state.promised {
+                    o.send(src, CasMsg::Nack(request_id, promised));
+                }
+            }
+            CasMsg::Promise(request_id, rid, accepted) => {
+                let state = state.to_mut();
+                let proposal = match state.proposals.get_mut(&request_id) {
+                    Some(proposal) if proposal.rid == rid && proposal.ret.is_none() => proposal,
+                    _ => return,
+                };
+                if let Some((accepted_rid, value)) = accepted {
+                    let higher = match proposal.current {
+                        Some((seen_rid, _)) => accepted_rid > seen_rid,
+                        None => true,
+                    };
+                    if higher {
+                        proposal.current = Some((accepted_rid, value));
+                    }
+                }
+                proposal.promises.insert(src);
+                if !self.quorum.is_phase1_quorum(&proposal.promises) {
+                    return;
+                }
+
+                // no acceptor in the quorum accepted anything, so the register is still empty
+                let current = match &proposal.current {
+                    Some((_, value)) => value.clone(),
+                    None => V::default(),
+                };
+                let (next, ret) = proposal.change.apply(&current);
+                proposal.ret = Some(ret);
+                o.broadcast(&self.acceptors, &CasMsg::Accept(request_id, rid, next));
+            }
+            CasMsg::Accept(request_id, rid, value) => {
+                let greater = match state.promised {
+                    Some(promised) => rid >= promised,
+                    None => true,
+                };
+                if greater {
+                    let state = state.to_mut();
+                    state.promised = Some(rid);
+                    state.accepted = Some((rid, value));
+                    o.send(src, CasMsg::Accepted(request_id, rid));
+                } else if let Some(promised) = state.promised {
+                    o.send(src, CasMsg::Nack(request_id, promised));
+                }
+            }
+            CasMsg::Accepted(request_id, rid) => {
+                let state = state.to_mut();
+                let proposal = match state.proposals.get_mut(&request_id) {
+                    Some(proposal) if proposal.rid == rid => proposal,
+                    _ => return,
+                };
+                proposal.accepts.insert(src);
+                if !self.quorum.is_phase2_quorum(&proposal.accepts) {
+                    return;
+                }
+                if let Some(ret) = proposal.ret.clone() {
+                    o.send(proposal.client, CasMsg::ChangeOk(request_id, ret));
+                }
+                state.proposals.remove(&request_id);
+                if state.proposals.is_empty() {
+                    o.cancel_timer();
+                }
+            }
+            CasMsg::Nack(request_id, seen) => {
+                // only restart if the nack outranks the ballot we are currently driving
+                let stale = match state.proposals.get(&request_id) {
+                    Some(proposal) => proposal.rid < seen,
+                    None => false,
+                };
+                if !stale {
+                    return;
+                }
+                let state = state.to_mut();
+                if state.round < seen.round_num() {
+                    state.round = seen.round_num();
+                }
+                self.prepare(state, request_id, o);
+            }
+            CasMsg::ChangeOk(_, _) => {}
+        }
+    }
+
+    fn on_timeout<V: Value>(&self, state: &mut Cow<CasState<V>>, o: &mut Out<CasActor<V>>) {
+        if state.proposals.is_empty() {
+            return;
+        }
+        let state = state.to_mut();
+        let outstanding: Vec<u64> = state.proposals.keys().copied().collect();
+        for request_id in outstanding {
+            self.prepare(state, request_id, o);
+        }
+    }
+}
+
+// clients and servers have to share an actor type in the model, as with `RegisterActor`
+pub enum CasActor<V> {
+    // submits its changes one at a time to a single server
+    Client {
+        changes: Vec<Change<V>>,
+        server_count: usize,
+    },
+    Server(CasPaxosActor),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum CasActorState<V> {
+    Client { awaiting: Option<u64>, sent: usize },
+    Server(CasState<V>),
+}
+
+impl<V: Value> CasActor<V> {
+    // unique across clients, since a server deduplicates on it
+    fn request_id(id: Id, index: usize) -> u64 {
+        ((usize::from(id) as u64) << 32) | index as u64
+    }
+
+    fn send_change(
+        id: Id,
+        changes: &[Change<V>],
+        server_count: usize,
+        sent: usize,
+        o: &mut Out<Self>,
+    ) -> Option<u64> {
+        let change = changes.get(sent)?;
+        let request_id = Self::request_id(id, sent);
+        let server = Id::from(usize::from(id) % server_count);
+        o.send(server, CasMsg::Change(request_id, change.clone()));
+        Some(request_id)
+    }
+}
+
+impl<V: Value> Actor for CasActor<V> {
+    type Msg = CasMsg<V>;
+    type State = CasActorState<V>;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        match self {
+            CasActor::Client {
+                changes,
+                server_count,
+            } => CasActorState::Client {
+                awaiting: Self::send_change(id, changes, *server_count, 0, o),
+                sent: 1,
+            },
+            CasActor::Server(_) => CasActorState::Server(CasState {
+                id,
+                round: 0,
+                promised: None,
+                accepted: None,
+                proposals: BTreeMap::new(),
+                handled: BTreeSet::new(),
+            }),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match (self, state.as_ref()) {
+            (
+                CasActor::Client {
+                    changes,
+                    server_count,
+                },
+                CasActorState::Client { awaiting, sent },
+            ) => {
+                let request_id = match msg {
+                    CasMsg::ChangeOk(request_id, _) => request_id,
+                    _ => return,
+                };
+                if *awaiting != Some(request_id) {
+                    return;
+                }
+                let sent = *sent;
+                *state = Cow::Owned(CasActorState::Client {
+                    awaiting: Self::send_change(id, changes, *server_count, sent, o),
+                    sent: sent + 1,
+                });
+            }
+            (CasActor::Server(server), CasActorState::Server(server_state)) => {
+                let mut server_state = Cow::Borrowed(server_state);
+                server.on_msg(&mut server_state, src, msg, o);
+                if let Cow::Owned(server_state) = server_state {
+                    *state = Cow::Owned(CasActorState::Server(server_state));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_timeout(&self, _: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        if let (CasActor::Server(server), CasActorState::Server(server_state)) =
+            (self, state.as_ref())
+        {
+            let mut server_state = Cow::Borrowed(server_state);
+            server.on_timeout(&mut server_state, o);
+            if let Cow::Owned(server_state) = server_state {
+                *state = Cow::Owned(CasActorState::Server(server_state));
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CasModelConfig {
+    client_count: usize,
+    server_count: usize,
+    change_count: usize,
+    network: NetworkMode,
+    quorum: QuorumSpec,
+}
+
+impl Default for CasModelConfig {
+    fn default() -> Self {
+        CasModelConfig {
+            client_count: 2,
+            server_count: 3,
+            change_count: 2,
+            network: NetworkMode::Unordered,
+            quorum: QuorumSpec::Majority,
+        }
+    }
+}
+
+impl CasModelConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn client_count(mut self, client_count: usize) -> Self {
+        self.client_count = client_count;
+        self
+    }
+
+    pub fn server_count(mut self, server_count: usize) -> Self {
+        self.server_count = server_count;
+        self
+    }
+
+    pub fn change_count(mut self, change_count: usize) -> Self {
+        self.change_count = change_count;
+        self
+    }
+
+    pub fn network(mut self, network: NetworkMode) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn quorum(mut self, quorum: QuorumSpec) -> Self {
+        self.quorum = quorum;
+        self
+    }
+
+    // every client tries to swap the empty register for its own value, then reads
+    // it back, so exactly one of the swaps should succeed
+    fn changes(&self, client: usize) -> Vec<Change<char>> {
+        let value = (b'A' + client as u8) as char;
+        (0..self.change_count)
+            .map(|i| match i % 2 {
+                0 => Change::Cas(char::default(), value),
+                _ => Change::Read,
+            })
+            .collect()
+    }
+
+    pub fn into_model(self) -> CasModel {
+        let servers: Vec<Id> = (0..self.server_count).map(Id::from).collect();
+        ActorModel::new(
+            self.clone(),
+            LinearizabilityTester::new(CasRegister::default()),
+        )
+        .actors((0..self.server_count).map(|_| {
+            CasActor::Server(
+                CasPaxosActor::new(servers.clone()).quorum(self.quorum.build(&servers)),
+            )
+        }))
+        .actors((0..self.client_count).map(|i| CasActor::Client {
+            changes: self.changes(i),
+            server_count: self.server_count,
+        }))
+        .duplicating_network(match self.network {
+            NetworkMode::UnorderedDuplicating => DuplicatingNetwork::Yes,
+            _ => DuplicatingNetwork::No,
+        })
+        .lossy_network(match self.network {
+            NetworkMode::Lossy => LossyNetwork::Yes,
+            _ => LossyNetwork::No,
+        })
+        .property(
+            stateright::Expectation::Always,
+            "linearizable",
+            |_, state| state.history.serialized_history().is_some(),
+        )
+        .property(
+            stateright::Expectation::Sometimes,
+            "swap succeeded",
+            |_, state| {
+                state
+                    .network
+                    .iter()
+                    .any(|env| matches!(env.msg, CasMsg::ChangeOk(_, ChangeRet::CasOk(true))))
+            },
+        )
+        .record_msg_out(|_, history, env| match env.msg {
+            CasMsg::Change(_, change) => {
+                let mut history = history.clone();
+                let _ = history.on_invoke(env.src, change.clone());
+                Some(history)
+            }
+            _ => None,
+        })
+        .record_msg_in(|_, history, env| match env.msg {
+            CasMsg::ChangeOk(_, ret) => {
+                let mut history = history.clone();
+                let _ = history.on_return(env.dst, ret.clone());
+                Some(history)
+            }
+            _ => None,
+        })
+    }
+}
+
+pub type CasHistory = LinearizabilityTester<Id, CasRegister<char>>;
+
+pub type CasModel = ActorModel<CasActor<char>, CasModelConfig, CasHistory>;
@@ -0,0 +1,596 @@
+use crate::rng::{Rng, SplitMix};
+use stateright::{Checker, Expectation, Model};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how often `progress` looks in on the checker, short so it notices the end quickly
+const POLL: Duration = Duration::from_millis(50);
+
+// how `check` explores the state space
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Search {
+    Bfs,
+    Dfs,
+    // seeded random walks, for state spaces too large to exhaust
+    Simulation,
+}
+
+impl FromStr for Search {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bfs" => Ok(Search::Bfs),
+            "dfs" => Ok(Search::Dfs),
+            "simulation" => Ok(Search::Simulation),
+            _ => Err(format!(
+                "unknown search {}, expected bfs, dfs or simulation",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Search {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Search::Bfs => write!(f, "bfs"),
+            Search::Dfs => write!(f, "dfs"),
+            Search::Simulation => write!(f, "simulation"),
+        }
+    }
+}
+
+// whether every property came out as expected, given which ones the search discovered an
+// example or counterexample for
+pub fn passed<M: Model>(model: &M, discovered: impl Fn(&'static str) -> bool) -> bool {
+    model
+        .properties()
+        .iter()
+        .all(|property| match property.expectation {
+            Expectation::Always | Expectation::Eventually => !discovered(property.name),
+            Expectation::Sometimes => discovered(property.name),
+        })
+}
+
+// a path from an initial state, each state with the action taken from it
+pub type Path<S, A> = Vec<(S, Option<A>)>;
+
+// the first path that showed something about a property
+struct Discovery<S, A> {
+    example: bool,
+    path: Path<S, A>,
+}
+
+// what a search found, with the paths that break a property
+pub struct Outcome<S, A> {
+    pub passed: bool,
+    pub counterexamples: Vec<(&'static str, Path<S, A>)>,
+}
+
+// how a schedule went when it was taken again
+pub struct Replay<S> {
+    // every state reached, starting with the initial one
+    pub states: Vec<S>,
+    // how many actions were taken, some of which may have left the state as it was
+    pub taken: usize,
+    // the properties the path broke, each with the number of actions taken by then
+    pub violated: Vec<(&'static str, usize)>,
+    // the first action the model no longer allows, if the schedule could not be taken in full
+    pub stuck: Option<usize>,
+}
+
+// Takes `schedule` from the model's first initial state, checking every state on the way like a
+// search would. A schedule recorded against older actors may stop applying once they change:
+// replay then stops at the first action that is no longer enabled, and says which.
+pub fn replay<M>(model: &M, schedule: &[M::Action]) -> Replay<M::State>
+where
+    M: Model,
+    M::State: Clone,
+    M::Action: Clone + PartialEq,
+{
+    follow(model, |taken, _| schedule.get(taken).cloned())
+}
+
+// Walks the model from its first initial state, taking the enabled action numbered `choice`
+// modulo how many there are for each of `choices`, until they run out or nothing is enabled.
+// Generated choices explore random delivery orders, and drops and redeliveries on a network
+// that allows them; shrinking the choices shrinks the schedule.
+pub fn walk<M>(model: &M, choices: &[usize]) -> Replay<M::State>
+where
+    M: Model,
+    M::State: Clone,
+    M::Action: Clone + PartialEq,
+{
+    follow(model, |taken, actions| {
+        let choice = choices.get(taken)?;
+        actions.get(choice % actions.len().max(1)).cloned()
+    })
+}
+
+// checks every state along the path `next` picks actions for, until it picks none
+fn follow<M>(
+    model: &M,
+    mut next: impl FnMut(usize, &[M::Action]) -> Option<M::Action>,
+) -> Replay<M::State>
+where
+    M: Model,
+    M::State: Clone,
+    M::Action: Clone + PartialEq,
+{
+    let properties = model.properties();
+    let mut replay = Replay {
+        states: model.init_states().into_iter().take(1).collect(),
+        taken: 0,
+        violated: Vec::new(),
+        stuck: None,
+    };
+    let mut eventually_held = vec![false; properties.len()];
+    let mut changed = true;
+    while let Some(state) = replay.states.last() {
+        let taken = replay.taken;
+        if changed {
+            for (i, property) in properties.iter().enumerate() {
+                let holds = (property.condition)(model, state);
+                match property.expectation {
+                    Expectation::Always if !holds => {
+                        if replay
+                            .violated
+                            .iter()
+                            .all(|(name, _)| *name != property.name)
+                        {
+                            replay.violated.push((property.name, taken));
+                        }
+                    }
+                    Expectation::Eventually => eventually_held[i] |= holds,
+                    _ => {}
+                }
+            }
+        }
+        let mut actions = Vec::new();
+        model.actions(state, &mut actions);
+        if actions.is_empty() {
+            // like a search, only a terminal state shows that something never happened
+            for (i, property) in properties.iter().enumerate() {
+                if matches!(property.expectation, Expectation::Eventually) && !eventually_held[i] {
+                    replay.violated.push((property.name, taken));
+                }
+            }
+        }
+        let action = match next(taken, &actions) {
+            Some(action) => action,
+            None => break,
+        };
+        if !actions.contains(&action) {
+            replay.stuck = Some(taken);
+            break;
+        }
+        replay.taken += 1;
+        // an action the actors ignore changes nothing
+        let next_state = model.next_state(state, action);
+        changed = next_state.is_some();
+        replay.states.extend(next_state);
+    }
+    replay
+}
+
+// Prints a line on the search every `every` until it is done: states generated and how many
+// were new, the deepest path so far and the rate since the last line. The workers only bump
+// counters that this reads, so watching costs them nothing; stateright keeps its queue to
+// itself, so how much is left to do is not known.
+pub fn progress<M, C>(checker: &C, every: Duration, w: &mut impl Write)
+where
+    M: Model,
+    C: Checker<M>,
+{
+    let mut last = (Instant::now(), 0);
+    while !checker.is_done() {
+        thread::sleep(POLL);
+        let elapsed = last.0.elapsed();
+        if elapsed < every {
+            continue;
+        }
+        let states = checker.state_count();
+        let rate = states.saturating_sub(last.1) as f64 / elapsed.as_secs_f64();
+        let _ = writeln!(
+            w,
+            "Checking. states={0}, unique={1}, max depth={2}, {3:.0} states/sec",
+            states,
+            checker.unique_state_count(),
+            checker.max_depth(),
+            rate
+        );
+        last = (Instant::now(), states);
+    }
+}
+
+// Explores every state within the model's boundary and returns the shortest path to one from
+// which no state satisfying `goal` can be reached, if there is any. Bounded this way, that
+// `goal` stays reachable from everywhere is what progress comes down to: the search for an
+// `Eventually` property only looks at terminal states, which a model with timers rarely has.
+pub fn unreachable_from<M>(
+    model: &M,
+    goal: impl Fn(&M::State) -> bool,
+) -> Option<Path<M::State, M::Action>>
+where
+    M: Model,
+    M::State: Clone + PartialEq + Hash,
+    M::Action: Clone,
+{
+    let mut states: Vec<M::State> = Vec::new();
+    // the states by fingerprint, those that share one told apart in full
+    let mut index: HashMap<u64, Vec<usize>> = HashMap::new();
+    // how each state was first reached, for the path back to it
+    let mut parents: Vec<Option<(usize, M::Action)>> = Vec::new();
+    let mut predecessors: Vec<Vec<usize>> = Vec::new();
+    let mut find = |states: &[M::State], state: &M::State| -> Result<usize, usize> {
+        let same = index.entry(fingerprint(state)).or_default();
+        match same.iter().find(|i| states[**i] == *state) {
+            Some(i) => Ok(*i),
+            None => {
+                same.push(states.len());
+                Err(states.len())
+            }
+        }
+    };
+    for state in model.init_states() {
+        if model.within_boundary(&state) && find(&states, &state).is_err() {
+            states.push(state);
+            parents.push(None);
+            predecessors.push(Vec::new());
+        }
+    }
+    let mut next = 0;
+    while next < states.len() {
+        let mut actions = Vec::new();
+        model.actions(&states[next], &mut actions);
+        for action in actions {
+            let state = match model.next_state(&states[next], action.clone()) {
+                Some(state) if model.within_boundary(&state) => state,
+                _ => continue,
+            };
+            let reached = match find(&states, &state) {
+                Ok(reached) => reached,
+                Err(reached) => {
+                    states.push(state);
+                    parents.push(Some((next, action)));
+                    predecessors.push(Vec::new());
+                    reached
+                }
+            };
+            predecessors[reached].push(next);
+        }
+        next += 1;
+    }
+    // every state that can still get to the goal, working back from the goal itself
+    let mut reaches: Vec<bool> = states.iter().map(&goal).collect();
+    let mut pending: Vec<usize> = (0..states.len()).filter(|i| reaches[*i]).collect();
+    while let Some(reached) = pending.pop() {
+        for predecessor in &predecessors[reached] {
+            if !reaches[*predecessor] {
+                reaches[*predecessor] = true;
+                pending.push(*predecessor);
+            }
+        }
+    }
+    // states were numbered breadth first, so the first one stuck is the closest
+    let stuck = reaches.iter().position(|reaches| !reaches)?;
+    let mut path = vec![(states[stuck].clone(), None)];
+    let mut at = stuck;
+    while let Some((parent, action)) = &parents[at] {
+        path.push((states[*parent].clone(), Some(action.clone())));
+        at = *parent;
+    }
+    path.reverse();
+    Some(path)
+}
+
+// every state a search reaches, numbered breadth first, with the actions between them
+pub struct StateGraph<S, A> {
+    pub states: Vec<S>,
+    // the first this many states are the initial ones
+    pub initial: usize,
+    // (from, to, action) by state number
+    pub edges: Vec<(usize, usize, A)>,
+}
+
+// Explores every state within the model's boundary like `unreachable_from`, at most
+// `max_depth` steps from an initial state if given, keeping every edge rather than the first
+// way to each state.
+pub fn state_graph<M>(model: &M, max_depth: Option<usize>) -> StateGraph<M::State, M::Action>
+where
+    M: Model,
+    M::State: Clone + PartialEq + Hash,
+    M::Action: Clone,
+{
+    let mut graph = StateGraph {
+        states: Vec::new(),
+        initial: 0,
+        edges: Vec::new(),
+    };
+    let mut depths: Vec<usize> = Vec::new();
+    let mut index: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut find = |states: &[M::State], state: &M::State| -> Result<usize, usize> {
+        let same = index.entry(fingerprint(state)).or_default();
+        match same.iter().find(|i| states[**i] == *state) {
+            Some(i) => Ok(*i),
+            None => {
+                same.push(states.len());
+                Err(states.len())
+            }
+        }
+    };
+    for state in model.init_states() {
+        if model.within_boundary(&state) && find(&graph.states, &state).is_err() {
+            graph.states.push(state);
+            depths.push(0);
+        }
+    }
+    graph.initial = graph.states.len();
+    let mut next = 0;
+    while next < graph.states.len() {
+        if max_depth.map_or(false, |max| depths[next] >= max) {
+            next += 1;
+            continue;
+        }
+        let mut actions = Vec::new();
+        model.actions(&graph.states[next], &mut actions);
+        for action in actions {
+            let state = match model.next_state(&graph.states[next], action.clone()) {
+                Some(state) if model.within_boundary(&state) => state,
+                _ => continue,
+            };
+            let reached = match find(&graph.states, &state) {
+                Ok(reached) => reached,
+                Err(reached) => {
+                    graph.states.push(state);
+                    depths.push(depths[next] + 1);
+                    reached
+                }
+            };
+            graph.edges.push((next, reached, action));
+        }
+        next += 1;
+    }
+    graph
+}
+
+// what an exhaustive search of a configuration holds on to, for tuning it before it runs out
+// of memory
+pub struct MemoryReport {
+    // states reached, counting every time one was reached again
+    pub generated: usize,
+    pub unique: usize,
+    // the bytes unique states feed their hash, which tracks what it takes to keep them
+    pub total_bytes: usize,
+    pub largest_bytes: usize,
+    // distinct states that share a 64 bit fingerprint, which a search would take for one
+    pub collisions: usize,
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "States generated: {0}, unique: {1}",
+            self.generated, self.unique
+        )?;
+        writeln!(
+            f,
+            "Unique state size: {0} bytes on average, {1} at most, {2} in all",
+            self.total_bytes / self.unique.max(1),
+            self.largest_bytes,
+            self.total_bytes
+        )?;
+        // the birthday bound for this many fingerprints
+        let expected = (self.unique as f64).powi(2) / 2f64.powi(65);
+        write!(
+            f,
+            "Fingerprint collisions: {0}, about {1:.2e} expected",
+            self.collisions, expected
+        )
+    }
+}
+
+// 64 bits, as many as a search tells states apart by
+pub(crate) fn fingerprint<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// counts the bytes a value hashes rather than hashing them
+struct ByteCount(usize);
+
+// what a value feeds its hash, which is what the checker hashes and compares of a state
+pub fn hashed_bytes<T: Hash>(value: &T) -> usize {
+    let mut bytes = ByteCount(0);
+    value.hash(&mut bytes);
+    bytes.0
+}
+
+impl Hasher for ByteCount {
+    fn finish(&self) -> u64 {
+        self.0 as u64
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0 += bytes.len();
+    }
+}
+
+// Explores every state within the model's boundary like `unreachable_from`, measuring rather
+// than searching. States are told apart in full, so collisions are counted, not suffered.
+pub fn memory_report<M>(model: &M) -> MemoryReport
+where
+    M: Model,
+    M::State: Clone + PartialEq + Hash,
+{
+    let mut report = MemoryReport {
+        generated: 0,
+        unique: 0,
+        total_bytes: 0,
+        largest_bytes: 0,
+        collisions: 0,
+    };
+    let mut fingerprints: HashMap<u64, Vec<M::State>> = HashMap::new();
+    let mut pending: Vec<M::State> = Vec::new();
+    let mut visit = |state: M::State, pending: &mut Vec<M::State>| {
+        report.generated += 1;
+        let same = fingerprints.entry(fingerprint(&state)).or_default();
+        if same.contains(&state) {
+            return;
+        }
+        if !same.is_empty() {
+            report.collisions += 1;
+        }
+        let bytes = hashed_bytes(&state);
+        report.unique += 1;
+        report.total_bytes += bytes;
+        report.largest_bytes = report.largest_bytes.max(bytes);
+        same.push(state.clone());
+        pending.push(state);
+    };
+    for state in model.init_states() {
+        if model.within_boundary(&state) {
+            visit(state, &mut pending);
+        }
+    }
+    while let Some(state) = pending.pop() {
+        let mut actions = Vec::new();
+        model.actions(&state, &mut actions);
+        for action in actions {
+            match model.next_state(&state, action) {
+                Some(next) if model.within_boundary(&next) => visit(next, &mut pending),
+                _ => {}
+            }
+        }
+    }
+    report
+}
+
+fn path_to<S: Clone, A: Clone>(taken: &[(S, A)], state: &S) -> Path<S, A> {
+    taken
+        .iter()
+        .map(|(state, action)| (state.clone(), Some(action.clone())))
+        .chain(std::iter::once((state.clone(), None)))
+        .collect()
+}
+
+// runs `runs` random walks of at most `depth` steps each, checking every state on the way,
+// and reports like the exhaustive checkers do; the same seed always takes the same walks
+pub fn simulate<M, W>(
+    model: &M,
+    seed: u64,
+    runs: usize,
+    depth: usize,
+    w: &mut W,
+) -> io::Result<Outcome<M::State, M::Action>>
+where
+    M: Model,
+    M::State: Clone,
+    M::Action: Clone + Debug,
+    W: Write,
+{
+    let properties = model.properties();
+    let mut rng = SplitMix(seed);
+    let mut discoveries: BTreeMap<&'static str, Discovery<M::State, M::Action>> = BTreeMap::new();
+    let mut state_count = 0;
+
+    for _ in 0..runs {
+        let mut init_states = model.init_states();
+        if init_states.is_empty() {
+            break;
+        }
+        let mut state = init_states.swap_remove(rng.below(init_states.len()));
+        let mut taken: Vec<(M::State, M::Action)> = Vec::new();
+
+        let mut eventually_held = vec![false; properties.len()];
+        for step in 0..=depth {
+            state_count += 1;
+            for (i, property) in properties.iter().enumerate() {
+                let holds = (property.condition)(model, &state);
+                let found = match property.expectation {
+                    Expectation::Always => !holds,
+                    Expectation::Sometimes => holds,
+                    Expectation::Eventually => {
+                        eventually_held[i] |= holds;
+                        false
+                    }
+                };
+                if found {
+                    discoveries
+                        .entry(property.name)
+                        .or_insert_with(|| Discovery {
+                            example: matches!(property.expectation, Expectation::Sometimes),
+                            path: path_to(&taken, &state),
+                        });
+                }
+            }
+
+            let mut actions = Vec::new();
+            model.actions(&state, &mut actions);
+            if actions.is_empty() {
+                // a terminal state, so whatever was meant to happen eventually never will
+                for (i, property) in properties.iter().enumerate() {
+                    if matches!(property.expectation, Expectation::Eventually)
+                        && !eventually_held[i]
+                    {
+                        discoveries
+                            .entry(property.name)
+                            .or_insert_with(|| Discovery {
+                                example: false,
+                                path: path_to(&taken, &state),
+                            });
+                    }
+                }
+                break;
+            }
+            if step == depth {
+                break;
+            }
+            let action = actions.swap_remove(rng.below(actions.len()));
+            match model.next_state(&state, action.clone()) {
+                // like the exhaustive checkers, never step past the model's boundary
+                Some(next) if !model.within_boundary(&next) => break,
+                Some(next) => taken.push((std::mem::replace(&mut state, next), action)),
+                None => {}
+            }
+        }
+    }
+
+    writeln!(
+        w,
+        "Simulated {} runs of up to {} steps from seed {}. states={}",
+        runs, depth, seed, state_count
+    )?;
+    for (name, discovery) in &discoveries {
+        let kind = if discovery.example {
+            "example"
+        } else {
+            "counterexample"
+        };
+        writeln!(w, "Discovered \"{}\" {} after:", name, kind)?;
+        for action in discovery
+            .path
+            .iter()
+            .filter_map(|(_, action)| action.as_ref())
+        {
+            writeln!(w, "  {:?}", action)?;
+        }
+    }
+    Ok(Outcome {
+        passed: passed(model, |name| discoveries.contains_key(name)),
+        counterexamples: discoveries
+            .into_iter()
+            .filter(|(_, discovery)| !discovery.example)
+            .map(|(name, discovery)| (name, discovery.path))
+            .collect(),
+    })
+}
@@ -0,0 +1,256 @@
+use crate::checking::{fingerprint, passed, Outcome, Path};
+use serde::{Deserialize, Serialize};
+use stateright::{Expectation, Model, Property};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Debug;
+use std::fs;
+use std::hash::Hash;
+use std::io::{self, Write};
+use std::path::Path as FilePath;
+use std::time::{Duration, Instant};
+
+// Everything a breadth first search needs to carry on where it stopped, small enough to write
+// out every so often during a check that runs for hours. States are kept by fingerprint, like
+// the checker keeps them, and those still to be expanded are rebuilt on resuming by taking
+// again the actions that first led to them from an initial state. Fingerprints only hold
+// within one build, so a checkpoint is resumed by the binary that wrote it.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    // the initial states and property names, to refuse a checkpoint of another configuration
+    model: u64,
+    // every state generated, with the one it was first reached from
+    generated: Vec<(u64, Option<u64>)>,
+    // the states left to expand, with a bit for each eventually property seen to hold on the
+    // way to them, which bounds a model to 64 properties
+    pending: Vec<(u64, u64)>,
+    discoveries: Vec<(String, u64)>,
+}
+
+struct Search<S> {
+    generated: HashMap<u64, Option<u64>>,
+    pending: VecDeque<(S, u64, u64)>,
+    discoveries: BTreeMap<&'static str, u64>,
+}
+
+impl<S: Clone + Hash> Search<S> {
+    // takes in a state reached from `parent`, checking it against every property the first
+    // time it is seen
+    fn visit<M>(
+        &mut self,
+        model: &M,
+        properties: &[Property<M>],
+        parent: Option<u64>,
+        state: S,
+        mut held: u64,
+    ) where
+        M: Model<State = S>,
+    {
+        if !model.within_boundary(&state) {
+            return;
+        }
+        let id = fingerprint(&state);
+        if self.generated.contains_key(&id) {
+            return;
+        }
+        self.generated.insert(id, parent);
+        for (i, property) in properties.iter().enumerate() {
+            let holds = (property.condition)(model, &state);
+            let found = match property.expectation {
+                Expectation::Always => !holds,
+                Expectation::Sometimes => holds,
+                Expectation::Eventually => {
+                    if holds {
+                        held |= 1 << i;
+                    }
+                    false
+                }
+            };
+            if found {
+                self.discoveries.entry(property.name).or_insert(id);
+            }
+        }
+        self.pending.push_back((state, id, held));
+    }
+
+    fn save(&self, file: &FilePath, model: u64) -> io::Result<()> {
+        let checkpoint = Checkpoint {
+            model,
+            generated: self
+                .generated
+                .iter()
+                .map(|(id, parent)| (*id, *parent))
+                .collect(),
+            pending: self
+                .pending
+                .iter()
+                .map(|(_, id, held)| (*id, *held))
+                .collect(),
+            discoveries: self
+                .discoveries
+                .iter()
+                .map(|(name, id)| (name.to_string(), *id))
+                .collect(),
+        };
+        let bytes = bincode::serialize(&checkpoint)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // written aside and renamed over, so an interruption never leaves half a checkpoint
+        let partial = file.with_extension("partial");
+        fs::write(&partial, bytes)?;
+        fs::rename(&partial, file)
+    }
+}
+
+// the path the search first took to the state of fingerprint `id`
+fn path_to<M>(
+    model: &M,
+    generated: &HashMap<u64, Option<u64>>,
+    id: u64,
+) -> Option<Path<M::State, M::Action>>
+where
+    M: Model,
+    M::State: Hash,
+{
+    let mut ids = vec![id];
+    while let Some(parent) = generated.get(ids.last()?)? {
+        ids.push(*parent);
+    }
+    ids.reverse();
+    let mut state = model
+        .init_states()
+        .into_iter()
+        .find(|state| fingerprint(state) == ids[0])?;
+    let mut path = Vec::new();
+    for next_id in &ids[1..] {
+        let mut actions = Vec::new();
+        model.actions(&state, &mut actions);
+        let (action, next) = actions.into_iter().find_map(|action| {
+            let next = model.next_state(&state, action.clone())?;
+            (fingerprint(&next) == *next_id).then(|| (action, next))
+        })?;
+        path.push((std::mem::replace(&mut state, next), Some(action)));
+    }
+    path.push((state, None));
+    Some(path)
+}
+
+// Checks the model breadth first on one thread, writing a checkpoint to `file` every `every`
+// and once more at the end, and reports like the other searches. With `resume` it carries on
+// from the checkpoint already in `file` rather than from the initial states.
+pub fn checkpointed_bfs<M, W>(
+    model: &M,
+    file: &FilePath,
+    resume: bool,
+    every: Duration,
+    w: &mut W,
+) -> io::Result<Outcome<M::State, M::Action>>
+where
+    M: Model,
+    M::State: Clone + Hash,
+    M::Action: Clone + Debug,
+    W: Write,
+{
+    let properties = model.properties();
+    let names: Vec<&'static str> = properties.iter().map(|property| property.name).collect();
+    let identity = fingerprint(&(model.init_states(), &names));
+    let mut search = Search {
+        generated: HashMap::new(),
+        pending: VecDeque::new(),
+        discoveries: BTreeMap::new(),
+    };
+    if resume {
+        let checkpoint: Checkpoint = bincode::deserialize(&fs::read(file)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if checkpoint.model != identity {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the checkpoint is of another model configuration",
+            ));
+        }
+        search.generated = checkpoint.generated.into_iter().collect();
+        for (id, held) in checkpoint.pending {
+            let (state, _) = path_to(model, &search.generated, id)
+                .and_then(|mut path| path.pop())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "a pending state is no longer reachable",
+                    )
+                })?;
+            search.pending.push_back((state, id, held));
+        }
+        for (name, id) in checkpoint.discoveries {
+            if let Some(name) = names.iter().find(|known| **known == name) {
+                search.discoveries.insert(*name, id);
+            }
+        }
+        writeln!(
+            w,
+            "Resuming with {} states generated, {} pending",
+            search.generated.len(),
+            search.pending.len()
+        )?;
+    } else {
+        for state in model.init_states() {
+            search.visit(model, &properties, None, state, 0);
+        }
+    }
+
+    let mut last = Instant::now();
+    while search.discoveries.len() < properties.len() {
+        let (state, id, held) = match search.pending.pop_front() {
+            Some(pending) => pending,
+            None => break,
+        };
+        let mut actions = Vec::new();
+        model.actions(&state, &mut actions);
+        if actions.is_empty() {
+            // a terminal state, so whatever was meant to happen eventually never will
+            for (i, property) in properties.iter().enumerate() {
+                if matches!(property.expectation, Expectation::Eventually) && held & (1 << i) == 0 {
+                    search.discoveries.entry(property.name).or_insert(id);
+                }
+            }
+        }
+        for action in actions {
+            if let Some(next) = model.next_state(&state, action) {
+                search.visit(model, &properties, Some(id), next, held);
+            }
+        }
+        if last.elapsed() >= every {
+            search.save(file, identity)?;
+            writeln!(
+                w,
+                "Checkpointed {} states, {} pending, to {}",
+                search.generated.len(),
+                search.pending.len(),
+                file.display()
+            )?;
+            last = Instant::now();
+        }
+    }
+    search.save(file, identity)?;
+
+    writeln!(w, "Done. unique={}", search.generated.len())?;
+    let mut counterexamples = Vec::new();
+    for (name, id) in &search.discoveries {
+        let path = match path_to(model, &search.generated, *id) {
+            Some(path) => path,
+            None => continue,
+        };
+        let example = properties.iter().any(|property| {
+            property.name == *name && matches!(property.expectation, Expectation::Sometimes)
+        });
+        let kind = if example { "example" } else { "counterexample" };
+        writeln!(w, "Discovered \"{}\" {} after:", name, kind)?;
+        for action in path.iter().filter_map(|(_, action)| action.as_ref()) {
+            writeln!(w, "  {:?}", action)?;
+        }
+        if !example {
+            counterexamples.push((*name, path));
+        }
+    }
+    Ok(Outcome {
+        passed: passed(model, |name| search.discoveries.contains_key(name)),
+        counterexamples,
+    })
+}
@@ -0,0 +1,393 @@
+use crate::paxos::{PaxosActor, PaxosMsg, PaxosState, Value};
+use serde::{Deserialize, Serialize};
+use stateright::actor::register::RegisterMsg;
+use stateright::actor::{Actor, Command as ActorCommand, Id, Out};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+// transfers tracked at once either way, beyond which the oldest is given up on
+const MAX_TRANSFERS: usize = 64;
+// the largest message reassembled from chunks unless configured otherwise, a frame's limit
+const MAX_MESSAGE_SIZE: usize = 1 << 24;
+
+// a replica's messages as they go over the wire, those too big for one frame in pieces
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum Chunked<M> {
+    Whole(M),
+    // part `index` of `count` of a serialized message, named by the hash of all its bytes
+    Chunk(u64, u32, u32, Vec<u8>),
+    ChunkAck(u64, u32),
+}
+
+// FNV-1a, which only has to tell apart the messages in flight between two replicas
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct ChunkedState<V> {
+    paxos: PaxosState<V>,
+    // the chunks of each message still arriving, by sender and hash
+    incoming: BTreeMap<(Id, u64), Vec<Option<Vec<u8>>>>,
+    // the chunks of each message sent that the receiver has not acknowledged yet
+    outgoing: BTreeMap<(Id, u64), BTreeSet<u32>>,
+}
+
+impl<V> ChunkedState<V> {
+    pub fn paxos(&self) -> &PaxosState<V> {
+        &self.paxos
+    }
+}
+
+// Runs a replica with its larger messages, accepts of big values in practice, split into
+// chunks of at most `chunk_size` bytes. The receiver acknowledges every chunk and hands the
+// message on once all of them are in and their hash matches the one they were sent under. A
+// message sent again, as a proposer does when it retries, only goes out as the chunks not yet
+// acknowledged; chunks that are lost for good lose the message, which paxos already copes with.
+// Client requests and replies are never chunked, so clients need not know about any of this.
+pub struct ChunkedActor<V> {
+    paxos: PaxosActor<V>,
+    chunk_size: Option<usize>,
+    max_message_size: usize,
+}
+
+impl<V: Value> ChunkedActor<V> {
+    pub fn new(paxos: PaxosActor<V>) -> Self {
+        ChunkedActor {
+            paxos,
+            chunk_size: None,
+            max_message_size: MAX_MESSAGE_SIZE,
+        }
+    }
+
+    pub fn chunk_size(mut self, chunk_size: Option<usize>) -> Self {
+        self.chunk_size = chunk_size.filter(|size| *size > 0);
+        self
+    }
+
+    // Chunks claiming to be part of a bigger message than this, cut at our own chunk size, are
+    // dropped before anything is set aside for them, so a forged count cannot have us allocate
+    // more than the message could take. Without a chunk size of our own only messages that
+    // fit in one chunk are taken.
+    pub fn max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    // the most chunks a message we take can come in
+    fn max_chunks(&self) -> usize {
+        match self.chunk_size {
+            Some(size) => ((self.max_message_size + size - 1) / size).max(1),
+            None => 1,
+        }
+    }
+
+    fn send(&self, state: &mut ChunkedState<V>, dst: Id, msg: PaxosMsg<V>, o: &mut Out<Self>) {
+        let bytes = match (self.chunk_size, bincode::serialize(&msg)) {
+            (Some(size), Ok(bytes)) if bytes.len() > size => bytes,
+            _ => return o.send(dst, RegisterMsg::Internal(Chunked::Whole(msg))),
+        };
+        let size = self.chunk_size.unwrap_or(bytes.len());
+        let hash = content_hash(&bytes);
+        let count = ((bytes.len() + size - 1) / size) as u32;
+        if !state.outgoing.contains_key(&(dst, hash)) && state.outgoing.len() >= MAX_TRANSFERS {
+            if let Some(oldest) = state.outgoing.keys().next().copied() {
+                state.outgoing.remove(&oldest);
+            }
+        }
+        let unacknowledged = state
+            .outgoing
+            .entry((dst, hash))
+            .or_insert_with(|| (0..count).collect());
+        for (index, chunk) in bytes.chunks(size).enumerate() {
+            let index = index as u32;
+            if unacknowledged.contains(&index) {
+                let chunk = Chunked::Chunk(hash, index, count, chunk.to_vec());
+                o.send(dst, RegisterMsg::Internal(chunk));
+            }
+        }
+    }
+
+    // the message once every chunk of it is in, if they add up to what was sent
+    fn reassemble(
+        &self,
+        state: &mut ChunkedState<V>,
+        src: Id,
+        hash: u64,
+        index: u32,
+        count: u32,
+        chunk: Vec<u8>,
+    ) -> Option<PaxosMsg<V>> {
+        if index >= count {
+            return None;
+        }
+        if count as usize > self.max_chunks() {
+            tracing::warn!(
+                src = usize::from(src),
+                hash,
+                count,
+                "chunked message too big"
+            );
+            return None;
+        }
+        if !state.incoming.contains_key(&(src, hash)) && state.incoming.len() >= MAX_TRANSFERS {
+            let oldest = *state.incoming.keys().next()?;
+            state.incoming.remove(&oldest);
+        }
+        let chunks = state
+            .incoming
+            .entry((src, hash))
+            .or_insert_with(|| vec![None; count as usize]);
+        if chunks.len() != count as usize {
+            return None;
+        }
+        chunks[index as usize] = Some(chunk);
+        if chunks.iter().any(Option::is_none) {
+            return None;
+        }
+        let chunks = state.incoming.remove(&(src, hash))?;
+        let bytes: Vec<u8> = chunks.into_iter().flatten().flatten().collect();
+        if content_hash(&bytes) != hash {
+            tracing::warn!(src = usize::from(src), hash, "chunked message corrupt");
+            return None;
+        }
+        bincode::deserialize(&bytes).ok()
+    }
+
+    fn forward(
+        &self,
+        state: &mut ChunkedState<V>,
+        paxos_out: Vec<ActorCommand<RegisterMsg<u64, V, PaxosMsg<V>>>>,
+        o: &mut Out<Self>,
+    ) {
+        for command in paxos_out {
+            match command {
+                ActorCommand::Send(dst, RegisterMsg::Internal(msg)) => {
+                    self.send(state, dst, msg, o)
+                }
+                ActorCommand::Send(dst, RegisterMsg::Put(request_id, value)) => {
+                    o.send(dst, RegisterMsg::Put(request_id, value))
+                }
+                ActorCommand::Send(dst, RegisterMsg::Get(request_id)) => {
+                    o.send(dst, RegisterMsg::Get(request_id))
+                }
+                ActorCommand::Send(dst, RegisterMsg::PutOk(request_id)) => {
+                    o.send(dst, RegisterMsg::PutOk(request_id))
+                }
+                ActorCommand::Send(dst, RegisterMsg::GetOk(request_id, value)) => {
+                    o.send(dst, RegisterMsg::GetOk(request_id, value))
+                }
+                ActorCommand::SetTimer(duration) => o.set_timer(duration),
+                ActorCommand::CancelTimer => o.cancel_timer(),
+            }
+        }
+    }
+}
+
+impl<V: Value> Actor for ChunkedActor<V> {
+    type Msg = RegisterMsg<u64, V, Chunked<PaxosMsg<V>>>;
+    type State = ChunkedState<V>;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        let mut paxos_out = Out::new();
+        let mut state = ChunkedState {
+            paxos: self.paxos.on_start(id, &mut paxos_out),
+            incoming: BTreeMap::new(),
+            outgoing: BTreeMap::new(),
+        };
+        self.forward(&mut state, paxos_out.into_iter().collect(), o);
+        state
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        let paxos_msg = match msg {
+            RegisterMsg::Internal(Chunked::Whole(msg)) => RegisterMsg::Internal(msg),
+            RegisterMsg::Internal(Chunked::Chunk(hash, index, count, chunk)) => {
+                o.send(src, RegisterMsg::Internal(Chunked::ChunkAck(hash, index)));
+                match self.reassemble(state.to_mut(), src, hash, index, count, chunk) {
+                    Some(msg) => RegisterMsg::Internal(msg),
+                    None => return,
+                }
+            }
+            RegisterMsg::Internal(Chunked::ChunkAck(hash, index)) => {
+                let state = state.to_mut();
+                if let Some(unacknowledged) = state.outgoing.get_mut(&(src, hash)) {
+                    unacknowledged.remove(&index);
+                    if unacknowledged.is_empty() {
+                        state.outgoing.remove(&(src, hash));
+                    }
+                }
+                return;
+            }
+            RegisterMsg::Put(request_id, value) => RegisterMsg::Put(request_id, value),
+            RegisterMsg::Get(request_id) => RegisterMsg::Get(request_id),
+            RegisterMsg::PutOk(request_id) => RegisterMsg::PutOk(request_id),
+            RegisterMsg::GetOk(request_id, value) => RegisterMsg::GetOk(request_id, value),
+        };
+        let mut paxos_state = Cow::Borrowed(&state.paxos);
+        let mut paxos_out = Out::new();
+        self.paxos
+            .on_msg(id, &mut paxos_state, src, paxos_msg, &mut paxos_out);
+        if let Cow::Owned(paxos_state) = paxos_state {
+            state.to_mut().paxos = paxos_state;
+        }
+        let paxos_out: Vec<_> = paxos_out.into_iter().collect();
+        // most messages need no bookkeeping here, so leave the state borrowed if we can
+        if !paxos_out.is_empty() {
+            self.forward(state.to_mut(), paxos_out, o);
+        }
+    }
+
+    fn on_timeout(&self, id: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        let mut paxos_state = Cow::Borrowed(&state.paxos);
+        let mut paxos_out = Out::new();
+        self.paxos.on_timeout(id, &mut paxos_state, &mut paxos_out);
+        if let Cow::Owned(paxos_state) = paxos_state {
+            state.to_mut().paxos = paxos_state;
+        }
+        let paxos_out: Vec<_> = paxos_out.into_iter().collect();
+        // most messages need no bookkeeping here, so leave the state borrowed if we can
+        if !paxos_out.is_empty() {
+            self.forward(state.to_mut(), paxos_out, o);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::paxos::RoundIdentifier;
+    use crate::script::Script;
+
+    const CHUNK_SIZE: usize = 8;
+
+    type Msg = RegisterMsg<u64, char, Chunked<PaxosMsg<char>>>;
+
+    fn replica() -> ChunkedActor<char> {
+        ChunkedActor::new(PaxosActor::new(vec![Id::from(1), Id::from(2)]))
+            .chunk_size(Some(CHUNK_SIZE))
+    }
+
+    fn rid() -> RoundIdentifier {
+        RoundIdentifier::new(1, Id::from(1))
+    }
+
+    fn prepare() -> PaxosMsg<char> {
+        PaxosMsg::Prepare(7, Id::from(3), 0, rid())
+    }
+
+    // the chunks `msg` goes out as, with the hash they are sent under
+    fn split(msg: &PaxosMsg<char>) -> (u64, Vec<Msg>) {
+        let bytes = bincode::serialize(msg).unwrap();
+        let hash = content_hash(&bytes);
+        let count = bytes.chunks(CHUNK_SIZE).count() as u32;
+        let chunks = bytes
+            .chunks(CHUNK_SIZE)
+            .enumerate()
+            .map(|(index, chunk)| {
+                RegisterMsg::Internal(Chunked::Chunk(hash, index as u32, count, chunk.to_vec()))
+            })
+            .collect();
+        (hash, chunks)
+    }
+
+    fn ack(hash: u64, index: u32) -> Msg {
+        RegisterMsg::Internal(Chunked::ChunkAck(hash, index))
+    }
+
+    fn promised(state: &ChunkedState<char>) -> bool {
+        state.paxos().durable().last_seen(0) == Some(rid())
+    }
+
+    #[test]
+    fn chunks_are_acknowledged_and_reassembled_in_any_order() {
+        let (hash, chunks) = split(&prepare());
+        assert!(chunks.len() > 2);
+        let mut script = Script::new(replica());
+        // the last chunk first, then the rest
+        let order = std::iter::once(chunks.len() - 1).chain(0..chunks.len() - 1);
+        for (step, index) in order.enumerate() {
+            script = script
+                .expect_state("nothing promised before every chunk is in", |state| {
+                    !promised(state)
+                })
+                .recv(Id::from(1), chunks[index].clone())
+                .expect_send(Id::from(1), ack(hash, index as u32));
+            if step < chunks.len() - 1 {
+                script = script.expect_quiet();
+            }
+        }
+        script.expect_state("the reassembled prepare promised", |state| {
+            promised(state) && state.incoming.is_empty()
+        });
+    }
+
+    #[test]
+    fn only_unacknowledged_chunks_are_sent_again() {
+        let promise = PaxosMsg::Promise(7, Id::from(3), 0, rid(), None);
+        let (hash, sent) = split(&promise);
+        let mut script = Script::new(replica()).recv(
+            Id::from(1),
+            RegisterMsg::Internal(Chunked::Whole(prepare())),
+        );
+        for chunk in &sent {
+            script = script.expect_send(Id::from(1), chunk.clone());
+        }
+        script = script
+            .expect_quiet()
+            .recv(Id::from(1), ack(hash, 0))
+            .expect_quiet()
+            // a redelivered prepare is promised again, with the same bytes
+            .recv(
+                Id::from(1),
+                RegisterMsg::Internal(Chunked::Whole(prepare())),
+            );
+        for chunk in &sent[1..] {
+            script = script.expect_send(Id::from(1), chunk.clone());
+        }
+        script = script.expect_quiet();
+        for index in 1..sent.len() {
+            script = script.recv(Id::from(1), ack(hash, index as u32));
+        }
+        script.expect_state("the transfer forgotten once every chunk is in", |state| {
+            state.outgoing.is_empty()
+        });
+    }
+
+    #[test]
+    fn chunk_counts_beyond_the_largest_message_are_refused() {
+        // 64 bytes in chunks of 8 is at most 8 chunks
+        let script = Script::new(replica().max_message_size(64))
+            .recv(
+                Id::from(1),
+                RegisterMsg::Internal(Chunked::Chunk(1, 0, u32::MAX, vec![0])),
+            )
+            .expect_state("nothing set aside for a forged count", |state| {
+                state.incoming.is_empty()
+            })
+            .recv(
+                Id::from(1),
+                RegisterMsg::Internal(Chunked::Chunk(2, 0, 9, vec![0])),
+            )
+            .expect_state("nothing set aside past the limit", |state| {
+                state.incoming.is_empty()
+            });
+        script
+            .recv(
+                Id::from(1),
+                RegisterMsg::Internal(Chunked::Chunk(3, 0, 8, vec![0])),
+            )
+            .expect_state("a message within the limit awaited", |state| {
+                state.incoming.len() == 1
+            });
+    }
+}
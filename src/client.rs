@@ -0,0 +1,427 @@
+use crate::paxos::{PaxosActor, PaxosMsg, PaxosState, Value};
+use serde::{Deserialize, Serialize};
+use stateright::actor::register::RegisterMsg;
+use stateright::actor::{Actor, Command as ActorCommand, Id, Out};
+use stateright::{Rewrite, RewritePlan};
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Which value each put of a check writes. A value of its own for every put lets a check tell
+// requests apart by what was decided, but agreement and validity only ask whether two values
+// differ, and Paxos treats every value alike. Folding the puts onto a few symbolic values leaves
+// those properties as they were, while states that differ only in which value went where merge.
+pub trait ValueAbstraction<V>: Send + Sync {
+    // the value of the `put`th put of the run, counting each client's puts in turn
+    fn value(&self, put: usize) -> V;
+}
+
+// every put its own value, 'A' for the first
+pub struct Concrete;
+
+impl<V: From<char>> ValueAbstraction<V> for Concrete {
+    fn value(&self, put: usize) -> V {
+        V::from((b'A' + put as u8) as char)
+    }
+}
+
+// the puts take turns among this many symbolic values, v1 written 'A', v2 'B' and so on
+pub struct Symbolic {
+    tokens: usize,
+}
+
+impl Symbolic {
+    pub fn new(tokens: usize) -> Self {
+        Symbolic {
+            tokens: tokens.max(1),
+        }
+    }
+}
+
+impl<V: From<char>> ValueAbstraction<V> for Symbolic {
+    fn value(&self, put: usize) -> V {
+        V::from((b'A' + (put % self.tokens) as u8) as char)
+    }
+}
+
+// the puts take turns among these values, whatever they are
+pub struct Domain<V> {
+    values: Vec<V>,
+}
+
+impl<V> Domain<V> {
+    pub fn new(values: Vec<V>) -> Self {
+        assert!(!values.is_empty(), "a value domain needs a value");
+        Domain { values }
+    }
+}
+
+impl<V: Clone + Send + Sync> ValueAbstraction<V> for Domain<V> {
+    fn value(&self, put: usize) -> V {
+        self.values[put % self.values.len()].clone()
+    }
+}
+
+// the order a client makes its puts and gets in
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    // every put, then every get
+    PutsThenGets,
+    // a get straight after each put, so the history holds a read of every write, then the
+    // remaining gets
+    ReadAfterWrite,
+}
+
+// one operation of a client's workload
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Op {
+    // the client's `n`th put
+    Put(usize),
+    Get,
+}
+
+impl Pattern {
+    // the client's `op`th operation, None once it has made them all
+    fn op(self, op: usize, put_count: usize, get_count: usize) -> Option<Op> {
+        let paired = match self {
+            Pattern::PutsThenGets => 0,
+            Pattern::ReadAfterWrite => put_count,
+        };
+        if op < 2 * paired {
+            Some(if op % 2 == 0 { Op::Put(op / 2) } else { Op::Get })
+        } else if op < put_count + paired {
+            Some(Op::Put(op - paired))
+        } else if op < put_count + paired + get_count {
+            Some(Op::Get)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "puts-then-gets" => Ok(Pattern::PutsThenGets),
+            "read-after-write" => Ok(Pattern::ReadAfterWrite),
+            _ => Err(format!(
+                "unknown workload pattern {}, expected puts-then-gets or read-after-write",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::PutsThenGets => write!(f, "puts-then-gets"),
+            Pattern::ReadAfterWrite => write!(f, "read-after-write"),
+        }
+    }
+}
+
+// A register client that does not give up: a request left unanswered for the retry timeout is
+// sent again, under the same request id, to the next server in turn. Like `RegisterActor`'s
+// clients it makes `put_count` puts and then its gets, one at a time, unless its pattern says
+// otherwise. A thinking client waits on a timer before each request, so that it may be sent at
+// any point of the run rather than straight after the answer to the last one.
+pub struct PaxosClientActor<V> {
+    put_count: usize,
+    get_count: usize,
+    server_count: usize,
+    // how long to wait for an answer before trying the next server, never when None
+    retry: Option<Range<Duration>>,
+    // how long to wait before each request, never when None
+    think: Option<Range<Duration>>,
+    pattern: Pattern,
+    values: Arc<dyn ValueAbstraction<V>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct PaxosClientState {
+    // the request waiting for an answer, which a retry sends again as it was
+    awaiting: Option<u64>,
+    // requests sent so far, puts first and then the gets
+    op_count: usize,
+    // the server the request last went to
+    server: Id,
+}
+
+impl PaxosClientState {
+    pub fn awaiting(&self) -> Option<u64> {
+        self.awaiting
+    }
+
+    pub fn op_count(&self) -> usize {
+        self.op_count
+    }
+}
+
+impl<V: Value + From<char>> PaxosClientActor<V> {
+    pub fn new(put_count: usize, server_count: usize) -> Self {
+        PaxosClientActor {
+            put_count,
+            get_count: 1,
+            server_count,
+            retry: None,
+            think: None,
+            pattern: Pattern::PutsThenGets,
+            values: Arc::new(Concrete),
+        }
+    }
+
+    pub fn get_count(mut self, get_count: usize) -> Self {
+        self.get_count = get_count;
+        self
+    }
+
+    pub fn retry(mut self, retry: Option<Range<Duration>>) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn think(mut self, think: Option<Range<Duration>>) -> Self {
+        self.think = think;
+        self
+    }
+
+    pub fn pattern(mut self, pattern: Pattern) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    pub fn values(mut self, values: Arc<dyn ValueAbstraction<V>>) -> Self {
+        self.values = values;
+        self
+    }
+
+    // unique across clients and their requests, since a server deduplicates on it
+    fn request_id(id: Id, op: usize) -> u64 {
+        ((usize::from(id) as u64) << 32) | op as u64
+    }
+
+    // the request for an operation, the same however often it is sent; unless the values are
+    // abstracted every put writes a value of its own, so a decided value says which request it
+    // came from
+    fn request(&self, id: Id, op: usize) -> Option<RegisterMsg<u64, V, PaxosMsg<V>>> {
+        let request_id = Self::request_id(id, op);
+        match self.pattern.op(op, self.put_count, self.get_count)? {
+            Op::Put(put) => {
+                let client = usize::from(id) - self.server_count;
+                let value = self.values.value(client * self.put_count + put);
+                Some(RegisterMsg::Put(request_id, value))
+            }
+            Op::Get => Some(RegisterMsg::Get(request_id)),
+        }
+    }
+
+    // waits before an operation's request, or goes straight on when not thinking
+    fn next(&self, id: Id, op: usize, server: Id, o: &mut Out<Self>) -> PaxosClientState {
+        match &self.think {
+            Some(think) if self.request(id, op).is_some() => {
+                o.set_timer(think.clone());
+                PaxosClientState {
+                    awaiting: None,
+                    op_count: op,
+                    server,
+                }
+            }
+            _ => self.send(id, op, server, o),
+        }
+    }
+
+    // sends an operation's request and waits on it, or stops once every operation is done
+    fn send(&self, id: Id, op: usize, server: Id, o: &mut Out<Self>) -> PaxosClientState {
+        let awaiting = match self.request(id, op) {
+            Some(msg) => {
+                o.send(server, msg);
+                if let Some(retry) = &self.retry {
+                    o.set_timer(retry.clone());
+                }
+                Some(Self::request_id(id, op))
+            }
+            None => {
+                o.cancel_timer();
+                None
+            }
+        };
+        PaxosClientState {
+            awaiting,
+            op_count: op + 1,
+            server,
+        }
+    }
+}
+
+impl<V: Value + From<char>> Actor for PaxosClientActor<V> {
+    type Msg = RegisterMsg<u64, V, PaxosMsg<V>>;
+    type State = PaxosClientState;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        let server = Id::from(usize::from(id) % self.server_count);
+        self.next(id, 0, server, o)
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        let request_id = match msg {
+            RegisterMsg::PutOk(request_id) | RegisterMsg::GetOk(request_id, _) => request_id,
+            _ => return,
+        };
+        // answers to earlier attempts at a request already answered are ignored
+        if state.awaiting != Some(request_id) {
+            return;
+        }
+        // the server that answered, perhaps the leader a follower forwarded to, gets the next one
+        *state = Cow::Owned(self.next(id, state.op_count, src, o));
+    }
+
+    fn on_timeout(&self, id: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        if state.awaiting.is_none() {
+            // done thinking, the next request goes out
+            if self.think.is_some() && self.request(id, state.op_count).is_some() {
+                *state = Cow::Owned(self.send(id, state.op_count, state.server, o));
+            }
+            return;
+        }
+        let server = Id::from((usize::from(state.server) + 1) % self.server_count);
+        *state = Cow::Owned(self.send(id, state.op_count - 1, server, o));
+    }
+}
+
+impl Rewrite<Id> for PaxosClientState {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        PaxosClientState {
+            awaiting: self.awaiting,
+            op_count: self.op_count,
+            server: plan.rewrite(&self.server),
+        }
+    }
+}
+
+// clients and servers have to share an actor type in the model, as with `RegisterActor`
+pub enum PaxosModelActor<V> {
+    Client(PaxosClientActor<V>),
+    Server(PaxosActor<V>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub enum PaxosModelActorState<V> {
+    Client(PaxosClientState),
+    Server(PaxosState<V>),
+}
+
+impl<V: Value + From<char>> PaxosModelActor<V> {
+    // both sides speak the same messages, only the type of the output differs
+    fn forward<A>(inner: Out<A>, o: &mut Out<Self>)
+    where
+        A: Actor<Msg = RegisterMsg<u64, V, PaxosMsg<V>>>,
+    {
+        for command in inner {
+            match command {
+                ActorCommand::Send(dst, msg) => o.send(dst, msg),
+                ActorCommand::SetTimer(duration) => o.set_timer(duration),
+                ActorCommand::CancelTimer => o.cancel_timer(),
+            }
+        }
+    }
+}
+
+impl<V: Value + From<char>> Actor for PaxosModelActor<V> {
+    type Msg = RegisterMsg<u64, V, PaxosMsg<V>>;
+    type State = PaxosModelActorState<V>;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        let mut inner = Out::new();
+        let state = match self {
+            PaxosModelActor::Client(client) => {
+                PaxosModelActorState::Client(client.on_start(id, &mut inner))
+            }
+            PaxosModelActor::Server(server) => {
+                PaxosModelActorState::Server(server.on_start(id, &mut inner))
+            }
+        };
+        Self::forward(inner, o);
+        state
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match (self, state.as_ref()) {
+            (PaxosModelActor::Client(client), PaxosModelActorState::Client(client_state)) => {
+                let mut client_state = Cow::Borrowed(client_state);
+                let mut inner = Out::new();
+                client.on_msg(id, &mut client_state, src, msg, &mut inner);
+                if let Cow::Owned(client_state) = client_state {
+                    *state = Cow::Owned(PaxosModelActorState::Client(client_state));
+                }
+                Self::forward(inner, o);
+            }
+            (PaxosModelActor::Server(server), PaxosModelActorState::Server(server_state)) => {
+                let mut server_state = Cow::Borrowed(server_state);
+                let mut inner = Out::new();
+                server.on_msg(id, &mut server_state, src, msg, &mut inner);
+                if let Cow::Owned(server_state) = server_state {
+                    *state = Cow::Owned(PaxosModelActorState::Server(server_state));
+                }
+                Self::forward(inner, o);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_timeout(&self, id: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        match (self, state.as_ref()) {
+            (PaxosModelActor::Client(client), PaxosModelActorState::Client(client_state)) => {
+                let mut client_state = Cow::Borrowed(client_state);
+                let mut inner = Out::new();
+                client.on_timeout(id, &mut client_state, &mut inner);
+                if let Cow::Owned(client_state) = client_state {
+                    *state = Cow::Owned(PaxosModelActorState::Client(client_state));
+                }
+                Self::forward(inner, o);
+            }
+            (PaxosModelActor::Server(server), PaxosModelActorState::Server(server_state)) => {
+                let mut server_state = Cow::Borrowed(server_state);
+                let mut inner = Out::new();
+                server.on_timeout(id, &mut server_state, &mut inner);
+                if let Cow::Owned(server_state) = server_state {
+                    *state = Cow::Owned(PaxosModelActorState::Server(server_state));
+                }
+                Self::forward(inner, o);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<V: Clone> Rewrite<Id> for PaxosModelActorState<V> {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        match self {
+            PaxosModelActorState::Client(client_state) => {
+                PaxosModelActorState::Client(client_state.rewrite(plan))
+            }
+            PaxosModelActorState::Server(server_state) => {
+                PaxosModelActorState::Server(server_state.rewrite(plan))
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::{Duration, Instant};
+
+// Time as a replica reads it, which only ever goes forward. In the model a replica's clock is
+// the count of ticks kept in its state, each tick a message the replica sends itself and that
+// is delivered at an arbitrary point, so the checker tries every rate the clocks could run at
+// and the model bounds how far apart they get. Outside the model it is the process's monotonic
+// clock, and the ticks stay at zero.
+#[derive(Clone, Debug)]
+pub enum Clock {
+    // each tick stands for `tick` of time
+    Logical { tick: Duration },
+    Monotonic { started: Instant },
+}
+
+impl Clock {
+    pub fn logical(tick: Duration) -> Self {
+        Clock::Logical { tick }
+    }
+
+    // starts counting now
+    pub fn monotonic() -> Self {
+        Clock::Monotonic {
+            started: Instant::now(),
+        }
+    }
+
+    pub fn is_logical(&self) -> bool {
+        matches!(self, Clock::Logical { .. })
+    }
+
+    // the time since the replica started, given the ticks its state has counted
+    pub fn now(&self, ticks: u64) -> Duration {
+        match self {
+            Clock::Logical { tick } => *tick * ticks as u32,
+            Clock::Monotonic { started } => started.elapsed(),
+        }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::monotonic()
+    }
+}
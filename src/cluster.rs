@@ -0,0 +1,267 @@
+use crate::paxos::{PaxosActor, Value};
+use crate::roles::{AcceptorActor, LearnerActor, ProposerActor, RoleActor};
+use serde::{Deserialize, Serialize};
+use stateright::actor::Id;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::net::SocketAddrV4;
+use std::path::{Path, PathBuf};
+
+// the replicas of a real deployment, as listed in a JSON config file:
+// { "peers": ["127.0.0.1:3000", "127.0.0.1:3001", "127.0.0.1:3002"] }
+// With the roles separated the peers are the proposers, the replicas clients talk to, and the
+// acceptors and learners are listed after them:
+// { "peers": ["127.0.0.1:3000"], "acceptors": ["127.0.0.1:3100", ...], "learners": [...] }
+// A file ending in .toml lists the nodes instead, each with the certificate and key it proves
+// itself with when the replicas talk over TLS:
+// [[nodes]]
+// id = 1
+// address = "127.0.0.1:3000"
+// cert = "certs/node1.pem"
+// key = "certs/node1.key"
+// and `role = "acceptor"` or `"learner"` for nodes that are not peers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClusterConfig {
+    peers: Vec<SocketAddrV4>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    acceptors: Vec<SocketAddrV4>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    learners: Vec<SocketAddrV4>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    nodes: Vec<Node>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeRole {
+    Peer,
+    Acceptor,
+    Learner,
+}
+
+impl Default for NodeRole {
+    fn default() -> Self {
+        NodeRole::Peer
+    }
+}
+
+// a node as a TOML config lists it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Node {
+    id: u64,
+    address: SocketAddrV4,
+    #[serde(default)]
+    role: NodeRole,
+    // PEM files, the certificate chain starting with the node's own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cert: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<PathBuf>,
+}
+
+impl Node {
+    pub fn new(id: u64, address: SocketAddrV4) -> Self {
+        Node {
+            id,
+            address,
+            role: NodeRole::Peer,
+            cert: None,
+            key: None,
+        }
+    }
+
+    pub fn role(mut self, role: NodeRole) -> Self {
+        self.role = role;
+        self
+    }
+
+    pub fn tls(mut self, cert: PathBuf, key: PathBuf) -> Self {
+        self.cert = Some(cert);
+        self.key = Some(key);
+        self
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn address(&self) -> SocketAddrV4 {
+        self.address
+    }
+
+    pub fn cert(&self) -> Option<&Path> {
+        self.cert.as_deref()
+    }
+
+    pub fn key(&self) -> Option<&Path> {
+        self.key.as_deref()
+    }
+}
+
+#[derive(Deserialize)]
+struct NodeList {
+    nodes: Vec<Node>,
+}
+
+fn invalid_data(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl ClusterConfig {
+    pub fn new(peers: Vec<SocketAddrV4>) -> Self {
+        ClusterConfig {
+            peers,
+            acceptors: Vec::new(),
+            learners: Vec::new(),
+            nodes: Vec::new(),
+        }
+    }
+
+    // the peers, acceptors and learners among `nodes`, which must not share an id or address
+    pub fn from_nodes(nodes: Vec<Node>) -> io::Result<Self> {
+        let ids: BTreeSet<u64> = nodes.iter().map(|node| node.id).collect();
+        let addresses: BTreeSet<SocketAddrV4> = nodes.iter().map(|node| node.address).collect();
+        if ids.len() < nodes.len() || addresses.len() < nodes.len() {
+            return Err(invalid_data("two nodes share an id or an address"));
+        }
+        let with = |role| {
+            nodes
+                .iter()
+                .filter(|node| node.role == role)
+                .map(|node| node.address)
+                .collect()
+        };
+        Ok(ClusterConfig {
+            peers: with(NodeRole::Peer),
+            acceptors: with(NodeRole::Acceptor),
+            learners: with(NodeRole::Learner),
+            nodes,
+        })
+    }
+
+    // runs the peers as proposers in front of separate acceptors and learners
+    pub fn roles(mut self, acceptors: Vec<SocketAddrV4>, learners: Vec<SocketAddrV4>) -> Self {
+        self.acceptors = acceptors;
+        self.learners = learners;
+        self
+    }
+
+    pub fn is_role_separated(&self) -> bool {
+        !self.acceptors.is_empty()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        if path
+            .extension()
+            .map_or(false, |extension| extension == "toml")
+        {
+            let text = String::from_utf8(bytes).map_err(invalid_data)?;
+            let list: NodeList = toml::from_str(&text).map_err(invalid_data)?;
+            return Self::from_nodes(list.nodes);
+        }
+        serde_json::from_slice(&bytes).map_err(invalid_data)
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    // whether every node has a certificate and key, so the replicas only talk over TLS
+    pub fn is_tls(&self) -> bool {
+        !self.nodes.is_empty()
+            && self
+                .nodes
+                .iter()
+                .all(|node| node.cert.is_some() && node.key.is_some())
+    }
+
+    pub fn peers(&self) -> &[SocketAddrV4] {
+        &self.peers
+    }
+
+    // every address in the cluster, whatever its role
+    pub fn members(&self) -> Vec<SocketAddrV4> {
+        self.peers
+            .iter()
+            .chain(&self.acceptors)
+            .chain(&self.learners)
+            .copied()
+            .collect()
+    }
+
+    // the actor listening on the given replica address, wired up to every other replica
+    pub fn actor<V: Value>(&self, addr: SocketAddrV4) -> PaxosActor<V> {
+        let peers = self
+            .peers
+            .iter()
+            .filter(|peer| **peer != addr)
+            .map(|peer| Id::from(*peer))
+            .collect();
+        PaxosActor::new(peers)
+    }
+
+    // every replica in the config, ready to hand to `transport::run`
+    pub fn actors<V: Value>(&self) -> Vec<(SocketAddrV4, PaxosActor<V>)> {
+        self.peers
+            .iter()
+            .map(|addr| (*addr, self.actor(*addr)))
+            .collect()
+    }
+
+    // every proposer, acceptor and learner in the config, each wired up to the roles it talks to
+    pub fn role_actors<V: Value>(&self) -> Vec<(SocketAddrV4, RoleActor<V>)> {
+        let ids =
+            |addrs: &[SocketAddrV4]| -> Vec<Id> { addrs.iter().copied().map(Id::from).collect() };
+        let proposers = self.peers.iter().map(|addr| {
+            let proposer = ProposerActor::new(ids(&self.acceptors), ids(&self.learners));
+            (*addr, RoleActor::Proposer(proposer))
+        });
+        let acceptors = self.acceptors.iter().map(|addr| {
+            let acceptor = AcceptorActor::new(ids(&self.learners));
+            (*addr, RoleActor::Acceptor(acceptor))
+        });
+        let learners = self.learners.iter().map(|addr| {
+            let learner = LearnerActor::new(ids(&self.acceptors));
+            (*addr, RoleActor::Learner(learner))
+        });
+        proposers.chain(acceptors).chain(learners).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn toml_configs_sort_nodes_by_role() {
+        let list: NodeList = toml::from_str(
+            r#"
+            [[nodes]]
+            id = 1
+            address = "127.0.0.1:3000"
+            cert = "certs/node1.pem"
+            key = "certs/node1.key"
+
+            [[nodes]]
+            id = 2
+            address = "127.0.0.1:3100"
+            role = "acceptor"
+            "#,
+        )
+        .unwrap();
+        let cluster = ClusterConfig::from_nodes(list.nodes).unwrap();
+        assert_eq!(cluster.peers(), ["127.0.0.1:3000".parse().unwrap()]);
+        assert!(cluster.is_role_separated());
+        assert!(!cluster.is_tls());
+        assert_eq!(
+            cluster.nodes()[0].cert(),
+            Some(Path::new("certs/node1.pem"))
+        );
+
+        let twice = vec![Node::new(1, "127.0.0.1:3000".parse().unwrap()); 2];
+        assert!(ClusterConfig::from_nodes(twice).is_err());
+    }
+}
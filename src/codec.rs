@@ -0,0 +1,285 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io;
+
+// a serde data format that messages can go over the wire in
+pub trait WireCodec {
+    fn encode<M: Serialize>(msg: &M) -> io::Result<Vec<u8>>;
+    fn decode<M: DeserializeOwned>(bytes: &[u8]) -> io::Result<M>;
+}
+
+// what `stateright::actor::spawn` sends, and the easiest to read off the wire
+pub struct Json;
+
+// the most compact, though hardly anything but Rust reads it
+pub struct Bincode;
+
+// binary like bincode, but self-describing and with a library in most languages
+pub struct Cbor;
+
+fn invalid_data(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl WireCodec for Json {
+    fn encode<M: Serialize>(msg: &M) -> io::Result<Vec<u8>> {
+        serde_json::to_vec(msg).map_err(invalid_data)
+    }
+
+    fn decode<M: DeserializeOwned>(bytes: &[u8]) -> io::Result<M> {
+        serde_json::from_slice(bytes).map_err(invalid_data)
+    }
+}
+
+impl WireCodec for Bincode {
+    fn encode<M: Serialize>(msg: &M) -> io::Result<Vec<u8>> {
+        bincode::serialize(msg).map_err(invalid_data)
+    }
+
+    fn decode<M: DeserializeOwned>(bytes: &[u8]) -> io::Result<M> {
+        bincode::deserialize(bytes).map_err(invalid_data)
+    }
+}
+
+impl WireCodec for Cbor {
+    fn encode<M: Serialize>(msg: &M) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(msg, &mut bytes).map_err(|e| invalid_data(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    fn decode<M: DeserializeOwned>(bytes: &[u8]) -> io::Result<M> {
+        ciborium::de::from_reader(bytes).map_err(|e| invalid_data(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chunking::Chunked;
+    use crate::features::Features;
+    use crate::paxos::{Applied, Command, PaxosMsg, RoundIdentifier, Session};
+    use stateright::actor::register::RegisterMsg;
+    use stateright::actor::Id;
+    use std::fmt::Debug;
+
+    // xorshift, so that every run fuzzes the same messages and a failure can be replayed
+    struct Fuzz(u64);
+
+    impl Fuzz {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        // mostly small numbers, with the edges of the range thrown in
+        fn number(&mut self) -> u64 {
+            match self.below(4) {
+                0 => 0,
+                1 => u64::MAX,
+                2 => self.next(),
+                _ => self.below(8),
+            }
+        }
+
+        fn id(&mut self) -> Id {
+            Id::from(self.number() as usize)
+        }
+
+        fn ids(&mut self) -> Vec<Id> {
+            (0..self.below(4)).map(|_| self.id()).collect()
+        }
+
+        // ascii, but also multi byte characters and the last one there is
+        fn value(&mut self) -> char {
+            match self.below(4) {
+                0 => char::MAX,
+                1 => 'λ',
+                _ => (b'A' + self.below(26) as u8) as char,
+            }
+        }
+
+        fn round(&mut self) -> RoundIdentifier {
+            RoundIdentifier::new(self.number(), self.id())
+        }
+
+        fn command(&mut self) -> Command<char> {
+            match self.below(4) {
+                0 => Command::Put(self.value()),
+                1 => Command::SessionPut(Session::new(self.id(), self.number()), self.value()),
+                2 => Command::Batch((0..self.below(4)).map(|_| self.value()).collect()),
+                _ => Command::Reconfigure(self.ids()),
+            }
+        }
+
+        fn applied(&mut self) -> Applied<char> {
+            let members = match self.below(2) {
+                0 => None,
+                _ => Some((self.number(), self.ids())),
+            };
+            let sessions = (0..self.below(4))
+                .map(|_| (self.id(), self.number()))
+                .collect();
+            Applied::from_parts(self.number(), self.value(), members, sessions)
+        }
+
+        fn paxos(&mut self) -> PaxosMsg<char> {
+            match self.below(31) {
+                0 => PaxosMsg::Prepare(self.number(), self.id(), self.number(), self.round()),
+                1 => {
+                    let accepted = match self.below(2) {
+                        0 => None,
+                        _ => Some((self.round(), self.command())),
+                    };
+                    PaxosMsg::Promise(
+                        self.number(),
+                        self.id(),
+                        self.number(),
+                        self.round(),
+                        accepted,
+                    )
+                }
+                2 => PaxosMsg::Accept(
+                    self.number(),
+                    self.id(),
+                    self.number(),
+                    self.round(),
+                    self.command(),
+                ),
+                3 => PaxosMsg::Accepted(
+                    self.number(),
+                    self.id(),
+                    self.number(),
+                    self.round(),
+                    self.command(),
+                ),
+                4 => PaxosMsg::Nack(self.number(), self.number(), self.round()),
+                5 => PaxosMsg::Decided(self.number(), self.round(), self.command()),
+                6 => PaxosMsg::Heartbeat(self.number(), self.round()),
+                7 => PaxosMsg::Forward(self.number(), self.id(), self.command()),
+                8 => PaxosMsg::Reconfigure(self.number(), self.ids()),
+                9 => PaxosMsg::Crash,
+                10 => PaxosMsg::Split,
+                11 => PaxosMsg::Heal,
+                12 => PaxosMsg::Snapshot(self.applied()),
+                13 => PaxosMsg::Read(self.number(), self.number()),
+                14 => {
+                    let decided = match self.below(2) {
+                        0 => None,
+                        _ => Some(self.value()),
+                    };
+                    let accepted = match self.below(2) {
+                        0 => None,
+                        _ => Some((self.round(), self.value())),
+                    };
+                    PaxosMsg::ReadReply(self.number(), self.number(), decided, accepted)
+                }
+                15 => PaxosMsg::LeaseRequest(self.round()),
+                16 => PaxosMsg::LeaseGrant(self.round(), self.number()),
+                17 => PaxosMsg::LeaseExpired(self.round()),
+                18 => PaxosMsg::Suspect(self.round()),
+                19 => PaxosMsg::Trust(self.round()),
+                20 => PaxosMsg::CatchUpRequest(self.number()),
+                21 => {
+                    let witnessed = match self.below(2) {
+                        0 => None,
+                        _ => Some(self.round()),
+                    };
+                    PaxosMsg::WitnessPromise(
+                        self.number(),
+                        self.id(),
+                        self.number(),
+                        self.round(),
+                        witnessed,
+                    )
+                }
+                22 => PaxosMsg::Expire(self.number(), self.round(), self.round()),
+                23 => PaxosMsg::Tick,
+                24 => PaxosMsg::Probe(self.number(), self.round()),
+                25 => PaxosMsg::ProbeReply(self.number(), self.round(), self.below(2) == 0),
+                26 => PaxosMsg::TransferLeadership(self.id()),
+                27 => PaxosMsg::Handoff(
+                    self.round(),
+                    self.number(),
+                    (0..self.below(4))
+                        .map(|_| (self.number(), self.id(), self.command()))
+                        .collect(),
+                ),
+                28 => PaxosMsg::Hello(Features::from_bits(self.below(8) as u32)),
+                29 => PaxosMsg::Welcome(Features::from_bits(self.below(8) as u32)),
+                _ => PaxosMsg::CatchUpReply(
+                    (0..self.below(4))
+                        .map(|_| (self.number(), self.command()))
+                        .collect(),
+                ),
+            }
+        }
+
+        // what a spawned replica puts on the wire
+        fn replica_msg(&mut self) -> RegisterMsg<u64, char, Chunked<PaxosMsg<char>>> {
+            match self.below(7) {
+                0 => RegisterMsg::Put(self.number(), self.value()),
+                1 => RegisterMsg::Get(self.number()),
+                2 => RegisterMsg::PutOk(self.number()),
+                3 => RegisterMsg::GetOk(self.number(), self.value()),
+                4 => {
+                    let data = (0..self.below(16)).map(|_| self.next() as u8).collect();
+                    let chunk = Chunked::Chunk(self.next(), self.number() as u32, 2, data);
+                    RegisterMsg::Internal(chunk)
+                }
+                5 => RegisterMsg::Internal(Chunked::ChunkAck(self.next(), self.number() as u32)),
+                _ => RegisterMsg::Internal(Chunked::Whole(self.paxos())),
+            }
+        }
+    }
+
+    fn round_trips<M: Serialize + DeserializeOwned + PartialEq + Debug>(
+        msg: M,
+        encode: fn(&M) -> io::Result<Vec<u8>>,
+        decode: fn(&[u8]) -> io::Result<M>,
+    ) {
+        let bytes = encode(&msg).unwrap_or_else(|e| panic!("encoding {:?}: {}", msg, e));
+        let decoded = decode(&bytes).unwrap_or_else(|e| panic!("decoding {:?}: {}", msg, e));
+        assert_eq!(decoded, msg);
+    }
+
+    fn fuzz<C: WireCodec>() {
+        let mut fuzz = Fuzz(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2_000 {
+            round_trips(fuzz.paxos(), C::encode, C::decode);
+            round_trips(fuzz.replica_msg(), C::encode, C::decode);
+        }
+    }
+
+    #[test]
+    fn json_round_trips_every_message() {
+        fuzz::<Json>();
+    }
+
+    #[test]
+    fn bincode_round_trips_every_message() {
+        fuzz::<Bincode>();
+    }
+
+    #[test]
+    fn cbor_round_trips_every_message() {
+        fuzz::<Cbor>();
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn protobuf_round_trips_every_message() {
+        let mut fuzz = Fuzz(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2_000 {
+            let msg = fuzz.replica_msg();
+            let bytes = crate::wire::encode(&msg).unwrap();
+            assert_eq!(crate::wire::decode(&bytes).unwrap(), Some(msg));
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use crate::paxos::Slot;
+use stateright::{Model, Property};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Checking Multi-Paxos whole means exploring every interleaving of every slot's messages with
+// every other's, which is what keeps the actor models to a put or two. Here the two concerns
+// are checked apart: `SingleDecree` checks one instance of consensus on its own, with an
+// abstract environment proposing values in place of clients, and `LogModel` checks the
+// replicated log on the assumption that each slot behaves as an instance was just shown to,
+// deciding at most one value and only one proposed in it. Each model's states stay small, and
+// together they cover what the log relies on.
+pub trait Consensus: Model {
+    // every value each slot has been chosen with so far, more than one breaks agreement
+    fn chosen(&self, state: &Self::State) -> BTreeMap<Slot, BTreeSet<char>>;
+    // the values the environment has asked for in each slot
+    fn proposed(&self, state: &Self::State) -> BTreeMap<Slot, BTreeSet<char>>;
+}
+
+// no slot is chosen with two values
+pub fn agreement<M: Consensus>(model: &M, state: &M::State) -> bool {
+    model.chosen(state).values().all(|values| values.len() <= 1)
+}
+
+// only a value proposed in a slot is ever chosen in it
+pub fn validity<M: Consensus>(model: &M, state: &M::State) -> bool {
+    let proposed = model.proposed(state);
+    model.chosen(state).iter().all(|(slot, values)| {
+        proposed
+            .get(slot)
+            .map_or(values.is_empty(), |proposed| values.is_subset(proposed))
+    })
+}
+
+pub fn value_chosen<M: Consensus>(model: &M, state: &M::State) -> bool {
+    model
+        .chosen(state)
+        .values()
+        .any(|values| !values.is_empty())
+}
+
+// Single-decree Paxos over the set of messages sent so far, as in Lamport's `Paxos.tla`: a
+// message once sent can be received any number of times, so there is no network to model
+// beyond the set. Ballots are owned by whichever proposer prepares them first, and each gets at
+// most one accept.
+#[derive(Clone, Debug)]
+pub struct SingleDecree {
+    acceptors: usize,
+    values: Vec<char>,
+    ballots: u64,
+}
+
+impl SingleDecree {
+    pub fn new(acceptors: usize, values: Vec<char>, ballots: u64) -> Self {
+        SingleDecree {
+            acceptors,
+            values,
+            ballots,
+        }
+    }
+
+    fn is_quorum(&self, count: usize) -> bool {
+        count > self.acceptors / 2
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DecreeMsg {
+    Prepare(u64),
+    // the acceptor, the ballot it promised and its latest vote
+    Promise(usize, u64, Option<(u64, char)>),
+    Accept(u64, char),
+    Accepted(usize, u64, char),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DecreeAction {
+    // the environment asks for a value
+    Propose(char),
+    Prepare(u64),
+    Promise(usize, u64),
+    Accept(u64, char),
+    Accepted(usize, u64, char),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DecreeState {
+    // each acceptor's highest promised ballot and latest vote
+    promised: Vec<Option<u64>>,
+    voted: Vec<Option<(u64, char)>>,
+    msgs: BTreeSet<DecreeMsg>,
+    proposed: BTreeSet<char>,
+}
+
+impl DecreeState {
+    // the value a quorum of promises for `ballot` leaves the proposer free to accept: the
+    // latest voted for among them, or anything proposed when none voted
+    fn acceptable(&self, ballot: u64) -> (usize, Option<char>) {
+        let promises: Vec<Option<(u64, char)>> = self
+            .msgs
+            .iter()
+            .filter_map(|msg| match msg {
+                DecreeMsg::Promise(_, b, voted) if *b == ballot => Some(*voted),
+                _ => None,
+            })
+            .collect();
+        let latest = promises.iter().flatten().max().map(|(_, value)| *value);
+        (promises.len(), latest)
+    }
+}
+
+impl Model for SingleDecree {
+    type State = DecreeState;
+    type Action = DecreeAction;
+
+    fn init_states(&self) -> Vec<Self::State> {
+        vec![DecreeState {
+            promised: vec![None; self.acceptors],
+            voted: vec![None; self.acceptors],
+            msgs: BTreeSet::new(),
+            proposed: BTreeSet::new(),
+        }]
+    }
+
+    fn actions(&self, state: &Self::State, actions: &mut Vec<Self::Action>) {
+        for value in &self.values {
+            if !state.proposed.contains(value) {
+                actions.push(DecreeAction::Propose(*value));
+            }
+        }
+        for ballot in 0..self.ballots {
+            if !state.msgs.contains(&DecreeMsg::Prepare(ballot)) {
+                actions.push(DecreeAction::Prepare(ballot));
+            }
+        }
+        for msg in &state.msgs {
+            match msg {
+                DecreeMsg::Prepare(ballot) => {
+                    for acceptor in 0..self.acceptors {
+                        if state.promised[acceptor] < Some(*ballot) {
+                            actions.push(DecreeAction::Promise(acceptor, *ballot));
+                        }
+                    }
+                    let accepted = state
+                        .msgs
+                        .iter()
+                        .any(|msg| matches!(msg, DecreeMsg::Accept(b, _) if b == ballot));
+                    let (promises, latest) = state.acceptable(*ballot);
+                    if accepted || !self.is_quorum(promises) {
+                        continue;
+                    }
+                    match latest {
+                        Some(value) => actions.push(DecreeAction::Accept(*ballot, value)),
+                        None => {
+                            for value in &state.proposed {
+                                actions.push(DecreeAction::Accept(*ballot, *value));
+                            }
+                        }
+                    }
+                }
+                DecreeMsg::Accept(ballot, value) => {
+                    for acceptor in 0..self.acceptors {
+                        let voted = state.voted[acceptor] == Some((*ballot, *value));
+                        if !voted && state.promised[acceptor] <= Some(*ballot) {
+                            actions.push(DecreeAction::Accepted(acceptor, *ballot, *value));
+                        }
+                    }
+                }
+                DecreeMsg::Promise(..) | DecreeMsg::Accepted(..) => {}
+            }
+        }
+    }
+
+    fn next_state(&self, last_state: &Self::State, action: Self::Action) -> Option<Self::State> {
+        let mut state = last_state.clone();
+        match action {
+            DecreeAction::Propose(value) => {
+                state.proposed.insert(value);
+            }
+            DecreeAction::Prepare(ballot) => {
+                state.msgs.insert(DecreeMsg::Prepare(ballot));
+            }
+            DecreeAction::Promise(acceptor, ballot) => {
+                state.promised[acceptor] = Some(ballot);
+                let voted = state.voted[acceptor];
+                state
+                    .msgs
+                    .insert(DecreeMsg::Promise(acceptor, ballot, voted));
+            }
+            DecreeAction::Accept(ballot, value) => {
+                state.msgs.insert(DecreeMsg::Accept(ballot, value));
+            }
+            DecreeAction::Accepted(acceptor, ballot, value) => {
+                state.promised[acceptor] = Some(ballot);
+                state.voted[acceptor] = Some((ballot, value));
+                state
+                    .msgs
+                    .insert(DecreeMsg::Accepted(acceptor, ballot, value));
+            }
+        }
+        Some(state)
+    }
+
+    fn properties(&self) -> Vec<Property<Self>> {
+        vec![
+            Property::always("agreement", agreement::<Self>),
+            Property::always("validity", validity::<Self>),
+            Property::sometimes("value chosen", value_chosen::<Self>),
+        ]
+    }
+}
+
+impl Consensus for SingleDecree {
+    fn chosen(&self, state: &Self::State) -> BTreeMap<Slot, BTreeSet<char>> {
+        let mut votes: BTreeMap<(u64, char), usize> = BTreeMap::new();
+        for msg in &state.msgs {
+            if let DecreeMsg::Accepted(_, ballot, value) = msg {
+                *votes.entry((*ballot, *value)).or_default() += 1;
+            }
+        }
+        let chosen = votes
+            .into_iter()
+            .filter(|(_, count)| self.is_quorum(*count))
+            .map(|((_, value), _)| value)
+            .collect();
+        BTreeMap::from([(0, chosen)])
+    }
+
+    fn proposed(&self, state: &Self::State) -> BTreeMap<Slot, BTreeSet<char>> {
+        BTreeMap::from([(0, state.proposed.clone())])
+    }
+}
+
+// The replicated log, with each slot's instance of consensus cut down to what `SingleDecree`
+// shows of it: a slot decides once, on one of the values proposed in it. The environment hands
+// each value to a free slot, and again to another should it lose its slot to a different
+// value; replicas apply decided slots in order.
+#[derive(Clone, Debug)]
+pub struct LogModel {
+    replicas: usize,
+    slots: usize,
+    values: Vec<char>,
+}
+
+impl LogModel {
+    pub fn new(replicas: usize, slots: usize, values: Vec<char>) -> Self {
+        LogModel {
+            replicas,
+            slots,
+            values,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LogAction {
+    Propose(Slot, char),
+    // the slot's instance decides
+    Decide(Slot, char),
+    // the replica applies its next slot
+    Apply(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LogState {
+    proposed: BTreeMap<Slot, BTreeSet<char>>,
+    decided: BTreeMap<Slot, char>,
+    applied: Vec<Vec<char>>,
+}
+
+impl Model for LogModel {
+    type State = LogState;
+    type Action = LogAction;
+
+    fn init_states(&self) -> Vec<Self::State> {
+        vec![LogState {
+            proposed: BTreeMap::new(),
+            decided: BTreeMap::new(),
+            applied: vec![Vec::new(); self.replicas],
+        }]
+    }
+
+    fn actions(&self, state: &Self::State, actions: &mut Vec<Self::Action>) {
+        let open = |slot: &Slot| !state.decided.contains_key(slot);
+        for value in &self.values {
+            // waiting on a slot, or already in the log
+            let pending = state
+                .proposed
+                .iter()
+                .any(|(slot, values)| open(slot) && values.contains(value));
+            if pending || state.decided.values().any(|decided| decided == value) {
+                continue;
+            }
+            for slot in (0..self.slots).filter(open) {
+                actions.push(LogAction::Propose(slot, *value));
+            }
+        }
+        for (slot, values) in &state.proposed {
+            if open(slot) {
+                for value in values {
+                    actions.push(LogAction::Decide(*slot, *value));
+                }
+            }
+        }
+        for (replica, applied) in state.applied.iter().enumerate() {
+            if state.decided.contains_key(&applied.len()) {
+                actions.push(LogAction::Apply(replica));
+            }
+        }
+    }
+
+    fn next_state(&self, last_state: &Self::State, action: Self::Action) -> Option<Self::State> {
+        let mut state = last_state.clone();
+        match action {
+            LogAction::Propose(slot, value) => {
+                state.proposed.entry(slot).or_default().insert(value);
+            }
+            LogAction::Decide(slot, value) => {
+                state.decided.insert(slot, value);
+            }
+            LogAction::Apply(replica) => {
+                let next = state.applied[replica].len();
+                let value = *state.decided.get(&next)?;
+                state.applied[replica].push(value);
+            }
+        }
+        Some(state)
+    }
+
+    fn properties(&self) -> Vec<Property<Self>> {
+        vec![
+            Property::always("agreement", agreement::<Self>),
+            Property::always("validity", validity::<Self>),
+            Property::always("applied logs agree", |_, state| {
+                let longest = state.applied.iter().max_by_key(|log| log.len());
+                state
+                    .applied
+                    .iter()
+                    .all(|log| longest.map_or(true, |longest| longest.starts_with(log)))
+            }),
+            Property::always("applied once", |_, state| {
+                state.applied.iter().all(|log| {
+                    let distinct: BTreeSet<&char> = log.iter().collect();
+                    distinct.len() == log.len()
+                })
+            }),
+            Property::sometimes("value chosen", value_chosen::<Self>),
+            Property::sometimes("log applied", |model, state| {
+                state.applied.iter().all(|log| log.len() == model.slots)
+            }),
+        ]
+    }
+}
+
+impl Consensus for LogModel {
+    fn chosen(&self, state: &Self::State) -> BTreeMap<Slot, BTreeSet<char>> {
+        // what the replicas applied, which is all anyone outside the log sees
+        let mut chosen: BTreeMap<Slot, BTreeSet<char>> = BTreeMap::new();
+        for log in &state.applied {
+            for (slot, value) in log.iter().enumerate() {
+                chosen.entry(slot).or_default().insert(*value);
+            }
+        }
+        chosen
+    }
+
+    fn proposed(&self, state: &Self::State) -> BTreeMap<Slot, BTreeSet<char>> {
+        state.proposed.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use stateright::Checker;
+
+    #[test]
+    fn single_decree_paxos_chooses_one_proposed_value() {
+        let checker = SingleDecree::new(3, vec!['A', 'B'], 2)
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("agreement");
+        checker.assert_no_discovery("validity");
+        assert!(checker.discovery("value chosen").is_some());
+    }
+
+    #[test]
+    fn log_composed_of_instances_applies_one_history() {
+        let checker = LogModel::new(3, 2, vec!['A', 'B', 'C'])
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("agreement");
+        checker.assert_no_discovery("validity");
+        checker.assert_no_discovery("applied logs agree");
+        checker.assert_no_discovery("applied once");
+        assert!(checker.discovery("log applied").is_some());
+    }
+}
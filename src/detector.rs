@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+// decides from the heartbeats heard so far whether a leader has gone quiet; times are measured
+// from any fixed point, the same for every call
+pub trait FailureDetector: Send {
+    // the leader was heard from at `now`
+    fn heartbeat(&mut self, now: Duration);
+    // whether the leader's silence at `now` is long enough to give up on it
+    fn suspects(&self, now: Duration) -> bool;
+}
+
+// suspects a leader not heard from for a fixed time
+pub struct HeartbeatTimeout {
+    timeout: Duration,
+    last: Option<Duration>,
+}
+
+impl HeartbeatTimeout {
+    pub fn new(timeout: Duration) -> Self {
+        HeartbeatTimeout {
+            timeout,
+            last: None,
+        }
+    }
+}
+
+impl FailureDetector for HeartbeatTimeout {
+    fn heartbeat(&mut self, now: Duration) {
+        self.last = Some(now);
+    }
+
+    fn suspects(&self, now: Duration) -> bool {
+        match self.last {
+            Some(last) => now.saturating_sub(last) > self.timeout,
+            None => false,
+        }
+    }
+}
+
+// heartbeat intervals remembered to estimate how late the next one may be
+const PHI_WINDOW: usize = 100;
+
+// The phi accrual detector of Hayashibara et al., as Cassandra and Akka use it. The gaps
+// between recent heartbeats are taken to be normally distributed, and phi is how unlikely it
+// is that the next heartbeat is still to come after the current silence, as -log10 of the
+// probability. A leader is suspected once phi passes the threshold, so a threshold of 8 gives
+// up on it when a heartbeat this late would happen once in 10^8 times. Jittery links widen the
+// distribution and so wait longer before suspecting.
+pub struct PhiAccrual {
+    threshold: f64,
+    // a floor on the deviation, so perfectly regular heartbeats do not make any delay suspect
+    min_std_dev: Duration,
+    intervals: VecDeque<Duration>,
+    last: Option<Duration>,
+}
+
+impl PhiAccrual {
+    pub fn new(threshold: f64) -> Self {
+        PhiAccrual {
+            threshold,
+            min_std_dev: Duration::from_millis(100),
+            intervals: VecDeque::new(),
+            last: None,
+        }
+    }
+
+    pub fn min_std_dev(mut self, min_std_dev: Duration) -> Self {
+        self.min_std_dev = min_std_dev;
+        self
+    }
+
+    // how suspicious the silence at `now` is, zero before there is anything to go on
+    pub fn phi(&self, now: Duration) -> f64 {
+        let last = match self.last {
+            Some(last) if !self.intervals.is_empty() => last,
+            _ => return 0.0,
+        };
+        let n = self.intervals.len() as f64;
+        let mean = self
+            .intervals
+            .iter()
+            .map(Duration::as_secs_f64)
+            .sum::<f64>()
+            / n;
+        let variance = self
+            .intervals
+            .iter()
+            .map(|interval| (interval.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        let std_dev = variance.sqrt().max(self.min_std_dev.as_secs_f64());
+        // the logistic approximation of the normal distribution Akka uses, which stays finite
+        // far out in the tail
+        let y = (now.saturating_sub(last).as_secs_f64() - mean) / std_dev;
+        let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+        let later = if y > 0.0 {
+            e / (1.0 + e)
+        } else {
+            1.0 - 1.0 / (1.0 + e)
+        };
+        -later.max(f64::MIN_POSITIVE).log10()
+    }
+}
+
+impl FailureDetector for PhiAccrual {
+    fn heartbeat(&mut self, now: Duration) {
+        if let Some(last) = self.last {
+            if self.intervals.len() == PHI_WINDOW {
+                self.intervals.pop_front();
+            }
+            self.intervals.push_back(now.saturating_sub(last));
+        }
+        self.last = Some(now);
+    }
+
+    fn suspects(&self, now: Duration) -> bool {
+        self.phi(now) > self.threshold
+    }
+}
+
+// names a detector so the CLI can pick one
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DetectorSpec {
+    Timeout(Duration),
+    PhiAccrual(f64),
+}
+
+impl DetectorSpec {
+    pub fn build(&self) -> Box<dyn FailureDetector> {
+        match *self {
+            DetectorSpec::Timeout(timeout) => Box::new(HeartbeatTimeout::new(timeout)),
+            DetectorSpec::PhiAccrual(threshold) => Box::new(PhiAccrual::new(threshold)),
+        }
+    }
+}
+
+impl FromStr for DetectorSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("timeout", ms)) => ms
+                .parse::<u64>()
+                .map(|ms| DetectorSpec::Timeout(Duration::from_millis(ms)))
+                .map_err(|_| format!("expected milliseconds, got {}", ms)),
+            Some(("phi", threshold)) => match threshold.parse::<f64>() {
+                Ok(threshold) if threshold > 0.0 => Ok(DetectorSpec::PhiAccrual(threshold)),
+                _ => Err(format!("expected a positive threshold, got {}", threshold)),
+            },
+            _ => Err(format!(
+                "unknown failure detector {}, expected timeout:<ms> or phi:<threshold>",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DetectorSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DetectorSpec::Timeout(timeout) => write!(f, "timeout:{}", timeout.as_millis()),
+            DetectorSpec::PhiAccrual(threshold) => write!(f, "phi:{}", threshold),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn phi_grows_with_silence_past_the_usual_interval() {
+        let mut detector = PhiAccrual::new(8.0).min_std_dev(ms(10));
+        assert!(!detector.suspects(ms(10_000)), "nothing heard yet");
+        for beat in 0..20 {
+            detector.heartbeat(ms(beat * 100));
+        }
+        let last = ms(1900);
+        assert!(detector.phi(last + ms(50)) < 1.0);
+        assert!(detector.phi(last + ms(150)) < detector.phi(last + ms(200)));
+        assert!(!detector.suspects(last + ms(100)));
+        assert!(detector.suspects(last + ms(500)));
+    }
+
+    #[test]
+    fn specs_parse_back_from_display() {
+        for spec in [
+            DetectorSpec::Timeout(ms(1500)),
+            DetectorSpec::PhiAccrual(8.0),
+        ] {
+            assert_eq!(spec.to_string().parse::<DetectorSpec>(), Ok(spec));
+        }
+        assert!("phi:-1".parse::<DetectorSpec>().is_err());
+        assert!("timeout:soon".parse::<DetectorSpec>().is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::checking::fingerprint;
+use stateright::actor::register::RegisterMsg;
+use stateright::actor::{Actor, ActorModel, ActorModelAction, ActorModelState};
+use stateright::Model;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+// Differential checking compares two implementations by what their clients see, whatever
+// happens inside. Each model is explored in full, carrying along every path what the clients
+// have been answered so far, and the two sets of observations that come out should be the
+// same: an observation only one implementation allows is a behaviour the other never shows a
+// client. Both models need the same clients, as the last actors, sending the same workload;
+// request ids and internal messages may differ. A model bounded more tightly than the other
+// shows fewer observations without either implementation being wrong.
+
+// what a client was answered
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Response<V> {
+    PutOk,
+    GetOk(V),
+}
+
+// each client's answers, in the order it got them, counting an answer to a request once
+pub type Observation<V> = Vec<Vec<Response<V>>>;
+
+// a state on the way, with what the clients have seen and the requests already answered
+struct Node<S, V, M> {
+    state: S,
+    observation: Observation<V>,
+    answered: BTreeSet<(usize, u64)>,
+    parent: Option<(usize, ActorModelAction<M>)>,
+}
+
+// Every observation `model` allows, with the actions of the first path found to it. The
+// clients are the last `client_count` actors.
+pub fn observations<A, C, H, V, I>(
+    model: &ActorModel<A, C, H>,
+    client_count: usize,
+) -> BTreeMap<Observation<V>, Vec<ActorModelAction<A::Msg>>>
+where
+    A: Actor<Msg = RegisterMsg<u64, V, I>>,
+    V: Clone + Debug + Ord + Hash,
+    I: Clone + Debug + Eq + Hash,
+    H: Clone + Debug + Hash,
+{
+    let mut nodes: Vec<Node<ActorModelState<A, H>, V, A::Msg>> = Vec::new();
+    // the nodes by fingerprint, those that share one told apart in full
+    let mut index: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut add = |nodes: &mut Vec<Node<_, _, _>>, node: Node<ActorModelState<A, H>, V, A::Msg>| {
+        let key = fingerprint(&(&node.state, &node.observation, &node.answered));
+        let same = index.entry(key).or_default();
+        let seen = same.iter().any(|i| {
+            let other: &Node<_, _, _> = &nodes[*i];
+            other.state == node.state
+                && other.observation == node.observation
+                && other.answered == node.answered
+        });
+        if !seen {
+            same.push(nodes.len());
+            nodes.push(node);
+        }
+    };
+    for state in model.init_states() {
+        if model.within_boundary(&state) {
+            let actor_count = state.actor_states.len();
+            let node = Node {
+                state,
+                observation: vec![Vec::new(); client_count.min(actor_count)],
+                answered: BTreeSet::new(),
+                parent: None,
+            };
+            add(&mut nodes, node);
+        }
+    }
+    let mut next = 0;
+    while next < nodes.len() {
+        let mut actions = Vec::new();
+        model.actions(&nodes[next].state, &mut actions);
+        for action in actions {
+            let state = match model.next_state(&nodes[next].state, action.clone()) {
+                Some(state) if model.within_boundary(&state) => state,
+                _ => continue,
+            };
+            let mut observation = nodes[next].observation.clone();
+            let mut answered = nodes[next].answered.clone();
+            if let ActorModelAction::Deliver { dst, msg, .. } = &action {
+                let first_client = state.actor_states.len() - observation.len();
+                let response = match msg {
+                    RegisterMsg::PutOk(request_id) => Some((*request_id, Response::PutOk)),
+                    RegisterMsg::GetOk(request_id, value) => {
+                        Some((*request_id, Response::GetOk(value.clone())))
+                    }
+                    _ => None,
+                };
+                let client = usize::from(*dst).checked_sub(first_client);
+                if let (Some(client), Some((request_id, response))) = (client, response) {
+                    if answered.insert((client, request_id)) {
+                        observation[client].push(response);
+                    }
+                }
+            }
+            let node = Node {
+                state,
+                observation,
+                answered,
+                parent: Some((next, action)),
+            };
+            add(&mut nodes, node);
+        }
+        next += 1;
+    }
+
+    let mut observations = BTreeMap::new();
+    for (i, node) in nodes.iter().enumerate() {
+        if observations.contains_key(&node.observation) {
+            continue;
+        }
+        let mut actions = Vec::new();
+        let mut at = i;
+        while let Some((parent, action)) = &nodes[at].parent {
+            actions.push(action.clone());
+            at = *parent;
+        }
+        actions.reverse();
+        observations.insert(node.observation.clone(), actions);
+    }
+    observations
+}
+
+// the observations of `left` that `right` lacks, with how `left` got to each
+pub fn missing_from<'a, V: Ord, P, Q>(
+    left: &'a BTreeMap<Observation<V>, P>,
+    right: &BTreeMap<Observation<V>, Q>,
+) -> Vec<(&'a Observation<V>, &'a P)> {
+    left.iter()
+        .filter(|(observation, _)| !right.contains_key(*observation))
+        .collect()
+}
@@ -0,0 +1,499 @@
+use crate::ballot;
+use crate::paxos::{RoundIdentifier, Slot, Value};
+use serde::{Deserialize, Serialize};
+use stateright::actor::{register::*, Actor, Command as ActorCommand, Id, Out};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::time::Duration;
+
+// a client request, tagged so the processor that took it on can answer once it is decided
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum Decree<V> {
+    Put(u64, Id, V),
+    Get(u64, Id),
+}
+
+impl<V> Decree<V> {
+    fn request(&self) -> (u64, Id) {
+        match self {
+            Decree::Put(request_id, client, _) | Decree::Get(request_id, client) => {
+                (*request_id, *client)
+            }
+        }
+    }
+}
+
+// what one processor keeps on each disk for a slot: the highest ballot it has started, and the
+// ballot and value it last accepted
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Block<V> {
+    mbal: Option<RoundIdentifier>,
+    bal: Option<RoundIdentifier>,
+    inp: Option<Decree<V>>,
+}
+
+impl<V> Default for Block<V> {
+    fn default() -> Self {
+        Block {
+            mbal: None,
+            bal: None,
+            inp: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum Phase {
+    // choosing a value, the first phase of paxos
+    Prepare,
+    // committing to it, the second
+    Accept,
+}
+
+// the disks only ever answer, and only about the blocks they store
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum DiskMsg<V> {
+    Write(Slot, Phase, Block<V>),
+    Written(Slot, Phase, RoundIdentifier),
+    // asks for every other processor's block for the slot
+    Read(Slot, Phase, RoundIdentifier),
+    Blocks(Slot, Phase, RoundIdentifier, Vec<Block<V>>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct DiskState<V> {
+    blocks: BTreeMap<(Slot, Id), Block<V>>,
+}
+
+// a disk shared by every processor, passive like the network attached disks of Disk Paxos: a
+// processor writes its own block and reads everyone else's, nothing more
+pub struct SharedDisk<V> {
+    _value: PhantomData<V>,
+}
+
+impl<V: Value> SharedDisk<V> {
+    pub fn new() -> Self {
+        SharedDisk {
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<V: Value> Default for SharedDisk<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: Value> Actor for SharedDisk<V> {
+    type Msg = RegisterMsg<u64, V, DiskMsg<V>>;
+    type State = DiskState<V>;
+
+    fn on_start(&self, _: Id, _o: &mut Out<Self>) -> Self::State {
+        DiskState {
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        _: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match msg {
+            RegisterMsg::Internal(DiskMsg::Write(slot, phase, block)) => {
+                let ballot = match block.mbal {
+                    Some(ballot) => ballot,
+                    None => return,
+                };
+                state.to_mut().blocks.insert((slot, src), block);
+                o.send(
+                    src,
+                    RegisterMsg::Internal(DiskMsg::Written(slot, phase, ballot)),
+                );
+            }
+            RegisterMsg::Internal(DiskMsg::Read(slot, phase, ballot)) => {
+                let blocks = state
+                    .blocks
+                    .iter()
+                    .filter(|((block_slot, owner), _)| *block_slot == slot && *owner != src)
+                    .map(|(_, block)| block.clone())
+                    .collect();
+                o.send(
+                    src,
+                    RegisterMsg::Internal(DiskMsg::Blocks(slot, phase, ballot, blocks)),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+// one ballot's trip through the disks
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Attempt<V> {
+    ballot: RoundIdentifier,
+    phase: Phase,
+    // disks that hold our block, and those we have since read back from
+    written: BTreeSet<Id>,
+    read: BTreeSet<Id>,
+    // everyone else's blocks, from the disks read so far
+    seen: Vec<Block<V>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct DiskPaxosState<V> {
+    id: Id,
+    // the highest round this processor has started or seen on a disk
+    round: u64,
+    // the slot being decided, every earlier one is in the log
+    slot: Slot,
+    // our block for that slot, as written to the disks
+    block: Block<V>,
+    attempt: Option<Attempt<V>>,
+    log: Vec<Decree<V>>,
+    register: V,
+    // client requests taken on and not yet decided, oldest first
+    queue: VecDeque<Decree<V>>,
+    handled: BTreeSet<(Id, u64)>,
+}
+
+impl<V: Value> DiskPaxosState<V> {
+    pub fn log(&self) -> &[Decree<V>] {
+        &self.log
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+}
+
+// Disk Paxos: processors reach agreement through blocks on a set of shared disks instead of
+// through acceptors. For each slot a processor writes its block, carrying its ballot, to every
+// disk, then reads everyone else's back; a majority of disks read after writing plays the part
+// of a quorum of promises, or of accepts in the second phase. Seeing a higher ballot on any disk
+// aborts the attempt, which is retried on a timeout. A processor learns what earlier slots
+// decided by running them itself, so processors never message one another.
+pub struct DiskPaxosActor<V> {
+    disks: Vec<Id>,
+    timeout: Range<Duration>,
+    _value: PhantomData<V>,
+}
+
+impl<V: Value> DiskPaxosActor<V> {
+    pub fn new(disks: Vec<Id>) -> Self {
+        DiskPaxosActor {
+            disks,
+            timeout: Duration::from_millis(500)..Duration::from_millis(1000),
+            _value: PhantomData,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Range<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn is_majority(&self, count: usize) -> bool {
+        count > self.disks.len() / 2
+    }
+
+    // starts a new ballot for the current slot, if there is anything to decide
+    fn start(&self, state: &mut DiskPaxosState<V>, o: &mut Out<Self>) {
+        if state.attempt.is_some() || state.queue.is_empty() {
+            return;
+        }
+        state.round = match ballot::next_round(state.round) {
+            Some(round) => round,
+            None => return,
+        };
+        let ballot = RoundIdentifier::new(state.round, state.id);
+        state.block.mbal = Some(ballot);
+        self.write(state, ballot, Phase::Prepare, o);
+    }
+
+    fn write(
+        &self,
+        state: &mut DiskPaxosState<V>,
+        ballot: RoundIdentifier,
+        phase: Phase,
+        o: &mut Out<Self>,
+    ) {
+        state.attempt = Some(Attempt {
+            ballot,
+            phase,
+            written: BTreeSet::new(),
+            read: BTreeSet::new(),
+            seen: Vec::new(),
+        });
+        let msg = RegisterMsg::Internal(DiskMsg::Write(state.slot, phase, state.block.clone()));
+        o.broadcast(&self.disks, &msg);
+    }
+
+    // moves on once a majority of disks have been written and read back
+    fn advance(&self, state: &mut DiskPaxosState<V>, o: &mut Out<Self>) {
+        let attempt = match &state.attempt {
+            Some(attempt) => attempt.clone(),
+            None => return,
+        };
+        let outranked = attempt
+            .seen
+            .iter()
+            .filter_map(|block| block.mbal)
+            .max()
+            .filter(|mbal| *mbal > attempt.ballot);
+        if let Some(mbal) = outranked {
+            state.round = state.round.max(mbal.round_num());
+            state.attempt = None;
+            o.set_timer(self.timeout.clone());
+            return;
+        }
+        if !self.is_majority(attempt.read.len()) {
+            return;
+        }
+        match attempt.phase {
+            Phase::Prepare => {
+                // the value accepted with the highest ballot anywhere, or our own request
+                let accepted = attempt
+                    .seen
+                    .iter()
+                    .chain(std::iter::once(&state.block))
+                    .filter(|block| block.inp.is_some())
+                    .max_by_key(|block| block.bal)
+                    .and_then(|block| block.inp.clone());
+                let value = match accepted.or_else(|| state.queue.front().cloned()) {
+                    Some(value) => value,
+                    None => return,
+                };
+                state.block.bal = Some(attempt.ballot);
+                state.block.inp = Some(value);
+                self.write(state, attempt.ballot, Phase::Accept, o);
+            }
+            Phase::Accept => {
+                if let Some(decree) = state.block.inp.clone() {
+                    self.decide(state, decree, o);
+                }
+            }
+        }
+    }
+
+    fn decide(&self, state: &mut DiskPaxosState<V>, decree: Decree<V>, o: &mut Out<Self>) {
+        state.log.push(decree.clone());
+        state.slot += 1;
+        state.block = Block::default();
+        state.attempt = None;
+        if let Decree::Put(_, _, value) = &decree {
+            state.register = value.clone();
+        }
+        let request = decree.request();
+        if let Some(position) = state
+            .queue
+            .iter()
+            .position(|queued| queued.request() == request)
+        {
+            state.queue.remove(position);
+            let (request_id, client) = request;
+            match decree {
+                Decree::Put(..) => o.send(client, RegisterMsg::PutOk(request_id)),
+                Decree::Get(..) => o.send(
+                    client,
+                    RegisterMsg::GetOk(request_id, state.register.clone()),
+                ),
+            }
+        }
+        self.start(state, o);
+    }
+}
+
+impl<V: Value> Actor for DiskPaxosActor<V> {
+    type Msg = RegisterMsg<u64, V, DiskMsg<V>>;
+    type State = DiskPaxosState<V>;
+
+    fn on_start(&self, id: Id, _o: &mut Out<Self>) -> Self::State {
+        DiskPaxosState {
+            id,
+            round: 0,
+            slot: 0,
+            block: Block::default(),
+            attempt: None,
+            log: Vec::new(),
+            register: V::default(),
+            queue: VecDeque::new(),
+            handled: BTreeSet::new(),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        _: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        let (request_id, decree) = match msg {
+            RegisterMsg::Put(request_id, value) => {
+                (request_id, Decree::Put(request_id, src, value))
+            }
+            RegisterMsg::Get(request_id) => (request_id, Decree::Get(request_id, src)),
+            RegisterMsg::Internal(DiskMsg::Written(slot, phase, ballot)) => {
+                let current = match &state.attempt {
+                    Some(attempt) => {
+                        slot == state.slot
+                            && attempt.ballot == ballot
+                            && attempt.phase == phase
+                            && !attempt.written.contains(&src)
+                    }
+                    None => false,
+                };
+                if !current {
+                    return;
+                }
+                if let Some(attempt) = &mut state.to_mut().attempt {
+                    attempt.written.insert(src);
+                }
+                let msg = RegisterMsg::Internal(DiskMsg::Read(slot, phase, ballot));
+                o.send(src, msg);
+                return;
+            }
+            RegisterMsg::Internal(DiskMsg::Blocks(slot, phase, ballot, blocks)) => {
+                let current = match &state.attempt {
+                    Some(attempt) => {
+                        slot == state.slot
+                            && attempt.ballot == ballot
+                            && attempt.phase == phase
+                            && attempt.written.contains(&src)
+                            && !attempt.read.contains(&src)
+                    }
+                    None => false,
+                };
+                if !current {
+                    return;
+                }
+                let state = state.to_mut();
+                if let Some(attempt) = &mut state.attempt {
+                    attempt.read.insert(src);
+                    attempt.seen.extend(blocks);
+                }
+                return self.advance(state, o);
+            }
+            _ => return,
+        };
+        if state.handled.contains(&(src, request_id)) {
+            return;
+        }
+        let state = state.to_mut();
+        state.handled.insert((src, request_id));
+        state.queue.push_back(decree);
+        self.start(state, o);
+    }
+
+    fn on_timeout(&self, _: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        if state.attempt.is_some() || state.queue.is_empty() {
+            return;
+        }
+        self.start(state.to_mut(), o);
+    }
+}
+
+// processors and disks have to share an actor type in the model
+pub enum DiskPaxosModelActor<V> {
+    Processor(DiskPaxosActor<V>),
+    Disk(SharedDisk<V>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum DiskPaxosModelActorState<V> {
+    Processor(DiskPaxosState<V>),
+    Disk(DiskState<V>),
+}
+
+impl<V: Value> DiskPaxosModelActor<V> {
+    // both speak the same messages, only the type of the output differs
+    fn forward<A>(inner: Out<A>, o: &mut Out<Self>)
+    where
+        A: Actor<Msg = RegisterMsg<u64, V, DiskMsg<V>>>,
+    {
+        for command in inner {
+            match command {
+                ActorCommand::Send(dst, msg) => o.send(dst, msg),
+                ActorCommand::SetTimer(duration) => o.set_timer(duration),
+                ActorCommand::CancelTimer => o.cancel_timer(),
+            }
+        }
+    }
+}
+
+impl<V: Value> Actor for DiskPaxosModelActor<V> {
+    type Msg = RegisterMsg<u64, V, DiskMsg<V>>;
+    type State = DiskPaxosModelActorState<V>;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        let mut inner = Out::new();
+        let state = match self {
+            DiskPaxosModelActor::Processor(processor) => {
+                DiskPaxosModelActorState::Processor(processor.on_start(id, &mut inner))
+            }
+            DiskPaxosModelActor::Disk(disk) => {
+                DiskPaxosModelActorState::Disk(disk.on_start(id, &mut inner))
+            }
+        };
+        Self::forward(inner, o);
+        state
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match (self, state.as_ref()) {
+            (
+                DiskPaxosModelActor::Processor(processor),
+                DiskPaxosModelActorState::Processor(processor_state),
+            ) => {
+                let mut processor_state = Cow::Borrowed(processor_state);
+                let mut inner = Out::new();
+                processor.on_msg(id, &mut processor_state, src, msg, &mut inner);
+                if let Cow::Owned(processor_state) = processor_state {
+                    *state = Cow::Owned(DiskPaxosModelActorState::Processor(processor_state));
+                }
+                Self::forward(inner, o);
+            }
+            (DiskPaxosModelActor::Disk(disk), DiskPaxosModelActorState::Disk(disk_state)) => {
+                let mut disk_state = Cow::Borrowed(disk_state);
+                let mut inner = Out::new();
+                disk.on_msg(id, &mut disk_state, src, msg, &mut inner);
+                if let Cow::Owned(disk_state) = disk_state {
+                    *state = Cow::Owned(DiskPaxosModelActorState::Disk(disk_state));
+                }
+                Self::forward(inner, o);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_timeout(&self, id: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        if let (
+            DiskPaxosModelActor::Processor(processor),
+            DiskPaxosModelActorState::Processor(processor_state),
+        ) = (self, state.as_ref())
+        {
+            let mut processor_state = Cow::Borrowed(processor_state);
+            let mut inner = Out::new();
+            processor.on_timeout(id, &mut processor_state, &mut inner);
+            if let Cow::Owned(processor_state) = processor_state {
+                *state = Cow::Owned(DiskPaxosModelActorState::Processor(processor_state));
+            }
+            Self::forward(inner, o);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt::{self, Display};
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+// Turns what a replica writes to disk into ciphertext and back. The write-ahead log seals each
+// record on its own, so a torn final write still only costs the last record. The model and the
+// simulator keep their durable state in memory and never call this; what they stand in for is
+// `NoEncryption`.
+pub trait Encryptor: Send + Sync {
+    fn encrypt(&self, plaintext: &[u8]) -> io::Result<Vec<u8>>;
+    // fails if the ciphertext was not sealed under this key or has been tampered with
+    fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>>;
+
+    // false when the ciphertext is the plaintext, so logs stay as readable as they were
+    fn encrypts(&self) -> bool {
+        true
+    }
+}
+
+pub struct NoEncryption;
+
+impl Encryptor for NoEncryption {
+    fn encrypt(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(plaintext.to_vec())
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(ciphertext.to_vec())
+    }
+
+    fn encrypts(&self) -> bool {
+        false
+    }
+}
+
+// AES-256-GCM, each record behind a random 96 bit nonce of its own
+#[cfg(feature = "encryption")]
+pub struct AesGcm {
+    cipher: aes_gcm::Aes256Gcm,
+}
+
+#[cfg(feature = "encryption")]
+impl AesGcm {
+    const NONCE: usize = 12;
+
+    pub fn new(key: &Key) -> Self {
+        use aes_gcm::KeyInit;
+        AesGcm {
+            cipher: aes_gcm::Aes256Gcm::new(aes_gcm::Key::<aes_gcm::Aes256Gcm>::from_slice(&key.0)),
+        }
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl Encryptor for AesGcm {
+    fn encrypt(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        use aes_gcm::aead::{Aead, AeadCore, OsRng};
+        let nonce = aes_gcm::Aes256Gcm::generate_nonce(&mut OsRng);
+        let sealed = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "unable to encrypt"))?;
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&sealed);
+        Ok(out)
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+        use aes_gcm::aead::Aead;
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "record does not decrypt");
+        if ciphertext.len() < Self::NONCE {
+            return Err(invalid());
+        }
+        let (nonce, sealed) = ciphertext.split_at(Self::NONCE);
+        self.cipher
+            .decrypt(aes_gcm::Nonce::from_slice(nonce), sealed)
+            .map_err(|_| invalid())
+    }
+}
+
+// a 256 bit key, written as 64 hex digits
+#[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+pub struct Key([u8; 32]);
+
+impl Key {
+    pub fn parse(hex: &str) -> io::Result<Self> {
+        let hex = hex.trim();
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "expected a key of 64 hex digits",
+            )
+        };
+        // from_str_radix alone would take a sign in front of a digit
+        if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let mut key = [0; 32];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(Key(key))
+    }
+}
+
+// where a replica finds its key: file:<path> or env:<variable>
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeySource {
+    File(PathBuf),
+    Env(String),
+}
+
+impl KeySource {
+    pub fn load(&self) -> io::Result<Key> {
+        match self {
+            KeySource::File(path) => Key::parse(&std::fs::read_to_string(path)?),
+            KeySource::Env(var) => match std::env::var(var) {
+                Ok(hex) => Key::parse(&hex),
+                Err(e) => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{}: {}", var, e),
+                )),
+            },
+        }
+    }
+}
+
+impl FromStr for KeySource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("file", path)) if !path.is_empty() => Ok(KeySource::File(PathBuf::from(path))),
+            Some(("env", var)) if !var.is_empty() => Ok(KeySource::Env(var.to_string())),
+            _ => Err(format!("expected file:<path> or env:<variable>, got {}", s)),
+        }
+    }
+}
+
+impl Display for KeySource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeySource::File(path) => write!(f, "file:{}", path.display()),
+            KeySource::Env(var) => write!(f, "env:{}", var),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn keys_are_64_hex_digits() {
+        assert!(Key::parse(KEY).is_ok());
+        assert!(Key::parse(&format!("{}\n", KEY)).is_ok());
+        assert!(Key::parse(&KEY[2..]).is_err());
+        assert!(Key::parse(&format!("{}00", KEY)).is_err());
+        assert!(Key::parse(&"zz".repeat(32)).is_err());
+        assert!(Key::parse(&format!("+f{}", &KEY[2..])).is_err());
+    }
+
+    #[test]
+    fn key_sources_read_back_as_written() {
+        for source in ["file:/etc/paxos/wal.key", "env:WAL_KEY"] {
+            let parsed: KeySource = source.parse().unwrap();
+            assert_eq!(parsed.to_string(), source);
+        }
+        assert!("file:".parse::<KeySource>().is_err());
+        assert!("vault:wal".parse::<KeySource>().is_err());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn records_decrypt_only_untouched_and_under_their_key() {
+        let aes = AesGcm::new(&Key::parse(KEY).unwrap());
+        let sealed = aes.encrypt(b"accept slot 3").unwrap();
+        assert!(!sealed.windows(13).any(|window| window == b"accept slot 3"));
+        assert_eq!(aes.decrypt(&sealed).unwrap(), b"accept slot 3");
+        // every record has a nonce of its own
+        assert_ne!(aes.encrypt(b"accept slot 3").unwrap(), sealed);
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        let error = aes.decrypt(&tampered).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(aes.decrypt(&sealed[..8]).is_err());
+
+        let other = AesGcm::new(&Key::parse(&KEY.replace('0', "f")).unwrap());
+        let error = other.decrypt(&sealed).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,576 @@
+use crate::kv::{Key, KvCommand, KvResponse, KvStore};
+use crate::model::NetworkMode;
+use crate::paxos::Value;
+use crate::smr::StateMachine;
+use serde::{Deserialize, Serialize};
+use stateright::actor::*;
+use stateright::semantics::LinearizabilityTester;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+// every replica owns its own row of instances, so instances need no agreement on who leads them
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Hash)]
+pub struct InstanceId {
+    replica: Id,
+    slot: u64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum Status {
+    PreAccepted,
+    Accepted,
+    Committed,
+    Executed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Instance<V> {
+    command: KvCommand<V>,
+    seq: u64,
+    deps: BTreeSet<InstanceId>,
+    status: Status,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum EPaxosMsg<V> {
+    // client facing
+    Request(u64, KvCommand<V>),
+    Reply(u64, KvResponse<V>),
+    PreAccept(InstanceId, KvCommand<V>, u64, BTreeSet<InstanceId>),
+    PreAcceptOk(InstanceId, u64, BTreeSet<InstanceId>),
+    Accept(InstanceId, KvCommand<V>, u64, BTreeSet<InstanceId>),
+    AcceptOk(InstanceId),
+    Commit(InstanceId, KvCommand<V>, u64, BTreeSet<InstanceId>),
+}
+
+// an instance this replica is the command leader for
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Leading {
+    client: Id,
+    request_id: u64,
+    replies: BTreeSet<Id>,
+    // set as soon as some replica reports attributes differing from ours, ruling out the fast path
+    changed: bool,
+    accepts: BTreeSet<Id>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct EPaxosState<V> {
+    id: Id,
+    next_slot: u64,
+    instances: BTreeMap<InstanceId, Instance<V>>,
+    leading: BTreeMap<InstanceId, Leading>,
+    store: KvStore<V>,
+    // instances in the order they were executed, per key
+    executed: BTreeMap<Key, Vec<InstanceId>>,
+    handled: BTreeSet<u64>,
+}
+
+impl<V: Value> EPaxosState<V> {
+    pub fn executed(&self) -> &BTreeMap<Key, Vec<InstanceId>> {
+        &self.executed
+    }
+
+    // the attributes a new command gets from what this replica already knows
+    fn attributes(&self, id: InstanceId, command: &KvCommand<V>) -> (u64, BTreeSet<InstanceId>) {
+        let mut seq = 0;
+        let mut deps = BTreeSet::new();
+        for (other, instance) in &self.instances {
+            if *other != id && instance.command.key() == command.key() {
+                seq = seq.max(instance.seq);
+                deps.insert(*other);
+            }
+        }
+        (seq + 1, deps)
+    }
+
+    fn is_committed(&self, id: &InstanceId) -> bool {
+        match self.instances.get(id) {
+            Some(instance) => matches!(instance.status, Status::Committed | Status::Executed),
+            None => false,
+        }
+    }
+
+    // every instance reachable through deps, or None while some of them are not yet committed
+    fn reachable(&self, from: InstanceId) -> Option<BTreeSet<InstanceId>> {
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![from];
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            if !self.is_committed(&id) {
+                return None;
+            }
+            stack.extend(self.instances[&id].deps.iter().copied());
+        }
+        Some(seen)
+    }
+}
+
+// Egalitarian Paxos, every replica leads the commands sent to it and commits them in one
+// round trip unless a conflicting command got in the way
+pub struct EPaxosActor {
+    peers: Vec<Id>,
+}
+
+impl EPaxosActor {
+    pub fn new(peers: Vec<Id>) -> Self {
+        EPaxosActor { peers }
+    }
+
+    fn cluster_size(&self) -> usize {
+        self.peers.len() + 1
+    }
+
+    // replies needed, counting the command leader itself
+    fn slow_quorum(&self) -> usize {
+        self.cluster_size() / 2 + 1
+    }
+
+    fn fast_quorum(&self) -> usize {
+        2 * (self.cluster_size() / 2)
+    }
+
+    fn commit<V: Value>(
+        &self,
+        state: &mut EPaxosState<V>,
+        id: InstanceId,
+        command: KvCommand<V>,
+        seq: u64,
+        deps: BTreeSet<InstanceId>,
+        o: &mut Out<EPaxosModelActor<V>>,
+    ) {
+        if state.is_committed(&id) {
+            return;
+        }
+        state.instances.insert(
+            id,
+            Instance {
+                command,
+                seq,
+                deps,
+                status: Status::Committed,
+            },
+        );
+        self.execute(state, o);
+    }
+
+    // executes strongly connected components of the dependency graph in dependency order,
+    // ordering commands inside a component by seq
+    fn execute<V: Value>(&self, state: &mut EPaxosState<V>, o: &mut Out<EPaxosModelActor<V>>) {
+        loop {
+            let pending: Vec<InstanceId> = state
+                .instances
+                .iter()
+                .filter(|(_, instance)| instance.status == Status::Committed)
+                .map(|(id, _)| *id)
+                .collect();
+            let mut component = None;
+            for id in pending {
+                let reach = match state.reachable(id) {
+                    Some(reach) => reach,
+                    None => continue,
+                };
+                let members: BTreeSet<InstanceId> = reach
+                    .iter()
+                    .copied()
+                    .filter(|other| {
+                        state
+                            .reachable(*other)
+                            .map_or(false, |back| back.contains(&id))
+                    })
+                    .collect();
+                let blocked = reach.iter().any(|other| {
+                    !members.contains(other) && state.instances[other].status != Status::Executed
+                });
+                if !blocked {
+                    component = Some(members);
+                    break;
+                }
+            }
+            let members = match component {
+                Some(members) => members,
+                None => return,
+            };
+
+            let mut order: Vec<InstanceId> = members.into_iter().collect();
+            order.sort_by_key(|id| (state.instances[id].seq, *id));
+            for id in order {
+                let instance = state.instances.get_mut(&id).unwrap();
+                if instance.status == Status::Executed {
+                    continue;
+                }
+                instance.status = Status::Executed;
+                let command = instance.command.clone();
+                let ret = state.store.apply(&command);
+                state.executed.entry(command.key()).or_default().push(id);
+                if let Some(leading) = state.leading.remove(&id) {
+                    o.send(leading.client, EPaxosMsg::Reply(leading.request_id, ret));
+                }
+            }
+        }
+    }
+
+    fn on_msg<V: Value>(
+        &self,
+        state: &mut Cow<EPaxosState<V>>,
+        src: Id,
+        msg: EPaxosMsg<V>,
+        o: &mut Out<EPaxosModelActor<V>>,
+    ) {
+        match msg {
+            EPaxosMsg::Request(request_id, command) => {
+                if state.handled.contains(&request_id) {
+                    return;
+                }
+                let state = state.to_mut();
+                state.handled.insert(request_id);
+                let id = InstanceId {
+                    replica: state.id,
+                    slot: state.next_slot,
+                };
+                state.next_slot += 1;
+                let (seq, deps) = state.attributes(id, &command);
+                state.instances.insert(
+                    id,
+                    Instance {
+                        command: command.clone(),
+                        seq,
+                        deps: deps.clone(),
+                        status: Status::PreAccepted,
+                    },
+                );
+                state.leading.insert(
+                    id,
+                    Leading {
+                        client: src,
+                        request_id,
+                        replies: BTreeSet::new(),
+                        changed: false,
+                        accepts: BTreeSet::new(),
+                    },
+                );
+                o.broadcast(&self.peers, &EPaxosMsg::PreAccept(id, command, seq, deps));
+            }
+            EPaxosMsg::PreAccept(id, command, seq, deps) => {
+                if state.instances.contains_key(&id) {
+                    return;
+                }
+                let state = state.to_mut();
+                // widen the leader's attributes with whatever conflicting commands we know of
+                let (local_seq, local_deps) = state.attributes(id, &command);
+                let seq = seq.max(local_seq);
+                let deps: BTreeSet<InstanceId> = deps.union(&local_deps).copied().collect();
+                state.instances.insert(
+                    id,
+                    Instance {
+                        command,
+                        seq,
+                        deps: deps.clone(),
+                        status: Status::PreAccepted,
+                    },
+                );
+                o.send(src, EPaxosMsg::PreAcceptOk(id, seq, deps));
+            }
+            EPaxosMsg::PreAcceptOk(id, seq, deps) => {
+                let state = state.to_mut();
+                let (leading, instance) =
+                    match (state.leading.get_mut(&id), state.instances.get_mut(&id)) {
+                        (Some(leading), Some(instance))
+                            if instance.status == Status::PreAccepted =>
+                        {
+                            (leading, instance)
+                        }
+                        _ => return,
+                    };
+                if !leading.replies.insert(src) {
+                    return;
+                }
+                if seq != instance.seq || deps != instance.deps {
+                    leading.changed = true;
+                    instance.seq = instance.seq.max(seq);
+                    instance.deps.extend(deps);
+                }
+
+                let count = leading.replies.len() + 1;
+                if !leading.changed && count >= self.fast_quorum() {
+                    // every replica in a fast quorum agreed, so commit in one round trip
+                    let (command, seq, deps) = (
+                        instance.command.clone(),
+                        instance.seq,
+                        instance.deps.clone(),
+                    );
+                    let msg = EPaxosMsg::Commit(id, command.clone(), seq, deps.clone());
+                    o.broadcast(&self.peers, &msg);
+                    self.commit(state, id, command, seq, deps, o);
+                } else if leading.changed && count >= self.slow_quorum() {
+                    instance.status = Status::Accepted;
+                    let msg = EPaxosMsg::Accept(
+                        id,
+                        instance.command.clone(),
+                        instance.seq,
+                        instance.deps.clone(),
+                    );
+                    o.broadcast(&self.peers, &msg);
+                }
+            }
+            EPaxosMsg::Accept(id, command, seq, deps) => {
+                if state.is_committed(&id) {
+                    return;
+                }
+                state.to_mut().instances.insert(
+                    id,
+                    Instance {
+                        command,
+                        seq,
+                        deps,
+                        status: Status::Accepted,
+                    },
+                );
+                o.send(src, EPaxosMsg::AcceptOk(id));
+            }
+            EPaxosMsg::AcceptOk(id) => {
+                let state = state.to_mut();
+                let leading = match state.leading.get_mut(&id) {
+                    Some(leading) => leading,
+                    None => return,
+                };
+                leading.accepts.insert(src);
+                if leading.accepts.len() + 1 < self.slow_quorum() {
+                    return;
+                }
+                let instance = match state.instances.get(&id) {
+                    Some(instance) if instance.status == Status::Accepted => instance.clone(),
+                    _ => return,
+                };
+                let msg = EPaxosMsg::Commit(
+                    id,
+                    instance.command.clone(),
+                    instance.seq,
+                    instance.deps.clone(),
+                );
+                o.broadcast(&self.peers, &msg);
+                self.commit(state, id, instance.command, instance.seq, instance.deps, o);
+            }
+            EPaxosMsg::Commit(id, command, seq, deps) => {
+                if state.is_committed(&id) {
+                    return;
+                }
+                self.commit(state.to_mut(), id, command, seq, deps, o);
+            }
+            EPaxosMsg::Reply(_, _) => {}
+        }
+    }
+}
+
+// clients and servers have to share an actor type in the model, as with `RegisterActor`
+pub enum EPaxosModelActor<V> {
+    // submits its commands one at a time to a single server
+    Client {
+        commands: Vec<KvCommand<V>>,
+        server_count: usize,
+    },
+    Server(EPaxosActor),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum EPaxosActorState<V> {
+    Client { awaiting: Option<u64>, sent: usize },
+    Server(EPaxosState<V>),
+}
+
+impl<V: Value> EPaxosModelActor<V> {
+    fn send_command(
+        id: Id,
+        commands: &[KvCommand<V>],
+        server_count: usize,
+        sent: usize,
+        o: &mut Out<Self>,
+    ) -> Option<u64> {
+        let command = commands.get(sent)?;
+        // unique across clients, since a server deduplicates on it
+        let request_id = ((usize::from(id) as u64) << 32) | sent as u64;
+        let server = Id::from(usize::from(id) % server_count);
+        o.send(server, EPaxosMsg::Request(request_id, command.clone()));
+        Some(request_id)
+    }
+}
+
+impl<V: Value> Actor for EPaxosModelActor<V> {
+    type Msg = EPaxosMsg<V>;
+    type State = EPaxosActorState<V>;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        match self {
+            EPaxosModelActor::Client {
+                commands,
+                server_count,
+            } => EPaxosActorState::Client {
+                awaiting: Self::send_command(id, commands, *server_count, 0, o),
+                sent: 1,
+            },
+            EPaxosModelActor::Server(_) => EPaxosActorState::Server(EPaxosState {
+                id,
+                next_slot: 0,
+                instances: BTreeMap::new(),
+                leading: BTreeMap::new(),
+                store: KvStore::default(),
+                executed: BTreeMap::new(),
+                handled: BTreeSet::new(),
+            }),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match (self, state.as_ref()) {
+            (
+                EPaxosModelActor::Client {
+                    commands,
+                    server_count,
+                },
+                EPaxosActorState::Client { awaiting, sent },
+            ) => {
+                let request_id = match msg {
+                    EPaxosMsg::Reply(request_id, _) => request_id,
+                    _ => return,
+                };
+                if *awaiting != Some(request_id) {
+                    return;
+                }
+                let sent = *sent;
+                *state = Cow::Owned(EPaxosActorState::Client {
+                    awaiting: Self::send_command(id, commands, *server_count, sent, o),
+                    sent: sent + 1,
+                });
+            }
+            (EPaxosModelActor::Server(server), EPaxosActorState::Server(server_state)) => {
+                let mut server_state = Cow::Borrowed(server_state);
+                server.on_msg(&mut server_state, src, msg, o);
+                if let Cow::Owned(server_state) = server_state {
+                    *state = Cow::Owned(EPaxosActorState::Server(server_state));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EPaxosModelConfig {
+    server_count: usize,
+    network: NetworkMode,
+}
+
+impl Default for EPaxosModelConfig {
+    fn default() -> Self {
+        EPaxosModelConfig {
+            server_count: 3,
+            network: NetworkMode::Unordered,
+        }
+    }
+}
+
+impl EPaxosModelConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn server_count(mut self, server_count: usize) -> Self {
+        self.server_count = server_count;
+        self
+    }
+
+    pub fn network(mut self, network: NetworkMode) -> Self {
+        self.network = network;
+        self
+    }
+
+    // the first two clients write the same key and conflict, the other two get a key each;
+    // every client reads its key back afterwards
+    fn commands(client: usize) -> Vec<KvCommand<char>> {
+        let key = match client {
+            0 | 1 => 0,
+            _ => client as Key,
+        };
+        let value = (b'A' + client as u8) as char;
+        vec![KvCommand::Put(key, value), KvCommand::Get(key)]
+    }
+
+    pub fn into_model(self) -> EPaxosModel {
+        ActorModel::new(self.clone(), LinearizabilityTester::new(KvStore::default()))
+            .actors((0..self.server_count).map(|i| {
+                EPaxosModelActor::Server(EPaxosActor::new(model_peers(i, self.server_count)))
+            }))
+            .actors((0..4).map(|i| EPaxosModelActor::Client {
+                commands: Self::commands(i),
+                server_count: self.server_count,
+            }))
+            .duplicating_network(match self.network {
+                NetworkMode::UnorderedDuplicating => DuplicatingNetwork::Yes,
+                _ => DuplicatingNetwork::No,
+            })
+            .lossy_network(match self.network {
+                NetworkMode::Lossy => LossyNetwork::Yes,
+                _ => LossyNetwork::No,
+            })
+            .property(
+                stateright::Expectation::Always,
+                "linearizable",
+                |_, state| state.history.serialized_history().is_some(),
+            )
+            .property(
+                stateright::Expectation::Always,
+                "same execution order",
+                |_, state| {
+                    // conflicting commands run in the same order everywhere, other commands may not
+                    let executed: Vec<&BTreeMap<Key, Vec<InstanceId>>> = state
+                        .actor_states
+                        .iter()
+                        .filter_map(|actor_state| match actor_state.as_ref() {
+                            EPaxosActorState::Server(server_state) => Some(server_state.executed()),
+                            _ => None,
+                        })
+                        .collect();
+                    executed.iter().all(|a| {
+                        executed.iter().all(|b| {
+                            a.iter().all(|(key, a_order)| match b.get(key) {
+                                Some(b_order) => {
+                                    let common = a_order.len().min(b_order.len());
+                                    a_order[..common] == b_order[..common]
+                                }
+                                None => true,
+                            })
+                        })
+                    })
+                },
+            )
+            .record_msg_out(|_, history, env| match env.msg {
+                EPaxosMsg::Request(_, command) => {
+                    let mut history = history.clone();
+                    let _ = history.on_invoke(env.src, command.clone());
+                    Some(history)
+                }
+                _ => None,
+            })
+            .record_msg_in(|_, history, env| match env.msg {
+                EPaxosMsg::Reply(_, ret) => {
+                    let mut history = history.clone();
+                    let _ = history.on_return(env.dst, ret.clone());
+                    Some(history)
+                }
+                _ => None,
+            })
+    }
+}
+
+pub type EPaxosHistory = LinearizabilityTester<Id, KvStore<char>>;
+
+pub type EPaxosModel = ActorModel<EPaxosModelActor<char>, EPaxosModelConfig, EPaxosHistory>;
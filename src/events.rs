@@ -0,0 +1,315 @@
+use crate::checksum::sha1;
+use crate::rng::{Rng, SplitMix};
+use crate::trace::action_json;
+use serde_json::json;
+use stateright::actor::{Actor, ActorModel, ActorModelState};
+use stateright::{Expectation, Model};
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// what RFC 6455 has the server append to the client's key before hashing it
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC11B65";
+// a client that takes longer than this to send its handshake is dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+// handshakes past this are not worth reading
+const MAX_REQUEST: usize = 8192;
+
+// Streams walks through a model to WebSocket clients as JSON events, for dashboards that
+// animate the protocol rather than poll the explorer. Each client gets walks of its own, taking
+// a random enabled action every `interval`, seeded from `seed` and the order the client
+// connected in so that the same dashboard sees the same run again. A walk ends at a state with
+// nothing enabled, after `depth` steps or on stepping past the model's boundary, and the next
+// one starts over. Events are text frames, each an `init`, `step` or `end` object as
+// `schema/events.json` describes; what the client sends is never read.
+pub struct EventServer<A: Actor, C, H> {
+    model: ActorModel<A, C, H>,
+    seed: u64,
+    interval: Duration,
+    depth: usize,
+}
+
+impl<A, C, H> EventServer<A, C, H>
+where
+    A: Actor + Send + Sync + 'static,
+    A::Msg: Send + Sync,
+    A::State: Send + Sync,
+    C: Send + Sync + 'static,
+    H: Clone + Debug + Hash + Send + Sync + 'static,
+{
+    pub fn new(model: ActorModel<A, C, H>) -> Self {
+        EventServer {
+            model,
+            seed: 0,
+            interval: Duration::from_millis(500),
+            depth: 100,
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    // a thread per client, each streaming until its client goes away
+    pub fn serve(self, addr: SocketAddr) -> io::Result<()> {
+        let server = Arc::new(self);
+        let listener = TcpListener::bind(addr)?;
+        for (i, stream) in listener.incoming().enumerate() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let server = Arc::clone(&server);
+            let seed = server.seed.wrapping_add(i as u64);
+            thread::spawn(move || {
+                // a client that hangs up only ends its own stream
+                let _ = handshake(stream).and_then(|stream| server.stream(stream, seed));
+            });
+        }
+        Ok(())
+    }
+
+    fn properties(&self, state: &ActorModelState<A, H>) -> Vec<serde_json::Value> {
+        self.model
+            .properties()
+            .iter()
+            .map(|property| {
+                let expectation = match property.expectation {
+                    Expectation::Always => "always",
+                    Expectation::Sometimes => "sometimes",
+                    Expectation::Eventually => "eventually",
+                };
+                json!({
+                    "name": property.name,
+                    "expectation": expectation,
+                    "holds": (property.condition)(&self.model, state),
+                })
+            })
+            .collect()
+    }
+
+    fn stream(&self, mut stream: TcpStream, seed: u64) -> io::Result<()> {
+        let mut rng = SplitMix(seed);
+        for walk in 0u64.. {
+            let mut init_states = self.model.init_states();
+            if init_states.is_empty() {
+                return Ok(());
+            }
+            let mut state = init_states.swap_remove(rng.below(init_states.len()));
+            send(
+                &mut stream,
+                &json!({
+                    "event": "init",
+                    "walk": walk,
+                    "actors": actors(&state),
+                    "network": network(&state),
+                    "properties": self.properties(&state),
+                }),
+            )?;
+            // actions the actors ignore count towards the depth, without an event
+            let mut step = 0;
+            let reason = loop {
+                if step == self.depth {
+                    break "depth";
+                }
+                let mut actions = Vec::new();
+                self.model.actions(&state, &mut actions);
+                if actions.is_empty() {
+                    break "terminal";
+                }
+                let action = actions.swap_remove(rng.below(actions.len()));
+                let taken = action_json(&action);
+                step += 1;
+                let next = match self.model.next_state(&state, action) {
+                    Some(next) if !self.model.within_boundary(&next) => break "boundary",
+                    Some(next) => next,
+                    None => continue,
+                };
+                thread::sleep(self.interval);
+                let (before, after) = (network(&state), network(&next));
+                send(
+                    &mut stream,
+                    &json!({
+                        "event": "step",
+                        "walk": walk,
+                        "step": step,
+                        "action": taken,
+                        "changed": changed(&state, &next),
+                        "sent": missing_from(&after, &before),
+                        "consumed": missing_from(&before, &after),
+                        "properties": self.properties(&next),
+                    }),
+                )?;
+                state = next;
+            };
+            send(
+                &mut stream,
+                &json!({ "event": "end", "walk": walk, "step": step, "reason": reason }),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn actors<A: Actor, H>(state: &ActorModelState<A, H>) -> Vec<String> {
+    state
+        .actor_states
+        .iter()
+        .map(|actor_state| format!("{:?}", actor_state))
+        .collect()
+}
+
+fn network<A: Actor, H>(state: &ActorModelState<A, H>) -> Vec<serde_json::Value> {
+    state
+        .network
+        .iter()
+        .map(|env| {
+            json!({
+                "src": usize::from(env.src),
+                "dst": usize::from(env.dst),
+                "msg": format!("{:?}", env.msg),
+            })
+        })
+        .collect()
+}
+
+// the actors whose state the step changed, with their new state
+fn changed<A: Actor, H>(
+    before: &ActorModelState<A, H>,
+    after: &ActorModelState<A, H>,
+) -> Vec<serde_json::Value> {
+    after
+        .actor_states
+        .iter()
+        .enumerate()
+        .filter(|(i, actor_state)| before.actor_states.get(*i) != Some(*actor_state))
+        .map(|(i, actor_state)| json!({ "actor": i, "state": format!("{:?}", actor_state) }))
+        .collect()
+}
+
+// the envelopes of `a` not in `b`, counting repeats
+fn missing_from(a: &[serde_json::Value], b: &[serde_json::Value]) -> Vec<serde_json::Value> {
+    let mut b = b.to_vec();
+    a.iter()
+        .filter(|env| match b.iter().position(|other| other == *env) {
+            Some(i) => {
+                b.swap_remove(i);
+                false
+            }
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
+
+// what the server answers a client's `Sec-WebSocket-Key` with
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// reads the upgrade request, whatever its path, and switches protocols
+fn handshake(stream: TcpStream) -> io::Result<TcpStream> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?.take(MAX_REQUEST as u64));
+    let mut key = None;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            }
+        }
+        line.clear();
+    }
+    let mut stream = stream;
+    let key = match key {
+        Some(key) => key,
+        None => {
+            write!(
+                stream,
+                "HTTP/1.1 426 Upgrade Required\r\nUpgrade: websocket\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )?;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a WebSocket handshake",
+            ));
+        }
+    };
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )?;
+    Ok(stream)
+}
+
+// one unmasked text frame, as a server sends them
+fn send(stream: &mut TcpStream, event: &serde_json::Value) -> io::Result<()> {
+    let payload = event.to_string().into_bytes();
+    let mut frame = vec![0x81];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&payload);
+    stream.write_all(&frame)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn handshakes_answer_as_the_rfc_does() {
+        // the example from RFC 6455, section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"a"), "YQ==");
+    }
+}
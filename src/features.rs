@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// The optional parts of the protocol a replica can speak, as a bit mask. Replicas advertise
+// theirs to each other when they start, and only use a feature once every peer has advertised
+// it, so during a rolling upgrade a replica on the newer build keeps to what the older ones
+// understand. A replica from before the handshake advertises nothing, which rules out every
+// feature for as long as it is in the cluster.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct Features(u32);
+
+// names as on the command line, in bit order
+const NAMES: &[(&str, Features)] = &[
+    ("batching", Features::BATCHING),
+    ("pre-vote", Features::PRE_VOTE),
+    ("handoff", Features::HANDOFF),
+];
+
+impl Features {
+    pub const NONE: Features = Features(0);
+    // batches of puts decided as one command
+    pub const BATCHING: Features = Features(1);
+    // probes before a round
+    pub const PRE_VOTE: Features = Features(1 << 1);
+    // leadership handed to another replica
+    pub const HANDOFF: Features = Features(1 << 2);
+    pub const ALL: Features = Features(0b111);
+
+    pub fn from_bits(bits: u32) -> Self {
+        Features(bits & Features::ALL.0)
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(self, other: Features) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    // what both sides speak
+    pub fn common(self, other: Features) -> Self {
+        Features(self.0 & other.0)
+    }
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features::ALL
+    }
+}
+
+// a comma separated list of names, `all` or `none`
+impl FromStr for Features {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => return Ok(Features::ALL),
+            "none" => return Ok(Features::NONE),
+            _ => {}
+        }
+        s.split(',').try_fold(Features::NONE, |features, name| {
+            match NAMES.iter().find(|(known, _)| *known == name.trim()) {
+                Some((_, feature)) => Ok(Features(features.0 | feature.0)),
+                None => Err(format!(
+                    "unknown feature {}, expected batching, pre-vote or handoff",
+                    name
+                )),
+            }
+        })
+    }
+}
+
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self == Features::NONE {
+            return write!(f, "none");
+        }
+        let names: Vec<&str> = NAMES
+            .iter()
+            .filter(|(_, feature)| self.contains(*feature))
+            .map(|(name, _)| *name)
+            .collect();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn features_read_back_as_written() {
+        for features in (0..=Features::ALL.bits()).map(Features::from_bits) {
+            assert_eq!(features.to_string().parse(), Ok(features));
+        }
+        assert_eq!("all".parse(), Ok(Features::ALL));
+        assert!("batching,gossip".parse::<Features>().is_err());
+    }
+
+    #[test]
+    fn common_features_are_those_both_advertise() {
+        let new = Features::ALL;
+        let old: Features = "pre-vote".parse().unwrap();
+        assert_eq!(new.common(old), Features::PRE_VOTE);
+        assert!(!new.common(old).contains(Features::BATCHING));
+        assert!(new.common(old).contains(Features::NONE));
+    }
+}
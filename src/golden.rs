@@ -0,0 +1,143 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Golden flows: the messages of a canonical run, one a line, kept in `golden/` so that a change
+// to how the protocol talks shows up as a diff in review. `UPDATE_GOLDEN=1 cargo test golden`
+// records them all again after an intended change; otherwise a missing file fails, so a flow
+// that was never committed can't pass unchecked.
+pub fn assert_golden(name: &str, actual: &str) {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("golden")
+        .join(format!("{}.flow", name));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, actual).unwrap();
+        return;
+    }
+    if !file.exists() {
+        panic!(
+            "{} is missing, record it with UPDATE_GOLDEN=1 and commit it",
+            file.display()
+        );
+    }
+    let expected = fs::read_to_string(&file).unwrap();
+    if expected == actual {
+        return;
+    }
+    // the lines from the first that differs, enough to see where the flows part ways
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let first = expected
+        .iter()
+        .zip(&actual)
+        .position(|(e, a)| e != a)
+        .unwrap_or_else(|| expected.len().min(actual.len()));
+    let mut diff = String::new();
+    for line in &expected[first..] {
+        diff.push_str(&format!("- {}\n", line));
+    }
+    for line in &actual[first..] {
+        diff.push_str(&format!("+ {}\n", line));
+    }
+    panic!(
+        "{} differs from line {}, rerun with UPDATE_GOLDEN=1 if that is intended:\n{}",
+        file.display(),
+        first + 1,
+        diff
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::PaxosModelActorState;
+    use crate::model::{NetworkMode, PaxosModelConfig, PaxosModelState};
+    use crate::paxos::PaxosMsg;
+    use crate::trace;
+    use stateright::actor::register::RegisterMsg;
+    use stateright::actor::{ActorModelAction, Id};
+
+    type Msg = RegisterMsg<u64, char, PaxosMsg<char>>;
+    type Action = ActorModelAction<Msg>;
+
+    // long enough for any of these runs to go quiet
+    const MAX_STEPS: usize = 200;
+
+    fn config() -> PaxosModelConfig {
+        PaxosModelConfig::new()
+            .client_count(1)
+            .server_count(3)
+            .put_count(1)
+            .network(NetworkMode::Unordered)
+    }
+
+    fn delivery(action: &Action) -> Option<&Msg> {
+        match action {
+            ActorModelAction::Deliver { msg, .. } => Some(msg),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn golden_single_proposer() {
+        let path = trace::ranked_path(
+            &config().into_model(),
+            |_: &PaxosModelState, action: &Action| delivery(action).map(|_| ()),
+            MAX_STEPS,
+        );
+        assert_golden("single-proposer", &trace::to_flow(&path));
+    }
+
+    #[test]
+    fn golden_competing_proposers() {
+        // both clients' puts arrive, at servers 0 and 1, before either server hears from the
+        // other
+        let path = trace::ranked_path(
+            &config().client_count(2).into_model(),
+            |_: &PaxosModelState, action: &Action| match delivery(action)? {
+                RegisterMsg::Put(..) => Some(0),
+                _ => Some(1),
+            },
+            MAX_STEPS,
+        );
+        assert_golden("competing-proposers", &trace::to_flow(&path));
+    }
+
+    #[test]
+    fn golden_recovery() {
+        // server 0 crashes with its accepts in flight, and the client's retry has server 1
+        // finish the slot with the value the acceptors report
+        let client = Id::from(3);
+        let path = trace::ranked_path(
+            &config()
+                .crash_count(1)
+                .retrying_clients(true)
+                .into_model(),
+            |state: &PaxosModelState, action: &Action| match action {
+                ActorModelAction::Deliver {
+                    msg: RegisterMsg::Internal(PaxosMsg::Crash),
+                    ..
+                } => {
+                    let accepting = state.network.iter().any(|env| {
+                        matches!(env.msg, RegisterMsg::Internal(PaxosMsg::Accept(..)))
+                    });
+                    Some(0).filter(|_| accepting)
+                }
+                ActorModelAction::Deliver { .. } => Some(1),
+                ActorModelAction::Timeout(id) if *id == client => Some(2),
+                _ => None,
+            },
+            MAX_STEPS,
+        );
+        let flow = trace::to_flow(&path);
+        assert!(flow.contains("Crash"), "{}", flow);
+        // the client heard back in the end
+        let (last, _) = path.last().unwrap();
+        assert!(matches!(
+            last.actor_states[usize::from(client)].as_ref(),
+            PaxosModelActorState::Client(client_state) if client_state.awaiting().is_none()
+        ));
+        assert_golden("recovery", &flow);
+    }
+}
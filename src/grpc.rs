@@ -0,0 +1,188 @@
+use crate::cluster::ClusterConfig;
+use crate::paxos::PaxosMsg;
+use crate::transport::{self, TransportMode, WireFormat};
+use stateright::actor::register::RegisterMsg;
+use std::io;
+use std::net::{SocketAddr, SocketAddrV4};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("paxos");
+}
+
+use proto::register_server::{Register, RegisterServer};
+use proto::{ClusterStatusReply, ClusterStatusRequest, GetReply, GetRequest, PutReply, PutRequest};
+
+type Msg = RegisterMsg<u64, char, PaxosMsg<char>>;
+
+// how long to wait on the cluster when the caller sets no deadline
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// the deadline a caller set, as carried in the `grpc-timeout` header
+fn deadline(metadata: &MetadataMap) -> Option<Duration> {
+    let timeout = metadata.get("grpc-timeout")?.to_str().ok()?;
+    if !timeout.is_ascii() || timeout.is_empty() {
+        return None;
+    }
+    let (amount, unit) = timeout.split_at(timeout.len() - 1);
+    let amount: u64 = amount.parse().ok()?;
+    Some(match unit {
+        "H" => Duration::from_secs(amount.saturating_mul(3600)),
+        "M" => Duration::from_secs(amount.saturating_mul(60)),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    })
+}
+
+// Translates RPCs into the register messages the client binary sends. Requests go to the
+// replica that answered the last one, which after a forwarded put is the leader, and back to
+// the local replica if that one stops answering.
+pub struct Gateway {
+    cluster: ClusterConfig,
+    local: SocketAddrV4,
+    transport: TransportMode,
+    wire: WireFormat,
+    leader: Mutex<Option<SocketAddrV4>>,
+    requests: AtomicU64,
+}
+
+impl Gateway {
+    pub fn new(cluster: ClusterConfig, local: SocketAddrV4, transport: TransportMode) -> Self {
+        Gateway {
+            cluster,
+            local,
+            transport,
+            wire: transport.default_wire(),
+            leader: Mutex::new(None),
+            requests: AtomicU64::new(0),
+        }
+    }
+
+    // how the replicas lay out their messages
+    pub fn wire(mut self, wire: WireFormat) -> Self {
+        self.wire = wire;
+        self
+    }
+
+    fn leader(&self) -> Option<SocketAddrV4> {
+        self.leader.lock().ok().and_then(|leader| *leader)
+    }
+
+    fn set_leader(&self, leader: Option<SocketAddrV4>) {
+        if let Ok(mut current) = self.leader.lock() {
+            *current = leader;
+        }
+    }
+
+    // replicas ignore ids they have served, so these have to be unique across every client
+    fn request_id(&self) -> u64 {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        nanos.wrapping_add(self.requests.fetch_add(1, Ordering::Relaxed))
+    }
+
+    async fn call(&self, metadata: &MetadataMap, request_id: u64, msg: Msg) -> Result<Msg, Status> {
+        let timeout = deadline(metadata).unwrap_or(DEFAULT_TIMEOUT);
+        let server = self.leader().unwrap_or(self.local);
+        let (mode, wire) = (self.transport, self.wire);
+        let called = tokio::task::spawn_blocking(move || {
+            transport::call(mode, wire, server, &msg, timeout, |reply| match reply {
+                RegisterMsg::PutOk(id) | RegisterMsg::GetOk(id, _) => *id == request_id,
+                _ => false,
+            })
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+        match called {
+            Ok((src, reply)) => {
+                if self.cluster.peers().contains(&src) {
+                    self.set_leader(Some(src));
+                }
+                Ok(reply)
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                // the leader may be gone, the local replica knows where to forward to
+                self.set_leader(None);
+                Err(Status::deadline_exceeded(format!(
+                    "no reply from {} within {:?}",
+                    server, timeout
+                )))
+            }
+            Err(e) => Err(Status::unavailable(e.to_string())),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Register for Gateway {
+    async fn put(&self, request: Request<PutRequest>) -> Result<Response<PutReply>, Status> {
+        let metadata = request.metadata().clone();
+        let put = request.into_inner();
+        if put.key.is_some() {
+            return Err(Status::invalid_argument("the register has no keys"));
+        }
+        let mut chars = put.value.chars();
+        let value = match (chars.next(), chars.next()) {
+            (Some(value), None) => value,
+            _ => {
+                return Err(Status::invalid_argument(
+                    "the value must be a single character",
+                ))
+            }
+        };
+        let request_id = self.request_id();
+        self.call(&metadata, request_id, RegisterMsg::Put(request_id, value))
+            .await?;
+        Ok(Response::new(PutReply {}))
+    }
+
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetReply>, Status> {
+        let request_id = self.request_id();
+        match self
+            .call(request.metadata(), request_id, RegisterMsg::Get(request_id))
+            .await?
+        {
+            RegisterMsg::GetOk(_, value) => Ok(Response::new(GetReply {
+                value: value.to_string(),
+            })),
+            _ => Err(Status::internal("the replica answered a get with a put")),
+        }
+    }
+
+    async fn cluster_status(
+        &self,
+        _request: Request<ClusterStatusRequest>,
+    ) -> Result<Response<ClusterStatusReply>, Status> {
+        Ok(Response::new(ClusterStatusReply {
+            local: self.local.to_string(),
+            peers: self
+                .cluster
+                .peers()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            leader: self.leader().map(|leader| leader.to_string()),
+        }))
+    }
+}
+
+pub async fn serve(addr: SocketAddr, gateway: Gateway) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(RegisterServer::new(gateway))
+        .serve(addr)
+        .await
+}
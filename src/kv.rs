@@ -0,0 +1,382 @@
+use crate::model::NetworkMode;
+use crate::paxos::{PaxosActor, Value};
+use crate::shard::ShardedPaxos;
+use crate::smr::{SmrActor, SmrModelActor, SmrModelState, SmrMsg, StateMachine, Tagged};
+use serde::{Deserialize, Serialize};
+use stateright::actor::*;
+use stateright::semantics::{LinearizabilityTester, SequentialSpec};
+use std::collections::BTreeMap;
+
+pub type Key = u8;
+
+// commands interfere when they touch the same key
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum KvCommand<V> {
+    Put(Key, V),
+    Get(Key),
+    Delete(Key),
+    // swaps in the second value if the key holds the first, a missing key holding the default
+    Cas(Key, V, V),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum KvResponse<V> {
+    PutOk,
+    GetOk(V),
+    DeleteOk,
+    // whether the value was swapped
+    CasOk(bool),
+}
+
+impl<V> KvCommand<V> {
+    pub fn key(&self) -> Key {
+        match self {
+            KvCommand::Put(key, _)
+            | KvCommand::Get(key)
+            | KvCommand::Delete(key)
+            | KvCommand::Cas(key, _, _) => *key,
+        }
+    }
+}
+
+// the log has to hold something before the first command arrives
+impl<V> Default for KvCommand<V> {
+    fn default() -> Self {
+        KvCommand::Get(0)
+    }
+}
+
+// a map from key to the last value written, both the example state machine and the
+// sequential reference object it is checked against
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct KvStore<V>(pub BTreeMap<Key, V>);
+
+impl<V: Value> StateMachine for KvStore<V> {
+    type Command = KvCommand<V>;
+    type Response = KvResponse<V>;
+
+    fn apply(&mut self, command: &KvCommand<V>) -> KvResponse<V> {
+        match command {
+            KvCommand::Put(key, value) => {
+                self.0.insert(*key, value.clone());
+                KvResponse::PutOk
+            }
+            KvCommand::Get(key) => KvResponse::GetOk(self.0.get(key).cloned().unwrap_or_default()),
+            KvCommand::Delete(key) => {
+                self.0.remove(key);
+                KvResponse::DeleteOk
+            }
+            KvCommand::Cas(key, expected, value) => {
+                let swapped = self.0.get(key).cloned().unwrap_or_default() == *expected;
+                if swapped {
+                    self.0.insert(*key, value.clone());
+                }
+                KvResponse::CasOk(swapped)
+            }
+        }
+    }
+
+    // gets of a key commute with each other, and nothing on one key affects another
+    fn conflicts(a: &KvCommand<V>, b: &KvCommand<V>) -> bool {
+        let reads = matches!((a, b), (KvCommand::Get(_), KvCommand::Get(_)));
+        a.key() == b.key() && !reads
+    }
+
+    fn shard_key(command: &KvCommand<V>) -> u64 {
+        u64::from(command.key())
+    }
+}
+
+impl<V: Value> SequentialSpec for KvStore<V> {
+    type Op = KvCommand<V>;
+    type Ret = KvResponse<V>;
+
+    fn invoke(&mut self, op: &Self::Op) -> Self::Ret {
+        self.apply(op)
+    }
+}
+
+#[derive(Clone)]
+pub struct KvModelConfig {
+    client_count: usize,
+    server_count: usize,
+    network: NetworkMode,
+    // batch client commands and let replicas reorder those that do not conflict
+    generalized: bool,
+    // independent paxos groups of `server_count` servers each, keys hashed between them
+    shard_count: usize,
+    // clients put, swap, get and delete across two keys rather than writing and reading one
+    multi_key: bool,
+}
+
+impl Default for KvModelConfig {
+    fn default() -> Self {
+        KvModelConfig {
+            client_count: 2,
+            server_count: 3,
+            network: NetworkMode::Unordered,
+            generalized: false,
+            shard_count: 1,
+            multi_key: false,
+        }
+    }
+}
+
+impl KvModelConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn client_count(mut self, client_count: usize) -> Self {
+        self.client_count = client_count;
+        self
+    }
+
+    pub fn server_count(mut self, server_count: usize) -> Self {
+        self.server_count = server_count;
+        self
+    }
+
+    pub fn network(mut self, network: NetworkMode) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn generalized(mut self, generalized: bool) -> Self {
+        self.generalized = generalized;
+        self
+    }
+
+    pub fn shard_count(mut self, shard_count: usize) -> Self {
+        self.shard_count = shard_count.max(1);
+        self
+    }
+
+    pub fn multi_key(mut self, multi_key: bool) -> Self {
+        self.multi_key = multi_key;
+        self
+    }
+
+    fn shards(&self) -> ShardedPaxos {
+        ShardedPaxos::contiguous(self.shard_count, self.server_count)
+    }
+
+    // every client writes the shared key and then reads it back, so reads can observe
+    // the other clients' writes. Generalized, clients alternate between two keys, so that some
+    // of their commands commute. Sharded, clients take turns between the shards, each with a key
+    // of that shard's. Multi-key, each client puts a key of its own, swaps its value into the
+    // next client's key if that is still empty, reads the next key and deletes its own, so
+    // every operation meets another client's on some key.
+    fn commands(&self, client: usize) -> Vec<KvCommand<char>> {
+        let value = (b'A' + client as u8) as char;
+        if self.multi_key {
+            let own = (client % 2) as Key;
+            let next = ((client + 1) % 2) as Key;
+            return vec![
+                KvCommand::Put(own, value),
+                KvCommand::Cas(next, char::default(), value),
+                KvCommand::Get(next),
+                KvCommand::Delete(own),
+            ];
+        }
+        let key = if self.shard_count > 1 {
+            let shards = self.shards();
+            (0..=Key::MAX)
+                .find(|key| shards.shard(u64::from(*key)) == client % self.shard_count)
+                .unwrap_or(0)
+        } else if self.generalized {
+            client as Key % 2
+        } else {
+            0
+        };
+        vec![KvCommand::Put(key, value), KvCommand::Get(key)]
+    }
+
+    pub fn into_model(self) -> KvModel {
+        ActorModel::new(self.clone(), LinearizabilityTester::new(KvStore::default()))
+            .actors((0..self.shard_count * self.server_count).map(|i| {
+                let paxos = PaxosActor::new(self.shards().peers(Id::from(i)))
+                    .batch_size(self.generalized.then_some(self.client_count));
+                SmrModelActor::Server(SmrActor::new(paxos).commutative(self.generalized))
+            }))
+            .actors((0..self.client_count).map(|i| SmrModelActor::Client {
+                commands: self.commands(i),
+                shards: self.shards(),
+            }))
+            .duplicating_network(match self.network {
+                NetworkMode::UnorderedDuplicating => DuplicatingNetwork::Yes,
+                _ => DuplicatingNetwork::No,
+            })
+            .lossy_network(match self.network {
+                NetworkMode::Lossy => LossyNetwork::Yes,
+                _ => LossyNetwork::No,
+            })
+            .property(
+                stateright::Expectation::Always,
+                "linearizable",
+                |_, state| state.history.serialized_history().is_some(),
+            )
+            .property(
+                stateright::Expectation::Always,
+                "replicas agree",
+                |model, state| {
+                    // replicas of a group that applied the same number of commands hold the
+                    // same store
+                    let shards = model.cfg.shards();
+                    let machines: Vec<_> = state
+                        .actor_states
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, actor_state)| match actor_state.as_ref() {
+                            SmrModelState::Server(server_state) => Some((
+                                shards.shard_of(Id::from(i)),
+                                server_state.next(),
+                                server_state.machine(),
+                            )),
+                            _ => None,
+                        })
+                        .collect();
+                    machines.iter().all(|(a_shard, a_next, a)| {
+                        machines.iter().all(|(b_shard, b_next, b)| {
+                            a_shard != b_shard || a_next != b_next || a == b
+                        })
+                    })
+                },
+            )
+            .property(
+                stateright::Expectation::Always,
+                "conflicting commands ordered alike",
+                |_, state| {
+                    // two replicas' orders are equivalent if they only differ in commands
+                    // that commute
+                    let orders: Vec<_> = server_orders(state).collect();
+                    orders.iter().all(|a| {
+                        orders.iter().all(|b| {
+                            a.iter().enumerate().all(|(i, first)| {
+                                a[i + 1..].iter().all(|second| {
+                                    !KvStore::<char>::conflicts(first.command(), second.command())
+                                        || !matches!(
+                                            (position(b, first), position(b, second)),
+                                            (Some(x), Some(y)) if x > y
+                                        )
+                                })
+                            })
+                        })
+                    })
+                },
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "commands reordered",
+                |model, state| {
+                    let cfg = &model.cfg;
+                    let orders: Vec<_> = server_orders(state).collect();
+                    !cfg.generalized
+                        || orders.iter().any(|a| {
+                            orders.iter().any(|b| {
+                                a.len() == b.len()
+                                    && a != b
+                                    && a.iter().all(|tagged| b.contains(tagged))
+                            })
+                        })
+                },
+            )
+            .property(
+                stateright::Expectation::Always,
+                "keys stay in their shard",
+                |model, state| {
+                    // a group only ever orders commands on its own keys
+                    let shards = model.cfg.shards();
+                    state
+                        .actor_states
+                        .iter()
+                        .enumerate()
+                        .all(|(i, actor_state)| match actor_state.as_ref() {
+                            SmrModelState::Server(server_state) => {
+                                server_state.order().iter().all(|tagged| {
+                                    let key = u64::from(tagged.command().key());
+                                    shards.shard_of(Id::from(i)) == Some(shards.shard(key))
+                                })
+                            }
+                            _ => true,
+                        })
+                },
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "every shard applied",
+                |model, state| {
+                    let shards = model.cfg.shards();
+                    (0..shards.group_count()).all(|shard| {
+                        shards.group(shard).iter().any(|id| {
+                            match state.actor_states[usize::from(*id)].as_ref() {
+                                SmrModelState::Server(server_state) => server_state.next() > 0,
+                                _ => false,
+                            }
+                        })
+                    })
+                },
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "value read",
+                |_, state| {
+                    for env in &state.network {
+                        if let SmrMsg::Response(_, KvResponse::GetOk(value)) = env.msg {
+                            if value != char::default() {
+                                return true;
+                            }
+                        }
+                    }
+                    false
+                },
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "value swapped",
+                |model, state| {
+                    !model.cfg.multi_key
+                        || state.network.iter().any(|env| {
+                            matches!(env.msg, SmrMsg::Response(_, KvResponse::CasOk(true)))
+                        })
+                },
+            )
+            .record_msg_out(|_, history, env| match env.msg {
+                SmrMsg::Request(_, command) => {
+                    let mut history = history.clone();
+                    let _ = history.on_invoke(env.src, command.clone());
+                    Some(history)
+                }
+                _ => None,
+            })
+            .record_msg_in(|_, history, env| match env.msg {
+                SmrMsg::Response(_, ret) => {
+                    let mut history = history.clone();
+                    let _ = history.on_return(env.dst, ret.clone());
+                    Some(history)
+                }
+                _ => None,
+            })
+    }
+}
+
+// the order each replica applied commands in, skipping the clients
+fn server_orders(
+    state: &ActorModelState<SmrModelActor<KvStore<char>>, KvHistory>,
+) -> impl Iterator<Item = &[Tagged<KvCommand<char>>]> {
+    state
+        .actor_states
+        .iter()
+        .filter_map(|actor_state| match actor_state.as_ref() {
+            SmrModelState::Server(server_state) => Some(server_state.order()),
+            _ => None,
+        })
+}
+
+fn position(order: &[Tagged<KvCommand<char>>], tagged: &Tagged<KvCommand<char>>) -> Option<usize> {
+    order.iter().position(|other| other == tagged)
+}
+
+pub type KvHistory = LinearizabilityTester<Id, KvStore<char>>;
+
+pub type KvModel = ActorModel<SmrModelActor<KvStore<char>>, KvModelConfig, KvHistory>;
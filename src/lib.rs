@@ -0,0 +1,55 @@
+pub mod admin;
+pub mod ballot;
+pub mod cas;
+pub mod checking;
+pub mod checkpoint;
+pub mod checksum;
+pub mod chunking;
+pub mod client;
+pub mod clock;
+pub mod cluster;
+pub mod codec;
+pub mod compose;
+pub mod detector;
+pub mod differential;
+pub mod disk;
+pub mod encryption;
+pub mod epaxos;
+pub mod events;
+pub mod features;
+#[cfg(test)]
+mod golden;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod kv;
+pub mod load;
+pub mod logging;
+pub mod mencius;
+pub mod metrics;
+pub mod model;
+pub mod observer;
+pub mod paxos;
+#[cfg(feature = "pbft")]
+pub mod pbft;
+pub mod quorum;
+pub mod raft;
+pub mod rng;
+pub mod roles;
+pub mod scenario;
+#[cfg(test)]
+mod script;
+pub mod shard;
+pub mod sim;
+pub mod smr;
+pub mod storage;
+pub mod strategy;
+pub mod tla;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod topology;
+pub mod trace;
+pub mod transport;
+pub mod txn;
+pub mod vertical;
+#[cfg(feature = "protobuf")]
+pub mod wire;
@@ -0,0 +1,231 @@
+use crate::paxos::PaxosMsg;
+use crate::rng::{Rng, SplitMix};
+use crate::transport::{self, TransportMode, WireFormat};
+use stateright::actor::register::RegisterMsg;
+use std::fmt;
+use std::net::SocketAddrV4;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+type Msg = RegisterMsg<u64, char, PaxosMsg<char>>;
+
+// how hard to drive a spawned cluster, and for how long
+pub struct LoadConfig {
+    clients: usize,
+    // chance of each operation being a get rather than a put
+    get_ratio: f64,
+    // what the gets, puts and values are drawn from, each client its own stream of it
+    seed: u64,
+    warmup: Duration,
+    duration: Duration,
+    timeout: Duration,
+    transport: TransportMode,
+    wire: WireFormat,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        LoadConfig {
+            clients: 4,
+            get_ratio: 0.5,
+            seed: 0,
+            warmup: Duration::from_secs(2),
+            duration: Duration::from_secs(10),
+            timeout: Duration::from_secs(1),
+            transport: TransportMode::Udp,
+            wire: TransportMode::Udp.default_wire(),
+        }
+    }
+}
+
+impl LoadConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clients(mut self, clients: usize) -> Self {
+        self.clients = clients.max(1);
+        self
+    }
+
+    pub fn get_ratio(mut self, get_ratio: f64) -> Self {
+        self.get_ratio = get_ratio.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn warmup(mut self, warmup: Duration) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn transport(mut self, transport: TransportMode, wire: WireFormat) -> Self {
+        self.transport = transport;
+        self.wire = wire;
+        self
+    }
+
+    // Each client is a thread with one request in flight at a time, sending to the replicas in
+    // turn starting from its own offset. Requests finishing before the warmup is over are not
+    // counted, so the report reflects a cluster that has elected its leaders and warmed its
+    // caches.
+    pub fn run(&self, servers: &[SocketAddrV4]) -> LoadReport {
+        let base = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        let start = Instant::now();
+        let measure_from = start + self.warmup;
+        let until = measure_from + self.duration;
+        let runs: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = (0..self.clients)
+                .map(|client| {
+                    s.spawn(move || {
+                        // ids are spread far apart per client so no two requests share one
+                        let first_id = base.wrapping_add((client as u64) << 40);
+                        self.client(servers, client, first_id, measure_from, until)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_default())
+                .collect()
+        });
+        let mut report = LoadReport {
+            clients: self.clients,
+            elapsed: self.duration,
+            ..LoadReport::default()
+        };
+        for run in runs {
+            report.puts += run.puts;
+            report.gets += run.gets;
+            report.failed += run.failed;
+            report.latencies.extend(run.latencies);
+        }
+        report
+    }
+
+    fn client(
+        &self,
+        servers: &[SocketAddrV4],
+        client: usize,
+        first_id: u64,
+        measure_from: Instant,
+        until: Instant,
+    ) -> LoadReport {
+        let mut report = LoadReport::default();
+        if servers.is_empty() {
+            return report;
+        }
+        let mut rng = SplitMix(self.seed).fork(client as u64);
+        let mut request_id = first_id;
+        let mut next = client;
+        while Instant::now() < until {
+            request_id = request_id.wrapping_add(1);
+            let get = rng.unit() < self.get_ratio;
+            let msg: Msg = if get {
+                RegisterMsg::Get(request_id)
+            } else {
+                RegisterMsg::Put(request_id, (b'A' + rng.below(26) as u8) as char)
+            };
+            let server = servers[next % servers.len()];
+            next += 1;
+            let sent = Instant::now();
+            let reply = transport::call(
+                self.transport,
+                self.wire,
+                server,
+                &msg,
+                self.timeout,
+                |reply| match reply {
+                    RegisterMsg::PutOk(id) | RegisterMsg::GetOk(id, _) => *id == request_id,
+                    _ => false,
+                },
+            );
+            if sent < measure_from {
+                continue;
+            }
+            match reply {
+                Ok(_) => {
+                    report.latencies.push(sent.elapsed());
+                    if get {
+                        report.gets += 1;
+                    } else {
+                        report.puts += 1;
+                    }
+                }
+                Err(_) => report.failed += 1,
+            }
+        }
+        report
+    }
+}
+
+// what the clients saw over the measured window
+#[derive(Clone, Debug, Default)]
+pub struct LoadReport {
+    clients: usize,
+    elapsed: Duration,
+    puts: usize,
+    gets: usize,
+    // requests that timed out or could not be sent
+    failed: usize,
+    latencies: Vec<Duration>,
+}
+
+impl LoadReport {
+    // completed requests per second of the measured window
+    pub fn throughput(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.latencies.len() as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        let mut sorted = self.latencies.clone();
+        sorted.sort();
+        let last = sorted.len().checked_sub(1)?;
+        sorted.get(((last as f64) * p).round() as usize).copied()
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} clients over {:?}: {} puts and {} gets completed, {} failed",
+            self.clients, self.elapsed, self.puts, self.gets, self.failed
+        )?;
+        match (
+            self.percentile(0.5),
+            self.percentile(0.99),
+            self.percentile(1.0),
+        ) {
+            (Some(p50), Some(p99), Some(max)) => writeln!(
+                f,
+                "Throughput {:.1}/s, latency p50 {:?}, p99 {:?}, max {:?}",
+                self.throughput(),
+                p50,
+                p99,
+                max
+            ),
+            _ => writeln!(f, "Completed no requests"),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+
+// prints events for spawned replicas, filtered by RUST_LOG and by default only decisions
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let _ = tracing_subscriber::fmt().with_env_filter(filter).try_init();
+}
+
+// an event as captured, with the fields of every span it happened in
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Captured {
+    pub message: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+struct Fields<'a>(&'a mut BTreeMap<String, String>);
+
+impl Visit for Fields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+// kept in a span's extensions until its events need them
+struct SpanFields(BTreeMap<String, String>);
+
+// Collects events in memory instead of printing them, for looking at what the actors did while
+// being model checked or tested.
+#[derive(Clone, Default)]
+pub struct Capture {
+    events: Arc<Mutex<Vec<Captured>>>,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<Captured> {
+        self.events
+            .lock()
+            .map(|events| events.clone())
+            .unwrap_or_default()
+    }
+
+    // runs `f` with the events on this thread captured, the checker's own threads are not
+    pub fn during<R>(&self, f: impl FnOnce() -> R) -> R {
+        let subscriber = tracing_subscriber::registry().with(self.clone());
+        tracing::subscriber::with_default(subscriber, f)
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = BTreeMap::new();
+        attrs.record(&mut Fields(&mut fields));
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(fields));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(SpanFields(fields)) = span.extensions_mut().get_mut::<SpanFields>() {
+                values.record(&mut Fields(fields));
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = BTreeMap::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(SpanFields(span_fields)) = span.extensions().get::<SpanFields>() {
+                    fields.extend(span_fields.clone());
+                }
+            }
+        }
+        event.record(&mut Fields(&mut fields));
+        let message = fields.remove("message").unwrap_or_default();
+        if let Ok(mut events) = self.events.lock() {
+            events.push(Captured { message, fields });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::paxos::{PaxosActor, PaxosMsg, RoundIdentifier};
+    use stateright::actor::register::RegisterMsg;
+    use stateright::actor::{Actor, Id as ActorId, Out};
+    use std::borrow::Cow;
+
+    #[test]
+    fn events_carry_their_round_and_request() {
+        let capture = Capture::new();
+        capture.during(|| {
+            let actor = PaxosActor::<char>::new(vec![ActorId::from(1), ActorId::from(2)]);
+            let mut o = Out::new();
+            let mut state = Cow::Owned(actor.on_start(ActorId::from(0), &mut o));
+            let rid = RoundIdentifier::new(1, ActorId::from(1));
+            let prepare = PaxosMsg::Prepare(7, ActorId::from(3), 0, rid);
+            actor.on_msg(
+                ActorId::from(0),
+                &mut state,
+                ActorId::from(1),
+                RegisterMsg::Internal(prepare),
+                &mut o,
+            );
+        });
+        let promised = capture
+            .events()
+            .into_iter()
+            .find(|event| event.message == "promised")
+            .expect("the acceptor promised");
+        assert_eq!(
+            promised.fields.get("round").map(String::as_str),
+            Some("1.1")
+        );
+        assert_eq!(
+            promised.fields.get("request_id").map(String::as_str),
+            Some("7")
+        );
+        assert_eq!(
+            promised.fields.get("replica").map(String::as_str),
+            Some("0")
+        );
+    }
+}
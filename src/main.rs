@@ -1,322 +1,1626 @@
-use serde::{Deserialize, Serialize};
-use stateright::actor::{register::*, *};
-use stateright::semantics::register::Register;
-use stateright::semantics::LinearizabilityTester;
-use stateright::Checker;
+use clap::{Args, Parser, Subcommand};
+use paxos_rs::admin::Admin;
+use paxos_rs::cas::CasModelConfig;
+use paxos_rs::checking::{self, Search};
+use paxos_rs::checkpoint;
+use paxos_rs::chunking::{ChunkedActor, ChunkedState};
+use paxos_rs::client::Pattern;
+use paxos_rs::cluster::ClusterConfig;
+use paxos_rs::compose::{Consensus, LogModel, SingleDecree};
+use paxos_rs::detector::DetectorSpec;
+use paxos_rs::differential::{self, Observation};
+use paxos_rs::encryption::{Encryptor, KeySource};
+use paxos_rs::epaxos::EPaxosModelConfig;
+use paxos_rs::events::EventServer;
+use paxos_rs::features::Features;
+use paxos_rs::kv::KvModelConfig;
+use paxos_rs::load::LoadConfig;
+use paxos_rs::logging;
+use paxos_rs::metrics::{Metrics, PrometheusMetrics};
+use paxos_rs::model::{NetworkMode, PaxosModel, PaxosModelConfig, Protocol};
+use paxos_rs::observer::FileObserver;
+use paxos_rs::paxos::{ClockSkew, ReadStrategy, Role};
+use paxos_rs::quorum::QuorumSpec;
+use paxos_rs::rng::{self, SplitMix};
+use paxos_rs::scenario::{self, Scenario};
+use paxos_rs::sim::{self, AdversarySpec, Latency, Link, Partition, SimConfig};
+use paxos_rs::storage::FileStorage;
+use paxos_rs::strategy::StrategySpec;
+use paxos_rs::tla;
+use paxos_rs::topology::Topology;
+use paxos_rs::trace::{self, Scheduled};
+use paxos_rs::transport::{
+    self, Mailbox, Plain, TcpTransport, TransportMode, UdpTransport, WireFormat, WireMessage,
+};
+use paxos_rs::txn::TxnModelConfig;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use stateright::actor::{Actor, ActorModel, ActorModelAction, ActorModelState, Id};
 use stateright::Model;
-use std::borrow::Cow;
-use std::collections::{BTreeMap, BTreeSet};
-
-type RegisterValue = char;
-
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
-pub struct PaxosState {
-    id: Id,
-    round: u32,
-    prepare_data: BTreeMap<RoundIdentifier, RegisterValue>,
-    promises: BTreeMap<RoundIdentifier, BTreeSet<Id>>,
-    accepts: BTreeMap<RoundIdentifier, BTreeSet<Id>>,
-    last_seen: Option<RoundIdentifier>,
-    value: Option<char>,
-    decided: bool,
+use stateright::{Checker, Expectation};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::io::{BufRead, Write};
+use std::net::SocketAddrV4;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[clap(about = "Model check, explore or run the Paxos replicated register")]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the model checker and report the property results, exiting with 1 if any property
+    /// came out other than expected
+    Check {
+        #[clap(flatten)]
+        model: ModelArgs,
+        #[clap(flatten)]
+        search: SearchArgs,
+        /// Name of a shipped scenario or path to a scenario file, whose settings take the place
+        /// of the matching flags
+        #[clap(long)]
+        scenario: Option<String>,
+    },
+    /// List the shipped scenarios that check --scenario runs by name
+    Scenarios,
+    /// Serve the interactive explorer
+    Explore {
+        #[clap(default_value = "localhost:3000")]
+        address: String,
+        #[clap(flatten)]
+        model: ModelArgs,
+        /// Show paxos states and messages in full rather than as one line summaries
+        #[clap(long)]
+        verbose_state: bool,
+        #[clap(flatten)]
+        events: EventArgs,
+    },
+    /// Explore --protocol and another protocol behind the same register clients and report
+    /// any answers the clients get from one but never from the other, exiting with 1 if there
+    /// are any
+    Diff {
+        #[clap(flatten)]
+        model: ModelArgs,
+        /// The protocol to compare --protocol against, one of paxos, raft, mencius, vertical,
+        /// disk or roles like it
+        #[clap(long)]
+        against: Protocol,
+    },
+    /// Step through the model in the terminal, choosing each action from those enabled, with
+    /// undo; the explorer for when there is no browser
+    Step {
+        #[clap(flatten)]
+        model: ModelArgs,
+        /// Show paxos states and messages in full rather than as one line summaries
+        #[clap(long)]
+        verbose_state: bool,
+    },
+    /// Run the model's actors under a seeded scheduler for many steps, checking agreement and
+    /// consistency after each one, and report throughput and latency; paxos only
+    Sim {
+        #[clap(flatten)]
+        model: ModelArgs,
+        /// Seed for every latency, drop and timer draw, a run is reproduced by its seed
+        #[clap(long, default_value_t = 0)]
+        seed: u64,
+        /// Number of deliveries and timeouts after which the run stops
+        #[clap(long, default_value_t = 1_000_000)]
+        steps: usize,
+        /// One of fixed:<ms>, uniform:<min_ms>:<max_ms>, exponential:<mean_ms> or
+        /// lognormal:<median_ms>:<sigma>
+        #[clap(long, default_value_t = Latency::Uniform(Duration::from_millis(1), Duration::from_millis(10)))]
+        latency: Latency,
+        /// Latency of messages one way between actors, as <id>,<id>,...:<id>,<id>,...:<latency>,
+        /// in place of --latency; may be repeated, the first match applying
+        #[clap(long)]
+        link: Vec<Link>,
+        /// Chance of each message between two actors being dropped
+        #[clap(long, default_value_t = 0.0)]
+        drop_rate: f64,
+        /// Chance of each message between two actors having a bit flipped, which its checksum
+        /// should catch
+        #[clap(long, default_value_t = 0.0)]
+        corrupt_rate: f64,
+        /// Cut actors off from the rest for a while, as <from_ms>:<until_ms>:<id>,<id>,...; may be
+        /// repeated
+        #[clap(long)]
+        partition: Vec<Partition>,
+        /// Who schedules messages: random, or an adversary, slow-leader:<ms> to hold back the
+        /// leader's messages or late-prepares:<ms> to deliver prepares newest first
+        #[clap(long, default_value_t = AdversarySpec::Random)]
+        adversary: AdversarySpec,
+        /// Latency of messages between servers in different --regions, in place of --latency
+        #[clap(long)]
+        cross_region_latency: Option<Latency>,
+        /// Name of a shipped scenario or path to a scenario file, whose settings and link
+        /// latencies take the place of the matching flags
+        #[clap(long)]
+        scenario: Option<String>,
+    },
+    /// Compare the fewest messages delivered before every client's puts are acknowledged under
+    /// Multi-Paxos and under Mencius, searching each model's whole state space
+    CommitLatency {
+        #[clap(flatten)]
+        model: ModelArgs,
+    },
+    /// Check one instance of consensus on its own, with values proposed by an abstract
+    /// environment instead of clients, then the replicated log built from instances behaving as
+    /// that check showed; --servers acceptors or replicas, --puts values per client,
+    /// --max-rounds ballots
+    Compose {
+        #[clap(flatten)]
+        model: ModelArgs,
+    },
+    /// Run replicas over UDP exchanging JSON messages, or over TCP exchanging bincode frames
+    Spawn {
+        /// Cluster config file listing every replica's socket address
+        #[clap(long, default_value = "cluster.json")]
+        config: PathBuf,
+        /// Only run the replica at this address, instead of the whole cluster
+        id: Option<SocketAddrV4>,
+        /// Directory holding each replica's write-ahead log, so replicas survive restarts
+        #[clap(long)]
+        wal_dir: Option<PathBuf>,
+        /// One of file:<path> or env:<variable>, holding a 256 bit key in hex to encrypt
+        /// write-ahead log records with; needs the encryption feature
+        #[clap(long)]
+        wal_key: Option<KeySource>,
+        /// One of udp or tcp
+        #[clap(long, default_value_t = TransportMode::Udp)]
+        transport: TransportMode,
+        /// One of json, bincode, cbor or, with the protobuf feature, protobuf; json over udp and
+        /// bincode over tcp unless given
+        #[clap(long)]
+        wire: Option<WireFormat>,
+        /// Serve /status, /peers, /metrics, /healthz and /readyz over HTTP for each replica, on
+        /// its own port plus N
+        #[clap(long)]
+        admin_offset: Option<u16>,
+        /// Send replica messages larger than N bytes, accepts of big values, in chunks of N
+        #[clap(long)]
+        chunk_size: Option<usize>,
+        /// Have proposers back off between retries, by random amounts, and yield to higher
+        /// ballots
+        #[clap(long)]
+        backoff: bool,
+        /// Seed for the timer jitter and backoff of every replica, each drawing from it by its
+        /// port; one from the clock unless given, printed at startup either way
+        #[clap(long)]
+        seed: Option<u64>,
+        /// Deprecated, the same as --backoff --seed N
+        #[clap(long, hide = true, conflicts_with = "seed")]
+        backoff_seed: Option<u64>,
+        /// One of timeout:<ms> or phi:<threshold>; leaders send heartbeats while idle and
+        /// followers that stop hearing them propose themselves
+        #[clap(long)]
+        failure_detector: Option<DetectorSpec>,
+        /// Run the replica at this address as a witness, which votes without storing values or
+        /// serving reads; may be given more than once
+        #[clap(long = "witness")]
+        witnesses: Vec<SocketAddrV4>,
+        /// Directory to write each replica's applied entries to, a line per slot in
+        /// DIR/ADDRESS.applied, for diffing replicas against each other
+        #[clap(long)]
+        apply_log: Option<PathBuf>,
+        /// Over TCP, the most messages queued for each peer, and for the replica itself, before
+        /// new ones to that peer are dropped or peers are no longer read from
+        #[clap(long, default_value_t = 1024)]
+        queue_depth: usize,
+        /// Over TCP, the most client requests queued for the replica before new ones are dropped
+        #[clap(long, default_value_t = 256)]
+        request_queue: usize,
+        /// Have proposers ask whether a quorum would promise a round before starting it, so a
+        /// replica rejoining after a partition does not abort the rounds of the others
+        #[clap(long)]
+        pre_vote: bool,
+        /// Let a proposer batch up to N puts into one decree
+        #[clap(long)]
+        batch: Option<usize>,
+        /// The protocol features to advertise to the other replicas, a comma separated list of
+        /// batching, pre-vote and handoff, or all or none; a feature is only used once every
+        /// replica advertises it, so a rolling upgrade can pin the newer replicas to the older
+        #[clap(long, default_value_t = Features::ALL)]
+        features: Features,
+        /// Serve the gRPC client API on this address, in front of the replica given by ID or the
+        /// first one in the config
+        #[cfg(feature = "grpc")]
+        #[clap(long)]
+        grpc: Option<std::net::SocketAddr>,
+    },
+    /// Drive a spawned cluster with concurrent clients, reporting throughput and latency
+    Bench {
+        /// Cluster config file listing every replica's socket address
+        #[clap(long, default_value = "cluster.json")]
+        config: PathBuf,
+        /// Number of clients, each with one request in flight at a time
+        #[clap(long, default_value_t = 4)]
+        clients: usize,
+        /// Chance of each request being a get rather than a put
+        #[clap(long, default_value_t = 0.5)]
+        get_ratio: f64,
+        /// Seed for which requests are gets and the values put; one from the clock unless
+        /// given, printed at startup either way
+        #[clap(long)]
+        seed: Option<u64>,
+        /// Seconds to send requests for before measuring
+        #[clap(long, default_value_t = 2)]
+        warmup: u64,
+        /// Seconds to measure for
+        #[clap(long, default_value_t = 10)]
+        duration: u64,
+        /// Milliseconds to wait for a reply before counting the request as failed
+        #[clap(long, default_value_t = 1000)]
+        timeout: u64,
+        /// One of udp or tcp, whichever the replicas were spawned with
+        #[clap(long, default_value_t = TransportMode::Udp)]
+        transport: TransportMode,
+        /// One of json, bincode, cbor or protobuf, whichever the replicas were spawned with
+        #[clap(long)]
+        wire: Option<WireFormat>,
+    },
 }
 
-impl PaxosState {
-    fn next_round(&mut self) -> RoundIdentifier {
-        self.round += 1;
-        RoundIdentifier {
-            id: self.id.clone(),
-            round_num: self.round,
+#[derive(Args)]
+struct SearchArgs {
+    /// One of bfs, dfs or simulation
+    #[clap(long, default_value_t = Search::Dfs)]
+    search: Search,
+    /// Seed for the random walks of a simulation
+    #[clap(long, default_value_t = 0)]
+    seed: u64,
+    /// Number of random walks a simulation takes
+    #[clap(long, default_value_t = 1000)]
+    runs: usize,
+    /// Number of steps after which a random walk gives up
+    #[clap(long, default_value_t = 100)]
+    depth: usize,
+    /// Directory to write each counterexample to, as JSON and as a mermaid sequence diagram
+    #[clap(long)]
+    trace_dir: Option<PathBuf>,
+    /// File to write the counterexamples to as a TLA+ module of Paxos.tla behaviors, one per
+    /// slot; paxos only
+    #[clap(long)]
+    export_tla: Option<PathBuf>,
+    /// File to write the explored state graph to in Graphviz DOT, as far as --max-depth if
+    /// given, before checking
+    #[clap(long)]
+    export_dot: Option<PathBuf>,
+    /// Take the actions in a schedule written to --trace-dir, rather than searching, and check
+    /// the states they lead to
+    #[clap(long)]
+    replay: Option<PathBuf>,
+    /// Treat states that only differ by a renaming of actors as one, searching depth first;
+    /// paxos only
+    #[clap(long)]
+    symmetry: bool,
+    /// Stop searching past this many steps from an initial state
+    #[clap(long)]
+    max_depth: Option<usize>,
+    /// Stop searching once this many states have been generated
+    #[clap(long)]
+    max_states: Option<NonZeroUsize>,
+    /// Seconds between progress lines while searching, 0 for none
+    #[clap(long, default_value_t = 1)]
+    progress: u64,
+    /// Explore every state without checking any property, and print how many there are, how
+    /// large they are and how many share a fingerprint
+    #[clap(long)]
+    report_memory: bool,
+    /// Check only the property of this name, may be given more than once
+    #[clap(long = "property")]
+    properties: Vec<String>,
+    /// Search breadth first on one thread, writing what is left to search to this file every
+    /// --checkpoint-every seconds, for --resume to carry on from
+    #[clap(long)]
+    checkpoint: Option<PathBuf>,
+    /// Seconds between checkpoints
+    #[clap(long, default_value_t = 300)]
+    checkpoint_every: u64,
+    /// Carry on the search a --checkpoint file was written for, with the same model options,
+    /// checkpointing to it again
+    #[clap(long)]
+    resume: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct EventArgs {
+    /// Also stream random walks through the model to WebSocket clients on this address, as
+    /// the JSON events schema/events.json describes
+    #[clap(long)]
+    events: Option<std::net::SocketAddr>,
+    /// Seed for the first client's walks, each later client taking the next one
+    #[clap(long, default_value_t = 0)]
+    events_seed: u64,
+    /// Milliseconds between the steps streamed
+    #[clap(long, default_value_t = 500)]
+    events_interval: u64,
+    /// Number of steps after which a streamed walk starts over
+    #[clap(long, default_value_t = 100)]
+    events_depth: usize,
+}
+
+#[derive(Args)]
+struct ModelArgs {
+    #[clap(long, default_value_t = 3)]
+    clients: usize,
+    #[clap(long, default_value_t = 3)]
+    servers: usize,
+    #[clap(long, default_value_t = 2)]
+    puts: usize,
+    /// Gets each client makes after its puts, under paxos
+    #[clap(long, default_value_t = 1)]
+    gets: usize,
+    /// Worker threads for the checker and explorer, one per core unless given
+    #[clap(long)]
+    threads: Option<usize>,
+    /// Have clients send an unanswered request again, under the same request id, to the next
+    /// server in turn; paxos only
+    #[clap(long)]
+    retry: bool,
+    /// Have servers tag each client put with its session and apply it once, however often it
+    /// is decided; paxos only
+    #[clap(long)]
+    sessions: bool,
+    /// Fold the puts onto this many symbolic values, v1 written A and so on, shrinking the
+    /// state space while agreement and validity are checked as before; paxos only
+    #[clap(long)]
+    abstract_values: Option<usize>,
+    /// Values the puts take turns writing, as a string of characters, in place of one for
+    /// each put; paxos only
+    #[clap(long)]
+    values: Option<String>,
+    /// Order of each client's operations, puts-then-gets or read-after-write, which follows
+    /// every put with a get; paxos only
+    #[clap(long, default_value_t = Pattern::PutsThenGets)]
+    pattern: Pattern,
+    /// Have clients wait an arbitrary while before each request; paxos only
+    #[clap(long)]
+    think: bool,
+    /// One of unordered, duplicating or lossy
+    #[clap(long, default_value_t = NetworkMode::UnorderedDuplicating)]
+    network: NetworkMode,
+    /// Number of servers that crash and restart during the run
+    #[clap(long, default_value_t = 0)]
+    crashes: usize,
+    /// Index of a server that forges promises and accepts, breaking agreement
+    #[clap(long)]
+    byzantine: Option<usize>,
+    /// Cut the first N servers off from the rest at some point during the run, until the
+    /// partition heals
+    #[clap(long)]
+    split: Option<usize>,
+    /// Sizes of the regions the servers run in, as <size>,<size>,..., filled with servers in
+    /// order
+    #[clap(long)]
+    regions: Option<Topology>,
+    /// Index of a region whose servers stay down for the whole run
+    #[clap(long)]
+    region_down: Option<usize>,
+    /// Servers in the initial configuration, defaults to all of them
+    #[clap(long)]
+    members: Option<usize>,
+    /// Have server 0 reconfigure the cluster to the first N servers during the run
+    #[clap(long)]
+    reconfigure: Option<usize>,
+    /// Have server 0 hand its leadership to server N once it leads
+    #[clap(long)]
+    transfer_leadership: Option<usize>,
+    /// Compact each server's log into a snapshot every N applied slots
+    #[clap(long)]
+    snapshot_every: Option<u64>,
+    /// Let leaders serve reads locally under a lease, with clock skew bounded or unbounded
+    #[clap(long)]
+    read_leases: Option<ClockSkew>,
+    /// Time read leases on logical clocks assumed to be at most N ticks apart; paxos only
+    #[clap(long)]
+    clock_skew: Option<u64>,
+    /// How servers answer gets: quorum, lease or local, which serves stale reads; paxos only
+    #[clap(long, default_value_t = ReadStrategy::Quorum)]
+    reads: ReadStrategy,
+    /// Let a proposer batch up to N puts into one decree, best tried with --clients 3 or more
+    #[clap(long)]
+    batch: Option<usize>,
+    /// Run the last N servers as the build from before batching and the feature handshake,
+    /// partway through a rolling upgrade; paxos only
+    #[clap(long)]
+    upgrade: Option<usize>,
+    /// Have the newer servers in an upgrade use their features without agreeing on them first
+    #[clap(long)]
+    no_negotiation: bool,
+    /// Let a proposer have at most N slots past its applied prefix in flight
+    #[clap(long)]
+    pipeline: Option<u64>,
+    /// Let a leader have at most N slots sent to each follower without an answer, holding
+    /// back later accepts to it until it catches up; paxos only
+    #[clap(long)]
+    replication_window: Option<usize>,
+    /// Have proposers ask whether a quorum would promise a round before starting it, so one
+    /// cut off by a partition does not drive rounds up; paxos only
+    #[clap(long)]
+    pre_vote: bool,
+    /// Have proposers back off between retries and hand a proposal nacked by a higher ballot
+    /// to its proposer; paxos only
+    #[clap(long)]
+    backoff: bool,
+    /// Let followers suspect their leader at any point, and trust it again, proposing
+    /// themselves while they suspect it; paxos only
+    #[clap(long)]
+    suspicion: bool,
+    /// Make the last N servers witnesses, which vote in both phases without storing values or
+    /// serving reads; paxos only
+    #[clap(long, default_value_t = 0)]
+    witnesses: usize,
+    /// Leave states where a server has gone past round N unexplored; paxos, disk and txn only,
+    /// txn bounding at 2 unless given
+    #[clap(long)]
+    max_rounds: Option<u64>,
+    /// Leave states with more than N messages in flight unexplored; paxos and disk only
+    #[clap(long)]
+    max_messages: Option<usize>,
+    /// Batch commands and let replicas apply those that do not conflict in their own order;
+    /// kv only
+    #[clap(long)]
+    generalized: bool,
+    /// Split the keys between N independent paxos groups of --servers servers each; kv only
+    #[clap(long, default_value_t = 1)]
+    shards: usize,
+    /// Have clients put, compare-and-swap, get and delete across two keys; kv only
+    #[clap(long)]
+    multi_key: bool,
+    /// Number of shared disks the servers agree through; disk only
+    #[clap(long, default_value_t = 3)]
+    disks: usize,
+    /// Number of acceptors the servers, as proposers, go through; roles only
+    #[clap(long, default_value_t = 3)]
+    acceptors: usize,
+    /// Number of learners that apply the log and answer the proposers; roles only
+    #[clap(long, default_value_t = 2)]
+    learners: usize,
+    /// One of majority, flexible:<phase1>:<phase2>, grid:<row_len>, or by --regions: regions,
+    /// a majority in a majority of regions, or one-per-region
+    #[clap(long, default_value_t = QuorumSpec::Majority)]
+    quorum: QuorumSpec,
+    /// One of majority, any-promise or fast-path
+    #[clap(long, default_value_t = StrategySpec::MajorityPromises)]
+    strategy: StrategySpec,
+    /// One of paxos, raft, cas, epaxos, kv, mencius, vertical, disk, txn or roles; with cas, --puts
+    /// is the number of changes per client, epaxos always runs its four fixed clients, txn runs
+    /// one transfer between two shards of --servers servers, its coordinator crashing with
+    /// --crashes, and roles runs --servers proposers
+    #[clap(long, default_value_t = Protocol::Paxos)]
+    protocol: Protocol,
+}
+
+impl ModelArgs {
+    fn threads(&self) -> usize {
+        self.threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+
+    fn config(&self) -> PaxosModelConfig<char> {
+        PaxosModelConfig::new()
+            .client_count(self.clients)
+            .server_count(self.servers)
+            .put_count(self.puts)
+            .get_count(self.gets)
+            .retrying_clients(self.retry)
+            .sessions(self.sessions)
+            .abstract_values(self.abstract_values)
+            .value_domain(self.values.as_ref().map(|values| values.chars().collect()))
+            .pattern(self.pattern)
+            .think_time(self.think)
+            .network(self.network)
+            .crash_count(self.crashes)
+            .byzantine(self.byzantine)
+            .split(self.split)
+            .topology(self.regions.clone().unwrap_or_default())
+            .region_down(self.region_down)
+            .member_count(self.members)
+            .reconfigure(self.reconfigure)
+            .transfer_leadership(self.transfer_leadership)
+            .snapshot_every(self.snapshot_every)
+            .read_leases(self.read_leases)
+            .clock_skew(self.clock_skew)
+            .read_strategy(self.reads)
+            .batch_size(self.batch)
+            .upgrade(self.upgrade)
+            .negotiate(!self.no_negotiation)
+            .pipeline(self.pipeline)
+            .replication_window(self.replication_window)
+            .pre_vote(self.pre_vote)
+            .backoff(self.backoff)
+            .suspicion(self.suspicion)
+            .witness_count(self.witnesses)
+            .max_rounds(self.max_rounds)
+            .max_network_messages(self.max_messages)
+            .disk_count(self.disks)
+            .acceptor_count(self.acceptors)
+            .learner_count(self.learners)
+            .quorum(self.quorum)
+            .strategy(self.strategy)
+    }
+
+    // the scenario's settings in place of those given as flags
+    fn apply(&mut self, scenario: &Scenario) {
+        self.protocol = scenario.protocol.unwrap_or(self.protocol);
+        self.servers = scenario.servers.unwrap_or(self.servers);
+        self.clients = scenario.clients.unwrap_or(self.clients);
+        self.puts = scenario.puts.unwrap_or(self.puts);
+        self.network = scenario.network.unwrap_or(self.network);
+        self.backoff = scenario.backoff.unwrap_or(self.backoff);
+        self.max_rounds = scenario.max_rounds.or(self.max_rounds);
+        self.max_messages = scenario.max_messages.or(self.max_messages);
+        self.crashes = scenario.faults.crashes.unwrap_or(self.crashes);
+        self.byzantine = scenario.faults.byzantine.or(self.byzantine);
+        self.split = scenario.faults.split.or(self.split);
+        self.regions = scenario.regions.clone().or_else(|| self.regions.clone());
+        self.quorum = scenario.quorum.unwrap_or(self.quorum);
+        self.region_down = scenario.faults.region_down.or(self.region_down);
+        self.batch = scenario.batch.or(self.batch);
+        self.upgrade = scenario.upgrade.or(self.upgrade);
+    }
+
+    fn cas_config(&self) -> CasModelConfig {
+        CasModelConfig::new()
+            .client_count(self.clients)
+            .server_count(self.servers)
+            .change_count(self.puts)
+            .network(self.network)
+            .quorum(self.quorum)
+    }
+
+    fn epaxos_config(&self) -> EPaxosModelConfig {
+        EPaxosModelConfig::new()
+            .server_count(self.servers)
+            .network(self.network)
+    }
+
+    fn kv_config(&self) -> KvModelConfig {
+        KvModelConfig::new()
+            .client_count(self.clients)
+            .server_count(self.servers)
+            .network(self.network)
+            .generalized(self.generalized)
+            .shard_count(self.shards)
+            .multi_key(self.multi_key)
+    }
+
+    fn txn_config(&self) -> TxnModelConfig {
+        let config = TxnModelConfig::new()
+            .server_count(self.servers)
+            .network(self.network)
+            .coordinator_crashes(self.crashes > 0);
+        match self.max_rounds {
+            Some(max) => config.max_rounds(Some(max)),
+            None => config,
         }
     }
 }
 
-// strategy to make forward progress on Paxos
-// "majority wins" is not needed for linearizability only for the strict (arguably correct) definition of "consensus".
-trait ForwardStrategy {
-    fn majority_promises() -> bool;
-    fn majority_acceptor() -> bool;
+// counterexamples as `check` finds them, by property name
+type Counterexamples<A, H> = [(&'static str, Vec<trace::Step<A, H>>)];
+
+// whether every property came out as expected
+fn check<A, C, H>(model: ActorModel<A, C, H>, threads: usize, search: &SearchArgs) -> bool
+where
+    A: Actor + Send + Sync + 'static,
+    A::Msg: Serialize + DeserializeOwned + Send + Sync,
+    A::State: Send + Sync,
+    C: Send + Sync + 'static,
+    H: Clone + Debug + Hash + Send + Sync + 'static,
+{
+    check_exporting(model, threads, search, |_, _| {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "only --protocol paxos maps onto Paxos.tla",
+        ))
+    })
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
-pub struct RoundIdentifier {
-    round_num: u32,
-    id: Id,
+// checks one of the compositional models, whose states are small enough not to need any of
+// the search options
+fn check_consensus<M>(model: M, threads: usize) -> bool
+where
+    M: Consensus + Send + Sync + 'static,
+    M::State: Clone + Debug + Hash + Send + Sync,
+    M::Action: Debug + Send + Sync,
+{
+    let checker = model
+        .checker()
+        .threads(threads)
+        .spawn_bfs()
+        .report(&mut std::io::stdout());
+    let discoveries = checker.discoveries();
+    checking::passed(checker.model(), |name| discoveries.contains_key(name))
 }
 
-impl PartialOrd for RoundIdentifier {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        return Some(self.cmp(other));
+// `check`, with `export_tla` writing the counterexamples to the --export-tla file
+fn check_exporting<A, C, H>(
+    mut model: ActorModel<A, C, H>,
+    threads: usize,
+    search: &SearchArgs,
+    export_tla: impl Fn(&Path, &Counterexamples<A, H>) -> std::io::Result<()>,
+) -> bool
+where
+    A: Actor + Send + Sync + 'static,
+    A::Msg: Serialize + DeserializeOwned + Send + Sync,
+    A::State: Send + Sync,
+    C: Send + Sync + 'static,
+    H: Clone + Debug + Hash + Send + Sync + 'static,
+{
+    for name in &search.properties {
+        if !model
+            .properties
+            .iter()
+            .any(|property| property.name == name)
+        {
+            println!("No property named \"{}\" to check", name);
+        }
+    }
+    if !search.properties.is_empty() {
+        model
+            .properties
+            .retain(|property| search.properties.iter().any(|name| name == property.name));
+    }
+    if let Some(file) = &search.replay {
+        return replay(&model, file);
+    }
+    if search.report_memory {
+        println!("{}", checking::memory_report(&model));
+        return true;
+    }
+    if let Some(file) = &search.export_dot {
+        // node labels take the one line paxos summaries
+        paxos_rs::paxos::summarize_debug(true);
+        let graph = checking::state_graph(&model, search.max_depth);
+        let dot = trace::to_dot(&graph);
+        paxos_rs::paxos::summarize_debug(false);
+        match std::fs::write(file, dot) {
+            Ok(()) => println!(
+                "Wrote {0} states and {1} transitions to {2}",
+                graph.states.len(),
+                graph.edges.len(),
+                file.display()
+            ),
+            Err(e) => println!("Unable to write {0}: {1}", file.display(), e),
+        }
+    }
+    let mut stdout = std::io::stdout();
+    let checkpointed = search.resume.as_ref().or(search.checkpoint.as_ref());
+    let outcome = if let Some(file) = checkpointed {
+        let every = Duration::from_secs(search.checkpoint_every);
+        let resume = search.resume.is_some();
+        match checkpoint::checkpointed_bfs(&model, file, resume, every, &mut stdout) {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                println!("Unable to checkpoint to {0}: {1}", file.display(), e);
+                return false;
+            }
+        }
+    } else if search.search == Search::Simulation {
+        let (seed, runs, depth) = (search.seed, search.runs, search.depth);
+        println!(
+            "Simulating from seed {0}, --seed {0} repeats the walks",
+            seed
+        );
+        match checking::simulate(&model, seed, runs, depth, &mut stdout) {
+            Ok(outcome) => outcome,
+            Err(_) => return false,
+        }
+    } else {
+        let mut checker = model.checker().threads(threads);
+        if let Some(depth) = search.max_depth {
+            checker = checker.target_max_depth(depth);
+        }
+        if let Some(count) = search.max_states {
+            checker = checker.target_state_count(count);
+        }
+        let checker = if search.search == Search::Bfs {
+            checker.spawn_bfs()
+        } else {
+            checker.spawn_dfs()
+        };
+        if search.progress > 0 {
+            let every = Duration::from_secs(search.progress);
+            checking::progress(&checker, every, &mut stdout);
+        }
+        let checker = checker.report(&mut stdout);
+        let discoveries = checker.discoveries();
+        let counterexamples = checker
+            .model()
+            .properties()
+            .into_iter()
+            .filter(|property| !matches!(property.expectation, Expectation::Sometimes))
+            .filter_map(|property| {
+                let path = discoveries.get(property.name)?.clone();
+                Some((property.name, path.into_vec()))
+            })
+            .collect();
+        checking::Outcome {
+            passed: checking::passed(checker.model(), |name| discoveries.contains_key(name)),
+            counterexamples,
+        }
+    };
+    if let Some(dir) = &search.trace_dir {
+        for (name, path) in &outcome.counterexamples {
+            match trace::export(dir, name, path) {
+                Ok(()) => println!("Wrote the \"{0}\" trace to {1}", name, dir.display()),
+                Err(e) => println!("Unable to write the \"{0}\" trace: {1}", name, e),
+            }
+        }
     }
+    if let Some(file) = &search.export_tla {
+        match export_tla(file, &outcome.counterexamples) {
+            Ok(()) => println!("Wrote the TLA+ behaviors to {0}", file.display()),
+            Err(e) => println!("Unable to write the TLA+ behaviors: {0}", e),
+        }
+    }
+    outcome.passed
 }
 
-impl Ord for RoundIdentifier {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        if self.round_num != other.round_num {
-            return self.round_num.cmp(&other.round_num);
+// whether the schedule in `file` leads only to states with every property intact
+fn replay<A, C, H>(model: &ActorModel<A, C, H>, file: &Path) -> bool
+where
+    A: Actor,
+    A::Msg: DeserializeOwned,
+    H: Clone + Debug + Hash,
+{
+    let schedule: Vec<_> = match trace::read_schedule(file) {
+        Ok(schedule) => schedule.into_iter().map(Scheduled::into_action).collect(),
+        Err(e) => {
+            println!("Unable to read {0}: {1}", file.display(), e);
+            return false;
         }
-        return self.id.cmp(&other.id);
+    };
+    let replayed = checking::replay(model, &schedule);
+    println!("Replayed {0} of {1} steps", replayed.taken, schedule.len());
+    if let Some(step) = replayed.stuck {
+        println!(
+            "Step {0} is no longer possible: {1:?}",
+            step + 1,
+            schedule[step]
+        );
+    }
+    for (name, taken) in &replayed.violated {
+        println!("Property \"{0}\" broken after {1} steps", name, taken);
     }
+    replayed.violated.is_empty()
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
-pub enum PaxosMsg {
-    Prepare(u64, Id, RoundIdentifier),
-    Promise(u64, Id, RoundIdentifier),
-    Accept(u64, Id, RoundIdentifier, RegisterValue),
-    Accepted(u64, Id, RoundIdentifier, RegisterValue),
+// checks with and without symmetry reduction, to report how much it saved
+fn check_symmetric(model: impl Fn() -> PaxosModel, threads: usize) -> bool {
+    let mut stdout = std::io::stdout();
+    let full = model().checker().threads(threads).spawn_dfs().join();
+    let reduced = model()
+        .checker()
+        .threads(threads)
+        .symmetry()
+        .spawn_dfs()
+        .report(&mut stdout);
+    println!(
+        "Unique states without symmetry reduction: {0}, with: {1}",
+        full.unique_state_count(),
+        reduced.unique_state_count()
+    );
+    let discoveries = reduced.discoveries();
+    checking::passed(reduced.model(), |name| discoveries.contains_key(name))
 }
 
-pub struct PaxosActor {
-    peers: Vec<Id>,
+fn explore<A, C, H>(
+    model: impl Fn() -> ActorModel<A, C, H>,
+    threads: usize,
+    address: String,
+    events: &EventArgs,
+) where
+    A: Actor + Send + Sync + 'static,
+    A::Msg: Send + Sync,
+    A::State: Send + Sync,
+    C: Send + Sync + 'static,
+    H: Clone + Debug + Hash + Send + Sync + 'static,
+{
+    if let Some(addr) = events.events {
+        let server = EventServer::new(model())
+            .seed(events.events_seed)
+            .interval(Duration::from_millis(events.events_interval))
+            .depth(events.events_depth);
+        println!(
+            "Streaming events from ws://{0}, walks seeded from {1}",
+            addr, events.events_seed
+        );
+        thread::spawn(move || {
+            if let Err(e) = server.serve(addr) {
+                println!("Unable to stream events from {0}: {1}", addr, e);
+            }
+        });
+    }
+    model().checker().threads(threads).serve(address);
 }
 
-impl Actor for PaxosActor {
-    type Msg = RegisterMsg<u64, RegisterValue, PaxosMsg>;
-    type State = PaxosState;
+// the envelopes in flight, one line each, for telling what a step sent and took
+fn envelopes<A: Actor, H>(state: &ActorModelState<A, H>) -> Vec<String> {
+    state
+        .network
+        .iter()
+        .map(|env| {
+            format!(
+                "{}->{} {:?}",
+                usize::from(env.src),
+                usize::from(env.dst),
+                env.msg
+            )
+        })
+        .collect()
+}
 
-    fn on_start(&self, id: Id, _o: &mut Out<Self>) -> Self::State {
-        PaxosState {
-            id,
-            round: 0,
-            prepare_data: BTreeMap::new(),
-            last_seen: None,
-            promises: BTreeMap::new(),
-            accepts: BTreeMap::new(),
-            decided: false,
-            value: None,
+// those of `a` not in `b`, counting repeats
+fn missing_from(a: &[String], b: &[String]) -> Vec<String> {
+    let mut b = b.to_vec();
+    a.iter()
+        .filter(|line| match b.iter().position(|other| other == *line) {
+            Some(i) => {
+                b.swap_remove(i);
+                false
+            }
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
+
+fn print_state<A: Actor, H>(state: &ActorModelState<A, H>) {
+    for (i, actor_state) in state.actor_states.iter().enumerate() {
+        println!("  actor {0}: {1:?}", i, actor_state);
+    }
+    for line in envelopes(state) {
+        println!("  in flight: {0}", line);
+    }
+}
+
+// what changed from one state to the next, and any property it broke
+fn print_diff<A, C, H>(
+    model: &ActorModel<A, C, H>,
+    before: &ActorModelState<A, H>,
+    after: &ActorModelState<A, H>,
+) where
+    A: Actor,
+    H: Clone + Debug + Hash,
+{
+    for (i, actor_state) in after.actor_states.iter().enumerate() {
+        if before.actor_states.get(i) != Some(actor_state) {
+            println!("  actor {0}: {1:?}", i, actor_state);
         }
     }
-    fn on_msg(
-        &self,
-        _: Id,
-        state: &mut Cow<Self::State>,
-        src: Id,
-        msg: Self::Msg,
-        o: &mut Out<Self>,
-    ) {
-        match msg {
-            RegisterMsg::Internal(internal_msg) => {
-                match internal_msg {
-                    // request_id is stateright specific while rid is the round identifier
-                    PaxosMsg::Prepare(request_id, org_sender, rid) => {
-                        if state.decided {
-                            return;
-                        }
+    let (before, after_envelopes) = (envelopes(before), envelopes(after));
+    for line in missing_from(&before, &after_envelopes) {
+        println!("  taken: {0}", line);
+    }
+    for line in missing_from(&after_envelopes, &before) {
+        println!("  sent: {0}", line);
+    }
+    for property in model.properties() {
+        if matches!(property.expectation, Expectation::Always)
+            && !(property.condition)(model, after)
+        {
+            println!("  Property \"{0}\" broken", property.name);
+        }
+    }
+}
 
-                        let greater = match state.last_seen {
-                            Some(val) => rid > val,
-                            None => true,
-                        };
-                        if greater {
-                            let state = state.to_mut();
-                            state.last_seen = Some(rid);
-                            let msg = RegisterMsg::Internal(PaxosMsg::Promise(
-                                request_id, org_sender, rid,
-                            ));
-                            o.send(src, msg);
-                        } else {
-                            // nack
-                        }
+// A terminal stand-in for the explorer: lists the actions enabled in the current state, takes
+// the one chosen by number and prints what it changed. `u` goes back a step, `s` prints the
+// whole state and `q`, or the end of input, stops.
+fn step<A, C, H>(model: ActorModel<A, C, H>)
+where
+    A: Actor,
+    H: Clone + Debug + Hash,
+{
+    let mut taken = match model.init_states().into_iter().next() {
+        Some(state) => vec![state],
+        None => return,
+    };
+    print_state(&taken[0]);
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        let state = &taken[taken.len() - 1];
+        let mut actions = Vec::new();
+        model.actions(state, &mut actions);
+        println!(
+            "Step {0}, {1} action(s) enabled:",
+            taken.len() - 1,
+            actions.len()
+        );
+        for (i, action) in actions.iter().enumerate() {
+            println!("  {0}: {1}", i, trace::action_label(action));
+        }
+        print!("step> ");
+        let _ = std::io::stdout().flush();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return,
+        };
+        match line.trim() {
+            "q" | "quit" => return,
+            "u" | "undo" if taken.len() > 1 => {
+                taken.pop();
+            }
+            "u" | "undo" => println!("Nothing to undo"),
+            "s" | "state" => print_state(state),
+            choice => match choice.parse::<usize>().ok().and_then(|i| actions.get(i)) {
+                Some(action) => match model.next_state(state, action.clone()) {
+                    Some(next) => {
+                        print_diff(&model, state, &next);
+                        taken.push(next);
                     }
+                    None => println!("The actors ignore that, nothing changes"),
+                },
+                None => {
+                    println!("Enter an action's number, u to undo, s for the state or q to quit")
+                }
+            },
+        }
+    }
+}
 
-                    // request_id is stateright specific while rid is the round identifier
-                    PaxosMsg::Promise(request_id, org_sender, rid) => {
-                        if state.decided {
-                            return;
-                        }
-                        let state = state.to_mut();
-                        match state.promises.get_mut(&rid) {
-                            Some(set) => {
-                                set.insert(src);
-                            }
-                            None => {
-                                let mut set = BTreeSet::new();
-                                set.insert(src);
-                                state.promises.insert(rid, set);
-                            }
-                        };
-
-                        let value = match state.prepare_data.get(&rid) {
-                            Some(data) => *data,
-                            None => return,
-                        };
-
-                        let count = match state.promises.get(&rid) {
-                            Some(s) => s.len(),
-                            None => 0,
-                        };
-                        let num_peers = self.peers.len();
-                        // we have a majority
-                        if count > num_peers / 2 {
-                            let msg = RegisterMsg::Internal(PaxosMsg::Accept(
-                                request_id, org_sender, rid, value,
-                            ));
-                            o.broadcast(&self.peers, &msg);
-                        }
-                    }
-                    PaxosMsg::Accept(request_id, org_sender, rid, value) => {
-                        if state.decided {
-                            return;
-                        }
-                        if Some(rid) == state.last_seen {
-                            let msg = RegisterMsg::Internal(PaxosMsg::Accepted(
-                                request_id, org_sender, rid, value,
-                            ));
-                            o.broadcast(&self.peers, &msg);
-                        }
-                    }
-                    PaxosMsg::Accepted(request_id, org_sender, rid, value) => {
-                        if state.decided {
-                            return;
-                        }
-                        let state = state.to_mut();
+// what the clients can be answered under `protocol`, with the actions that first led to each
+// observation, if the protocol serves the register clients `diff` compares by
+fn observe(
+    protocol: Protocol,
+    model: &ModelArgs,
+) -> Option<BTreeMap<Observation<char>, Vec<String>>> {
+    fn labelled<M: Debug>(
+        observations: BTreeMap<Observation<char>, Vec<ActorModelAction<M>>>,
+    ) -> BTreeMap<Observation<char>, Vec<String>> {
+        observations
+            .into_iter()
+            .map(|(observation, actions)| {
+                (
+                    observation,
+                    actions.iter().map(trace::action_label).collect(),
+                )
+            })
+            .collect()
+    }
+    let (config, clients) = (model.config(), model.clients);
+    let observations = match protocol {
+        Protocol::Paxos => labelled(differential::observations(&config.into_model(), clients)),
+        Protocol::Raft => labelled(differential::observations(
+            &config.into_raft_model(),
+            clients,
+        )),
+        Protocol::Mencius => labelled(differential::observations(
+            &config.into_mencius_model(),
+            clients,
+        )),
+        Protocol::Vertical => labelled(differential::observations(
+            &config.into_vertical_model(),
+            clients,
+        )),
+        Protocol::Disk => labelled(differential::observations(
+            &config.into_disk_model(),
+            clients,
+        )),
+        Protocol::Roles => labelled(differential::observations(
+            &config.into_roles_model(),
+            clients,
+        )),
+        _ => return None,
+    };
+    Some(observations)
+}
 
-                        match state.accepts.get_mut(&rid) {
-                            Some(set) => {
-                                set.insert(src);
-                            }
-                            None => {
-                                let mut set = BTreeSet::new();
-                                set.insert(src);
-                                state.accepts.insert(rid, set);
-                            }
-                        };
-
-                        let count = match state.accepts.get(&rid) {
-                            Some(s) => s.len(),
-                            None => 0,
-                        };
-
-                        let num_peers = self.peers.len();
-                        if count > num_peers / 2 {
-                            let msg = RegisterMsg::PutOk(request_id);
-                            state.value = Some(value);
-                            state.decided = true;
-                            o.send(org_sender, msg);
-                        }
-                    }
-                }
-            }
-            RegisterMsg::Put(request_id, value) => {
-                let state = state.to_mut();
-                let rid = state.next_round();
-                state.prepare_data.insert(rid, value);
-                let msg = RegisterMsg::Internal(PaxosMsg::Prepare(request_id, src, rid));
-                o.broadcast(&self.peers, &msg);
-            }
-            _ => {}
+// a shipped scenario or file by name, describing it before it runs
+fn load_scenario(name: &str) -> Scenario {
+    match Scenario::load(name) {
+        Ok(scenario) => {
+            println!("{}", scenario.description);
+            scenario
+        }
+        Err(e) => {
+            println!("Unable to load scenario {0}: {1}", name, e);
+            std::process::exit(1);
         }
     }
 }
 
-#[derive(Clone)]
-struct PaxosModelConfig {
-    client_count: usize,
-    server_count: usize,
+// the TCP transport for the node at `addr`, over TLS when the config gives every node a
+// certificate
+fn tcp_transport<M: WireMessage + Send + 'static>(
+    cluster: &ClusterConfig,
+    addr: SocketAddrV4,
+    wire: WireFormat,
+    mailbox: Mailbox,
+) -> std::io::Result<TcpTransport<M>> {
+    if cluster.is_tls() {
+        tls_transport(cluster, addr, wire, mailbox)
+    } else {
+        TcpTransport::bind_with(addr, wire, Arc::new(Plain), mailbox)
+    }
 }
 
-impl PaxosModelConfig {
-    fn into_model(
-        self,
-    ) -> ActorModel<
-        RegisterActor<PaxosActor>,
-        Self,
-        LinearizabilityTester<Id, Register<RegisterValue>>,
-    > {
-        ActorModel::new(
-            self.clone(),
-            LinearizabilityTester::new(Register(RegisterValue::default())),
-        )
-        .actors((0..self.server_count).map(|i| {
-            RegisterActor::Server(PaxosActor {
-                peers: model_peers(i, self.server_count),
-            })
-        }))
-        .actors((0..self.client_count).map(|i| RegisterActor::Client {
-            put_count: 1,
-            server_count: self.server_count,
-        }))
-        .duplicating_network(DuplicatingNetwork::No)
-        .property(
-            stateright::Expectation::Always,
-            "linearizable",
-            |_, state| state.history.serialized_history().is_some(),
-        )
-        .property(
-            stateright::Expectation::Sometimes,
-            "value chosen",
-            |_, state| {
-                for env in &state.network {
-                    if let RegisterMsg::GetOk(_, value) = env.msg {
-                        if value != RegisterValue::default() {
-                            return true;
+#[cfg(feature = "tls")]
+fn tls_transport<M: WireMessage + Send + 'static>(
+    cluster: &ClusterConfig,
+    addr: SocketAddrV4,
+    wire: WireFormat,
+    mailbox: Mailbox,
+) -> std::io::Result<TcpTransport<M>> {
+    let tls = paxos_rs::tls::Tls::new(cluster, addr)?;
+    TcpTransport::bind_with(addr, wire, Arc::new(tls), mailbox)
+}
+
+#[cfg(not(feature = "tls"))]
+fn tls_transport<M>(
+    _: &ClusterConfig,
+    _: SocketAddrV4,
+    _: WireFormat,
+    _: Mailbox,
+) -> std::io::Result<TcpTransport<M>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "the config gives nodes certificates, which needs the tls feature",
+    ))
+}
+
+#[cfg(feature = "encryption")]
+fn wal_encryptor(key: &KeySource) -> std::io::Result<Box<dyn Encryptor>> {
+    Ok(Box::new(paxos_rs::encryption::AesGcm::new(&key.load()?)))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn wal_encryptor(_: &KeySource) -> std::io::Result<Box<dyn Encryptor>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "--wal-key needs the encryption feature",
+    ))
+}
+
+// Runs the proposers, acceptors and learners of a cluster whose roles are separated, or just
+// the one at `id`. They keep nothing on disk and serve no admin endpoint, so the replica flags
+// of spawn do not apply to them.
+fn spawn_roles(
+    cluster: &ClusterConfig,
+    id: Option<SocketAddrV4>,
+    transport: TransportMode,
+    wire: WireFormat,
+    seed: u64,
+) {
+    let actors = cluster
+        .role_actors::<char>()
+        .into_iter()
+        .filter(|(addr, _)| id.map_or(true, |id| id == *addr));
+    let mut handles = Vec::new();
+    for (addr, actor) in actors {
+        let name = actor.name();
+        println!(
+            "Spawning {0} {1} over {2} as {3}",
+            name, addr, transport, wire
+        );
+        let cluster = cluster.clone();
+        // the same stream a replica at this address times from
+        let timers = SplitMix(seed).fork(u64::from(Id::from(addr))).fork(0);
+        handles.push(thread::spawn(move || {
+            let id = Id::from(addr);
+            let result = match transport {
+                TransportMode::Udp => UdpTransport::bind(addr, wire)
+                    .and_then(|udp| transport::run(actor, id, udp, timers)),
+                TransportMode::Tcp => {
+                    let mailbox = Mailbox::new().peers(cluster.members().into_iter().map(Id::from));
+                    tcp_transport(&cluster, addr, wire, mailbox)
+                        .and_then(|tcp| transport::run(actor, id, tcp, timers))
+                }
+            };
+            if let Err(e) = result {
+                println!("The {0} at {1} stopped: {2}", name, addr, e);
+            }
+        }));
+    }
+    for handle in handles {
+        let _ = handle.join();
+    }
+}
+
+fn main() {
+    match Cli::parse().command {
+        Command::Check {
+            mut model,
+            mut search,
+            scenario,
+        } => {
+            if let Some(name) = scenario {
+                let scenario = load_scenario(&name);
+                model.apply(&scenario);
+                if search.properties.is_empty() {
+                    search.properties = scenario.properties;
+                }
+            }
+            let threads = model.threads();
+            let passed = match model.protocol {
+                Protocol::Paxos if search.symmetry => {
+                    check_symmetric(|| model.config().into_model(), threads)
+                }
+                Protocol::Paxos => {
+                    check_exporting(model.config().into_model(), threads, &search, tla::export)
+                }
+                Protocol::Raft => check(model.config().into_raft_model(), threads, &search),
+                Protocol::Cas => check(model.cas_config().into_model(), threads, &search),
+                Protocol::EPaxos => check(model.epaxos_config().into_model(), threads, &search),
+                Protocol::Kv => check(model.kv_config().into_model(), threads, &search),
+                Protocol::Mencius => check(model.config().into_mencius_model(), threads, &search),
+                Protocol::Vertical => check(model.config().into_vertical_model(), threads, &search),
+                Protocol::Disk => check(model.config().into_disk_model(), threads, &search),
+                Protocol::Txn => check(model.txn_config().into_model(), threads, &search),
+                Protocol::Roles => check(model.config().into_roles_model(), threads, &search),
+            };
+            if paxos_rs::model::pruned() {
+                println!(
+                    "Pruned states past --max-rounds or --max-messages, the check was bounded"
+                );
+            }
+            if !passed {
+                std::process::exit(1);
+            }
+        }
+        Command::Scenarios => {
+            for (name, scenario) in scenario::shipped() {
+                match scenario {
+                    Ok(scenario) => println!("{0}: {1}", name, scenario.description),
+                    Err(e) => println!("{0}: unreadable, {1}", name, e),
+                }
+            }
+        }
+        Command::Explore {
+            address,
+            model,
+            verbose_state,
+            events,
+        } => {
+            println!("Serving from {0} for {1} client(s)", address, model.clients);
+            paxos_rs::paxos::summarize_debug(!verbose_state);
+            let threads = model.threads();
+            match model.protocol {
+                Protocol::Paxos => {
+                    explore(|| model.config().into_model(), threads, address, &events)
+                }
+                Protocol::Raft => explore(
+                    || model.config().into_raft_model(),
+                    threads,
+                    address,
+                    &events,
+                ),
+                Protocol::Cas => explore(
+                    || model.cas_config().into_model(),
+                    threads,
+                    address,
+                    &events,
+                ),
+                Protocol::EPaxos => explore(
+                    || model.epaxos_config().into_model(),
+                    threads,
+                    address,
+                    &events,
+                ),
+                Protocol::Kv => {
+                    explore(|| model.kv_config().into_model(), threads, address, &events)
+                }
+                Protocol::Mencius => explore(
+                    || model.config().into_mencius_model(),
+                    threads,
+                    address,
+                    &events,
+                ),
+                Protocol::Vertical => explore(
+                    || model.config().into_vertical_model(),
+                    threads,
+                    address,
+                    &events,
+                ),
+                Protocol::Disk => explore(
+                    || model.config().into_disk_model(),
+                    threads,
+                    address,
+                    &events,
+                ),
+                Protocol::Txn => explore(
+                    || model.txn_config().into_model(),
+                    threads,
+                    address,
+                    &events,
+                ),
+                Protocol::Roles => explore(
+                    || model.config().into_roles_model(),
+                    threads,
+                    address,
+                    &events,
+                ),
+            }
+        }
+        Command::Diff { model, against } => {
+            let protocol = model.protocol;
+            let (left, right) = match (observe(protocol, &model), observe(against, &model)) {
+                (Some(left), Some(right)) => (left, right),
+                _ => {
+                    println!("Only paxos, raft, mencius, vertical, disk and roles can be compared");
+                    std::process::exit(1);
+                }
+            };
+            println!(
+                "{0} allows {1} observation(s), {2} allows {3}",
+                protocol,
+                left.len(),
+                against,
+                right.len()
+            );
+            let mut diverged = false;
+            for (name, one, other) in [(protocol, &left, &right), (against, &right, &left)] {
+                for (observation, actions) in differential::missing_from(one, other) {
+                    diverged = true;
+                    println!(
+                        "Only {0} answers the clients {1:?}, after:",
+                        name, observation
+                    );
+                    for action in actions {
+                        println!("  {0}", action);
+                    }
+                }
+            }
+            if diverged {
+                std::process::exit(1);
+            }
+        }
+        Command::Step {
+            model,
+            verbose_state,
+        } => {
+            paxos_rs::paxos::summarize_debug(!verbose_state);
+            match model.protocol {
+                Protocol::Paxos => step(model.config().into_model()),
+                Protocol::Raft => step(model.config().into_raft_model()),
+                Protocol::Cas => step(model.cas_config().into_model()),
+                Protocol::EPaxos => step(model.epaxos_config().into_model()),
+                Protocol::Kv => step(model.kv_config().into_model()),
+                Protocol::Mencius => step(model.config().into_mencius_model()),
+                Protocol::Vertical => step(model.config().into_vertical_model()),
+                Protocol::Disk => step(model.config().into_disk_model()),
+                Protocol::Txn => step(model.txn_config().into_model()),
+                Protocol::Roles => step(model.config().into_roles_model()),
+            }
+        }
+        Command::Sim {
+            mut model,
+            seed,
+            steps,
+            mut latency,
+            mut link,
+            drop_rate,
+            corrupt_rate,
+            partition,
+            mut adversary,
+            mut cross_region_latency,
+            scenario,
+        } => {
+            if let Some(name) = scenario {
+                let scenario = load_scenario(&name);
+                model.apply(&scenario);
+                latency = scenario.latency.unwrap_or(latency);
+                adversary = scenario.adversary.unwrap_or(adversary);
+                cross_region_latency = scenario.cross_region_latency.or(cross_region_latency);
+                link.extend(scenario.links.iter().map(|link| {
+                    Link::new(
+                        link.from.iter().copied().map(Id::from).collect(),
+                        link.to.iter().copied().map(Id::from).collect(),
+                        link.latency,
+                    )
+                }));
+            }
+            if model.protocol != Protocol::Paxos {
+                println!("Only paxos can be simulated");
+                std::process::exit(1);
+            }
+            let cfg = link.into_iter().fold(
+                partition.into_iter().fold(
+                    SimConfig::new()
+                        .seed(seed)
+                        .steps(steps)
+                        .latency(latency)
+                        .drop_rate(drop_rate)
+                        .corrupt_rate(corrupt_rate)
+                        .adversary(adversary)
+                        .topology(model.regions.clone().unwrap_or_default())
+                        .cross_region_latency(cross_region_latency),
+                    SimConfig::partition,
+                ),
+                SimConfig::link,
+            );
+            println!("Simulating from seed {0}, --seed {0} repeats the run", seed);
+            let actors = model.config().seed(seed).into_model().actors;
+            let report = sim::run(&actors, &cfg, &paxos_rs::model::sim_invariants());
+            println!("{}", report);
+            if report.violation.is_some() {
+                std::process::exit(1);
+            }
+        }
+        Command::Compose { model } => {
+            let threads = model.threads();
+            let values: Vec<char> = ('A'..='Z').take(model.clients * model.puts).collect();
+            let ballots = model.max_rounds.unwrap_or(2);
+            println!("A single instance:");
+            let instance = check_consensus(
+                SingleDecree::new(model.servers, values.clone(), ballots),
+                threads,
+            );
+            println!("The log composed of instances:");
+            let log = check_consensus(LogModel::new(model.servers, values.len(), values), threads);
+            if !instance || !log {
+                std::process::exit(1);
+            }
+        }
+        Command::CommitLatency { model } => {
+            let describe = |deliveries: Option<usize>| match deliveries {
+                Some(deliveries) => format!("{} messages delivered", deliveries),
+                None => "never acknowledged".to_string(),
+            };
+            let (paxos, mencius) = model.config().commit_latency();
+            println!("Multi-Paxos: {}", describe(paxos));
+            println!("Mencius: {}", describe(mencius));
+        }
+        Command::Spawn {
+            config,
+            id,
+            wal_dir,
+            wal_key,
+            transport,
+            wire,
+            admin_offset,
+            chunk_size,
+            backoff,
+            seed,
+            backoff_seed,
+            failure_detector,
+            witnesses,
+            apply_log,
+            queue_depth,
+            request_queue,
+            pre_vote,
+            batch,
+            features,
+            #[cfg(feature = "grpc")]
+            grpc,
+        } => {
+            logging::init();
+            let wire = wire.unwrap_or_else(|| transport.default_wire());
+            let cluster = match ClusterConfig::load(&config) {
+                Ok(cluster) => cluster,
+                Err(e) => {
+                    println!("Unable to read {0}: {1}", config.display(), e);
+                    return;
+                }
+            };
+            if cluster.is_tls() && transport == TransportMode::Udp {
+                println!(
+                    "{0} gives nodes certificates, which needs --transport tcp",
+                    config.display()
+                );
+                return;
+            }
+            // kept for scripts written before --seed, though the draws it gives have changed
+            if backoff_seed.is_some() {
+                println!("--backoff-seed N is deprecated, use --backoff --seed N");
+            }
+            let backoff = backoff || backoff_seed.is_some();
+            let seed = rng::seed_or_fresh(seed.or(backoff_seed));
+            println!(
+                "Seeding replicas from {0}, --seed {0} repeats their draws",
+                seed
+            );
+            if cluster.is_role_separated() {
+                return spawn_roles(&cluster, id, transport, wire, seed);
+            }
+            let mut actors = match id {
+                Some(id) => vec![(id, cluster.actor::<char>(id))],
+                None => cluster.actors::<char>(),
+            };
+            if let Some(wal_dir) = &wal_dir {
+                let mut logged = Vec::new();
+                for (id, actor) in actors {
+                    let path = wal_dir.join(format!("{}.wal", id));
+                    let wal = FileStorage::open(&path).and_then(|wal| match &wal_key {
+                        Some(key) => Ok(wal.encryptor(wal_encryptor(key)?)),
+                        None => Ok(wal),
+                    });
+                    match wal {
+                        Ok(wal) => logged.push((id, actor.wal(wal))),
+                        Err(e) => {
+                            println!("Unable to open {0}: {1}", path.display(), e);
+                            return;
                         }
                     }
                 }
-                false
-            },
-        )
-        .property(
-            stateright::Expectation::Eventually,
-            "consensus reached",
-            |_, state| {
-                let mut map = BTreeMap::new();
-                let server_count = &state.actor_states.len();
-                for actor_state in &state.actor_states {
-                    match actor_state.as_ref() {
-                        RegisterActorState::Server(ref server_state) => {
-                            if let Some(val) = server_state.value {
-                                *map.entry(val).or_insert(0) += 1;
+                actors = logged;
+            }
+            // one registry for every replica in this process, each series labelled by replica
+            let exporter = admin_offset.map(|_| Arc::new(PrometheusMetrics::new()));
+            let mut handles = Vec::new();
+            for (addr, actor) in actors {
+                println!(
+                    "Spawning replica {0} over {1} as {2}",
+                    addr, transport, wire
+                );
+                let role = if witnesses.contains(&addr) {
+                    Role::Witness
+                } else {
+                    Role::Data
+                };
+                // the replica's own draws, timers on one stream and backoff on another; picked by
+                // the whole address, as replicas on different hosts often share a port
+                let draws = SplitMix(seed).fork(u64::from(Id::from(addr)));
+                let timers = draws.fork(0);
+                let actor = actor
+                    .backoff(Some(draws.fork(1).0).filter(|_| backoff))
+                    .failure_detector(failure_detector)
+                    .pre_vote(pre_vote)
+                    .batch_size(batch)
+                    .features(Some(features))
+                    .role(role)
+                    .witnesses(witnesses.iter().copied().map(Id::from).collect());
+                let actor = match &exporter {
+                    Some(exporter) => {
+                        exporter.register(Id::from(addr));
+                        actor.metrics(Arc::clone(exporter) as Arc<dyn Metrics>)
+                    }
+                    None => actor,
+                };
+                let actor = match &apply_log {
+                    Some(dir) => {
+                        let path = dir.join(format!("{}.applied", addr));
+                        match FileObserver::create(&path) {
+                            Ok(observer) => actor.observer(Arc::new(observer)),
+                            Err(e) => {
+                                println!("Unable to create {0}: {1}", path.display(), e);
+                                return;
                             }
                         }
-                        _ => {}
+                    }
+                    None => actor,
+                };
+                let mailbox = Mailbox::new()
+                    .peers(cluster.members().into_iter().map(Id::from))
+                    .queue_depth(queue_depth)
+                    .request_depth(request_queue);
+                let admin = match admin_offset.map(|offset| addr.port().checked_add(offset)) {
+                    Some(Some(port)) => {
+                        let admin_addr = SocketAddrV4::new(*addr.ip(), port);
+                        let peers = cluster.peers().to_vec();
+                        let mut admin = Admin::new(addr, peers);
+                        if let Some(wal_dir) = &wal_dir {
+                            admin = admin.wal(wal_dir.join(format!("{}.wal", addr)));
+                        }
+                        if let Some(exporter) = &exporter {
+                            admin = admin.exporter(Arc::clone(exporter));
+                        }
+                        if transport == TransportMode::Tcp {
+                            admin = admin.queues(mailbox.depths());
+                        }
+                        let admin = Arc::new(admin);
+                        let served = Arc::clone(&admin);
+                        println!(
+                            "Serving replica {0}'s admin endpoint from {1}",
+                            addr, admin_addr
+                        );
+                        handles.push(thread::spawn(move || {
+                            if let Err(e) = served.serve(admin_addr) {
+                                println!("Admin endpoint for {0} stopped: {1}", addr, e);
+                            }
+                        }));
+                        Some(admin)
+                    }
+                    Some(None) => {
+                        println!(
+                            "No admin endpoint for {0}, its port plus the offset overflows",
+                            addr
+                        );
+                        None
+                    }
+                    None => None,
+                };
+                let actor = ChunkedActor::new(actor).chunk_size(chunk_size);
+                let cluster = cluster.clone();
+                handles.push(thread::spawn(move || {
+                    let _running = admin.as_ref().map(|admin| admin.running());
+                    let observe = |event, state: &ChunkedState<char>, sent| {
+                        if let Some(admin) = &admin {
+                            admin.observe(event, state.paxos(), sent);
+                        }
                     };
-                }
-
-                for (_, count) in &map {
-                    if count != server_count && count != &0 {
-                        return false;
+                    let id = Id::from(addr);
+                    let result = match transport {
+                        TransportMode::Udp => UdpTransport::bind(addr, wire).and_then(|udp| {
+                            transport::run_observed(actor, id, udp, timers, observe)
+                        }),
+                        TransportMode::Tcp => tcp_transport(&cluster, addr, wire, mailbox)
+                            .and_then(|tcp| {
+                                transport::run_observed(actor, id, tcp, timers, observe)
+                            }),
+                    };
+                    if let Err(e) = result {
+                        println!("Replica {0} stopped: {1}", addr, e);
                     }
-                }
-
-                true
-            },
-        )
-        .record_msg_in(RegisterMsg::record_returns)
-        .record_msg_out(RegisterMsg::record_invocations)
-    }
-}
-/*
-#[cfg(test)]
-mod test {
-    use super::*;
-    use stateright::{semantics::register::*, semantics::*, *};
-    use ActorModelAction::Deliver;
-    use RegisterMsg::{Get, GetOk, Put, PutOk};
-}
- */
-fn main() {
-    let address = "localhost:3000";
-    let clients = 3;
-    let action = std::env::args().nth(1).unwrap_or("check".to_string());
-    let model = PaxosModelConfig {
-        client_count: clients,
-        server_count: 3,
-    }
-    .into_model()
-    .checker()
-    .threads(12);
-    match action.as_str() {
-        "check" => {
-            model.spawn_dfs().report(&mut std::io::stdout());
-        }
-        "explore" => {
-            println!("Serving from {0} for {1} client(s)", address, clients);
-            model.serve(address);
+                }));
+            }
+            #[cfg(feature = "grpc")]
+            if let (Some(grpc), Some(local)) =
+                (grpc, id.or_else(|| cluster.peers().first().copied()))
+            {
+                let gateway =
+                    paxos_rs::grpc::Gateway::new(cluster.clone(), local, transport).wire(wire);
+                println!("Serving gRPC from {0} in front of replica {1}", grpc, local);
+                handles.push(thread::spawn(move || {
+                    let served = tokio::runtime::Runtime::new()
+                        .map_err(|e| e.to_string())
+                        .and_then(|runtime| {
+                            runtime
+                                .block_on(paxos_rs::grpc::serve(grpc, gateway))
+                                .map_err(|e| e.to_string())
+                        });
+                    if let Err(e) = served {
+                        println!("gRPC gateway stopped: {0}", e);
+                    }
+                }));
+            }
+            for handle in handles {
+                let _ = handle.join();
+            }
         }
-        _ => {
-            println!("Unknown option passed");
+        Command::Bench {
+            config,
+            clients,
+            get_ratio,
+            seed,
+            warmup,
+            duration,
+            timeout,
+            transport,
+            wire,
+        } => {
+            let cluster = match ClusterConfig::load(&config) {
+                Ok(cluster) => cluster,
+                Err(e) => {
+                    println!("Unable to read {0}: {1}", config.display(), e);
+                    return;
+                }
+            };
+            let wire = wire.unwrap_or_else(|| transport.default_wire());
+            println!(
+                "Benchmarking {0} replicas over {1} as {2} with {3} clients",
+                cluster.peers().len(),
+                transport,
+                wire,
+                clients
+            );
+            let seed = rng::seed_or_fresh(seed);
+            println!(
+                "Seeding clients from {0}, --seed {0} repeats their requests",
+                seed
+            );
+            let report = LoadConfig::new()
+                .clients(clients)
+                .get_ratio(get_ratio)
+                .seed(seed)
+                .warmup(Duration::from_secs(warmup))
+                .duration(Duration::from_secs(duration))
+                .timeout(Duration::from_millis(timeout))
+                .transport(transport, wire)
+                .run(cluster.peers());
+            print!("{}", report);
         }
     }
 }
@@ -0,0 +1,254 @@
+use crate::paxos::{Slot, Value};
+use serde::{Deserialize, Serialize};
+use stateright::actor::{register::*, *};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+// what a slot ends up holding; reads go through the log too so they are linearizable
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum Decree<V> {
+    Put(V),
+    Get,
+    // the owner had nothing to propose in the slot
+    Skip,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum MenciusMsg<V> {
+    // the owner's proposal for one of its slots, the first round of which it holds already
+    Accept(Slot, Decree<V>),
+    Accepted(Slot),
+    Commit(Slot, Decree<V>),
+    // the sender's own slots from the first slot up to the second hold nothing
+    Skip(Slot, Slot),
+}
+
+// one of our own proposals, waiting on a majority
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Proposal<V> {
+    decree: Decree<V>,
+    accepts: BTreeSet<Id>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct MenciusState<V> {
+    id: Id,
+    // every server, in the order they take turns owning slots
+    members: Vec<Id>,
+    // the first of our own slots we have neither proposed in nor skipped
+    next_own: Slot,
+    proposals: BTreeMap<Slot, Proposal<V>>,
+    // committed slots, skipped ones included
+    log: BTreeMap<Slot, Decree<V>>,
+    applied: Slot,
+    register: V,
+    // the client waiting on each of our own slots
+    waiting: BTreeMap<Slot, (u64, Id)>,
+    // client requests already taken on, so redelivered requests are not served twice
+    handled: BTreeSet<u64>,
+}
+
+impl<V: Value> MenciusState<V> {
+    pub fn log(&self) -> &BTreeMap<Slot, Decree<V>> {
+        &self.log
+    }
+
+    pub fn applied(&self) -> Slot {
+        self.applied
+    }
+
+    fn owner(&self, slot: Slot) -> Id {
+        self.members[slot as usize % self.members.len()]
+    }
+
+    // gives up our own slots below `slot`, since someone has proposed past them
+    fn skip_below(&mut self, slot: Slot) -> Option<(Slot, Slot)> {
+        let from = self.next_own;
+        while self.next_own < slot {
+            self.log.insert(self.next_own, Decree::Skip);
+            self.next_own += self.members.len() as Slot;
+        }
+        if from == self.next_own {
+            return None;
+        }
+        Some((from, self.next_own))
+    }
+}
+
+// Mencius, Multi-Paxos with the slots dealt out round robin, so every server leads its own
+// share of them and commits a client's command without going through a single leader. A server
+// with nothing to propose skips its slots once it sees a proposal past them, so the log has no
+// gaps to wait on. There is no revocation: a server that stops answering stalls every slot
+// after its next one, so the model never crashes one.
+pub struct MenciusActor<V> {
+    peers: Vec<Id>,
+    _value: std::marker::PhantomData<V>,
+}
+
+impl<V: Value> MenciusActor<V> {
+    pub fn new(peers: Vec<Id>) -> Self {
+        MenciusActor {
+            peers,
+            _value: std::marker::PhantomData,
+        }
+    }
+
+    // counting ourselves, since peers never include this actor
+    fn is_majority(&self, count: usize) -> bool {
+        count > (self.peers.len() + 1) / 2
+    }
+
+    // proposes a client request in the next of our own slots
+    fn request(
+        &self,
+        state: &mut Cow<MenciusState<V>>,
+        request_id: u64,
+        client: Id,
+        decree: Decree<V>,
+        o: &mut Out<Self>,
+    ) {
+        if state.handled.contains(&request_id) {
+            return;
+        }
+        let state = state.to_mut();
+        state.handled.insert(request_id);
+        let slot = state.next_own;
+        state.next_own += state.members.len() as Slot;
+        state.waiting.insert(slot, (request_id, client));
+        state.proposals.insert(
+            slot,
+            Proposal {
+                decree: decree.clone(),
+                accepts: BTreeSet::from([state.id]),
+            },
+        );
+        let msg = RegisterMsg::Internal(MenciusMsg::Accept(slot, decree));
+        o.broadcast(&self.peers, &msg);
+        self.decide(state, slot, o);
+    }
+
+    // commits one of our proposals once a majority has accepted it
+    fn decide(&self, state: &mut MenciusState<V>, slot: Slot, o: &mut Out<Self>) {
+        let proposal = match state.proposals.get(&slot) {
+            Some(proposal) if self.is_majority(proposal.accepts.len()) => proposal.clone(),
+            _ => return,
+        };
+        state.proposals.remove(&slot);
+        let msg = RegisterMsg::Internal(MenciusMsg::Commit(slot, proposal.decree.clone()));
+        o.broadcast(&self.peers, &msg);
+        state.log.insert(slot, proposal.decree);
+        self.apply(state, o);
+    }
+
+    // runs the gap-free prefix of the log, answering the clients of our own slots
+    fn apply(&self, state: &mut MenciusState<V>, o: &mut Out<Self>) {
+        while let Some(decree) = state.log.get(&state.applied).cloned() {
+            let slot = state.applied;
+            state.applied += 1;
+            if let Decree::Put(value) = &decree {
+                state.register = value.clone();
+            }
+            let (request_id, client) = match state.waiting.remove(&slot) {
+                Some(waiting) => waiting,
+                None => continue,
+            };
+            match decree {
+                Decree::Put(_) => o.send(client, RegisterMsg::PutOk(request_id)),
+                Decree::Get => o.send(
+                    client,
+                    RegisterMsg::GetOk(request_id, state.register.clone()),
+                ),
+                Decree::Skip => {}
+            }
+        }
+    }
+}
+
+impl<V: Value> Actor for MenciusActor<V> {
+    type Msg = RegisterMsg<u64, V, MenciusMsg<V>>;
+    type State = MenciusState<V>;
+
+    fn on_start(&self, id: Id, _o: &mut Out<Self>) -> Self::State {
+        let mut members = self.peers.clone();
+        members.push(id);
+        members.sort();
+        let position = members.iter().position(|member| *member == id).unwrap_or(0);
+        MenciusState {
+            id,
+            members,
+            next_own: position as Slot,
+            proposals: BTreeMap::new(),
+            log: BTreeMap::new(),
+            applied: 0,
+            register: V::default(),
+            waiting: BTreeMap::new(),
+            handled: BTreeSet::new(),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        _: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        let internal_msg = match msg {
+            RegisterMsg::Internal(internal_msg) => internal_msg,
+            RegisterMsg::Put(request_id, value) => {
+                return self.request(state, request_id, src, Decree::Put(value), o);
+            }
+            RegisterMsg::Get(request_id) => {
+                return self.request(state, request_id, src, Decree::Get, o);
+            }
+            _ => return,
+        };
+        match internal_msg {
+            // nothing but the owner ever proposes in a slot, so there is no round to check
+            MenciusMsg::Accept(slot, _) => {
+                o.send(src, RegisterMsg::Internal(MenciusMsg::Accepted(slot)));
+                if state.next_own >= slot {
+                    return;
+                }
+                let state = state.to_mut();
+                if let Some((from, to)) = state.skip_below(slot) {
+                    let msg = RegisterMsg::Internal(MenciusMsg::Skip(from, to));
+                    o.broadcast(&self.peers, &msg);
+                }
+                self.apply(state, o);
+            }
+            MenciusMsg::Accepted(slot) => {
+                if !state.proposals.contains_key(&slot) {
+                    return;
+                }
+                let state = state.to_mut();
+                if let Some(proposal) = state.proposals.get_mut(&slot) {
+                    proposal.accepts.insert(src);
+                }
+                self.decide(state, slot, o);
+            }
+            MenciusMsg::Commit(slot, decree) => {
+                if state.log.contains_key(&slot) {
+                    return;
+                }
+                let state = state.to_mut();
+                state.log.insert(slot, decree);
+                self.apply(state, o);
+            }
+            MenciusMsg::Skip(from, to) => {
+                let skipped: Vec<Slot> = (from..to)
+                    .filter(|slot| state.owner(*slot) == src && !state.log.contains_key(slot))
+                    .collect();
+                if skipped.is_empty() {
+                    return;
+                }
+                let state = state.to_mut();
+                for slot in skipped {
+                    state.log.insert(slot, Decree::Skip);
+                }
+                self.apply(state, o);
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use stateright::actor::Id;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddrV4;
+use std::sync::Mutex;
+use std::time::Instant;
+
+// what an actor counts as it runs
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Counter {
+    PreparesSent,
+    PromisesReceived,
+    NacksReceived,
+    SlotsDecided,
+}
+
+impl Counter {
+    const ALL: [Counter; 4] = [
+        Counter::PreparesSent,
+        Counter::PromisesReceived,
+        Counter::NacksReceived,
+        Counter::SlotsDecided,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Counter::PreparesSent => "paxos_prepares_sent_total",
+            Counter::PromisesReceived => "paxos_promises_received_total",
+            Counter::NacksReceived => "paxos_nacks_received_total",
+            Counter::SlotsDecided => "paxos_slots_decided_total",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Counter::PreparesSent => "Prepare messages sent, one per acceptor asked",
+            Counter::PromisesReceived => "Promises received as a proposer",
+            Counter::NacksReceived => "Nacks received as a proposer",
+            Counter::SlotsDecided => "Slots this replica learned the decision for",
+        }
+    }
+}
+
+// Where an actor reports what it does. Timing is left to the implementation, so the actor stays
+// deterministic for the model checker, which uses `NoMetrics`.
+pub trait Metrics: Send + Sync {
+    fn increment(&self, id: Id, counter: Counter, by: u64);
+    // a client request a proposer took on, and when the proposer acknowledged it
+    fn proposal_started(&self, id: Id, request_id: u64);
+    fn proposal_decided(&self, id: Id, request_id: u64);
+}
+
+pub struct NoMetrics;
+
+impl Metrics for NoMetrics {
+    fn increment(&self, _: Id, _: Counter, _: u64) {}
+
+    fn proposal_started(&self, _: Id, _: u64) {}
+
+    fn proposal_decided(&self, _: Id, _: u64) {}
+}
+
+const LATENCY: &str = "paxos_proposal_latency_seconds";
+// upper bounds of the latency histogram's buckets, in seconds
+const LATENCY_BUCKETS: [f64; 8] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+// proposals still waiting for a decision past this many are forgotten, oldest first
+const MAX_STARTED: usize = 4096;
+
+#[derive(Default)]
+struct Histogram {
+    // not cumulative, that is left to rendering
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|bound| value <= *bound) {
+            self.buckets[i] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Series {
+    counters: BTreeMap<Counter, u64>,
+    latency: Histogram,
+    started: HashMap<u64, Instant>,
+}
+
+// Every replica's series, labelled with its address, for spawned replicas to share. Rendered in
+// the Prometheus text format by the admin endpoint's `/metrics`.
+#[derive(Default)]
+pub struct PrometheusMetrics {
+    replicas: Mutex<BTreeMap<Id, Series>>,
+}
+
+impl PrometheusMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // a registered replica's series show up, at zero, before anything has happened to it
+    pub fn register(&self, id: Id) {
+        self.with(id, |_| {});
+    }
+
+    fn with(&self, id: Id, f: impl FnOnce(&mut Series)) {
+        if let Ok(mut replicas) = self.replicas.lock() {
+            f(replicas.entry(id).or_default());
+        }
+    }
+
+    // the series of one replica
+    pub fn render(&self, id: Id) -> String {
+        let replicas = match self.replicas.lock() {
+            Ok(replicas) => replicas,
+            Err(_) => return String::new(),
+        };
+        let series = match replicas.get(&id) {
+            Some(series) => series,
+            None => return String::new(),
+        };
+        let replica = SocketAddrV4::from(id);
+        let mut text = String::new();
+        for counter in Counter::ALL {
+            let value = series.counters.get(&counter).copied().unwrap_or(0);
+            text.push_str(&format!("# HELP {} {}\n", counter.name(), counter.help()));
+            text.push_str(&format!("# TYPE {} counter\n", counter.name()));
+            text.push_str(&format!(
+                "{}{{replica=\"{}\"}} {}\n",
+                counter.name(),
+                replica,
+                value
+            ));
+        }
+
+        let latency = &series.latency;
+        text.push_str(&format!(
+            "# HELP {} Time from a proposer taking on a request to acknowledging it\n",
+            LATENCY
+        ));
+        text.push_str(&format!("# TYPE {} histogram\n", LATENCY));
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(latency.buckets) {
+            cumulative += count;
+            text.push_str(&format!(
+                "{}_bucket{{replica=\"{}\",le=\"{}\"}} {}\n",
+                LATENCY, replica, bound, cumulative
+            ));
+        }
+        text.push_str(&format!(
+            "{}_bucket{{replica=\"{}\",le=\"+Inf\"}} {}\n",
+            LATENCY, replica, latency.count
+        ));
+        text.push_str(&format!(
+            "{}_sum{{replica=\"{}\"}} {}\n",
+            LATENCY, replica, latency.sum
+        ));
+        text.push_str(&format!(
+            "{}_count{{replica=\"{}\"}} {}\n",
+            LATENCY, replica, latency.count
+        ));
+        text
+    }
+}
+
+impl Metrics for PrometheusMetrics {
+    fn increment(&self, id: Id, counter: Counter, by: u64) {
+        self.with(id, |series| {
+            *series.counters.entry(counter).or_insert(0) += by
+        });
+    }
+
+    fn proposal_started(&self, id: Id, request_id: u64) {
+        self.with(id, |series| {
+            if series.started.len() >= MAX_STARTED {
+                let oldest = series
+                    .started
+                    .iter()
+                    .min_by_key(|(_, started)| **started)
+                    .map(|(request_id, _)| *request_id);
+                if let Some(oldest) = oldest {
+                    series.started.remove(&oldest);
+                }
+            }
+            series.started.insert(request_id, Instant::now());
+        });
+    }
+
+    fn proposal_decided(&self, id: Id, request_id: u64) {
+        self.with(id, |series| {
+            if let Some(started) = series.started.remove(&request_id) {
+                series.latency.observe(started.elapsed().as_secs_f64());
+            }
+        });
+    }
+}
@@ -0,0 +1,3066 @@
+use crate::client::{
+    Domain, Pattern, PaxosClientActor, PaxosModelActor, PaxosModelActorState, Symbolic,
+};
+use crate::clock::Clock;
+use crate::disk::{DiskPaxosActor, DiskPaxosModelActor, DiskPaxosModelActorState, SharedDisk};
+use crate::features::Features;
+use crate::mencius::{MenciusActor, MenciusState};
+use crate::observer::{ApplyObserver, NoObserver};
+use crate::paxos::{
+    Applied, ClockSkew, Command, PaxosActor, PaxosMsg, PaxosState, ReadStrategy, Role,
+    RoundIdentifier, Slot, Value,
+};
+use crate::quorum::QuorumSpec;
+use crate::raft::{RaftActor, RaftState};
+use crate::rng::SplitMix;
+use crate::roles::{AcceptorActor, LearnerActor, ProposerActor, RoleActor, RoleActorState};
+use crate::sim::Invariant;
+use crate::strategy::StrategySpec;
+use crate::topology::Topology;
+use crate::vertical::{ConfigMaster, VerticalActor, VerticalActorState, VerticalPaxosActor};
+use stateright::actor::{register::*, *};
+use stateright::semantics::register::Register;
+use stateright::semantics::LinearizabilityTester;
+use stateright::{Rewrite, RewritePlan};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// what one tick of a server's logical clock stands for
+const TICK: Duration = Duration::from_millis(100);
+
+// stateright's register clients only ever put chars
+type RegisterValue = char;
+
+// how the model network may misbehave
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NetworkMode {
+    // every message is delivered exactly once, in any order
+    Unordered,
+    // messages may additionally be redelivered any number of times
+    UnorderedDuplicating,
+    // messages may additionally be dropped
+    Lossy,
+}
+
+impl FromStr for NetworkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unordered" => Ok(NetworkMode::Unordered),
+            "duplicating" => Ok(NetworkMode::UnorderedDuplicating),
+            "lossy" => Ok(NetworkMode::Lossy),
+            _ => Err(format!(
+                "unknown network mode {}, expected unordered, duplicating or lossy",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for NetworkMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkMode::Unordered => write!(f, "unordered"),
+            NetworkMode::UnorderedDuplicating => write!(f, "duplicating"),
+            NetworkMode::Lossy => write!(f, "lossy"),
+        }
+    }
+}
+
+// which replication protocol the model runs
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Paxos,
+    Raft,
+    // compare-and-swap clients against CASPaxos, see `crate::cas`
+    Cas,
+    // leaderless commits over a small key value store, see `crate::epaxos`
+    EPaxos,
+    // the same key value store as a state machine on top of Multi-Paxos, see `crate::smr`
+    Kv,
+    // Multi-Paxos with the slots dealt out round robin, see `crate::mencius`
+    Mencius,
+    // a configuration master hands a 3 server log to 2 of them, see `crate::vertical`
+    Vertical,
+    // processors agreeing through blocks on shared disks, see `crate::disk`
+    Disk,
+    // a transfer committed across two shards with two-phase commit, see `crate::txn`
+    Txn,
+    // proposers, acceptors and learners as separate actors, see `crate::roles`
+    Roles,
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "paxos" => Ok(Protocol::Paxos),
+            "raft" => Ok(Protocol::Raft),
+            "cas" => Ok(Protocol::Cas),
+            "epaxos" => Ok(Protocol::EPaxos),
+            "kv" => Ok(Protocol::Kv),
+            "mencius" => Ok(Protocol::Mencius),
+            "vertical" => Ok(Protocol::Vertical),
+            "disk" => Ok(Protocol::Disk),
+            "txn" => Ok(Protocol::Txn),
+            "roles" => Ok(Protocol::Roles),
+            _ => Err(format!(
+                "unknown protocol {}, expected paxos, raft, cas, epaxos, kv, mencius, vertical, disk, txn or roles",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Protocol::Paxos => write!(f, "paxos"),
+            Protocol::Raft => write!(f, "raft"),
+            Protocol::Cas => write!(f, "cas"),
+            Protocol::EPaxos => write!(f, "epaxos"),
+            Protocol::Kv => write!(f, "kv"),
+            Protocol::Mencius => write!(f, "mencius"),
+            Protocol::Vertical => write!(f, "vertical"),
+            Protocol::Disk => write!(f, "disk"),
+            Protocol::Txn => write!(f, "txn"),
+            Protocol::Roles => write!(f, "roles"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PaxosModelConfig<V = RegisterValue> {
+    client_count: usize,
+    server_count: usize,
+    put_count: usize,
+    // gets each client makes after its puts, under paxos; stateright's clients make one
+    get_count: usize,
+    // clients send an unanswered request again, to the next server
+    retrying_clients: bool,
+    // servers apply each client put once, however often it is decided
+    sessions: bool,
+    // the puts write only this many distinct values between them, see `client::Symbolic`
+    abstract_values: Option<usize>,
+    // the values the puts take turns writing, in place of one for each put
+    value_domain: Option<Vec<V>>,
+    // the order each client makes its puts and gets in, under paxos
+    pattern: Pattern,
+    // clients wait an arbitrary while before each request, under paxos
+    think_time: bool,
+    network: NetworkMode,
+    crash_count: usize,
+    byzantine: Option<usize>,
+    // the first this many servers are cut off from the rest for a while
+    split: Option<usize>,
+    // the region each server runs in, which region aware quorum systems count votes by
+    topology: Topology,
+    // a region whose servers are down for the whole run
+    region_down: Option<usize>,
+    // servers in the initial configuration, the rest start out as spares
+    member_count: Option<usize>,
+    // server 0 proposes switching to the first this many servers
+    reconfigure: Option<usize>,
+    // server 0 hands its leadership to this server once it leads
+    transfer: Option<usize>,
+    // the last this many servers run the build from before batching and the feature handshake,
+    // as partway through a rolling upgrade
+    upgrade: Option<usize>,
+    // the newer servers agree on features with the rest before using them
+    negotiate: bool,
+    // servers compact their log into a snapshot every this many applied slots
+    snapshot_every: Option<Slot>,
+    // leaders serve reads locally, with clocks drifting within or beyond the lease's bound
+    read_leases: Option<ClockSkew>,
+    // how servers answer gets, the lease strategy going by `read_leases` for its clocks
+    read_strategy: ReadStrategy,
+    // servers keep logical clocks, timing read leases on them as if no two were further apart
+    // than this many ticks; `read_leases` says whether they really stay that close
+    clock_skew: Option<u64>,
+    // how many puts a proposer may batch into one decree
+    batch_size: Option<usize>,
+    // how many slots past the applied prefix a proposer may have in flight
+    pipeline: Option<Slot>,
+    // how many unacknowledged slots a leader may have sent each follower
+    replication_window: Option<usize>,
+    // proposers ask whether a quorum would promise a round before starting it
+    pre_vote: bool,
+    // proposers back off between retries and yield to higher ballots
+    backoff: bool,
+    // what the servers' backoff draws from, each server its own stream of it
+    seed: u64,
+    // followers suspect and trust their leader at arbitrary points
+    suspicion: bool,
+    // the last this many servers are witnesses, voting without storing values
+    witness_count: usize,
+    // states past these bounds are left unexplored, trading exhaustiveness for larger clusters
+    max_rounds: Option<u64>,
+    max_network_messages: Option<usize>,
+    // shared disks under disk paxos, which has no acceptors
+    disk_count: usize,
+    // with the roles separated, the servers are the proposers and these come after them
+    acceptor_count: usize,
+    learner_count: usize,
+    quorum: QuorumSpec,
+    strategy: StrategySpec,
+    // told of every entry a server applies, along whatever paths the checker explores
+    observer: Arc<dyn ApplyObserver<V>>,
+    _value: PhantomData<V>,
+}
+
+impl<V: Value> Default for PaxosModelConfig<V> {
+    fn default() -> Self {
+        PaxosModelConfig {
+            client_count: 2,
+            server_count: 3,
+            put_count: 1,
+            get_count: 1,
+            retrying_clients: false,
+            sessions: false,
+            abstract_values: None,
+            value_domain: None,
+            pattern: Pattern::PutsThenGets,
+            think_time: false,
+            network: NetworkMode::UnorderedDuplicating,
+            crash_count: 0,
+            byzantine: None,
+            split: None,
+            topology: Topology::default(),
+            region_down: None,
+            member_count: None,
+            reconfigure: None,
+            transfer: None,
+            upgrade: None,
+            negotiate: true,
+            snapshot_every: None,
+            read_leases: None,
+            read_strategy: ReadStrategy::Quorum,
+            clock_skew: None,
+            batch_size: None,
+            pipeline: None,
+            replication_window: None,
+            pre_vote: false,
+            backoff: false,
+            seed: 0,
+            suspicion: false,
+            witness_count: 0,
+            max_rounds: None,
+            max_network_messages: None,
+            disk_count: 3,
+            acceptor_count: 3,
+            learner_count: 2,
+            quorum: QuorumSpec::Majority,
+            strategy: StrategySpec::MajorityPromises,
+            observer: Arc::new(NoObserver),
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<V: Value> PaxosModelConfig<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn client_count(mut self, client_count: usize) -> Self {
+        self.client_count = client_count;
+        self
+    }
+
+    pub fn server_count(mut self, server_count: usize) -> Self {
+        self.server_count = server_count;
+        self
+    }
+
+    pub fn put_count(mut self, put_count: usize) -> Self {
+        self.put_count = put_count;
+        self
+    }
+
+    pub fn get_count(mut self, get_count: usize) -> Self {
+        self.get_count = get_count;
+        self
+    }
+
+    pub fn retrying_clients(mut self, retrying_clients: bool) -> Self {
+        self.retrying_clients = retrying_clients;
+        self
+    }
+
+    pub fn sessions(mut self, sessions: bool) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    // folds the puts onto this many symbolic values, under paxos
+    pub fn abstract_values(mut self, abstract_values: Option<usize>) -> Self {
+        self.abstract_values = abstract_values;
+        self
+    }
+
+    // the values the puts write, under paxos, taking precedence over `abstract_values`
+    pub fn value_domain(mut self, value_domain: Option<Vec<V>>) -> Self {
+        self.value_domain = value_domain.filter(|values| !values.is_empty());
+        self
+    }
+
+    pub fn pattern(mut self, pattern: Pattern) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    pub fn think_time(mut self, think_time: bool) -> Self {
+        self.think_time = think_time;
+        self
+    }
+
+    // whether two puts may write the same value, leaving a value no way to tell them apart
+    fn values_repeat(&self) -> bool {
+        self.abstract_values.is_some() || self.value_domain.is_some()
+    }
+
+    pub fn network(mut self, network: NetworkMode) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn quorum(mut self, quorum: QuorumSpec) -> Self {
+        self.quorum = quorum;
+        self
+    }
+
+    pub fn strategy(mut self, strategy: StrategySpec) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn observer(mut self, observer: Arc<dyn ApplyObserver<V>>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    // how many of the servers crash and restart at some point
+    pub fn crash_count(mut self, crash_count: usize) -> Self {
+        self.crash_count = crash_count;
+        self
+    }
+
+    // the index of a server that forges promises and accepts, plain Paxos is not safe against it
+    pub fn byzantine(mut self, byzantine: Option<usize>) -> Self {
+        self.byzantine = byzantine;
+        self
+    }
+
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    // the servers of this region of the topology never come up
+    pub fn region_down(mut self, region_down: Option<usize>) -> Self {
+        self.region_down = region_down;
+        self
+    }
+
+    fn is_down(&self, i: usize) -> bool {
+        self.region_down.is_some() && self.topology.region(Id::from(i)) == self.region_down
+    }
+
+    // a partition that forms and heals at arbitrary points, each server noticing on its own
+    pub fn split(mut self, split: Option<usize>) -> Self {
+        self.split = split;
+        self
+    }
+
+    // the servers on the other side of the partition from server `i`
+    fn other_side(&self, i: usize) -> Vec<Id> {
+        match self.split {
+            Some(split) => (0..self.server_count)
+                .filter(|j| (*j < split) != (i < split))
+                .map(Id::from)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // whether server `i`'s side of the partition is too small to decide anything on its own
+    fn in_minority(&self, i: usize) -> bool {
+        let members = self.member_count.unwrap_or(self.server_count);
+        let side = (0..members).filter(|j| {
+            self.split
+                .map_or(false, |split| (*j < split) == (i < split))
+        });
+        side.count() * 2 <= members
+    }
+
+    pub fn member_count(mut self, member_count: Option<usize>) -> Self {
+        self.member_count = member_count;
+        self
+    }
+
+    // adding spares or removing members, depending on how it compares to the member count
+    pub fn reconfigure(mut self, reconfigure: Option<usize>) -> Self {
+        self.reconfigure = reconfigure;
+        self
+    }
+
+    pub fn transfer_leadership(mut self, transfer: Option<usize>) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
+    pub fn snapshot_every(mut self, snapshot_every: Option<Slot>) -> Self {
+        self.snapshot_every = snapshot_every;
+        self
+    }
+
+    pub fn read_leases(mut self, read_leases: Option<ClockSkew>) -> Self {
+        self.read_leases = read_leases;
+        self
+    }
+
+    pub fn read_strategy(mut self, read_strategy: ReadStrategy) -> Self {
+        self.read_strategy = read_strategy;
+        self
+    }
+
+    pub fn clock_skew(mut self, clock_skew: Option<u64>) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    // A read lease lasts long enough for the leader to get two ticks of reads out of it, past
+    // the margin it leaves for the skew on either side.
+    fn lease_ticks(&self) -> Option<u64> {
+        self.clock_skew.map(|skew| 2 * skew + 2)
+    }
+
+    // the clocks leases are served under, if the read strategy takes any
+    fn leases(&self) -> Option<ClockSkew> {
+        match self.read_strategy {
+            ReadStrategy::Quorum => self.read_leases,
+            ReadStrategy::Lease => Some(self.read_leases.unwrap_or(ClockSkew::Bounded)),
+            ReadStrategy::Local => None,
+        }
+    }
+
+    pub fn batch_size(mut self, batch_size: Option<usize>) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn pipeline(mut self, pipeline: Option<Slot>) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    pub fn replication_window(mut self, replication_window: Option<usize>) -> Self {
+        self.replication_window = replication_window;
+        self
+    }
+
+    pub fn pre_vote(mut self, pre_vote: bool) -> Self {
+        self.pre_vote = pre_vote;
+        self
+    }
+
+    pub fn backoff(mut self, backoff: bool) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn suspicion(mut self, suspicion: bool) -> Self {
+        self.suspicion = suspicion;
+        self
+    }
+
+    pub fn witness_count(mut self, witness_count: usize) -> Self {
+        self.witness_count = witness_count;
+        self
+    }
+
+    fn is_witness(&self, i: usize) -> bool {
+        i < self.server_count && i + self.witness_count >= self.server_count
+    }
+
+    pub fn upgrade(mut self, upgrade: Option<usize>) -> Self {
+        self.upgrade = upgrade;
+        self
+    }
+
+    pub fn negotiate(mut self, negotiate: bool) -> Self {
+        self.negotiate = negotiate;
+        self
+    }
+
+    fn is_old(&self, i: usize) -> bool {
+        self.upgrade.map_or(false, |old| {
+            i < self.server_count && i + old >= self.server_count
+        })
+    }
+
+    // what a server advertises, only the newer ones in an upgrade taking part in the handshake
+    fn features(&self, i: usize) -> Option<Features> {
+        Some(Features::ALL).filter(|_| self.upgrade.is_some() && self.negotiate && !self.is_old(i))
+    }
+
+    // no server gets past this round, a bound on how often proposers may retry
+    pub fn max_rounds(mut self, max_rounds: Option<u64>) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    pub fn max_network_messages(mut self, max_network_messages: Option<usize>) -> Self {
+        self.max_network_messages = max_network_messages;
+        self
+    }
+
+    pub fn disk_count(mut self, disk_count: usize) -> Self {
+        self.disk_count = disk_count;
+        self
+    }
+
+    pub fn acceptor_count(mut self, acceptor_count: usize) -> Self {
+        self.acceptor_count = acceptor_count;
+        self
+    }
+
+    pub fn learner_count(mut self, learner_count: usize) -> Self {
+        self.learner_count = learner_count;
+        self
+    }
+}
+
+// set once a bounded model has left a state unexplored, so a check that passed can say it was
+// only a bounded one
+pub(crate) static PRUNED: AtomicBool = AtomicBool::new(false);
+
+pub fn pruned() -> bool {
+    PRUNED.load(Ordering::Relaxed)
+}
+
+// what the properties need to know about a run beyond the actor states
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PaxosHistory {
+    linearizability: LinearizabilityTester<Id, Register<RegisterValue>>,
+    // every value a client has asked to write
+    proposed: BTreeSet<RegisterValue>,
+    // the request each client is waiting on, however many times it was sent or answered
+    in_flight: BTreeMap<Id, u64>,
+    // the highest round each acceptor has let on about for a slot, in a promise or an accept
+    last_seen: BTreeMap<(Id, Slot), RoundIdentifier>,
+    // every round an acceptor has promised for a slot
+    promised: BTreeSet<(Id, Slot, RoundIdentifier)>,
+    // every lease an acceptor has heard of, which stands in for a promise of the slots after it
+    leases: BTreeSet<(Id, Slot, RoundIdentifier)>,
+    last_seen_regressed: bool,
+    accepted_unpromised: bool,
+    // servers cut off by the partition right now, and those it has healed for
+    split: BTreeSet<Id>,
+    healed: BTreeSet<Id>,
+    // proposals that counted an accept from across the partition, before it cut them off
+    accepted_across: BTreeSet<(Id, Slot, RoundIdentifier)>,
+    minority_decided: bool,
+    decided_after_heal: bool,
+    // values whose put has been acknowledged, the initial value included
+    completed: BTreeSet<RegisterValue>,
+    // each writing client's value, and the values already acknowledged when it asked
+    writing: BTreeMap<Id, (RegisterValue, BTreeSet<RegisterValue>)>,
+    // values overwritten by a put that was acknowledged, and has not been written again since
+    superseded: BTreeSet<RegisterValue>,
+    // what had been overwritten when each reading client asked
+    reading: BTreeMap<Id, BTreeSet<RegisterValue>>,
+    stale_read: bool,
+    // each put's value with the values acknowledged before it began, which it is newer than
+    newer_than: BTreeMap<RegisterValue, BTreeSet<RegisterValue>>,
+    // each client's puts, in the order it made them
+    session_writes: BTreeMap<Id, Vec<RegisterValue>>,
+    // per client, values older than one of its own acknowledged puts, and than one it has read
+    written_past: BTreeMap<Id, BTreeSet<RegisterValue>>,
+    read_past: BTreeMap<Id, BTreeSet<RegisterValue>>,
+    read_own_write_lost: bool,
+    read_went_back: bool,
+    // a proposer on the minority side of the partition started a round while cut off, one
+    // that can only abort the rounds of the other side once it heals
+    prepared_cut_off: bool,
+}
+
+pub type PaxosModel = ActorModel<PaxosModelActor<RegisterValue>, PaxosModelConfig, PaxosHistory>;
+
+pub type PaxosModelState = ActorModelState<PaxosModelActor<RegisterValue>, PaxosHistory>;
+
+pub type RaftModel =
+    ActorModel<RegisterActor<RaftActor<RegisterValue>>, PaxosModelConfig, PaxosHistory>;
+
+pub type RaftModelState = ActorModelState<RegisterActor<RaftActor<RegisterValue>>, PaxosHistory>;
+
+pub type MenciusModel =
+    ActorModel<RegisterActor<MenciusActor<RegisterValue>>, PaxosModelConfig, PaxosHistory>;
+
+pub type MenciusModelState =
+    ActorModelState<RegisterActor<MenciusActor<RegisterValue>>, PaxosHistory>;
+
+pub type VerticalModel =
+    ActorModel<RegisterActor<VerticalActor<RegisterValue>>, PaxosModelConfig, PaxosHistory>;
+
+pub type VerticalModelState =
+    ActorModelState<RegisterActor<VerticalActor<RegisterValue>>, PaxosHistory>;
+
+pub type DiskPaxosModel =
+    ActorModel<RegisterActor<DiskPaxosModelActor<RegisterValue>>, PaxosModelConfig, PaxosHistory>;
+
+pub type DiskPaxosModelState =
+    ActorModelState<RegisterActor<DiskPaxosModelActor<RegisterValue>>, PaxosHistory>;
+
+pub type RolesModel =
+    ActorModel<RegisterActor<RoleActor<RegisterValue>>, PaxosModelConfig, PaxosHistory>;
+
+pub type RolesModelState = ActorModelState<RegisterActor<RoleActor<RegisterValue>>, PaxosHistory>;
+
+impl PaxosHistory {
+    pub fn new() -> Self {
+        PaxosHistory {
+            linearizability: LinearizabilityTester::new(Register(RegisterValue::default())),
+            proposed: BTreeSet::new(),
+            in_flight: BTreeMap::new(),
+            last_seen: BTreeMap::new(),
+            promised: BTreeSet::new(),
+            leases: BTreeSet::new(),
+            last_seen_regressed: false,
+            accepted_unpromised: false,
+            split: BTreeSet::new(),
+            healed: BTreeSet::new(),
+            accepted_across: BTreeSet::new(),
+            minority_decided: false,
+            decided_after_heal: false,
+            completed: BTreeSet::from([RegisterValue::default()]),
+            writing: BTreeMap::new(),
+            superseded: BTreeSet::new(),
+            reading: BTreeMap::new(),
+            stale_read: false,
+            newer_than: BTreeMap::new(),
+            session_writes: BTreeMap::new(),
+            written_past: BTreeMap::new(),
+            read_past: BTreeMap::new(),
+            read_own_write_lost: false,
+            read_went_back: false,
+            prepared_cut_off: false,
+        }
+    }
+
+    pub fn linearizability(&self) -> &LinearizabilityTester<Id, Register<RegisterValue>> {
+        &self.linearizability
+    }
+
+    pub fn proposed(&self) -> &BTreeSet<RegisterValue> {
+        &self.proposed
+    }
+
+    // whether some read returned a value overwritten before it began
+    pub fn stale_read(&self) -> bool {
+        self.stale_read
+    }
+
+    // each client's puts in the order it made them
+    pub fn session_writes(&self) -> &BTreeMap<Id, Vec<RegisterValue>> {
+        &self.session_writes
+    }
+
+    // generic over the internal messages, so any protocol behind the register interface can use it
+    fn record_invocations<I: Clone + fmt::Debug + Eq + std::hash::Hash>(
+        cfg: &PaxosModelConfig,
+        history: &Self,
+        env: Envelope<&RegisterMsg<u64, RegisterValue, I>>,
+    ) -> Option<Self> {
+        let request_id = match env.msg {
+            RegisterMsg::Put(request_id, _) | RegisterMsg::Get(request_id) => *request_id,
+            _ => return None,
+        };
+        // a retry is the same operation sent again
+        if history.in_flight.get(&env.src) == Some(&request_id) {
+            return None;
+        }
+        let mut next = history.clone();
+        next.in_flight.insert(env.src, request_id);
+        match env.msg {
+            RegisterMsg::Put(_, value) => {
+                next.proposed.insert(*value);
+                // written again, so a read may fairly return it
+                next.superseded.remove(value);
+                let acknowledged = next.completed.clone();
+                let mut older = acknowledged.clone();
+                older.remove(value);
+                next.newer_than.insert(*value, older);
+                next.session_writes.entry(env.src).or_default().push(*value);
+                next.writing.insert(env.src, (*value, acknowledged));
+            }
+            _ => {
+                let superseded = next.superseded.clone();
+                next.reading.insert(env.src, superseded);
+            }
+        }
+        if let Some(linearizability) =
+            RegisterMsg::record_invocations(cfg, &next.linearizability, env)
+        {
+            next.linearizability = linearizability;
+        }
+        Some(next)
+    }
+
+    fn record_returns<I: Clone + fmt::Debug + Eq + std::hash::Hash>(
+        cfg: &PaxosModelConfig,
+        history: &Self,
+        env: Envelope<&RegisterMsg<u64, RegisterValue, I>>,
+    ) -> Option<Self> {
+        let request_id = match env.msg {
+            RegisterMsg::PutOk(request_id) | RegisterMsg::GetOk(request_id, _) => *request_id,
+            _ => return None,
+        };
+        // only the first answer to the request a client is waiting on returns it
+        if history.in_flight.get(&env.dst) != Some(&request_id) {
+            return None;
+        }
+        let mut next = history.clone();
+        next.in_flight.remove(&env.dst);
+        match env.msg {
+            RegisterMsg::GetOk(_, value) => {
+                if let Some(superseded) = next.reading.remove(&env.dst) {
+                    next.stale_read |= superseded.contains(value);
+                }
+                // a client's reads go no further back than its own writes and earlier reads,
+                // going by what was surely acknowledged before each of those values was written
+                let written_past = next.written_past.get(&env.dst);
+                next.read_own_write_lost |= written_past.map_or(false, |past| past.contains(value));
+                let read_past = next.read_past.entry(env.dst).or_default();
+                next.read_went_back |= read_past.contains(value);
+                if let Some(older) = next.newer_than.get(value) {
+                    read_past.extend(older.iter().copied());
+                }
+            }
+            _ => {
+                // everything acknowledged before this put began is overwritten now, unless a
+                // put still in flight may write it again
+                if let Some((value, acknowledged)) = next.writing.remove(&env.dst) {
+                    next.completed.insert(value);
+                    next.written_past
+                        .entry(env.dst)
+                        .or_default()
+                        .extend(acknowledged.iter().copied().filter(|old| *old != value));
+                    let rewriting: BTreeSet<RegisterValue> =
+                        next.writing.values().map(|(value, _)| *value).collect();
+                    next.superseded.extend(
+                        acknowledged
+                            .into_iter()
+                            .filter(|old| *old != value && !rewriting.contains(old)),
+                    );
+                }
+            }
+        }
+        if let Some(linearizability) = RegisterMsg::record_returns(cfg, &next.linearizability, env)
+        {
+            next.linearizability = linearizability;
+        }
+        Some(next)
+    }
+
+    // what an acceptor reveals about its rounds as it promises and accepts
+    fn record_acceptor(
+        &self,
+        src: Id,
+        msg: &RegisterMsg<u64, RegisterValue, PaxosMsg<RegisterValue>>,
+    ) -> Option<Self> {
+        let (slot, rid, promise) = match msg {
+            RegisterMsg::Internal(PaxosMsg::Promise(_, _, slot, rid, _))
+            | RegisterMsg::Internal(PaxosMsg::WitnessPromise(_, _, slot, rid, _)) => {
+                (*slot, *rid, true)
+            }
+            RegisterMsg::Internal(PaxosMsg::Accepted(_, _, slot, rid, _)) => (*slot, *rid, false),
+            _ => return None,
+        };
+        let mut history = self.clone();
+        match history.last_seen.get(&(src, slot)) {
+            Some(seen) if rid < *seen => history.last_seen_regressed = true,
+            _ => {
+                history.last_seen.insert((src, slot), rid);
+            }
+        }
+        if promise {
+            history.promised.insert((src, slot, rid));
+        } else {
+            // accepting without a prepare is fine under the leader's lease for a later slot
+            let promised = history.promised.contains(&(src, slot, rid))
+                || history.leases.iter().any(|(id, lease_slot, ballot)| {
+                    *id == src && *ballot == rid && slot > *lease_slot
+                });
+            if !promised {
+                history.accepted_unpromised = true;
+            }
+        }
+        Some(history)
+    }
+
+    // which side of the partition a proposer was on when it decided
+    fn record_decision(
+        &self,
+        cfg: &PaxosModelConfig,
+        src: Id,
+        msg: &RegisterMsg<u64, RegisterValue, PaxosMsg<RegisterValue>>,
+    ) -> Option<Self> {
+        let (slot, rid) = match msg {
+            RegisterMsg::Internal(PaxosMsg::Decided(slot, rid, _)) => (*slot, *rid),
+            _ => return None,
+        };
+        let minority = self.split.contains(&src)
+            && cfg.in_minority(usize::from(src))
+            && !self.accepted_across.contains(&(src, slot, rid));
+        let after_heal = self.healed.len() == cfg.server_count;
+        if (minority && !self.minority_decided) || (after_heal && !self.decided_after_heal) {
+            let mut history = self.clone();
+            history.minority_decided |= minority;
+            history.decided_after_heal |= after_heal;
+            return Some(history);
+        }
+        None
+    }
+
+    fn record_paxos_invocations(
+        cfg: &PaxosModelConfig,
+        history: &Self,
+        env: Envelope<&RegisterMsg<u64, RegisterValue, PaxosMsg<RegisterValue>>>,
+    ) -> Option<Self> {
+        let src = env.src;
+        let msg = env.msg;
+        let mut next = Self::record_invocations(cfg, history, env);
+        if let Some(acceptor) = next.as_ref().unwrap_or(history).record_acceptor(src, msg) {
+            next = Some(acceptor);
+        }
+        if let RegisterMsg::Internal(PaxosMsg::Prepare(..)) = msg {
+            let base = next.as_ref().unwrap_or(history);
+            if !base.prepared_cut_off
+                && base.split.contains(&src)
+                && cfg.in_minority(usize::from(src))
+            {
+                let mut history = base.clone();
+                history.prepared_cut_off = true;
+                next = Some(history);
+            }
+        }
+        match next
+            .as_ref()
+            .unwrap_or(history)
+            .record_decision(cfg, src, msg)
+        {
+            Some(decision) => Some(decision),
+            None => next,
+        }
+    }
+
+    fn record_paxos_returns(
+        cfg: &PaxosModelConfig,
+        history: &Self,
+        env: Envelope<&RegisterMsg<u64, RegisterValue, PaxosMsg<RegisterValue>>>,
+    ) -> Option<Self> {
+        let (src, dst, msg) = (env.src, env.dst, env.msg);
+        let mut next = Self::record_returns(cfg, history, env);
+        if let RegisterMsg::Internal(PaxosMsg::Accepted(_, _, slot, rid, _)) = msg {
+            let base = next.as_ref().unwrap_or(history);
+            let across = !base.split.contains(&dst)
+                && cfg.other_side(usize::from(dst)).contains(&src)
+                && !base.accepted_across.contains(&(dst, *slot, *rid));
+            if across {
+                let mut history = base.clone();
+                history.accepted_across.insert((dst, *slot, *rid));
+                next = Some(history);
+            }
+        }
+        if let RegisterMsg::Internal(PaxosMsg::Split | PaxosMsg::Heal) = msg {
+            let mut history = next.unwrap_or_else(|| history.clone());
+            if let RegisterMsg::Internal(PaxosMsg::Split) = msg {
+                history.split.insert(dst);
+            } else {
+                history.split.remove(&dst);
+                history.healed.insert(dst);
+            }
+            next = Some(history);
+        }
+        // recorded whether or not the acceptor adopts it, the heartbeat may be stale
+        if let RegisterMsg::Internal(PaxosMsg::Heartbeat(slot, ballot)) = msg {
+            let base = next.as_ref().unwrap_or(history);
+            if !base.leases.contains(&(dst, *slot, *ballot)) {
+                let mut history = base.clone();
+                history.leases.insert((dst, *slot, *ballot));
+                return Some(history);
+            }
+        }
+        next
+    }
+}
+
+impl Rewrite<Id> for PaxosHistory {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        let rewrite_rounds = |rounds: &BTreeSet<(Id, Slot, RoundIdentifier)>| {
+            rounds
+                .iter()
+                .map(|(id, slot, rid)| (plan.rewrite(id), *slot, rid.rewrite(plan)))
+                .collect()
+        };
+        PaxosHistory {
+            linearizability: self.linearizability.rewrite(plan),
+            proposed: self.proposed.clone(),
+            in_flight: self
+                .in_flight
+                .iter()
+                .map(|(id, request_id)| (plan.rewrite(id), *request_id))
+                .collect(),
+            last_seen: self
+                .last_seen
+                .iter()
+                .map(|((id, slot), rid)| ((plan.rewrite(id), *slot), rid.rewrite(plan)))
+                .collect(),
+            promised: rewrite_rounds(&self.promised),
+            leases: rewrite_rounds(&self.leases),
+            last_seen_regressed: self.last_seen_regressed,
+            accepted_unpromised: self.accepted_unpromised,
+            split: self.split.iter().map(|id| plan.rewrite(id)).collect(),
+            healed: self.healed.iter().map(|id| plan.rewrite(id)).collect(),
+            accepted_across: rewrite_rounds(&self.accepted_across),
+            minority_decided: self.minority_decided,
+            decided_after_heal: self.decided_after_heal,
+            completed: self.completed.clone(),
+            writing: self
+                .writing
+                .iter()
+                .map(|(id, writing)| (plan.rewrite(id), writing.clone()))
+                .collect(),
+            superseded: self.superseded.clone(),
+            reading: self
+                .reading
+                .iter()
+                .map(|(id, superseded)| (plan.rewrite(id), superseded.clone()))
+                .collect(),
+            stale_read: self.stale_read,
+            newer_than: self.newer_than.clone(),
+            session_writes: self
+                .session_writes
+                .iter()
+                .map(|(id, writes)| (plan.rewrite(id), writes.clone()))
+                .collect(),
+            written_past: self
+                .written_past
+                .iter()
+                .map(|(id, past)| (plan.rewrite(id), past.clone()))
+                .collect(),
+            read_past: self
+                .read_past
+                .iter()
+                .map(|(id, past)| (plan.rewrite(id), past.clone()))
+                .collect(),
+            read_own_write_lost: self.read_own_write_lost,
+            read_went_back: self.read_went_back,
+            prepared_cut_off: self.prepared_cut_off,
+        }
+    }
+}
+
+impl Default for PaxosHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// the states of every server in a model state, skipping the clients
+pub fn server_states(state: &PaxosModelState) -> impl Iterator<Item = &PaxosState<RegisterValue>> {
+    state
+        .actor_states
+        .iter()
+        .filter_map(|actor_state| match actor_state.as_ref() {
+            PaxosModelActorState::Server(server_state) => Some(server_state),
+            _ => None,
+        })
+}
+
+// the servers that store values, which leaves out witnesses
+fn data_states<'a>(
+    cfg: &'a PaxosModelConfig,
+    state: &'a PaxosModelState,
+) -> impl Iterator<Item = &'a PaxosState<RegisterValue>> {
+    server_states(state).filter(move |server_state| {
+        let i = usize::from(server_state.id());
+        !cfg.is_witness(i) && !cfg.is_down(i)
+    })
+}
+
+// no two servers decide different values for the same slot
+fn agreement<'a>(servers: impl Iterator<Item = &'a PaxosState<RegisterValue>>) -> bool {
+    let mut chosen = BTreeMap::new();
+    for server_state in servers {
+        for (slot, val) in server_state.log() {
+            if *chosen.entry(*slot).or_insert(val) != val {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// the gap-free prefixes of any two logs must be prefixes of one another
+fn sequentially_consistent<'a>(
+    servers: impl Iterator<Item = &'a PaxosState<RegisterValue>>,
+) -> bool {
+    let mut prefixes: Vec<Vec<&Command<RegisterValue>>> = Vec::new();
+    for server_state in servers {
+        let mut prefix = Vec::new();
+        while let Some(val) = server_state.log().get(&(prefix.len() as Slot)) {
+            prefix.push(val);
+        }
+        prefixes.push(prefix);
+    }
+    for a in &prefixes {
+        for b in &prefixes {
+            let common = a.len().min(b.len());
+            if a[..common] != b[..common] {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+pub type PaxosActorState = PaxosModelActorState<RegisterValue>;
+
+// whether a message hands its recipient a batch of puts
+fn carries_batch<V>(msg: &PaxosMsg<V>) -> bool {
+    let batch = |command: &Command<V>| matches!(command, Command::Batch(_));
+    match msg {
+        PaxosMsg::Accept(.., command)
+        | PaxosMsg::Accepted(.., command)
+        | PaxosMsg::Decided(_, _, command)
+        | PaxosMsg::Forward(_, _, command)
+        | PaxosMsg::Promise(.., Some((_, command))) => batch(command),
+        PaxosMsg::CatchUpReply(entries) => entries.iter().any(|(_, command)| batch(command)),
+        PaxosMsg::Handoff(_, _, requests) => requests.iter().any(|(_, _, command)| batch(command)),
+        _ => false,
+    }
+}
+
+// the safety properties that only need the actor states, for the simulator to check after
+// every step, see `crate::sim`
+pub fn sim_invariants() -> Vec<Invariant<PaxosActorState>> {
+    fn servers(states: &[PaxosActorState]) -> impl Iterator<Item = &PaxosState<RegisterValue>> {
+        states.iter().filter_map(|actor_state| match actor_state {
+            PaxosModelActorState::Server(server_state) => Some(server_state),
+            _ => None,
+        })
+    }
+    fn agreed(states: &[PaxosActorState]) -> bool {
+        agreement(servers(states))
+    }
+    fn consistent(states: &[PaxosActorState]) -> bool {
+        sequentially_consistent(servers(states))
+    }
+    vec![
+        ("agreement", agreed as fn(&[PaxosActorState]) -> bool),
+        (
+            "sequentially consistent",
+            consistent as fn(&[PaxosActorState]) -> bool,
+        ),
+    ]
+}
+
+impl PaxosModelConfig<RegisterValue> {
+    pub fn into_model(self) -> PaxosModel {
+        ActorModel::new(self.clone(), PaxosHistory::new())
+            .actors((0..self.server_count).map(|i| {
+                let members = self.member_count.unwrap_or(self.server_count);
+                let peers: Vec<Id> = (0..members).filter(|j| *j != i).map(Id::from).collect();
+                let quorum = self.quorum.build_in(&peers, &self.topology);
+                let reconfigure = match self.reconfigure {
+                    Some(count) if i == 0 => Some((0..count).map(Id::from).collect()),
+                    _ => None,
+                };
+                PaxosModelActor::Server(
+                    PaxosActor::new(peers)
+                        .quorum(quorum)
+                        .strategy(self.strategy.build())
+                        .crashes(i < self.crash_count)
+                        .down(self.is_down(i))
+                        .byzantine(self.byzantine == Some(i))
+                        .partition(self.other_side(i))
+                        .sessions(self.sessions)
+                        .reconfigure(reconfigure)
+                        .transfer_leadership(self.transfer.filter(|_| i == 0).map(Id::from))
+                        .compact_after(self.snapshot_every)
+                        .read_leases(self.leases())
+                        .lease_timing(self.clock_skew.map(|skew| {
+                            (
+                                TICK * self.lease_ticks().unwrap() as u32,
+                                TICK * skew as u32,
+                            )
+                        }))
+                        .clock(match self.clock_skew {
+                            Some(_) => Clock::logical(TICK),
+                            None => Clock::monotonic(),
+                        })
+                        .stale_reads(self.read_strategy == ReadStrategy::Local)
+                        .batch_size(self.batch_size.filter(|_| !self.is_old(i)))
+                        .features(self.features(i))
+                        .pipeline(self.pipeline)
+                        .replication_window(self.replication_window)
+                        .pre_vote(self.pre_vote)
+                        .backoff(
+                            Some(SplitMix(self.seed).fork(i as u64).0).filter(|_| self.backoff),
+                        )
+                        .suspicion(self.suspicion)
+                        .observer(Arc::clone(&self.observer))
+                        .role(if self.is_witness(i) {
+                            Role::Witness
+                        } else {
+                            Role::Data
+                        })
+                        .witnesses(
+                            (0..self.server_count)
+                                .filter(|j| self.is_witness(*j))
+                                .map(Id::from)
+                                .collect(),
+                        ),
+                )
+            }))
+            .actors((0..self.client_count).map(|_| {
+                let retry = Duration::from_millis(1000)..Duration::from_millis(2000);
+                let think = Duration::from_millis(100)..Duration::from_millis(500);
+                let client = PaxosClientActor::new(self.put_count, self.server_count)
+                    .get_count(self.get_count)
+                    .retry(Some(retry).filter(|_| self.retrying_clients))
+                    .think(Some(think).filter(|_| self.think_time))
+                    .pattern(self.pattern);
+                PaxosModelActor::Client(match (&self.value_domain, self.abstract_values) {
+                    (Some(values), _) => client.values(Arc::new(Domain::new(values.clone()))),
+                    (None, Some(tokens)) => client.values(Arc::new(Symbolic::new(tokens))),
+                    (None, None) => client,
+                })
+            }))
+            .duplicating_network(match self.network {
+                NetworkMode::UnorderedDuplicating => DuplicatingNetwork::Yes,
+                _ => DuplicatingNetwork::No,
+            })
+            .lossy_network(match self.network {
+                NetworkMode::Lossy => LossyNetwork::Yes,
+                _ => LossyNetwork::No,
+            })
+            .property(
+                stateright::Expectation::Always,
+                "linearizable",
+                |_, state| state.history.linearizability.serialized_history().is_some(),
+            )
+            .property(
+                stateright::Expectation::Always,
+                "quorums intersect",
+                |model, _| {
+                    let cfg = &model.cfg;
+                    (0..cfg.server_count).all(|i| {
+                        let peers = model_peers(i, cfg.server_count);
+                        cfg.quorum
+                            .build_in(&peers, &cfg.topology)
+                            .intersects(&peers)
+                    })
+                },
+            )
+            .property(stateright::Expectation::Always, "agreement", |_, state| {
+                agreement(server_states(state))
+            })
+            .property(
+                stateright::Expectation::Always,
+                "state bounded by active rounds",
+                |_, state| {
+                    server_states(state).all(|server_state| server_state.holds_only_active_rounds())
+                },
+            )
+            .property(stateright::Expectation::Always, "validity", |_, state| {
+                // only values some client asked for are ever decided
+                server_states(state).all(|server_state| {
+                    server_state.log().values().all(|command| match command {
+                        Command::Put(val) | Command::SessionPut(_, val) => {
+                            state.history.proposed.contains(val)
+                        }
+                        Command::Batch(vals) => {
+                            vals.iter().all(|val| state.history.proposed.contains(val))
+                        }
+                        Command::Reconfigure(_) => true,
+                    })
+                })
+            })
+            .property(
+                stateright::Expectation::Always,
+                "sequentially consistent",
+                |_, state| sequentially_consistent(server_states(state)),
+            )
+            .property(
+                stateright::Expectation::Always,
+                "at most once",
+                |model, state| {
+                    // every put writes a value of its own, so a value decided in two slots is a
+                    // client command applied twice, unless its session skips the second; with
+                    // the values abstracted or drawn from a domain two puts may write the same
+                    // one, and this says nothing
+                    if model.cfg.values_repeat() {
+                        return true;
+                    }
+                    server_states(state).all(|server_state| {
+                        let mut decided = BTreeSet::new();
+                        let mut sessions = BTreeMap::new();
+                        server_state.log().values().all(|command| match command {
+                            Command::Put(val) => decided.insert(val),
+                            Command::SessionPut(session, val) => {
+                                let skipped = sessions
+                                    .get(&session.client())
+                                    .map_or(false, |seq| *seq >= session.seq());
+                                if !skipped {
+                                    sessions.insert(session.client(), session.seq());
+                                }
+                                skipped || decided.insert(val)
+                            }
+                            Command::Batch(vals) => vals.iter().all(|val| decided.insert(val)),
+                            Command::Reconfigure(_) => true,
+                        })
+                    })
+                },
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "value chosen",
+                |_, state| {
+                    for env in &state.network {
+                        if let RegisterMsg::GetOk(_, value) = env.msg {
+                            if value != RegisterValue::default() {
+                                return true;
+                            }
+                        }
+                    }
+                    false
+                },
+            )
+            .property(
+                stateright::Expectation::Always,
+                "one proposer per slot",
+                |_, state| {
+                    // a leader that has handed off never proposes a slot its target does
+                    server_states(state).all(|server_state| {
+                        let handoff = match server_state.handoff() {
+                            Some(handoff) if handoff.sent() => handoff,
+                            _ => return true,
+                        };
+                        let target = usize::from(handoff.target());
+                        let slots: BTreeSet<Slot> = match state.actor_states[target].as_ref() {
+                            PaxosModelActorState::Server(target) => {
+                                target.proposals().map(|(slot, _)| slot).collect()
+                            }
+                            _ => return true,
+                        };
+                        server_state
+                            .proposals()
+                            .all(|(slot, _)| !slots.contains(&slot))
+                    })
+                },
+            )
+            .property(
+                stateright::Expectation::Always,
+                "old servers see no batches",
+                |model, state| {
+                    // a server from before batching could not make sense of one
+                    state.network.iter().all(|env| match &env.msg {
+                        RegisterMsg::Internal(msg) if model.cfg.is_old(usize::from(env.dst)) => {
+                            !carries_batch(msg)
+                        }
+                        _ => true,
+                    })
+                },
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "leadership transferred",
+                |model, state| {
+                    let target = match model.cfg.transfer {
+                        Some(target) => target,
+                        None => return true,
+                    };
+                    match state.actor_states[target].as_ref() {
+                        PaxosModelActorState::Server(server_state) => server_state
+                            .lease()
+                            .map_or(false, |lease| lease.ballot().id() == Id::from(target)),
+                        _ => false,
+                    }
+                },
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "batch decided",
+                |model, state| {
+                    let cfg = &model.cfg;
+                    // only meaningful when batching is on
+                    cfg.batch_size.is_none()
+                        || server_states(state).any(|server_state| {
+                            server_state
+                                .log()
+                                .values()
+                                .any(|command| matches!(command, Command::Batch(_)))
+                        })
+                },
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "all servers learned",
+                |model, state| {
+                    // compared by applied state, since logs are compacted at different points
+                    let mut applied =
+                        data_states(&model.cfg, state).map(|server_state| server_state.applied());
+                    match applied.next() {
+                        Some(first) if first.next() > 0 => applied.all(|other| other == first),
+                        _ => false,
+                    }
+                },
+            )
+            .property(
+                stateright::Expectation::Always,
+                "snapshots match replay",
+                |_, state| {
+                    // whether caught up from a snapshot or not, a server holds the same state as
+                    // replaying any full log up to the same slot
+                    server_states(state).all(|server_state| {
+                        let applied = server_state.applied();
+                        server_states(state)
+                            .filter(|other| other.snapshot().is_none())
+                            .filter_map(|other| Applied::replay(other.log(), applied.next()))
+                            .all(|replayed| replayed == *applied)
+                    })
+                },
+            )
+            .property(
+                stateright::Expectation::Always,
+                "no holes in applied prefix",
+                |_, state| {
+                    // slots are decided out of order, but only a gap-free prefix is ever applied
+                    server_states(state).all(|server_state| {
+                        let compacted = server_state
+                            .snapshot()
+                            .map_or(0, |snapshot| snapshot.next());
+                        (compacted..server_state.applied().next())
+                            .all(|slot| server_state.log().contains_key(&slot))
+                    })
+                },
+            )
+            .property(
+                stateright::Expectation::Always,
+                "reads fresh",
+                |_, state| !state.history.stale_read,
+            )
+            .property(
+                stateright::Expectation::Always,
+                "read your writes",
+                |_, state| !state.history.read_own_write_lost,
+            )
+            .property(
+                stateright::Expectation::Always,
+                "monotonic reads",
+                |_, state| !state.history.read_went_back,
+            )
+            .property(
+                stateright::Expectation::Always,
+                "monotonic writes",
+                |_, state| {
+                    // every server's log has each client's puts in the order it made them
+                    server_states(state).all(|server_state| {
+                        let logged: Vec<&RegisterValue> = server_state
+                            .log()
+                            .values()
+                            .flat_map(|command| command.values())
+                            .collect();
+                        state.history.session_writes.values().all(|writes| {
+                            let order: Vec<usize> = logged
+                                .iter()
+                                .filter_map(|value| writes.iter().position(|w| w == *value))
+                                .collect();
+                            order.windows(2).all(|pair| pair[0] < pair[1])
+                        })
+                    })
+                },
+            )
+            .property(
+                stateright::Expectation::Always,
+                "read leases honoured",
+                |_, state| {
+                    // local reads are only safe while every grantor still keeps other proposers out
+                    server_states(state).all(|server_state| match server_state.read_lease() {
+                        Some(lease) => lease.grants().iter().all(|id| {
+                            match state.actor_states[usize::from(*id)].as_ref() {
+                                PaxosModelActorState::Server(grantor) => {
+                                    grantor.read_grant() == Some(lease.ballot())
+                                }
+                                _ => true,
+                            }
+                        }),
+                        None => true,
+                    })
+                },
+            )
+            .property(
+                stateright::Expectation::Always,
+                "no rounds while cut off",
+                |model, state| !model.cfg.pre_vote || !state.history.prepared_cut_off,
+            )
+            .property(
+                stateright::Expectation::Always,
+                "replication windows bounded",
+                |model, state| {
+                    // however slow a follower, a leader keeps at most a window of slots for it
+                    let window = match model.cfg.replication_window {
+                        Some(window) => window,
+                        None => return true,
+                    };
+                    server_states(state).all(|server_state| {
+                        server_state
+                            .unacked()
+                            .values()
+                            .all(|slots| slots.len() <= window)
+                    })
+                },
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "replication window full",
+                |model, state| match model.cfg.replication_window {
+                    Some(window) => server_states(state).any(|server_state| {
+                        server_state
+                            .unacked()
+                            .values()
+                            .any(|slots| slots.len() == window)
+                    }),
+                    None => true,
+                },
+            )
+            .property(
+                stateright::Expectation::Always,
+                "last seen never decreases",
+                |_, state| !state.history.last_seen_regressed,
+            )
+            .property(
+                stateright::Expectation::Always,
+                "accepted implies promised",
+                |_, state| !state.history.accepted_unpromised,
+            )
+            .property(
+                stateright::Expectation::Always,
+                "no minority decision",
+                |_, state| !state.history.minority_decided,
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "leader replaced",
+                |model, state| {
+                    let cfg = &model.cfg;
+                    // only meaningful when followers may suspect their leader
+                    let leaders: BTreeSet<Id> = state
+                        .history
+                        .leases
+                        .iter()
+                        .map(|(_, _, ballot)| ballot.id())
+                        .collect();
+                    !cfg.suspicion || leaders.len() > 1
+                },
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "decided after healing",
+                |model, state| {
+                    let cfg = &model.cfg;
+                    cfg.split.is_none() || state.history.decided_after_heal
+                },
+            )
+            .property(
+                stateright::Expectation::Eventually,
+                "caught up after rejoining",
+                |model, state| {
+                    let cfg = &model.cfg;
+                    // only meaningful under a partition: once it has healed everywhere, servers
+                    // that missed decisions pull them from the others
+                    if cfg.split.is_none() {
+                        return true;
+                    }
+                    let furthest = data_states(cfg, state)
+                        .map(|server_state| server_state.applied().next())
+                        .max()
+                        .unwrap_or(0);
+                    state.history.healed.len() == cfg.server_count
+                        && furthest > 0
+                        && data_states(cfg, state)
+                            .all(|server_state| server_state.applied().next() == furthest)
+                },
+            )
+            .property(
+                stateright::Expectation::Eventually,
+                "consensus reached",
+                |model, state| {
+                    let mut applied =
+                        data_states(&model.cfg, state).map(|server_state| server_state.applied());
+                    let first = match applied.next() {
+                        Some(first) => first,
+                        None => return true,
+                    };
+                    applied.all(|other| other == first)
+                },
+            )
+            .property(
+                stateright::Expectation::Always,
+                "no livelock",
+                |model, state| {
+                    let cfg = &model.cfg;
+                    // a bounded stand-in for eventually deciding: two proposers that have both
+                    // retried up to --max-rounds without anything decided are dueling
+                    let max = match cfg.max_rounds {
+                        Some(max) => max,
+                        None => return true,
+                    };
+                    let decided = server_states(state).any(|server_state| {
+                        !server_state.log().is_empty() || server_state.snapshot().is_some()
+                    });
+                    let dueling = server_states(state)
+                        .filter(|server_state| {
+                            server_state
+                                .proposals()
+                                .any(|(_, rid)| rid.round_num() >= max)
+                        })
+                        .count();
+                    decided || dueling < 2
+                },
+            )
+            .within_boundary(|cfg, state| {
+                let rounds = cfg.max_rounds.map_or(true, |max| {
+                    server_states(state).all(|server_state| server_state.durable().round() <= max)
+                });
+                let messages = cfg
+                    .max_network_messages
+                    .map_or(true, |max| state.network.len() <= max);
+                // clocks stop a couple of leases in, and under bounded skew never drift further
+                // apart than the leases allow for
+                let ticks: Vec<u64> = server_states(state)
+                    .map(|server_state| server_state.ticks())
+                    .collect();
+                let (slowest, fastest) = (
+                    ticks.iter().min().copied().unwrap_or(0),
+                    ticks.iter().max().copied().unwrap_or(0),
+                );
+                let running = cfg.lease_ticks().map_or(true, |lease| fastest <= 2 * lease);
+                let skewed = match (cfg.clock_skew, cfg.leases()) {
+                    (Some(skew), Some(ClockSkew::Bounded)) => fastest - slowest > skew,
+                    _ => false,
+                };
+                if !(rounds && messages && running) {
+                    PRUNED.store(true, Ordering::Relaxed);
+                }
+                rounds && messages && running && !skewed
+            })
+            .record_msg_in(PaxosHistory::record_paxos_returns)
+            .record_msg_out(PaxosHistory::record_paxos_invocations)
+    }
+}
+// the states of every raft server in a model state, skipping the clients
+pub fn raft_states(state: &RaftModelState) -> impl Iterator<Item = &RaftState<RegisterValue>> {
+    state
+        .actor_states
+        .iter()
+        .filter_map(|actor_state| match actor_state.as_ref() {
+            RegisterActorState::Server(server_state) => Some(server_state),
+            _ => None,
+        })
+}
+
+impl PaxosModelConfig<RegisterValue> {
+    // the same clients and network, but with raft servers, so the two can be compared
+    pub fn into_raft_model(self) -> RaftModel {
+        ActorModel::new(self.clone(), PaxosHistory::new())
+            .actors(
+                (0..self.server_count).map(|i| {
+                    RegisterActor::Server(RaftActor::new(model_peers(i, self.server_count)))
+                }),
+            )
+            .actors((0..self.client_count).map(|_| RegisterActor::Client {
+                put_count: self.put_count,
+                server_count: self.server_count,
+            }))
+            .duplicating_network(match self.network {
+                NetworkMode::UnorderedDuplicating => DuplicatingNetwork::Yes,
+                _ => DuplicatingNetwork::No,
+            })
+            .lossy_network(match self.network {
+                NetworkMode::Lossy => LossyNetwork::Yes,
+                _ => LossyNetwork::No,
+            })
+            .property(
+                stateright::Expectation::Always,
+                "linearizable",
+                |_, state| state.history.linearizability.serialized_history().is_some(),
+            )
+            .property(stateright::Expectation::Always, "agreement", |_, state| {
+                // committed prefixes never diverge
+                let committed: Vec<_> = raft_states(state)
+                    .map(|server_state| server_state.committed())
+                    .collect();
+                committed.iter().all(|a| {
+                    committed.iter().all(|b| {
+                        let common = a.len().min(b.len());
+                        a[..common] == b[..common]
+                    })
+                })
+            })
+            .property(
+                stateright::Expectation::Sometimes,
+                "value chosen",
+                |_, state| {
+                    for env in &state.network {
+                        if let RegisterMsg::GetOk(_, value) = env.msg {
+                            if value != RegisterValue::default() {
+                                return true;
+                            }
+                        }
+                    }
+                    false
+                },
+            )
+            .record_msg_in(PaxosHistory::record_returns)
+            .record_msg_out(PaxosHistory::record_invocations)
+    }
+}
+
+// the states of every mencius server in a model state, skipping the clients
+pub fn mencius_states(
+    state: &MenciusModelState,
+) -> impl Iterator<Item = &MenciusState<RegisterValue>> {
+    state
+        .actor_states
+        .iter()
+        .filter_map(|actor_state| match actor_state.as_ref() {
+            RegisterActorState::Server(server_state) => Some(server_state),
+            _ => None,
+        })
+}
+
+// the states of the vertical paxos replicas and master in a model state, skipping the clients
+pub fn vertical_states(
+    state: &VerticalModelState,
+) -> impl Iterator<Item = &VerticalActorState<RegisterValue>> {
+    state
+        .actor_states
+        .iter()
+        .filter_map(|actor_state| match actor_state.as_ref() {
+            RegisterActorState::Server(server_state) => Some(server_state),
+            _ => None,
+        })
+}
+
+// the states of the disk paxos processors and disks in a model state, skipping the clients
+pub fn disk_states(
+    state: &DiskPaxosModelState,
+) -> impl Iterator<Item = &DiskPaxosModelActorState<RegisterValue>> {
+    state
+        .actor_states
+        .iter()
+        .filter_map(|actor_state| match actor_state.as_ref() {
+            RegisterActorState::Server(server_state) => Some(server_state),
+            _ => None,
+        })
+}
+
+// the states of the proposers, acceptors and learners in a model state, skipping the clients
+pub fn role_states(
+    state: &RolesModelState,
+) -> impl Iterator<Item = &RoleActorState<RegisterValue>> {
+    state
+        .actor_states
+        .iter()
+        .filter_map(|actor_state| match actor_state.as_ref() {
+            RegisterActorState::Server(server_state) => Some(server_state),
+            _ => None,
+        })
+}
+
+impl PaxosModelConfig<RegisterValue> {
+    // the same clients and network as `into_raft_model`, with mencius servers
+    pub fn into_mencius_model(self) -> MenciusModel {
+        ActorModel::new(self.clone(), PaxosHistory::new())
+            .actors((0..self.server_count).map(|i| {
+                RegisterActor::Server(MenciusActor::new(model_peers(i, self.server_count)))
+            }))
+            .actors((0..self.client_count).map(|_| RegisterActor::Client {
+                put_count: self.put_count,
+                server_count: self.server_count,
+            }))
+            .duplicating_network(match self.network {
+                NetworkMode::UnorderedDuplicating => DuplicatingNetwork::Yes,
+                _ => DuplicatingNetwork::No,
+            })
+            .lossy_network(match self.network {
+                NetworkMode::Lossy => LossyNetwork::Yes,
+                _ => LossyNetwork::No,
+            })
+            .property(
+                stateright::Expectation::Always,
+                "linearizable",
+                |_, state| state.history.linearizability.serialized_history().is_some(),
+            )
+            .property(stateright::Expectation::Always, "agreement", |_, state| {
+                let mut chosen = BTreeMap::new();
+                mencius_states(state).all(|server_state| {
+                    server_state
+                        .log()
+                        .iter()
+                        .all(|(slot, decree)| *chosen.entry(*slot).or_insert(decree) == decree)
+                })
+            })
+            .property(
+                stateright::Expectation::Sometimes,
+                "value chosen",
+                |_, state| {
+                    for env in &state.network {
+                        if let RegisterMsg::GetOk(_, value) = env.msg {
+                            if value != RegisterValue::default() {
+                                return true;
+                            }
+                        }
+                    }
+                    false
+                },
+            )
+            .record_msg_in(PaxosHistory::record_returns)
+            .record_msg_out(PaxosHistory::record_invocations)
+    }
+
+    // the same clients and network as `into_raft_model`, with vertical paxos replicas and a
+    // configuration master after them, which at some point moves the log from every server to
+    // all but the first, so the primary changes too. Clients only talk to the replicas.
+    pub fn into_vertical_model(self) -> VerticalModel {
+        let replicas: Vec<Id> = (0..self.server_count).map(Id::from).collect();
+        let master = Id::from(self.server_count);
+        ActorModel::new(self.clone(), PaxosHistory::new())
+            .actors(replicas.iter().map(|_| {
+                RegisterActor::Server(VerticalActor::Replica(VerticalPaxosActor::new(
+                    master,
+                    replicas.clone(),
+                )))
+            }))
+            .actor(RegisterActor::Server(VerticalActor::Master(
+                ConfigMaster::new(replicas.clone()).reconfigure(Some(replicas[1..].to_vec())),
+            )))
+            .actors((0..self.client_count).map(|_| RegisterActor::Client {
+                put_count: self.put_count,
+                server_count: self.server_count,
+            }))
+            .duplicating_network(match self.network {
+                NetworkMode::UnorderedDuplicating => DuplicatingNetwork::Yes,
+                _ => DuplicatingNetwork::No,
+            })
+            .lossy_network(match self.network {
+                NetworkMode::Lossy => LossyNetwork::Yes,
+                _ => LossyNetwork::No,
+            })
+            .property(
+                stateright::Expectation::Always,
+                "linearizable",
+                |_, state| state.history.linearizability.serialized_history().is_some(),
+            )
+            .property(stateright::Expectation::Always, "agreement", |_, state| {
+                let mut chosen = BTreeMap::new();
+                vertical_states(state).all(|replica_state| match replica_state {
+                    VerticalActorState::Replica(replica_state) => replica_state
+                        .log()
+                        .iter()
+                        .all(|(slot, entry)| *chosen.entry(*slot).or_insert(entry) == entry),
+                    VerticalActorState::Master(_) => true,
+                })
+            })
+            .property(
+                stateright::Expectation::Sometimes,
+                "value chosen after reconfiguration",
+                |_, state| {
+                    let reconfigured =
+                        vertical_states(state).any(|actor_state| match actor_state {
+                            VerticalActorState::Master(master_state) => {
+                                master_state.active().epoch() > 0
+                            }
+                            _ => false,
+                        });
+                    reconfigured
+                        && state.network.iter().any(|env| match env.msg {
+                            RegisterMsg::GetOk(_, value) => value != RegisterValue::default(),
+                            _ => false,
+                        })
+                },
+            )
+            .record_msg_in(PaxosHistory::record_returns)
+            .record_msg_out(PaxosHistory::record_invocations)
+    }
+
+    // the same clients and network as `into_raft_model`, with disk paxos processors in place of
+    // the servers and the shared disks after them. `max_rounds` bounds how often a processor may
+    // retry after seeing a higher ballot on a disk.
+    pub fn into_disk_model(self) -> DiskPaxosModel {
+        let disks: Vec<Id> = (0..self.disk_count)
+            .map(|i| Id::from(self.server_count + i))
+            .collect();
+        ActorModel::new(self.clone(), PaxosHistory::new())
+            .actors((0..self.server_count).map(|_| {
+                RegisterActor::Server(DiskPaxosModelActor::Processor(DiskPaxosActor::new(
+                    disks.clone(),
+                )))
+            }))
+            .actors(
+                disks
+                    .iter()
+                    .map(|_| RegisterActor::Server(DiskPaxosModelActor::Disk(SharedDisk::new()))),
+            )
+            .actors((0..self.client_count).map(|_| RegisterActor::Client {
+                put_count: self.put_count,
+                server_count: self.server_count,
+            }))
+            .duplicating_network(match self.network {
+                NetworkMode::UnorderedDuplicating => DuplicatingNetwork::Yes,
+                _ => DuplicatingNetwork::No,
+            })
+            .lossy_network(match self.network {
+                NetworkMode::Lossy => LossyNetwork::Yes,
+                _ => LossyNetwork::No,
+            })
+            .property(
+                stateright::Expectation::Always,
+                "linearizable",
+                |_, state| state.history.linearizability.serialized_history().is_some(),
+            )
+            .property(stateright::Expectation::Always, "agreement", |_, state| {
+                let mut chosen = BTreeMap::new();
+                disk_states(state).all(|actor_state| match actor_state {
+                    DiskPaxosModelActorState::Processor(processor_state) => processor_state
+                        .log()
+                        .iter()
+                        .enumerate()
+                        .all(|(slot, decree)| *chosen.entry(slot).or_insert(decree) == decree),
+                    DiskPaxosModelActorState::Disk(_) => true,
+                })
+            })
+            .property(
+                stateright::Expectation::Sometimes,
+                "value chosen",
+                |_, state| {
+                    state.network.iter().any(|env| match env.msg {
+                        RegisterMsg::GetOk(_, value) => value != RegisterValue::default(),
+                        _ => false,
+                    })
+                },
+            )
+            .within_boundary(|cfg, state| {
+                let rounds = cfg.max_rounds.map_or(true, |max| {
+                    disk_states(state).all(|actor_state| match actor_state {
+                        DiskPaxosModelActorState::Processor(processor_state) => {
+                            processor_state.round() <= max
+                        }
+                        DiskPaxosModelActorState::Disk(_) => true,
+                    })
+                });
+                let messages = cfg
+                    .max_network_messages
+                    .map_or(true, |max| state.network.len() <= max);
+                if !(rounds && messages) {
+                    PRUNED.store(true, Ordering::Relaxed);
+                }
+                rounds && messages
+            })
+            .record_msg_in(PaxosHistory::record_returns)
+            .record_msg_out(PaxosHistory::record_invocations)
+    }
+
+    // The same clients and network as `into_raft_model`, with the servers as proposers and the
+    // acceptors and learners after them. Clients only talk to proposers, and `max_rounds`
+    // bounds how often a proposer may start a round over.
+    pub fn into_roles_model(self) -> RolesModel {
+        let acceptors: Vec<Id> = (0..self.acceptor_count)
+            .map(|i| Id::from(self.server_count + i))
+            .collect();
+        let learners: Vec<Id> = (0..self.learner_count)
+            .map(|i| Id::from(self.server_count + self.acceptor_count + i))
+            .collect();
+        ActorModel::new(self.clone(), PaxosHistory::new())
+            .actors((0..self.server_count).map(|_| {
+                RegisterActor::Server(RoleActor::Proposer(ProposerActor::new(
+                    acceptors.clone(),
+                    learners.clone(),
+                )))
+            }))
+            .actors(acceptors.iter().map(|_| {
+                RegisterActor::Server(RoleActor::Acceptor(AcceptorActor::new(learners.clone())))
+            }))
+            .actors(learners.iter().map(|_| {
+                RegisterActor::Server(RoleActor::Learner(LearnerActor::new(acceptors.clone())))
+            }))
+            .actors((0..self.client_count).map(|_| RegisterActor::Client {
+                put_count: self.put_count,
+                server_count: self.server_count,
+            }))
+            .duplicating_network(match self.network {
+                NetworkMode::UnorderedDuplicating => DuplicatingNetwork::Yes,
+                _ => DuplicatingNetwork::No,
+            })
+            .lossy_network(match self.network {
+                NetworkMode::Lossy => LossyNetwork::Yes,
+                _ => LossyNetwork::No,
+            })
+            .property(
+                stateright::Expectation::Always,
+                "linearizable",
+                |_, state| state.history.linearizability.serialized_history().is_some(),
+            )
+            .property(stateright::Expectation::Always, "agreement", |_, state| {
+                let mut chosen = BTreeMap::new();
+                role_states(state).all(|actor_state| match actor_state {
+                    RoleActorState::Learner(learner_state) => learner_state
+                        .log()
+                        .iter()
+                        .all(|(slot, command)| *chosen.entry(*slot).or_insert(command) == command),
+                    _ => true,
+                })
+            })
+            .property(
+                stateright::Expectation::Sometimes,
+                "value chosen",
+                |_, state| {
+                    state.network.iter().any(|env| match env.msg {
+                        RegisterMsg::GetOk(_, value) => value != RegisterValue::default(),
+                        _ => false,
+                    })
+                },
+            )
+            .within_boundary(|cfg, state| {
+                let rounds = cfg.max_rounds.map_or(true, |max| {
+                    role_states(state).all(|actor_state| match actor_state {
+                        RoleActorState::Proposer(proposer_state) => proposer_state.round() <= max,
+                        _ => true,
+                    })
+                });
+                let messages = cfg
+                    .max_network_messages
+                    .map_or(true, |max| state.network.len() <= max);
+                if !(rounds && messages) {
+                    PRUNED.store(true, Ordering::Relaxed);
+                }
+                rounds && messages
+            })
+            .record_msg_in(PaxosHistory::record_returns)
+            .record_msg_out(PaxosHistory::record_invocations)
+    }
+
+    // the fewest messages delivered, client requests and replies included, before every client
+    // has had all its puts acknowledged, under Multi-Paxos and under Mencius. A breadth first
+    // search finds the shortest such run, so this is the best case over every interleaving
+    // rather than a typical one, and it explores the whole state space to get there.
+    pub fn commit_latency(self) -> (Option<usize>, Option<usize>) {
+        fn acknowledged(model: &PaxosModel, state: &PaxosModelState) -> bool {
+            state
+                .actor_states
+                .iter()
+                .all(|actor_state| match actor_state.as_ref() {
+                    PaxosModelActorState::Client(client_state) => {
+                        client_state.op_count() > model.cfg.put_count
+                    }
+                    _ => true,
+                })
+        }
+        fn mencius_acknowledged(model: &MenciusModel, state: &MenciusModelState) -> bool {
+            state
+                .actor_states
+                .iter()
+                .all(|actor_state| match actor_state.as_ref() {
+                    RegisterActorState::Client { op_count, .. } => {
+                        *op_count as usize > model.cfg.put_count
+                    }
+                    _ => true,
+                })
+        }
+        // the clients make their puts first and straight away, like Mencius' register clients
+        let paxos = self
+            .clone()
+            .pattern(Pattern::PutsThenGets)
+            .think_time(false)
+            .into_model()
+            .property(
+                stateright::Expectation::Sometimes,
+                "puts acknowledged",
+                acknowledged,
+            );
+        let mencius = self.into_mencius_model().property(
+            stateright::Expectation::Sometimes,
+            "puts acknowledged",
+            mencius_acknowledged,
+        );
+        (deliveries_until(paxos), deliveries_until(mencius))
+    }
+}
+
+// deliveries along the shortest path to the "puts acknowledged" discovery
+fn deliveries_until<A, H>(model: ActorModel<A, PaxosModelConfig, H>) -> Option<usize>
+where
+    A: Actor + Send + Sync + 'static,
+    A::Msg: Send + Sync,
+    A::State: Send + Sync,
+    H: Clone + fmt::Debug + std::hash::Hash + Send + Sync + 'static,
+{
+    use stateright::{Checker, Model};
+    let path = model
+        .checker()
+        .spawn_bfs()
+        .join()
+        .discovery("puts acknowledged")?;
+    let deliveries = path
+        .into_actions()
+        .into_iter()
+        .filter(|action| matches!(action, ActorModelAction::Deliver { .. }))
+        .count();
+    Some(deliveries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ballot::{self, Ballot, ScrambledBallot};
+    use crate::rng::{Rng, SplitMix};
+    use stateright::{Checker, Model};
+    use ActorModelAction::Deliver;
+    use RegisterMsg::Internal;
+
+    // the rounds of every accept delivered for a slot along a counterexample
+    fn delivered_accepts(
+        actions: Vec<ActorModelAction<RegisterMsg<u64, char, PaxosMsg<char>>>>,
+        slot: Slot,
+    ) -> BTreeSet<RoundIdentifier> {
+        actions
+            .into_iter()
+            .filter_map(|action| match action {
+                Deliver {
+                    msg: Internal(PaxosMsg::Accept(_, _, s, rid, _)),
+                    ..
+                } if s == slot => Some(rid),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn majorities_are_linearizable() {
+        let checker = PaxosModelConfig::new()
+            .client_count(1)
+            .server_count(3)
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("linearizable");
+        checker.assert_no_discovery("agreement");
+        assert!(checker.discovery("value chosen").is_some());
+    }
+
+    #[test]
+    fn chosen_values_export_as_paxos_tla_behaviors() {
+        let path = PaxosModelConfig::new()
+            .client_count(1)
+            .server_count(3)
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join()
+            .discovery("value chosen")
+            .unwrap()
+            .into_vec();
+        let tla = crate::tla::to_tla("Trace", &[("value chosen", path)]);
+        assert!(tla.starts_with("---- MODULE Trace ----\nEXTENDS Paxos, TLC\n"));
+        assert!(tla.contains(
+            "value_chosen_slot0 == <<\n    [maxBal |-> (0 :> -1 @@ 1 :> -1 @@ 2 :> -1), "
+        ));
+        assert!(tla.contains("[type |-> \"2b\", acc |-> "));
+        assert!(tla.ends_with("\n====\n"));
+    }
+
+    #[test]
+    fn depth_limited_state_graphs_stop_at_the_limit() {
+        let model = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(3)
+            .into_model();
+        let graph = crate::checking::state_graph(&model, Some(1));
+        // only the initial state is expanded, into at least each client's put delivered
+        assert_eq!(graph.initial, 1);
+        assert!(graph.edges.len() >= 2);
+        assert!(graph.edges.iter().all(|(from, _, _)| *from == 0));
+        let dot = crate::trace::to_dot(&graph);
+        assert!(dot.starts_with("digraph states {"));
+        assert_eq!(dot.matches("peripheries=2").count(), 1);
+        assert_eq!(dot.matches(" -> ").count(), graph.edges.len());
+    }
+
+    #[test]
+    fn checkpointed_searches_resume_to_the_same_outcome() {
+        let model = PaxosModelConfig::new()
+            .client_count(1)
+            .server_count(3)
+            .into_model();
+        let file = std::env::temp_dir().join("paxos-rs-checkpoint-test.bin");
+        let every = Duration::ZERO;
+        let mut report = Vec::new();
+        let outcome =
+            crate::checkpoint::checkpointed_bfs(&model, &file, false, every, &mut report).unwrap();
+        assert!(outcome.passed);
+        let resumed =
+            crate::checkpoint::checkpointed_bfs(&model, &file, true, every, &mut report).unwrap();
+        assert!(resumed.passed);
+        let other = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(3)
+            .into_model();
+        assert!(
+            crate::checkpoint::checkpointed_bfs(&other, &file, true, every, &mut report).is_err()
+        );
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn majorities_survive_duplicating_networks() {
+        let checker = PaxosModelConfig::new()
+            .client_count(1)
+            .server_count(3)
+            .network(NetworkMode::UnorderedDuplicating)
+            .into_model()
+            .checker()
+            .spawn_dfs()
+            .join();
+        checker.assert_no_discovery("linearizable");
+        checker.assert_no_discovery("agreement");
+    }
+
+    // the kind of a paxos model message, to pick deliveries out by
+    fn kind(msg: &RegisterMsg<u64, char, PaxosMsg<char>>) -> &'static str {
+        match msg {
+            RegisterMsg::Put(..) => "put",
+            Internal(PaxosMsg::Prepare(..)) => "prepare",
+            Internal(PaxosMsg::Promise(..)) => "promise",
+            Internal(PaxosMsg::Accept(..)) => "accept",
+            Internal(PaxosMsg::Accepted(..)) => "accepted",
+            _ => "other",
+        }
+    }
+
+    #[test]
+    fn redelivered_accepts_count_once() {
+        let model = |network| {
+            PaxosModelConfig::new()
+                .client_count(1)
+                .server_count(3)
+                .network(network)
+                .into_model()
+        };
+        // server 0 takes the put and hears server 1 accept it twice, then server 2 once
+        let steps: [(usize, usize, &str); 10] = [
+            (3, 0, "put"),
+            (0, 1, "prepare"),
+            (0, 2, "prepare"),
+            (1, 0, "promise"),
+            (2, 0, "promise"),
+            (0, 1, "accept"),
+            (1, 0, "accepted"),
+            (1, 0, "accepted"),
+            (0, 2, "accept"),
+            (2, 0, "accepted"),
+        ];
+        let duplicating = model(NetworkMode::UnorderedDuplicating);
+        let mut state = duplicating.init_states().remove(0);
+        let mut schedule = Vec::new();
+        let mut decided = Vec::new();
+        for (src, dst, expected) in steps {
+            let mut actions = Vec::new();
+            duplicating.actions(&state, &mut actions);
+            let action = actions
+                .into_iter()
+                .find(|action| match action {
+                    Deliver {
+                        src: from,
+                        dst: to,
+                        msg,
+                    } => *from == Id::from(src) && *to == Id::from(dst) && kind(msg) == expected,
+                    _ => false,
+                })
+                .unwrap_or_else(|| panic!("no {} from {} to {}", expected, src, dst));
+            if let Some(next) = duplicating.next_state(&state, action.clone()) {
+                state = next;
+            }
+            schedule.push(action);
+            decided.push(server_states(&state).any(|server_state| !server_state.log().is_empty()));
+        }
+        // one acceptor, however often it answers, is no majority of two
+        assert_eq!(decided.iter().position(|decided| *decided), Some(9));
+
+        let replayed = crate::checking::replay(&duplicating, &schedule);
+        assert_eq!(replayed.stuck, None);
+        assert!(replayed.violated.is_empty(), "{:?}", replayed.violated);
+        // only a duplicating network delivers the second accepted
+        let unordered = crate::checking::replay(&model(NetworkMode::Unordered), &schedule);
+        assert_eq!(unordered.stuck, Some(7));
+    }
+
+    #[test]
+    fn sessions_apply_duplicated_requests_once() {
+        let checker = PaxosModelConfig::new()
+            .client_count(1)
+            .server_count(3)
+            .crash_count(1)
+            .sessions(true)
+            .network(NetworkMode::UnorderedDuplicating)
+            .into_model()
+            .checker()
+            .spawn_dfs()
+            .join();
+        checker.assert_no_discovery("at most once");
+        checker.assert_no_discovery("linearizable");
+    }
+
+    #[test]
+    fn mencius_skips_keep_concurrent_puts_linearizable() {
+        let checker = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(3)
+            .put_count(1)
+            .into_mencius_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("linearizable");
+        checker.assert_no_discovery("agreement");
+        assert!(checker.discovery("value chosen").is_some());
+    }
+
+    #[test]
+    fn vertical_reconfiguration_keeps_puts_linearizable() {
+        let checker = PaxosModelConfig::new()
+            .client_count(1)
+            .server_count(3)
+            .put_count(1)
+            .into_vertical_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("linearizable");
+        checker.assert_no_discovery("agreement");
+        assert!(checker
+            .discovery("value chosen after reconfiguration")
+            .is_some());
+    }
+
+    #[test]
+    fn only_local_reads_go_stale() {
+        let check = |read_strategy| {
+            PaxosModelConfig::new()
+                .client_count(2)
+                .server_count(3)
+                .read_strategy(read_strategy)
+                .into_model()
+                .checker()
+                .spawn_bfs()
+                .join()
+        };
+        check(ReadStrategy::Quorum).assert_no_discovery("reads fresh");
+        check(ReadStrategy::Lease).assert_no_discovery("reads fresh");
+        let local = check(ReadStrategy::Local);
+        assert!(local.discovery("reads fresh").is_some());
+        assert!(local.discovery("linearizable").is_some());
+    }
+
+    #[test]
+    fn timed_leases_hold_only_while_clocks_stay_within_the_skew() {
+        let check = |skew| {
+            PaxosModelConfig::new()
+                .client_count(1)
+                .server_count(3)
+                .read_strategy(ReadStrategy::Lease)
+                .read_leases(Some(skew))
+                .clock_skew(Some(1))
+                .into_model()
+                .checker()
+                .spawn_bfs()
+                .join()
+        };
+        let bounded = check(ClockSkew::Bounded);
+        bounded.assert_no_discovery("read leases honoured");
+        bounded.assert_no_discovery("linearizable");
+        let unbounded = check(ClockSkew::Unbounded);
+        assert!(unbounded.discovery("read leases honoured").is_some());
+    }
+
+    #[test]
+    fn local_reads_lose_session_guarantees_once_clients_switch_servers() {
+        // a client sticks to the server that last answered it, so only a retry sends its reads
+        // somewhere that may not have caught up
+        let check = |read_strategy| {
+            PaxosModelConfig::new()
+                .client_count(1)
+                .server_count(3)
+                .get_count(2)
+                .retrying_clients(true)
+                .read_strategy(read_strategy)
+                .into_model()
+                .checker()
+                .spawn_bfs()
+                .join()
+        };
+        let quorum = check(ReadStrategy::Quorum);
+        quorum.assert_no_discovery("read your writes");
+        quorum.assert_no_discovery("monotonic reads");
+        let local = check(ReadStrategy::Local);
+        assert!(local.discovery("read your writes").is_some());
+        assert!(local.discovery("monotonic reads").is_some());
+        local.assert_no_discovery("monotonic writes");
+    }
+
+    #[test]
+    fn abstract_values_shrink_the_state_space() {
+        let check = |abstract_values| {
+            PaxosModelConfig::new()
+                .client_count(2)
+                .server_count(3)
+                .network(NetworkMode::Unordered)
+                .abstract_values(abstract_values)
+                .max_rounds(Some(1))
+                .into_model()
+                .checker()
+                .spawn_bfs()
+                .join()
+        };
+        let concrete = check(None);
+        let symbolic = check(Some(1));
+        for checker in [&concrete, &symbolic] {
+            checker.assert_no_discovery("agreement");
+            checker.assert_no_discovery("validity");
+            checker.assert_no_discovery("linearizable");
+        }
+        assert!(symbolic.unique_state_count() < concrete.unique_state_count());
+    }
+
+    #[test]
+    fn workloads_read_after_writing_and_think_between_requests() {
+        let checker = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(3)
+            .put_count(1)
+            .get_count(0)
+            .network(NetworkMode::Unordered)
+            .value_domain(Some(vec!['X', 'Y']))
+            .pattern(Pattern::ReadAfterWrite)
+            .think_time(true)
+            .max_rounds(Some(1))
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("agreement");
+        checker.assert_no_discovery("validity");
+        checker.assert_no_discovery("linearizable");
+        // each client's only get follows its put, so something is read back
+        assert!(checker.discovery("value chosen").is_some());
+    }
+
+    #[test]
+    fn leadership_is_handed_off_without_losing_decisions() {
+        let checker = PaxosModelConfig::new()
+            .client_count(1)
+            .server_count(3)
+            .put_count(2)
+            .network(NetworkMode::Unordered)
+            .transfer_leadership(Some(1))
+            .max_rounds(Some(2))
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("one proposer per slot");
+        checker.assert_no_discovery("agreement");
+        checker.assert_no_discovery("linearizable");
+        // the get after both puts reads the second back, whoever leads by then
+        assert!(checker.discovery("leadership transferred").is_some());
+        assert!(checker.discovery("value chosen").is_some());
+    }
+
+    #[test]
+    fn rolling_upgrades_keep_batches_from_old_servers() {
+        // clients 0 and 3 both put to server 0, which may batch them; server 2 is old
+        let check = |negotiate| {
+            PaxosModelConfig::new()
+                .client_count(4)
+                .server_count(3)
+                .put_count(1)
+                .get_count(0)
+                .network(NetworkMode::Unordered)
+                .batch_size(Some(2))
+                .upgrade(Some(1))
+                .negotiate(negotiate)
+                .max_rounds(Some(1))
+                .into_model()
+                .checker()
+                .spawn_bfs()
+                .join()
+        };
+        let negotiated = check(true);
+        negotiated.assert_no_discovery("old servers see no batches");
+        negotiated.assert_no_discovery("agreement");
+        negotiated.assert_no_discovery("linearizable");
+        // without the handshake the newer servers batch regardless
+        assert!(check(false)
+            .discovery("old servers see no batches")
+            .is_some());
+    }
+
+    #[test]
+    fn lost_hellos_are_sent_again_until_welcomed() {
+        type Action = ActorModelAction<RegisterMsg<u64, char, PaxosMsg<char>>>;
+        let hello = |msg: &RegisterMsg<u64, char, PaxosMsg<char>>| {
+            matches!(msg, Internal(PaxosMsg::Hello(_)))
+        };
+        let (server_0, server_1) = (Id::from(0), Id::from(1));
+        // every server is on the newer build, and the network loses the first hello server 0
+        // sends server 1; after that messages are delivered before any server times out
+        let dropped = std::cell::Cell::new(false);
+        let path = crate::trace::ranked_path(
+            &PaxosModelConfig::new()
+                .client_count(1)
+                .server_count(3)
+                .put_count(1)
+                .network(NetworkMode::Lossy)
+                .upgrade(Some(0))
+                .into_model(),
+            |_: &PaxosModelState, action: &Action| match action {
+                ActorModelAction::Drop(env)
+                    if env.src == server_0 && env.dst == server_1 && hello(&env.msg) =>
+                {
+                    Some(0).filter(|_| !dropped.replace(true))
+                }
+                Deliver { .. } => Some(1),
+                ActorModelAction::Timeout(id) if usize::from(*id) < 3 => Some(2),
+                _ => None,
+            },
+            500,
+        );
+        assert!(dropped.get());
+        let resent = path.iter().any(|(_, action)| {
+            matches!(action, Some(Deliver { src, dst, msg })
+                if *src == server_0 && *dst == server_1 && hello(msg))
+        });
+        assert!(resent, "{}", crate::trace::to_flow(&path));
+        // each server knows what its peers speak, and never records its own
+        let (last, _) = path.last().unwrap();
+        for server in server_states(last) {
+            let peers: Vec<Id> = (0..3)
+                .map(Id::from)
+                .filter(|peer| *peer != server.id())
+                .collect();
+            assert_eq!(
+                server.features().keys().copied().collect::<Vec<_>>(),
+                peers,
+                "{:?}",
+                server
+            );
+        }
+    }
+
+    #[test]
+    fn replication_windows_hold_back_accepts_to_slow_followers() {
+        let checker = PaxosModelConfig::new()
+            .client_count(1)
+            .server_count(3)
+            .put_count(2)
+            .replication_window(Some(1))
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("replication windows bounded");
+        checker.assert_no_discovery("linearizable");
+        assert!(checker.discovery("replication window full").is_some());
+    }
+
+    #[test]
+    fn pre_vote_keeps_a_cut_off_proposer_from_starting_rounds() {
+        let check = |pre_vote| {
+            PaxosModelConfig::new()
+                .client_count(1)
+                .server_count(3)
+                .put_count(1)
+                .network(NetworkMode::Unordered)
+                .split(Some(1))
+                .max_rounds(Some(2))
+                .pre_vote(pre_vote)
+                .into_model()
+                .property(
+                    stateright::Expectation::Sometimes,
+                    "round started while cut off",
+                    |_, state| state.history.prepared_cut_off,
+                )
+                .checker()
+                .spawn_bfs()
+                .join()
+        };
+        // the client asks server 0, which the partition leaves on its own
+        let baseline = check(false);
+        assert!(baseline.discovery("round started while cut off").is_some());
+        let probed = check(true);
+        probed.assert_no_discovery("round started while cut off");
+        probed.assert_no_discovery("no rounds while cut off");
+        probed.assert_no_discovery("agreement");
+        probed.assert_no_discovery("linearizable");
+    }
+
+    #[test]
+    fn generalized_kv_reorders_only_commuting_commands() {
+        let checker = crate::kv::KvModelConfig::new()
+            .client_count(2)
+            .server_count(3)
+            .generalized(true)
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("linearizable");
+        checker.assert_no_discovery("conflicting commands ordered alike");
+        assert!(checker.discovery("commands reordered").is_some());
+    }
+
+    #[test]
+    fn multi_key_kv_workloads_stay_linearizable() {
+        let checker = crate::kv::KvModelConfig::new()
+            .client_count(2)
+            .server_count(2)
+            .multi_key(true)
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("linearizable");
+        checker.assert_no_discovery("replicas agree");
+        assert!(checker.discovery("value swapped").is_some());
+    }
+
+    #[test]
+    fn sharded_kv_groups_stay_linearizable_independently() {
+        let checker = crate::kv::KvModelConfig::new()
+            .client_count(2)
+            .server_count(2)
+            .shard_count(2)
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("linearizable");
+        checker.assert_no_discovery("replicas agree");
+        checker.assert_no_discovery("keys stay in their shard");
+        assert!(checker.discovery("every shard applied").is_some());
+    }
+
+    #[test]
+    fn coordinator_crash_never_splits_a_transfer() {
+        let checker = crate::txn::TxnModelConfig::new()
+            .server_count(2)
+            .coordinator_crashes(true)
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("atomic");
+        checker.assert_no_discovery("money conserved");
+        assert!(checker.discovery("committed").is_some());
+        assert!(
+            checker.discovery("aborted").is_some(),
+            "a restarted coordinator should be able to fence a shard that has not voted"
+        );
+    }
+
+    #[test]
+    fn disk_paxos_keeps_contending_puts_linearizable() {
+        let checker = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(2)
+            .put_count(1)
+            .max_rounds(Some(2))
+            .into_disk_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("linearizable");
+        checker.assert_no_discovery("agreement");
+        assert!(checker.discovery("value chosen").is_some());
+    }
+
+    #[test]
+    fn separated_roles_stay_linearizable() {
+        let checker = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(2)
+            .acceptor_count(3)
+            .learner_count(2)
+            .put_count(1)
+            .network(NetworkMode::Unordered)
+            .max_rounds(Some(2))
+            .into_roles_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("linearizable");
+        checker.assert_no_discovery("agreement");
+        assert!(checker.discovery("value chosen").is_some());
+    }
+
+    #[test]
+    fn paxos_and_disk_paxos_answer_clients_alike() {
+        use crate::differential::{missing_from, observations, Response};
+        let config = PaxosModelConfig::new()
+            .client_count(1)
+            .server_count(2)
+            .put_count(1)
+            .max_rounds(Some(2));
+        let paxos = observations(&config.clone().into_model(), 1);
+        let disk = observations(&config.into_disk_model(), 1);
+        assert!(missing_from(&paxos, &disk).is_empty());
+        assert!(missing_from(&disk, &paxos).is_empty());
+        assert!(paxos.contains_key(&vec![vec![Response::PutOk, Response::GetOk('A')]]));
+    }
+
+    #[test]
+    fn non_intersecting_flexible_quorums_disagree() {
+        let checker = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(3)
+            .quorum(QuorumSpec::Flexible {
+                phase1: 1,
+                phase2: 1,
+            })
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        assert!(checker.discovery("quorums intersect").is_some());
+        // two proposers each get slot 0 past a quorum that never hears from the other
+        let path = checker
+            .discovery("agreement")
+            .expect("disjoint quorums should decide two values");
+        assert!(delivered_accepts(path.into_actions(), 0).len() >= 2);
+    }
+
+    #[test]
+    fn any_promise_strategy_disagrees() {
+        let checker = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(3)
+            .strategy(StrategySpec::AnyPromise)
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        let path = checker
+            .discovery("agreement")
+            .expect("a single promise is not enough to adopt earlier values");
+        assert!(delivered_accepts(path.into_actions(), 0).len() >= 2);
+    }
+
+    #[test]
+    fn replayed_counterexample_passes_once_fixed() {
+        use crate::checking::replay;
+        use crate::trace::Scheduled;
+        let model = |strategy| {
+            PaxosModelConfig::new()
+                .client_count(2)
+                .server_count(3)
+                .strategy(strategy)
+                .into_model()
+        };
+        let path = model(StrategySpec::AnyPromise)
+            .checker()
+            .spawn_bfs()
+            .join()
+            .discovery("agreement")
+            .expect("a single promise is not enough to adopt earlier values");
+        // through the file format, as `check --replay` reads it
+        let schedule: Vec<_> = path.into_actions().iter().map(Scheduled::new).collect();
+        let json = serde_json::to_string(&schedule).unwrap();
+        let schedule: Vec<Scheduled<RegisterMsg<u64, char, PaxosMsg<char>>>> =
+            serde_json::from_str(&json).unwrap();
+        let schedule: Vec<_> = schedule.into_iter().map(Scheduled::into_action).collect();
+
+        let broken = replay(&model(StrategySpec::AnyPromise), &schedule);
+        assert_eq!(broken.stuck, None);
+        assert!(broken.violated.iter().any(|(name, _)| *name == "agreement"));
+        let fixed = replay(&model(StrategySpec::MajorityPromises), &schedule);
+        assert!(fixed.violated.iter().all(|(name, _)| *name != "agreement"));
+    }
+
+    fn network_mode() -> impl proptest::strategy::Strategy<Value = NetworkMode> {
+        use proptest::strategy::Just;
+        proptest::prop_oneof![
+            Just(NetworkMode::Unordered),
+            Just(NetworkMode::UnorderedDuplicating),
+            Just(NetworkMode::Lossy),
+        ]
+    }
+
+    // random walks through clusters too big to check exhaustively, shrunk to the shortest
+    // schedule that still breaks a property
+    proptest::proptest! {
+        #![proptest_config(proptest::test_runner::Config::with_cases(64))]
+
+        #[test]
+        fn random_schedules_keep_agreement_and_validity(
+            servers in 3..=5usize,
+            clients in 1..=3usize,
+            network in network_mode(),
+            choices in proptest::collection::vec(proptest::num::usize::ANY, 0..200),
+        ) {
+            let model = PaxosModelConfig::new()
+                .server_count(servers)
+                .client_count(clients)
+                .network(network)
+                .into_model();
+            let walked = crate::checking::walk(&model, &choices);
+            for (name, taken) in walked.violated {
+                proptest::prop_assert!(
+                    name != "agreement" && name != "validity",
+                    "{} broken after {} steps",
+                    name,
+                    taken
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn replicas_apply_each_slot_once_in_order_and_agree() {
+        let mut applied = 0;
+        for seed in 0..20 {
+            let observer = Arc::new(crate::observer::RecordingObserver::<char>::new());
+            let model = PaxosModelConfig::new()
+                .client_count(2)
+                .server_count(3)
+                .put_count(2)
+                .observer(observer.clone())
+                .into_model();
+            let mut rng = SplitMix(seed);
+            let choices: Vec<usize> = (0..300).map(|_| rng.next_u64() as usize).collect();
+            crate::checking::walk(&model, &choices);
+            assert!(
+                observer.in_order_once(),
+                "seed {} applied out of order",
+                seed
+            );
+            assert!(observer.converged(), "seed {} diverged", seed);
+            applied += observer.applied().len();
+        }
+        assert!(applied > 0, "some walk should get a put applied");
+    }
+
+    #[test]
+    fn superseded_rounds_are_forgotten() {
+        let checker = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(3)
+            .put_count(1)
+            .max_rounds(Some(2))
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("state bounded by active rounds");
+        checker.assert_no_discovery("agreement");
+        checker.assert_no_discovery("linearizable");
+    }
+
+    #[test]
+    fn dueling_proposers_livelock_within_bounded_rounds() {
+        let checker = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(3)
+            .put_count(1)
+            .max_rounds(Some(2))
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("agreement");
+        let path = checker
+            .discovery("no livelock")
+            .expect("two proposers should be able to retry until out of rounds");
+        // the shortest such run ends with both proposers in the last round, nothing decided
+        let last = path.into_states().pop().unwrap();
+        let proposers: BTreeSet<Id> = server_states(&last)
+            .filter(|server_state| {
+                server_state
+                    .proposals()
+                    .any(|(_, rid)| rid.round_num() >= 2)
+            })
+            .map(|server_state| server_state.id())
+            .collect();
+        assert_eq!(proposers.len(), 2);
+        assert!(server_states(&last).all(|server_state| server_state.log().is_empty()));
+    }
+
+    #[test]
+    fn suspecting_followers_take_over_without_breaking_agreement() {
+        let checker = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(3)
+            .put_count(1)
+            .suspicion(true)
+            .max_rounds(Some(3))
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("agreement");
+        checker.assert_no_discovery("linearizable");
+        assert!(
+            checker.discovery("leader replaced").is_some(),
+            "a follower suspecting its leader should be able to take over"
+        );
+    }
+
+    #[test]
+    fn yielding_proposers_can_always_still_decide() {
+        let model = PaxosModelConfig::new()
+            .client_count(2)
+            .server_count(3)
+            .put_count(1)
+            .network(NetworkMode::UnorderedDuplicating)
+            .backoff(true)
+            .max_rounds(Some(2))
+            .into_model();
+        let stuck = crate::checking::unreachable_from(&model, |state| {
+            server_states(state).any(|server_state| !server_state.log().is_empty())
+        });
+        if let Some(path) = stuck {
+            panic!("no decision reachable after {:?}", path.last());
+        }
+    }
+
+    #[test]
+    fn votes_hash_smaller_than_sets_of_ids() {
+        let roster: Vec<Id> = (0..5).map(Id::from).collect();
+        let mut votes = crate::quorum::Votes::default();
+        for id in &roster[1..4] {
+            assert!(votes.insert(&roster, *id));
+        }
+        assert!(!votes.insert(&roster, Id::from(7)));
+        let ids = votes.ids(&roster);
+        assert_eq!(ids, roster[1..4].iter().copied().collect());
+        let hashed = crate::checking::hashed_bytes;
+        assert!(hashed(&votes) < hashed(&ids));
+    }
+
+    #[test]
+    fn memory_report_counts_what_a_search_visits() {
+        let config = PaxosModelConfig::new().client_count(1).server_count(3);
+        let report = crate::checking::memory_report(&config.clone().into_model());
+        let checker = config.into_model().checker().spawn_bfs().join();
+        assert_eq!(report.unique, checker.unique_state_count());
+        assert!(report.generated >= report.unique);
+        assert_eq!(report.collisions, 0);
+        assert!(report.largest_bytes * report.unique >= report.total_bytes);
+    }
+
+    #[test]
+    fn isolated_replica_catches_up_after_rejoining() {
+        let scenario = crate::scenario::Scenario::load("isolated-replica").unwrap();
+        let checker = PaxosModelConfig::new()
+            .server_count(scenario.servers.unwrap())
+            .client_count(scenario.clients.unwrap())
+            .put_count(scenario.puts.unwrap())
+            .split(scenario.faults.split)
+            .max_rounds(scenario.max_rounds)
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("agreement");
+        checker.assert_no_discovery("caught up after rejoining");
+    }
+
+    #[test]
+    fn witness_keeps_agreement_with_a_data_replica_down() {
+        let checker = PaxosModelConfig::new()
+            .client_count(1)
+            .server_count(3)
+            .put_count(1)
+            .witness_count(1)
+            .crash_count(1)
+            .max_rounds(Some(2))
+            .into_model()
+            .checker()
+            .spawn_bfs()
+            .join();
+        checker.assert_no_discovery("agreement");
+        checker.assert_no_discovery("linearizable");
+        assert!(
+            checker.discovery("all servers learned").is_some(),
+            "both data replicas should be able to learn through the witness"
+        );
+    }
+
+    #[test]
+    fn adversaries_find_no_violation_in_paxos() {
+        use crate::sim::{self, AdversarySpec, SimConfig};
+        use std::time::Duration;
+        let actors = PaxosModelConfig::new()
+            .server_count(3)
+            .client_count(3)
+            .put_count(2)
+            .into_model()
+            .actors;
+        for adversary in [
+            AdversarySpec::SlowLeader(Duration::from_millis(50)),
+            AdversarySpec::LatePrepares(Duration::from_millis(20)),
+        ] {
+            for seed in 0..4 {
+                let cfg = SimConfig::new()
+                    .seed(seed)
+                    .steps(20_000)
+                    .adversary(adversary);
+                let report = sim::run(&actors, &cfg, &sim_invariants());
+                assert_eq!(report.violation, None, "{} with seed {}", adversary, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn corrupted_messages_are_caught_and_dropped() {
+        use crate::sim::{self, SimConfig};
+        let actors = PaxosModelConfig::new()
+            .server_count(3)
+            .client_count(2)
+            .put_count(2)
+            .retrying_clients(true)
+            .into_model()
+            .actors;
+        for seed in 0..4 {
+            let cfg = SimConfig::new().seed(seed).steps(20_000).corrupt_rate(0.1);
+            let report = sim::run(&actors, &cfg, &sim_invariants());
+            assert_eq!(report.violation, None, "seed {}", seed);
+            let (caught, undetected) = report.corrupted();
+            assert!(caught > 0, "seed {} corrupted nothing", seed);
+            // a CRC-32 catches every single flipped bit
+            assert_eq!(undetected, 0, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn region_quorums_decide_through_a_region_outage() {
+        let model = |quorum| {
+            PaxosModelConfig::new()
+                .server_count(5)
+                .client_count(1)
+                .put_count(1)
+                .network(NetworkMode::Unordered)
+                .topology("2,2,1".parse().unwrap())
+                .region_down(Some(1))
+                .quorum(quorum)
+                .max_rounds(Some(2))
+                .into_model()
+                .checker()
+                .spawn_bfs()
+                .join()
+        };
+        for quorum in [QuorumSpec::Regions, QuorumSpec::RegionVoters] {
+            let checker = model(quorum);
+            checker.assert_no_discovery("quorums intersect");
+            checker.assert_no_discovery("agreement");
+            checker.assert_no_discovery("linearizable");
+            assert!(checker.discovery("value chosen").is_some(), "{}", quorum);
+        }
+        // a majority of the other four needs one of the servers in the dark region
+        assert!(model(QuorumSpec::Majority)
+            .discovery("value chosen")
+            .is_none());
+    }
+
+    #[test]
+    fn shipped_scenarios_name_real_properties() {
+        for (name, scenario) in crate::scenario::shipped() {
+            let scenario = scenario.unwrap_or_else(|e| panic!("{}: {}", name, e));
+            let model = PaxosModelConfig::new()
+                .server_count(scenario.servers.unwrap_or(3))
+                .client_count(scenario.clients.unwrap_or(3))
+                .put_count(scenario.puts.unwrap_or(2))
+                .network(
+                    scenario
+                        .network
+                        .unwrap_or(NetworkMode::UnorderedDuplicating),
+                )
+                .crash_count(scenario.faults.crashes.unwrap_or(0))
+                .split(scenario.faults.split)
+                .topology(scenario.regions.clone().unwrap_or_default())
+                .region_down(scenario.faults.region_down)
+                .max_rounds(scenario.max_rounds)
+                .into_model();
+            for property in &scenario.properties {
+                assert!(
+                    model.properties().iter().any(|p| p.name == property),
+                    "{} checks unknown property {}",
+                    name,
+                    property
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn messages_summarize_on_one_line() {
+        let rid = RoundIdentifier::new(2, Id::from(1));
+        let accept: PaxosMsg<char> = PaxosMsg::Accept(7, Id::from(4), 0, rid, Command::Put('B'));
+        assert_eq!(accept.to_string(), "A(0,2.1,'B')");
+        let promise: PaxosMsg<char> = PaxosMsg::Promise(
+            7,
+            Id::from(4),
+            0,
+            rid,
+            Some((rid, Command::Batch(vec!['A']))),
+        );
+        assert_eq!(promise.to_string(), "Pm(0,2.1,2.1:['A'])");
+    }
+
+    #[test]
+    fn rounds_keep_their_order_across_an_epoch_rollover() {
+        let last = ballot::round(0, u32::MAX);
+        let rolled = ballot::next_round(last).unwrap();
+        assert_eq!((ballot::epoch(rolled), ballot::count(rolled)), (1, 0));
+        assert_eq!(ballot::next_round(rolled), Some(ballot::round(1, 1)));
+
+        // the lowest proposer in the new epoch outranks the highest in the old one
+        let before = RoundIdentifier::new(last, Id::from(2));
+        let after = RoundIdentifier::new(rolled, Id::from(0));
+        assert!(before < after);
+        assert_eq!(before.to_string(), format!("{}.2", u32::MAX));
+        assert_eq!(after.to_string(), "1/0.0");
+        let before = ScrambledBallot::new(last, Id::from(2));
+        let after = ScrambledBallot::new(rolled, Id::from(0));
+        assert!(before < after);
+
+        // a proposer out of epochs too has no round left to move to
+        assert_eq!(ballot::next_round(ballot::round(u32::MAX, u32::MAX)), None);
+        assert_eq!(ballot::next_round(u64::MAX), None);
+    }
+}
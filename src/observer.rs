@@ -0,0 +1,113 @@
+use crate::paxos::{Command, Slot};
+use stateright::actor::Id;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+// Told about every decided entry a replica applies to its register, in the order it applies
+// them, so tests can look at what replicas did without reaching into their state. A replica
+// restarted after a crash applies its log again from its last snapshot, and one installing a
+// snapshot sent by a peer skips the slots it covers without announcing them.
+pub trait ApplyObserver<V>: Send + Sync {
+    fn on_apply(&self, id: Id, slot: Slot, command: &Command<V>);
+}
+
+pub struct NoObserver;
+
+impl<V> ApplyObserver<V> for NoObserver {
+    fn on_apply(&self, _: Id, _: Slot, _: &Command<V>) {}
+}
+
+// Remembers every apply, by any replica, for a test to check afterwards. Under the model
+// checker the actors are shared by every path the search takes, so record a single path, as
+// `checking::walk` and `checking::replay` take.
+pub struct RecordingObserver<V> {
+    applied: Mutex<Vec<(Id, Slot, Command<V>)>>,
+}
+
+impl<V: Clone> RecordingObserver<V> {
+    pub fn new() -> Self {
+        RecordingObserver {
+            applied: Mutex::new(Vec::new()),
+        }
+    }
+
+    // every apply so far, in the order they happened
+    pub fn applied(&self) -> Vec<(Id, Slot, Command<V>)> {
+        self.applied.lock().unwrap().clone()
+    }
+
+    // the slots `id` applied, in the order it applied them
+    pub fn slots(&self, id: Id) -> Vec<Slot> {
+        self.applied
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(applier, _, _)| *applier == id)
+            .map(|(_, slot, _)| *slot)
+            .collect()
+    }
+
+    // whether every replica applied each slot once at most, in increasing slot order
+    pub fn in_order_once(&self) -> bool {
+        let mut last: BTreeMap<Id, Slot> = BTreeMap::new();
+        self.applied
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|(id, slot, _)| match last.insert(*id, *slot) {
+                Some(previous) => previous < *slot,
+                None => true,
+            })
+    }
+}
+
+impl<V: Clone + PartialEq> RecordingObserver<V> {
+    // whether replicas that applied the same slot applied the same command there
+    pub fn converged(&self) -> bool {
+        let applied = self.applied.lock().unwrap();
+        let mut chosen: BTreeMap<Slot, &Command<V>> = BTreeMap::new();
+        applied
+            .iter()
+            .all(|(_, slot, command)| *chosen.entry(*slot).or_insert(command) == command)
+    }
+}
+
+impl<V: Clone> Default for RecordingObserver<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: Clone + Send> ApplyObserver<V> for RecordingObserver<V> {
+    fn on_apply(&self, id: Id, slot: Slot, command: &Command<V>) {
+        self.applied
+            .lock()
+            .unwrap()
+            .push((id, slot, command.clone()));
+    }
+}
+
+// Writes a line per apply to a file, the slot then the command, so the files of replicas
+// spawned outside the model can be diffed to see where they part ways.
+pub struct FileObserver {
+    file: Mutex<LineWriter<File>>,
+}
+
+impl FileObserver {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(FileObserver {
+            file: Mutex::new(LineWriter::new(File::create(path)?)),
+        })
+    }
+}
+
+impl<V: Debug> ApplyObserver<V> for FileObserver {
+    fn on_apply(&self, _: Id, slot: Slot, command: &Command<V>) {
+        // losing a line is better than stopping the replica
+        let _ = writeln!(self.file.lock().unwrap(), "{} {:?}", slot, command);
+    }
+}
@@ -0,0 +1,3533 @@
+use crate::ballot;
+pub use crate::ballot::{Ballot, RoundIdentifier};
+use crate::clock::Clock;
+use crate::detector::{DetectorSpec, FailureDetector};
+use crate::features::Features;
+use crate::metrics::{Counter, Metrics, NoMetrics};
+use crate::observer::{ApplyObserver, NoObserver};
+use crate::quorum::{Majority, QuorumSystem, Votes};
+use crate::rng::{Rng, SplitMix};
+use crate::storage::{FileStorage, MemoryStorage, Storage, WalRecord};
+use crate::strategy::{ForwardStrategy, MajorityPromises};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use stateright::actor::{register::*, *};
+use stateright::{Rewrite, RewritePlan};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub type Slot = u64;
+
+// anything that can be replicated through the log
+pub trait Value: Clone + Debug + Default + Eq + Hash + Serialize + DeserializeOwned {}
+
+impl<T> Value for T where T: Clone + Debug + Default + Eq + Hash + Serialize + DeserializeOwned {}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct PaxosState<V, B = RoundIdentifier> {
+    id: Id,
+    // the acceptors this actor started with, later configurations are decided in the log
+    peers: Vec<Id>,
+    // the working copy of what this actor has made durable
+    durable: DurableState<V, B>,
+    // what a crash falls back on, only what went through the storage interface
+    storage: MemoryStorage<V, B>,
+    // the register rebuilt from the gap-free prefix of the log
+    applied: Applied<V>,
+    // proposals this actor is driving, keyed by round; a leader reuses its round
+    // across slots so the slot is part of the key
+    proposers: BTreeMap<(Slot, B), ProposerContext<V, B>>,
+    // quorum reads this actor is serving, keyed by the client's request id
+    reads: BTreeMap<u64, PendingRead<V, B>>,
+    // client requests already taken on, so redelivered requests are not served twice
+    handled: BTreeSet<u64>,
+    // the read lease this actor asked for as leader, until it runs out
+    read_lease: Option<ReadLease<B>>,
+    // the leader this actor promised to stay away from until its read lease runs out
+    read_grant: Option<B>,
+    // when that promise runs out on our clock, under timed leases
+    grant_until: Option<Duration>,
+    // the ticks of our logical clock so far, model only
+    ticks: u64,
+    // slots each follower was sent accepts for under our lease and has not acknowledged, only
+    // kept under a replication window
+    unacked: BTreeMap<Id, BTreeSet<Slot>>,
+    // whether this actor is cut off from the other side of a partition right now
+    split: bool,
+    // the leader this actor has given up on, proposing itself rather than forwarding to it
+    suspected: Option<B>,
+    // the replica this actor, as leader, is handing leadership to
+    handoff: Option<Handoff<V>>,
+    // what each peer advertised it speaks, see `Features`
+    features: BTreeMap<Id, Features>,
+    // the first slot past every decision a peer has told us of, learned or not
+    reported: Slot,
+}
+
+// what an acceptor must write to stable storage before answering
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct DurableState<V, B = RoundIdentifier> {
+    // reusing a round after a restart could pair it with a different value
+    round: u64,
+    last_seen: BTreeMap<Slot, B>,
+    // highest (round, command) this actor has accepted as an acceptor, per slot
+    accepted: BTreeMap<Slot, (B, Command<V>)>,
+    // what a witness keeps instead, the highest round it accepted in per slot
+    witnessed: BTreeMap<Slot, B>,
+    // decided commands, in slot order, from where the last snapshot ends
+    log: BTreeMap<Slot, Command<V>>,
+    // stands in for every slot of the log before it
+    snapshot: Option<Applied<V>>,
+    // the distinguished proposer this actor follows (possibly itself), a promise like any other
+    lease: Option<Lease<B>>,
+}
+
+impl<V: Value, B: Ballot> DurableState<V, B> {
+    pub fn new() -> Self {
+        DurableState {
+            round: 0,
+            last_seen: BTreeMap::new(),
+            accepted: BTreeMap::new(),
+            witnessed: BTreeMap::new(),
+            log: BTreeMap::new(),
+            snapshot: None,
+            lease: None,
+        }
+    }
+
+    pub fn promise(&mut self, slot: Slot, rid: B) {
+        self.last_seen.insert(slot, rid);
+    }
+
+    pub fn accept(&mut self, slot: Slot, rid: B, command: Command<V>) {
+        self.last_seen.insert(slot, rid);
+        self.accepted.insert(slot, (rid, command));
+    }
+
+    pub fn witness(&mut self, slot: Slot, rid: B) {
+        self.last_seen.insert(slot, rid);
+        self.witnessed.insert(slot, rid);
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    // the highest round promised or accepted in `slot`
+    pub fn last_seen(&self, slot: Slot) -> Option<B> {
+        self.last_seen.get(&slot).copied()
+    }
+
+    pub fn accepted(&self, slot: Slot) -> Option<&(B, Command<V>)> {
+        self.accepted.get(&slot)
+    }
+
+    pub fn set_round(&mut self, round: u64) {
+        self.round = round;
+    }
+
+    pub fn set_lease(&mut self, lease: Option<Lease<B>>) {
+        self.lease = lease;
+    }
+}
+
+impl<V: Value, B: Ballot> Default for DurableState<V, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// a client's request, numbered in the order the client sends them
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct Session {
+    client: Id,
+    seq: u64,
+}
+
+impl Session {
+    pub fn new(client: Id, seq: u64) -> Self {
+        Session { client, seq }
+    }
+
+    pub fn client(&self) -> Id {
+        self.client
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+}
+
+// what a slot of the log holds
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub enum Command<V> {
+    Put(V),
+    // a put applied once however many slots it is decided in, never batched
+    SessionPut(Session, V),
+    // puts from several clients decided together, applied in order
+    Batch(Vec<V>),
+    // every member of the new configuration, in effect `alpha` slots after it is decided
+    Reconfigure(Vec<Id>),
+}
+
+impl<V> Command<V> {
+    pub fn value(&self) -> Option<&V> {
+        match self {
+            Command::Put(value) | Command::SessionPut(_, value) => Some(value),
+            Command::Batch(values) => values.last(),
+            Command::Reconfigure(_) => None,
+        }
+    }
+
+    // every value the command writes, in the order it applies them
+    pub fn values(&self) -> &[V] {
+        match self {
+            Command::Put(value) | Command::SessionPut(_, value) => std::slice::from_ref(value),
+            Command::Batch(values) => values,
+            Command::Reconfigure(_) => &[],
+        }
+    }
+}
+
+// the state machine the log drives, a register plus the membership; a snapshot is just a
+// copy of it, standing in for every slot before `next`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct Applied<V> {
+    // first slot not applied yet
+    next: Slot,
+    value: V,
+    // the latest reconfiguration, with the slot it was decided in
+    members: Option<(Slot, Vec<Id>)>,
+    // the last request applied for each client session, replicated with the register so that
+    // every replica recognises a duplicate
+    sessions: BTreeMap<Id, u64>,
+}
+
+impl<V: Value> Applied<V> {
+    pub fn new() -> Self {
+        Applied {
+            next: 0,
+            value: V::default(),
+            members: None,
+            sessions: BTreeMap::new(),
+        }
+    }
+
+    pub fn next(&self) -> Slot {
+        self.next
+    }
+
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+
+    pub fn members(&self) -> Option<&(Slot, Vec<Id>)> {
+        self.members.as_ref()
+    }
+
+    pub fn sessions(&self) -> &BTreeMap<Id, u64> {
+        &self.sessions
+    }
+
+    // a snapshot as another replica sent it, which it built by applying the same log
+    pub fn from_parts(
+        next: Slot,
+        value: V,
+        members: Option<(Slot, Vec<Id>)>,
+        sessions: BTreeMap<Id, u64>,
+    ) -> Self {
+        Applied {
+            next,
+            value,
+            members,
+            sessions,
+        }
+    }
+
+    // whether a session's request has been applied already, which leaves nothing to do but answer
+    pub fn has_applied(&self, session: &Session) -> bool {
+        self.sessions
+            .get(&session.client)
+            .map_or(false, |seq| *seq >= session.seq)
+    }
+
+    pub(crate) fn apply(&mut self, command: &Command<V>) {
+        match command {
+            Command::Put(value) => self.value = value.clone(),
+            Command::SessionPut(session, value) => {
+                if !self.has_applied(session) {
+                    self.value = value.clone();
+                    self.sessions.insert(session.client, session.seq);
+                }
+            }
+            Command::Batch(values) => {
+                if let Some(value) = values.last() {
+                    self.value = value.clone();
+                }
+            }
+            Command::Reconfigure(ids) => self.members = Some((self.next, ids.clone())),
+        }
+        self.next += 1;
+    }
+
+    // the state reached by replaying a full log up to `next`, if it has no gaps before that
+    pub fn replay(log: &BTreeMap<Slot, Command<V>>, next: Slot) -> Option<Self> {
+        let mut applied = Self::new();
+        while applied.next < next {
+            applied.apply(log.get(&applied.next)?);
+        }
+        Some(applied)
+    }
+}
+
+impl<V: Value> Default for Applied<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// a leader's claim on every slot from `slot` onwards under a single round
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct Lease<B = RoundIdentifier> {
+    ballot: B,
+    slot: Slot,
+}
+
+// a leader serves reads on its own while a quorum has promised not to help anyone else
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct ReadLease<B = RoundIdentifier> {
+    ballot: B,
+    grants: BTreeSet<Id>,
+    // every slot a grantor had accepted anything in, the leader has to apply these first
+    floor: Slot,
+    // when the lease runs out on the leader's clock, under timed leases
+    expires: Option<Duration>,
+}
+
+impl<B: Ballot> ReadLease<B> {
+    pub fn ballot(&self) -> B {
+        self.ballot
+    }
+
+    pub fn grants(&self) -> &BTreeSet<Id> {
+        &self.grants
+    }
+}
+
+// A leader's handover to another replica. Until its own proposals are settled the leader holds
+// back new requests, then sends them to the target with its round, after which every request
+// goes straight to the target, which proposes them with a higher round.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct Handoff<V> {
+    target: Id,
+    // told once the target has been handed over to
+    requester: Id,
+    held: Vec<(u64, Id, Command<V>)>,
+    sent: bool,
+}
+
+impl<V> Handoff<V> {
+    pub fn target(&self) -> Id {
+        self.target
+    }
+
+    pub fn sent(&self) -> bool {
+        self.sent
+    }
+}
+
+// how far apart the replicas' clocks may drift while a read lease is out
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClockSkew {
+    // within what the lease duration allows for, so a grant always outlives the lease
+    Bounded,
+    // beyond it, a grantor may let go while the leader still serves reads
+    Unbounded,
+}
+
+impl FromStr for ClockSkew {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bounded" => Ok(ClockSkew::Bounded),
+            "unbounded" => Ok(ClockSkew::Unbounded),
+            _ => Err(format!(
+                "unknown clock skew {}, expected bounded or unbounded",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ClockSkew {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClockSkew::Bounded => write!(f, "bounded"),
+            ClockSkew::Unbounded => write!(f, "unbounded"),
+        }
+    }
+}
+
+// how a server answers a client's read
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReadStrategy {
+    // asks a phase 1 quorum for the latest slot they know of
+    Quorum,
+    // answers locally while it holds a read lease, otherwise asks a quorum
+    Lease,
+    // answers from its own applied register straight away, however far behind it is
+    Local,
+}
+
+impl FromStr for ReadStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quorum" => Ok(ReadStrategy::Quorum),
+            "lease" => Ok(ReadStrategy::Lease),
+            "local" => Ok(ReadStrategy::Local),
+            _ => Err(format!(
+                "unknown read strategy {}, expected quorum, lease or local",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ReadStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadStrategy::Quorum => write!(f, "quorum"),
+            ReadStrategy::Lease => write!(f, "lease"),
+            ReadStrategy::Local => write!(f, "local"),
+        }
+    }
+}
+
+// what a replica keeps: a witness votes in both phases like any acceptor but keeps only the
+// ballots it voted for, never values, so it neither proposes, learns nor serves reads
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Role {
+    Data,
+    Witness,
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "data" => Ok(Role::Data),
+            "witness" => Ok(Role::Witness),
+            _ => Err(format!("unknown role {}, expected data or witness", s)),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Role::Data => write!(f, "data"),
+            Role::Witness => write!(f, "witness"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub enum Phase {
+    // waiting for the slots that decide this slot's configuration
+    Waiting,
+    // waiting to hear whether a quorum would promise the round, before starting it
+    Probe,
+    // waiting on promises
+    Prepare,
+    // waiting on accepts
+    Accept,
+}
+
+// a client request this actor is currently proposing on behalf of
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct ProposerContext<V, B = RoundIdentifier> {
+    request_id: u64,
+    client: Id,
+    value: Command<V>,
+    phase: Phase,
+    promises: Votes,
+    // highest (round, command) reported by the promises, which must win over our own
+    adopted: Option<(B, Command<V>)>,
+    // highest round a witness among the promises accepted in, whose value only data replicas know
+    witnessed: Option<B>,
+    accepts: Votes,
+    // acceptors that have since promised a higher round, whose votes no longer count
+    expired: Votes,
+    // requests batched into `value` after the first, in the order of their values
+    riders: Vec<(u64, Id)>,
+}
+
+impl<V: Value, B: Ballot> ProposerContext<V, B> {
+    fn new(request_id: u64, client: Id, value: Command<V>) -> Self {
+        ProposerContext {
+            request_id,
+            client,
+            value,
+            phase: Phase::Prepare,
+            promises: Votes::default(),
+            adopted: None,
+            witnessed: None,
+            accepts: Votes::default(),
+            expired: Votes::default(),
+            riders: Vec::new(),
+        }
+    }
+
+    fn batch_len(&self) -> usize {
+        match &self.value {
+            Command::Put(_) => 1,
+            Command::Batch(values) => values.len(),
+            // neither takes riders
+            Command::SessionPut(..) | Command::Reconfigure(_) => usize::MAX,
+        }
+    }
+
+    // adds another client's put to the value this context proposes
+    fn join(&mut self, request_id: u64, client: Id, value: V) {
+        self.value = match &self.value {
+            Command::Put(first) => Command::Batch(vec![first.clone(), value]),
+            Command::Batch(values) => {
+                let mut values = values.clone();
+                values.push(value);
+                Command::Batch(values)
+            }
+            Command::SessionPut(..) | Command::Reconfigure(_) => return,
+        };
+        self.riders.push((request_id, client));
+    }
+
+    // every client request behind this context, with its own command
+    fn requests(&self) -> Vec<(u64, Id, Command<V>)> {
+        let clients = std::iter::once((self.request_id, self.client)).chain(self.riders.clone());
+        match &self.value {
+            Command::Batch(values) => clients
+                .zip(values.iter().cloned().map(Command::Put))
+                .map(|((request_id, client), command)| (request_id, client, command))
+                .collect(),
+            command => vec![(self.request_id, self.client, command.clone())],
+        }
+    }
+
+    // forget everything learned under the previous round
+    fn restart(&mut self, phase: Phase) {
+        self.phase = phase;
+        self.promises.clear();
+        self.adopted = None;
+        self.witnessed = None;
+        self.accepts.clear();
+        self.expired.clear();
+    }
+
+    fn witness(&mut self, rid: B) {
+        if self.witnessed.map_or(true, |witnessed| rid > witnessed) {
+            self.witnessed = Some(rid);
+        }
+    }
+
+    // A witness that accepted in a round cannot say what it accepted, so the value of that round
+    // has to come from a data replica. Either a promise already carried it or something later,
+    // or every data replica has promised, and then whoever accepted alongside the witness is
+    // among them.
+    fn accounts_for_witnesses(&self, roster: &[Id], data: &BTreeSet<Id>) -> bool {
+        let witnessed = match self.witnessed {
+            Some(witnessed) => witnessed,
+            None => return true,
+        };
+        match &self.adopted {
+            Some((adopted, _)) if *adopted >= witnessed => true,
+            _ => self.promises.ids(roster).is_superset(data),
+        }
+    }
+
+    fn adopt(&mut self, rid: B, value: Command<V>) {
+        let higher = match self.adopted {
+            Some((seen_rid, _)) => rid > seen_rid,
+            None => true,
+        };
+        if higher {
+            self.adopted = Some((rid, value));
+        }
+    }
+
+    // the value phase 2 has to carry
+    fn proposed_value(&self) -> Command<V> {
+        match &self.adopted {
+            Some((_, value)) => value.clone(),
+            None => self.value.clone(),
+        }
+    }
+}
+
+// a client read waiting on a majority of read replies
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct PendingRead<V, B = RoundIdentifier> {
+    client: Id,
+    replies: BTreeSet<Id>,
+    // the latest slot reported so far, and what is known about it
+    slot: Slot,
+    decided: Option<V>,
+    highest_accepted: Option<(B, V)>,
+}
+
+impl<V: Value, B: Ballot> PendingRead<V, B> {
+    fn observe(&mut self, slot: Slot, decided: Option<V>, accepted: Option<(B, V)>) {
+        if slot < self.slot {
+            return;
+        }
+        if slot > self.slot {
+            self.slot = slot;
+            self.decided = None;
+            self.highest_accepted = None;
+        }
+        if decided.is_some() {
+            self.decided = decided;
+        }
+        if let Some((rid, value)) = accepted {
+            let higher = match self.highest_accepted {
+                Some((seen_rid, _)) => rid > seen_rid,
+                None => true,
+            };
+            if higher {
+                self.highest_accepted = Some((rid, value));
+            }
+        }
+    }
+
+    // a decided value always wins, otherwise the highest accepted value in the quorum
+    fn value(&self) -> V {
+        match (&self.decided, &self.highest_accepted) {
+            (Some(value), _) => value.clone(),
+            (None, Some((_, value))) => value.clone(),
+            (None, None) => V::default(),
+        }
+    }
+}
+
+impl<B: Ballot> Lease<B> {
+    pub fn ballot(&self) -> B {
+        self.ballot
+    }
+
+    pub fn slot(&self) -> Slot {
+        self.slot
+    }
+}
+
+impl<V: Value, B: Ballot> PaxosState<V, B> {
+    // A freshly (re)started actor, holding only what was persisted. A lease of our own does not
+    // survive: what we proposed under it went with the crash, and proposing in the same slot
+    // under its ballot again could give that ballot a second value. Our next proposal runs
+    // phase 1 instead, in a round past the persisted counter.
+    pub fn recover(id: Id, peers: Vec<Id>, mut durable: DurableState<V, B>) -> Self {
+        if durable.lease.map_or(false, |lease| lease.ballot.id() == id) {
+            durable.lease = None;
+        }
+        PaxosState {
+            id,
+            peers,
+            storage: MemoryStorage::new(durable.clone()),
+            applied: durable.snapshot.clone().unwrap_or_default(),
+            durable,
+            proposers: BTreeMap::new(),
+            reads: BTreeMap::new(),
+            handled: BTreeSet::new(),
+            read_lease: None,
+            read_grant: None,
+            grant_until: None,
+            ticks: 0,
+            unacked: BTreeMap::new(),
+            split: false,
+            suspected: None,
+            handoff: None,
+            features: BTreeMap::new(),
+            reported: 0,
+        }
+    }
+
+    pub fn durable(&self) -> &DurableState<V, B> {
+        &self.durable
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    pub fn log(&self) -> &BTreeMap<Slot, Command<V>> {
+        &self.durable.log
+    }
+
+    pub fn lease(&self) -> Option<Lease<B>> {
+        self.durable.lease
+    }
+
+    pub fn applied(&self) -> &Applied<V> {
+        &self.applied
+    }
+
+    pub fn snapshot(&self) -> Option<&Applied<V>> {
+        self.durable.snapshot.as_ref()
+    }
+
+    // the slot and round of every proposal this replica has in flight
+    pub fn proposals(&self) -> impl Iterator<Item = (Slot, B)> + '_ {
+        self.proposers.keys().copied()
+    }
+
+    // Whether everything kept per slot and round is still in play: proposals and acceptor state
+    // only for slots not known to be decided, and a single round for each request being
+    // proposed, so the state grows with the rounds in progress rather than with the log.
+    pub fn holds_only_active_rounds(&self) -> bool {
+        let requests: BTreeSet<u64> = self.proposers.values().map(|ctx| ctx.request_id).collect();
+        requests.len() == self.proposers.len()
+            && self
+                .proposers
+                .keys()
+                .map(|(slot, _)| slot)
+                .chain(self.durable.last_seen.keys())
+                .chain(self.durable.accepted.keys())
+                .all(|slot| !self.is_decided(*slot))
+    }
+
+    pub fn read_lease(&self) -> Option<&ReadLease<B>> {
+        self.read_lease.as_ref()
+    }
+
+    pub fn read_grant(&self) -> Option<B> {
+        self.read_grant
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn unacked(&self) -> &BTreeMap<Id, BTreeSet<Slot>> {
+        &self.unacked
+    }
+
+    pub fn split(&self) -> bool {
+        self.split
+    }
+
+    pub fn suspected(&self) -> Option<B> {
+        self.suspected
+    }
+
+    pub fn handoff(&self) -> Option<&Handoff<V>> {
+        self.handoff.as_ref()
+    }
+
+    pub fn features(&self) -> &BTreeMap<Id, Features> {
+        &self.features
+    }
+
+    pub fn reported(&self) -> Slot {
+        self.reported
+    }
+
+    // a granted read lease keeps every other proposer out, whatever its round
+    fn granted_elsewhere(&self, rid: B) -> bool {
+        match self.read_grant {
+            Some(grant) => grant.id() != rid.id(),
+            None => false,
+        }
+    }
+
+    // every slot this actor has accepted or learned anything in
+    fn accepted_below(&self) -> Slot {
+        let accepted = self.durable.accepted.keys().next_back();
+        let decided = self.durable.log.keys().next_back();
+        accepted.max(decided).map_or(0, |slot| slot + 1)
+    }
+
+    // first slot still held in the log, everything before it is in the snapshot
+    fn compacted(&self) -> Slot {
+        self.durable
+            .snapshot
+            .as_ref()
+            .map_or(0, |snapshot| snapshot.next)
+    }
+
+    fn is_decided(&self, slot: Slot) -> bool {
+        slot < self.compacted() || self.durable.log.contains_key(&slot)
+    }
+
+    // applies whatever the log holds right after the applied prefix, telling `observer`
+    fn advance_applied(&mut self, observer: &dyn ApplyObserver<V>) {
+        while let Some(command) = self.durable.log.get(&self.applied.next) {
+            observer.on_apply(self.id, self.applied.next, command);
+            self.applied.apply(command);
+        }
+    }
+
+    // drops every slot the given snapshot covers, acceptor state included since prepares and
+    // accepts for those slots are answered with the snapshot from now on
+    fn compact(&mut self, snapshot: Applied<V>) {
+        let next = snapshot.next;
+        self.durable.log = self.durable.log.split_off(&next);
+        self.durable.accepted = self.durable.accepted.split_off(&next);
+        self.durable.last_seen = self.durable.last_seen.split_off(&next);
+        self.durable.snapshot = Some(snapshot);
+    }
+
+    // the decisions a replica that has applied up to `from` may be missing, in slot order
+    fn decided_from(&self, from: Slot) -> Vec<(Slot, Command<V>)> {
+        self.durable
+            .log
+            .range(from..)
+            .take(CATCH_UP_BATCH)
+            .map(|(slot, command)| (*slot, command.clone()))
+            .collect()
+    }
+
+    // keys of our proposals matching a predicate, collected so they can be removed
+    fn proposers_where(
+        &self,
+        f: impl Fn(&(Slot, B), &ProposerContext<V, B>) -> bool,
+    ) -> Vec<(Slot, B)> {
+        self.proposers
+            .iter()
+            .filter(|(key, ctx)| f(key, ctx))
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    // skip our round counter past a round observed elsewhere
+    fn observe_round(&mut self, rid: B) {
+        if self.durable.round < rid.round_num() {
+            self.durable.round = rid.round_num();
+        }
+    }
+
+    // none once every epoch's rounds have been used
+    fn next_round(&mut self) -> Option<B> {
+        self.durable.round = ballot::next_round(self.durable.round)?;
+        Some(B::new(self.durable.round, self.id))
+    }
+
+    fn is_leader(&self) -> bool {
+        match self.durable.lease {
+            Some(lease) => lease.ballot.id() == self.id,
+            None => false,
+        }
+    }
+
+    // whether an acceptor may accept `rid` in `slot` without a matching prepare
+    fn leader_covers(&self, slot: Slot, rid: B) -> bool {
+        let lease = match self.durable.lease {
+            Some(lease) => lease,
+            None => return false,
+        };
+        let unclaimed = match self.durable.last_seen.get(&slot) {
+            Some(seen) => *seen <= rid,
+            None => true,
+        };
+        let untouched = match self.durable.accepted.get(&slot) {
+            Some((accepted_rid, _)) => *accepted_rid == rid,
+            None => true,
+        } && match self.durable.witnessed.get(&slot) {
+            Some(witnessed) => *witnessed == rid,
+            None => true,
+        };
+        lease.ballot == rid && slot > lease.slot && unclaimed && untouched
+    }
+
+    // rounds below the leader's are rejected for every slot the leader has claimed
+    fn below_lease(&self, slot: Slot, rid: B) -> bool {
+        match self.durable.lease {
+            Some(lease) => slot >= lease.slot && rid < lease.ballot,
+            None => false,
+        }
+    }
+
+    // first slot that is neither decided nor already being proposed by us
+    fn next_slot(&self) -> Slot {
+        let mut slot = self.compacted();
+        while self.durable.log.contains_key(&slot) || self.proposers.keys().any(|(s, _)| *s == slot)
+        {
+            slot += 1;
+        }
+        slot
+    }
+
+    // the acceptors other than us for a slot, after every reconfiguration at least `alpha`
+    // slots before it
+    fn peers_for(&self, slot: Slot, alpha: Slot) -> Vec<Id> {
+        let mut peers = self.peers.clone();
+        let compacted = self
+            .durable
+            .snapshot
+            .as_ref()
+            .and_then(|snapshot| snapshot.members.as_ref());
+        if let Some((decided, ids)) = compacted {
+            if decided + alpha <= slot {
+                peers = ids.iter().copied().filter(|id| *id != self.id).collect();
+            }
+        }
+        for (decided, command) in &self.durable.log {
+            if decided + alpha > slot {
+                break;
+            }
+            if let Command::Reconfigure(ids) = command {
+                peers = ids.iter().copied().filter(|id| *id != self.id).collect();
+            }
+        }
+        peers
+    }
+
+    // every actor that was ever a member, so decisions reach joining and leaving nodes alike
+    fn everyone(&self) -> Vec<Id> {
+        let mut everyone: BTreeSet<Id> = self.peers.iter().copied().collect();
+        if let Some((_, ids)) = self.applied.members.as_ref() {
+            everyone.extend(ids.iter().copied());
+        }
+        for command in self.durable.log.values() {
+            if let Command::Reconfigure(ids) = command {
+                everyone.extend(ids.iter().copied());
+            }
+        }
+        everyone.remove(&self.id);
+        everyone.into_iter().collect()
+    }
+
+    // a slot's configuration is only known once every slot `alpha` or more before it is decided
+    fn window_open(&self, slot: Slot, alpha: Slot) -> bool {
+        match slot.checked_sub(alpha) {
+            Some(last) => (self.compacted()..=last).all(|s| self.durable.log.contains_key(&s)),
+            None => true,
+        }
+    }
+
+    // at most `depth` slots past the applied prefix may be in flight at once
+    fn in_pipeline(&self, slot: Slot, depth: Option<Slot>) -> bool {
+        match depth {
+            Some(depth) => slot < self.applied.next + depth,
+            None => true,
+        }
+    }
+
+    // the latest slot holding a put this actor knows anything about, with its decided and
+    // accepted values
+    fn latest_slot(&self) -> (Slot, Option<V>, Option<(B, V)>) {
+        let decided = self
+            .durable
+            .log
+            .iter()
+            .rev()
+            .find(|(_, command)| command.value().is_some())
+            .map(|(slot, _)| *slot);
+        let accepted = self
+            .durable
+            .accepted
+            .iter()
+            .rev()
+            .find(|(_, (_, command))| command.value().is_some())
+            .map(|(slot, _)| *slot);
+        let slot = match (decided, accepted) {
+            (Some(d), Some(a)) => d.max(a),
+            (Some(d), None) => d,
+            (None, Some(a)) => a,
+            // nothing left in the log, so the snapshot holds the latest value
+            (None, None) => {
+                return match &self.durable.snapshot {
+                    Some(snapshot) if snapshot.next > 0 => {
+                        (snapshot.next - 1, Some(snapshot.value.clone()), None)
+                    }
+                    _ => (0, None, None),
+                }
+            }
+        };
+        (
+            slot,
+            self.durable
+                .log
+                .get(&slot)
+                .and_then(|command| command.value().cloned()),
+            self.durable
+                .accepted
+                .get(&slot)
+                .and_then(|(rid, command)| Some((*rid, command.value()?.clone()))),
+        )
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum PaxosMsg<V, B = RoundIdentifier> {
+    Prepare(u64, Id, Slot, B),
+    Promise(u64, Id, Slot, B, Option<(B, Command<V>)>),
+    // a witness's promise, with the round it last accepted in but not what it accepted
+    WitnessPromise(u64, Id, Slot, B, Option<B>),
+    Accept(u64, Id, Slot, B, Command<V>),
+    Accepted(u64, Id, Slot, B, Command<V>),
+    Nack(u64, Slot, B),
+    // tells the proposer of the first round that the sender has promised the second in the slot,
+    // so will no longer accept in the first
+    Expire(Slot, B, B),
+    Decided(Slot, B, Command<V>),
+    Heartbeat(Slot, B),
+    Forward(u64, Id, Command<V>),
+    // proposes a new set of members, decided like any other command
+    Reconfigure(u64, Vec<Id>),
+    // model only: wipe everything that did not go through the storage interface
+    Crash,
+    // model only: a partition cuts this actor off from the other side, delivered at an
+    // arbitrary point, and later heals
+    Split,
+    Heal,
+    // the state machine up to a slot, sent to replicas asking about slots compacted away
+    Snapshot(Applied<V>),
+    Read(u64, Slot),
+    ReadReply(u64, Slot, Option<V>, Option<(B, V)>),
+    LeaseRequest(B),
+    LeaseGrant(B, Slot),
+    // stands in for the lease timer running out, delivered at an arbitrary point
+    LeaseExpired(B),
+    // model only: one tick of the recipient's logical clock
+    Tick,
+    // asks whether a prepare for the round would be promised, without the recipient changing
+    // anything, and the answer
+    Probe(Slot, B),
+    ProbeReply(Slot, B, bool),
+    // model only: a follower's failure detector giving up on the leader, and later hearing
+    // from it again, each delivered at an arbitrary point
+    Suspect(B),
+    Trust(B),
+    // asks for every decision from a slot on, from a replica that may have missed some; also
+    // tells the recipient how far the asker has got
+    CatchUpRequest(Slot),
+    // decided slots in order, at most `CATCH_UP_BATCH` of them
+    CatchUpReply(Vec<(Slot, Command<V>)>),
+    // asks the leader to stop proposing and hand leadership to a replica
+    TransferLeadership(Id),
+    // the leader's handover: its round, the slot it has applied up to, and requests for the
+    // recipient to propose; also tells whoever asked for the transfer that it is done
+    Handoff(B, Slot, Vec<(u64, Id, Command<V>)>),
+    // what a starting replica speaks, answered with what the recipient speaks
+    Hello(Features),
+    Welcome(Features),
+}
+
+// decisions sent in one catch-up reply, the asker comes back for more after a full one
+pub const CATCH_UP_BATCH: usize = 64;
+
+// whether states and messages debug print as their one line summaries, which fit many more
+// of them on an explorer page than the nested maps do
+static SUMMARIZED: AtomicBool = AtomicBool::new(false);
+
+pub fn summarize_debug(summarized: bool) {
+    SUMMARIZED.store(summarized, Ordering::Relaxed);
+}
+
+fn summarized() -> bool {
+    SUMMARIZED.load(Ordering::Relaxed)
+}
+
+// a put is just its value, a session put is tagged with its client and sequence number
+impl<V: Debug> fmt::Display for Command<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Put(value) => write!(f, "{:?}", value),
+            Command::SessionPut(session, value) => write!(
+                f,
+                "{:?}#{}.{}",
+                value,
+                usize::from(session.client),
+                session.seq
+            ),
+            Command::Batch(values) => write!(f, "{:?}", values),
+            Command::Reconfigure(ids) => {
+                let ids: Vec<usize> = ids.iter().copied().map(usize::from).collect();
+                write!(f, "cfg{:?}", ids)
+            }
+        }
+    }
+}
+
+// the message's initials, then its slot, round and command, like `A(0,2.1,'B')`
+impl<V: Debug, B: fmt::Display> fmt::Display for PaxosMsg<V, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaxosMsg::Prepare(_, _, slot, rid) => write!(f, "P({},{})", slot, rid),
+            PaxosMsg::Promise(_, _, slot, rid, None) => write!(f, "Pm({},{})", slot, rid),
+            PaxosMsg::Promise(_, _, slot, rid, Some((accepted, command))) => {
+                write!(f, "Pm({},{},{}:{})", slot, rid, accepted, command)
+            }
+            PaxosMsg::WitnessPromise(_, _, slot, rid, None) => write!(f, "WPm({},{})", slot, rid),
+            PaxosMsg::WitnessPromise(_, _, slot, rid, Some(witnessed)) => {
+                write!(f, "WPm({},{},{})", slot, rid, witnessed)
+            }
+            PaxosMsg::Accept(_, _, slot, rid, command) => {
+                write!(f, "A({},{},{})", slot, rid, command)
+            }
+            PaxosMsg::Accepted(_, _, slot, rid, command) => {
+                write!(f, "Ad({},{},{})", slot, rid, command)
+            }
+            PaxosMsg::Nack(_, slot, rid) => write!(f, "N({},{})", slot, rid),
+            PaxosMsg::Expire(slot, rid, seen) => write!(f, "X({},{},{})", slot, rid, seen),
+            PaxosMsg::Decided(slot, _, command) => write!(f, "D({},{})", slot, command),
+            PaxosMsg::Heartbeat(slot, rid) => write!(f, "HB({},{})", slot, rid),
+            PaxosMsg::Forward(_, _, command) => write!(f, "F({})", command),
+            PaxosMsg::Reconfigure(_, members) => {
+                let members: Vec<usize> = members.iter().copied().map(usize::from).collect();
+                write!(f, "Rc{:?}", members)
+            }
+            PaxosMsg::Crash => write!(f, "Crash"),
+            PaxosMsg::Split => write!(f, "Split"),
+            PaxosMsg::Heal => write!(f, "Heal"),
+            PaxosMsg::Snapshot(applied) => {
+                write!(f, "Snap({},{:?})", applied.next, applied.value)
+            }
+            PaxosMsg::Read(_, slot) => write!(f, "Rd({})", slot),
+            PaxosMsg::ReadReply(_, slot, decided, accepted) => {
+                write!(f, "RdOk({}", slot)?;
+                if let Some(decided) = decided {
+                    write!(f, ",{:?}", decided)?;
+                }
+                if let Some((rid, value)) = accepted {
+                    write!(f, ",{}:{:?}", rid, value)?;
+                }
+                write!(f, ")")
+            }
+            PaxosMsg::LeaseRequest(rid) => write!(f, "LReq({})", rid),
+            PaxosMsg::LeaseGrant(rid, slot) => write!(f, "LGrant({},{})", rid, slot),
+            PaxosMsg::LeaseExpired(rid) => write!(f, "LExp({})", rid),
+            PaxosMsg::Tick => write!(f, "Tick"),
+            PaxosMsg::Probe(slot, rid) => write!(f, "Pr({},{})", slot, rid),
+            PaxosMsg::ProbeReply(slot, rid, granted) => {
+                write!(f, "PrOk({},{},{})", slot, rid, granted)
+            }
+            PaxosMsg::Suspect(rid) => write!(f, "Sus({})", rid),
+            PaxosMsg::Trust(rid) => write!(f, "Tr({})", rid),
+            PaxosMsg::CatchUpRequest(slot) => write!(f, "CU({})", slot),
+            PaxosMsg::CatchUpReply(entries) => {
+                write!(f, "CUOk(")?;
+                for (i, (slot, command)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", slot, command)?;
+                }
+                write!(f, ")")
+            }
+            PaxosMsg::TransferLeadership(target) => write!(f, "TL({})", usize::from(*target)),
+            PaxosMsg::Handoff(rid, slot, requests) => {
+                write!(f, "HO({},{}", rid, slot)?;
+                for (_, _, command) in requests {
+                    write!(f, ",{}", command)?;
+                }
+                write!(f, ")")
+            }
+            PaxosMsg::Hello(features) => write!(f, "Hi({})", features),
+            PaxosMsg::Welcome(features) => write!(f, "Wc({})", features),
+        }
+    }
+}
+
+impl<V: Debug, B: Debug + fmt::Display> Debug for PaxosMsg<V, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if summarized() {
+            return fmt::Display::fmt(self, f);
+        }
+        match self {
+            PaxosMsg::Prepare(request_id, client, slot, rid) => f
+                .debug_tuple("Prepare")
+                .field(request_id)
+                .field(client)
+                .field(slot)
+                .field(rid)
+                .finish(),
+            PaxosMsg::Promise(request_id, client, slot, rid, accepted) => f
+                .debug_tuple("Promise")
+                .field(request_id)
+                .field(client)
+                .field(slot)
+                .field(rid)
+                .field(accepted)
+                .finish(),
+            PaxosMsg::WitnessPromise(request_id, client, slot, rid, witnessed) => f
+                .debug_tuple("WitnessPromise")
+                .field(request_id)
+                .field(client)
+                .field(slot)
+                .field(rid)
+                .field(witnessed)
+                .finish(),
+            PaxosMsg::Accept(request_id, client, slot, rid, command) => f
+                .debug_tuple("Accept")
+                .field(request_id)
+                .field(client)
+                .field(slot)
+                .field(rid)
+                .field(command)
+                .finish(),
+            PaxosMsg::Accepted(request_id, client, slot, rid, command) => f
+                .debug_tuple("Accepted")
+                .field(request_id)
+                .field(client)
+                .field(slot)
+                .field(rid)
+                .field(command)
+                .finish(),
+            PaxosMsg::Nack(request_id, slot, rid) => f
+                .debug_tuple("Nack")
+                .field(request_id)
+                .field(slot)
+                .field(rid)
+                .finish(),
+            PaxosMsg::Expire(slot, rid, seen) => f
+                .debug_tuple("Expire")
+                .field(slot)
+                .field(rid)
+                .field(seen)
+                .finish(),
+            PaxosMsg::Decided(slot, rid, command) => f
+                .debug_tuple("Decided")
+                .field(slot)
+                .field(rid)
+                .field(command)
+                .finish(),
+            PaxosMsg::Heartbeat(slot, rid) => {
+                f.debug_tuple("Heartbeat").field(slot).field(rid).finish()
+            }
+            PaxosMsg::Forward(request_id, client, command) => f
+                .debug_tuple("Forward")
+                .field(request_id)
+                .field(client)
+                .field(command)
+                .finish(),
+            PaxosMsg::Reconfigure(request_id, members) => f
+                .debug_tuple("Reconfigure")
+                .field(request_id)
+                .field(members)
+                .finish(),
+            PaxosMsg::Crash => f.write_str("Crash"),
+            PaxosMsg::Split => f.write_str("Split"),
+            PaxosMsg::Heal => f.write_str("Heal"),
+            PaxosMsg::Snapshot(applied) => f.debug_tuple("Snapshot").field(applied).finish(),
+            PaxosMsg::Read(request_id, slot) => {
+                f.debug_tuple("Read").field(request_id).field(slot).finish()
+            }
+            PaxosMsg::ReadReply(request_id, slot, decided, accepted) => f
+                .debug_tuple("ReadReply")
+                .field(request_id)
+                .field(slot)
+                .field(decided)
+                .field(accepted)
+                .finish(),
+            PaxosMsg::LeaseRequest(rid) => f.debug_tuple("LeaseRequest").field(rid).finish(),
+            PaxosMsg::LeaseGrant(rid, slot) => {
+                f.debug_tuple("LeaseGrant").field(rid).field(slot).finish()
+            }
+            PaxosMsg::LeaseExpired(rid) => f.debug_tuple("LeaseExpired").field(rid).finish(),
+            PaxosMsg::Tick => f.write_str("Tick"),
+            PaxosMsg::Probe(slot, rid) => f.debug_tuple("Probe").field(slot).field(rid).finish(),
+            PaxosMsg::ProbeReply(slot, rid, granted) => f
+                .debug_tuple("ProbeReply")
+                .field(slot)
+                .field(rid)
+                .field(granted)
+                .finish(),
+            PaxosMsg::Suspect(rid) => f.debug_tuple("Suspect").field(rid).finish(),
+            PaxosMsg::Trust(rid) => f.debug_tuple("Trust").field(rid).finish(),
+            PaxosMsg::CatchUpRequest(slot) => f.debug_tuple("CatchUpRequest").field(slot).finish(),
+            PaxosMsg::CatchUpReply(entries) => {
+                f.debug_tuple("CatchUpReply").field(entries).finish()
+            }
+            PaxosMsg::TransferLeadership(target) => {
+                f.debug_tuple("TransferLeadership").field(target).finish()
+            }
+            PaxosMsg::Handoff(rid, slot, requests) => f
+                .debug_tuple("Handoff")
+                .field(rid)
+                .field(slot)
+                .field(requests)
+                .finish(),
+            PaxosMsg::Hello(features) => f.debug_tuple("Hello").field(features).finish(),
+            PaxosMsg::Welcome(features) => f.debug_tuple("Welcome").field(features).finish(),
+        }
+    }
+}
+
+// the replica and its applied register, then only the parts of the log and of the proposals
+// in flight that hold anything, like `N1 2='B' log 0:'A' 1:'B' acc 2:3.1:'C' proposing 2@3.1`
+impl<V: Debug, B: fmt::Display> fmt::Display for PaxosState<V, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "N{} {}={:?}",
+            usize::from(self.id),
+            self.applied.next,
+            self.applied.value
+        )?;
+        if let Some(snapshot) = &self.durable.snapshot {
+            write!(f, " snap {}", snapshot.next)?;
+        }
+        if !self.durable.log.is_empty() {
+            write!(f, " log")?;
+            for (slot, command) in &self.durable.log {
+                write!(f, " {}:{}", slot, command)?;
+            }
+        }
+        if !self.durable.accepted.is_empty() {
+            write!(f, " acc")?;
+            for (slot, (rid, command)) in &self.durable.accepted {
+                write!(f, " {}:{}:{}", slot, rid, command)?;
+            }
+        }
+        if !self.durable.witnessed.is_empty() {
+            write!(f, " wit")?;
+            for (slot, rid) in &self.durable.witnessed {
+                write!(f, " {}:{}", slot, rid)?;
+            }
+        }
+        if !self.proposers.is_empty() {
+            write!(f, " proposing")?;
+            for (slot, rid) in self.proposers.keys() {
+                write!(f, " {}@{}", slot, rid)?;
+            }
+        }
+        if !self.reads.is_empty() {
+            write!(f, " reading {}", self.reads.len())?;
+        }
+        if let Some(lease) = &self.durable.lease {
+            write!(f, " following {}", lease.ballot)?;
+        }
+        if let Some(read_lease) = &self.read_lease {
+            write!(f, " leased {}", read_lease.ballot)?;
+        }
+        if let Some(read_grant) = &self.read_grant {
+            write!(f, " granted {}", read_grant)?;
+        }
+        if self.ticks > 0 {
+            write!(f, " t{}", self.ticks)?;
+        }
+        for (follower, slots) in &self.unacked {
+            if !slots.is_empty() {
+                write!(f, " unacked {}:{}", follower, slots.len())?;
+            }
+        }
+        if self.split {
+            write!(f, " split")?;
+        }
+        if let Some(suspected) = &self.suspected {
+            write!(f, " suspecting {}", suspected)?;
+        }
+        if let Some(handoff) = &self.handoff {
+            write!(f, " handing off to N{}", usize::from(handoff.target))?;
+        }
+        Ok(())
+    }
+}
+
+impl<V: Debug, B: Debug + fmt::Display> Debug for PaxosState<V, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if summarized() {
+            return fmt::Display::fmt(self, f);
+        }
+        f.debug_struct("PaxosState")
+            .field("id", &self.id)
+            .field("peers", &self.peers)
+            .field("durable", &self.durable)
+            .field("storage", &self.storage)
+            .field("applied", &self.applied)
+            .field("proposers", &self.proposers)
+            .field("reads", &self.reads)
+            .field("handled", &self.handled)
+            .field("read_lease", &self.read_lease)
+            .field("read_grant", &self.read_grant)
+            .field("grant_until", &self.grant_until)
+            .field("ticks", &self.ticks)
+            .field("unacked", &self.unacked)
+            .field("split", &self.split)
+            .field("suspected", &self.suspected)
+            .field("handoff", &self.handoff)
+            .field("features", &self.features)
+            .field("reported", &self.reported)
+            .finish()
+    }
+}
+
+pub struct PaxosActor<V, B = RoundIdentifier> {
+    // the initial configuration, handed to the state on start
+    peers: Vec<Id>,
+    // how many slots a decided reconfiguration waits before taking effect
+    alpha: Slot,
+    // members this actor proposes to switch to at some point during the run
+    reconfigure: Option<Vec<Id>>,
+    // the replica this actor, once leader, hands leadership to at some point during the run
+    transfer: Option<Id>,
+    crashes: bool,
+    // down for the whole run, a replica in a region that has gone dark
+    down: bool,
+    byzantine: bool,
+    // the servers on the other side of a partition this actor goes through, if any
+    partition: Vec<Id>,
+    // client puts are applied once per request, however often they are decided
+    sessions: bool,
+    // how many applied slots the log may hold before they are compacted into a snapshot
+    compact_after: Option<Slot>,
+    // a write-ahead log on disk, for replicas that run outside the model
+    wal: Option<Mutex<FileStorage<V, B>>>,
+    // leaders serve reads locally under a lease, given how far clocks may drift
+    read_leases: Option<ClockSkew>,
+    // how long a read lease lasts and how far apart the clocks may be assumed to drift, when
+    // leases are timed on the clock rather than run out at an arbitrary point
+    lease_timing: Option<(Duration, Duration)>,
+    // what leases and the failure detector go by
+    clock: Clock,
+    // every server answers reads from its applied register, which is not linearizable
+    stale_reads: bool,
+    // how many puts may share a decree, joining a proposal that has not reached phase 2
+    batch_size: Option<usize>,
+    // how many slots past the applied prefix a proposer may have in flight
+    pipeline: Option<Slot>,
+    // how many slots a leader may have sent a follower under its lease without an answer
+    replication_window: Option<usize>,
+    // proposers probe for a quorum before starting a round
+    pre_vote: bool,
+    // what this replica advertises it speaks; none for a replica from before the handshake,
+    // which uses whatever it is configured with and ignores the handshake
+    features: Option<Features>,
+    quorum: Box<dyn QuorumSystem>,
+    strategy: Box<dyn ForwardStrategy>,
+    // shared between replicas, which it tells apart by id
+    metrics: Arc<dyn Metrics>,
+    // told of every decided entry as it is applied, likewise shared
+    observer: Arc<dyn ApplyObserver<V>>,
+    // how long a proposer waits for a quorum before retrying with a higher round
+    timeout: Range<Duration>,
+    // retries back off and proposals yield to higher ballots, when set
+    backoff: Option<Mutex<Backoff>>,
+    // followers may give up on their leader at any point, model only
+    suspicion: bool,
+    // followers give up on their leader once its heartbeats stop, on real timers
+    watch: Option<Mutex<Watch>>,
+    role: Role,
+    // the replicas that only vote, which every phase 2 quorum has to reach beyond
+    witnesses: Vec<Id>,
+    _value: PhantomData<(V, B)>,
+}
+
+// doublings of the timeout past which retries stop backing off further
+const MAX_BACKOFF: u32 = 6;
+
+// randomized exponential backoff between a proposer's retries, seeded so that a run repeats
+struct Backoff {
+    rng: SplitMix,
+    // timeouts since this replica last learned a decision
+    retries: u32,
+}
+
+// the heartbeats a follower has heard from its current leader, on the replica's own clock
+struct Watch {
+    spec: DetectorSpec,
+    detector: Box<dyn FailureDetector>,
+}
+
+impl<V: Value> PaxosActor<V> {
+    pub fn new(peers: Vec<Id>) -> Self {
+        Self::with_ballot(peers)
+    }
+}
+
+impl<V: Value, B: Ballot> PaxosActor<V, B> {
+    // rounds are numbered with `B` rather than the default `RoundIdentifier`
+    pub fn with_ballot(peers: Vec<Id>) -> Self {
+        PaxosActor {
+            quorum: Box::new(Majority::new(peers.len())),
+            strategy: Box::new(MajorityPromises),
+            peers,
+            alpha: 2,
+            reconfigure: None,
+            transfer: None,
+            crashes: false,
+            down: false,
+            byzantine: false,
+            partition: Vec::new(),
+            sessions: false,
+            compact_after: None,
+            wal: None,
+            read_leases: None,
+            lease_timing: None,
+            clock: Clock::monotonic(),
+            stale_reads: false,
+            batch_size: None,
+            pipeline: None,
+            replication_window: None,
+            pre_vote: false,
+            features: None,
+            metrics: Arc::new(NoMetrics),
+            observer: Arc::new(NoObserver),
+            timeout: Duration::from_millis(500)..Duration::from_millis(1000),
+            backoff: None,
+            suspicion: false,
+            watch: None,
+            role: Role::Data,
+            witnesses: Vec::new(),
+            _value: PhantomData,
+        }
+    }
+
+    pub fn quorum(mut self, quorum: Box<dyn QuorumSystem>) -> Self {
+        self.quorum = quorum;
+        self
+    }
+
+    pub fn strategy(mut self, strategy: Box<dyn ForwardStrategy>) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Range<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Waits twice as long after every timeout without a decision, plus up to as much again
+    // drawn from `seed`, so dueling proposers drift apart. A proposal nacked by a higher ballot
+    // is handed to that ballot's proposer rather than tried again. In the model timers fire
+    // whenever, so only the yielding shows there.
+    pub fn backoff(mut self, seed: Option<u64>) -> Self {
+        self.backoff = seed.map(|seed| {
+            Mutex::new(Backoff {
+                rng: SplitMix(seed),
+                retries: 0,
+            })
+        });
+        self
+    }
+
+    // how long a proposal waits for a quorum before it is retried
+    fn retry_timeout(&self) -> Range<Duration> {
+        let backoff = match &self.backoff {
+            Some(backoff) => backoff,
+            None => return self.timeout.clone(),
+        };
+        let mut backoff = backoff.lock().unwrap();
+        let base = self.timeout.start * 2u32.pow(backoff.retries.min(MAX_BACKOFF));
+        let delay = base + base.mul_f64(backoff.rng.unit());
+        delay..delay + Duration::from_millis(1)
+    }
+
+    // Followers may suspect their leader whenever, and trust it again later. A follower that
+    // suspects its leader proposes commands itself with a round above the leader's instead of
+    // forwarding them, which elects it if the leader really is gone.
+    pub fn suspicion(mut self, suspicion: bool) -> Self {
+        self.suspicion = suspicion;
+        self
+    }
+
+    // Suspicion outside the model: leaders send heartbeats while idle, followers feed them and
+    // the leader's accepts to the detector and consult it on every timer.
+    pub fn failure_detector(mut self, detector: Option<DetectorSpec>) -> Self {
+        self.watch = detector.map(|spec| {
+            Mutex::new(Watch {
+                spec,
+                detector: spec.build(),
+            })
+        });
+        self
+    }
+
+    // how often an idle leader sends heartbeats, and an idle follower checks on it
+    fn heartbeat_interval(&self) -> Range<Duration> {
+        let interval = self.timeout.start / 4;
+        interval..interval + Duration::from_millis(1)
+    }
+
+    // with nothing to retry the timer only keeps up the heartbeats and the handshake, if there
+    // are any
+    fn idle(&self, state: &PaxosState<V, B>, o: &mut Out<Self>) {
+        if self.watch.is_some() || !self.unwelcomed(state).is_empty() {
+            o.set_timer(self.heartbeat_interval());
+        } else {
+            o.cancel_timer();
+        }
+    }
+
+    // the leader was heard from, clearing any suspicion of it
+    fn heard_from(&self, state: &mut Cow<PaxosState<V, B>>, ballot: B) {
+        if let Some(watch) = &self.watch {
+            let now = self.clock.now(state.ticks);
+            watch.lock().unwrap().detector.heartbeat(now);
+        }
+        if state.suspected == Some(ballot) {
+            tracing::info!(leader = %ballot, "trusting the leader again");
+            state.to_mut().suspected = None;
+        }
+    }
+
+    // gives up on the leader, so the next command starts a round above it
+    fn suspect(&self, state: &mut Cow<PaxosState<V, B>>, ballot: B) {
+        let following = match state.durable.lease {
+            Some(lease) => lease.ballot == ballot && ballot.id() != state.id,
+            None => false,
+        };
+        if !following || state.suspected == Some(ballot) {
+            return;
+        }
+        tracing::info!(leader = %ballot, "suspecting the leader");
+        let state = state.to_mut();
+        state.suspected = Some(ballot);
+        state.observe_round(ballot);
+    }
+
+    pub fn alpha(mut self, alpha: Slot) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn reconfigure(mut self, members: Option<Vec<Id>>) -> Self {
+        self.reconfigure = members;
+        self
+    }
+
+    pub fn transfer_leadership(mut self, target: Option<Id>) -> Self {
+        self.transfer = target;
+        self
+    }
+
+    pub fn role(mut self, role: Role) -> Self {
+        self.role = role;
+        self
+    }
+
+    // Which of the peers are witnesses. A decision then needs a data replica among its
+    // accepts, and a proposer that hears a witness accepted in a round no data replica vouches
+    // for waits for every data replica, so with a data replica down proposals can stall where
+    // plain majorities would have gone through.
+    pub fn witnesses(mut self, witnesses: Vec<Id>) -> Self {
+        self.witnesses = witnesses;
+        self
+    }
+
+    fn is_witness(&self) -> bool {
+        self.role == Role::Witness
+    }
+
+    // the initial configuration keeps the configured quorum system, later ones use majorities
+    fn is_quorum(
+        &self,
+        state: &PaxosState<V, B>,
+        slot: Slot,
+        ids: &BTreeSet<Id>,
+        phase1: bool,
+    ) -> bool {
+        let peers = state.peers_for(slot, self.alpha);
+        let ids: BTreeSet<Id> = ids
+            .iter()
+            .copied()
+            .filter(|id| peers.contains(id))
+            .collect();
+        let reconfigured;
+        let quorum: &dyn QuorumSystem = if peers == state.peers {
+            self.quorum.as_ref()
+        } else {
+            reconfigured = Majority::new(peers.len());
+            &reconfigured
+        };
+        if phase1 {
+            quorum.is_phase1_quorum(&ids)
+        } else {
+            // witnesses alone would decide a value nobody stored
+            let stored =
+                self.witnesses.is_empty() || ids.iter().any(|id| !self.witnesses.contains(id));
+            stored && quorum.is_phase2_quorum(&ids)
+        }
+    }
+
+    pub fn compact_after(mut self, compact_after: Option<Slot>) -> Self {
+        self.compact_after = compact_after;
+        self
+    }
+
+    pub fn wal(mut self, wal: FileStorage<V, B>) -> Self {
+        self.wal = Some(Mutex::new(wal));
+        self
+    }
+
+    pub fn metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn observer(mut self, observer: Arc<dyn ApplyObserver<V>>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    pub fn read_leases(mut self, read_leases: Option<ClockSkew>) -> Self {
+        self.read_leases = read_leases;
+        self
+    }
+
+    // Times read leases on the clock. A grantor keeps its promise for `duration` from when it
+    // grants, and the leader stops serving reads `duration` less twice `skew` after it asked,
+    // which is before any grantor lets go as long as no two clocks are more than `skew` apart.
+    pub fn lease_timing(mut self, lease_timing: Option<(Duration, Duration)>) -> Self {
+        self.lease_timing = lease_timing;
+        self
+    }
+
+    pub fn clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn stale_reads(mut self, stale_reads: bool) -> Self {
+        self.stale_reads = stale_reads;
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: Option<usize>) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn pipeline(mut self, pipeline: Option<Slot>) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    pub fn replication_window(mut self, replication_window: Option<usize>) -> Self {
+        self.replication_window = replication_window;
+        self
+    }
+
+    pub fn pre_vote(mut self, pre_vote: bool) -> Self {
+        self.pre_vote = pre_vote;
+        self
+    }
+
+    pub fn features(mut self, features: Option<Features>) -> Self {
+        self.features = features;
+        self
+    }
+
+    // whether `peer` advertised `feature`, or we are too old to ask
+    fn speaks(&self, state: &PaxosState<V, B>, peer: Id, feature: Features) -> bool {
+        match self.features {
+            Some(ours) => {
+                ours.contains(feature)
+                    && state
+                        .features
+                        .get(&peer)
+                        .map_or(false, |theirs| theirs.contains(feature))
+            }
+            None => true,
+        }
+    }
+
+    fn advertised(&self, state: &mut Cow<PaxosState<V, B>>, peer: Id, features: Features) {
+        if state.features.get(&peer) != Some(&features) {
+            tracing::info!(peer = usize::from(peer), %features, "peer advertised");
+            state.to_mut().features.insert(peer, features);
+        }
+    }
+
+    // the peers that have not yet told us what they speak, who are sent our hello again on
+    // every timeout; an old replica never does, and is sent it for as long as it is around
+    fn unwelcomed(&self, state: &PaxosState<V, B>) -> Vec<Id> {
+        match self.features {
+            Some(_) => self
+                .peers
+                .iter()
+                .filter(|peer| !state.features.contains_key(peer))
+                .copied()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // whether every replica we know of advertised `feature`
+    fn negotiated(&self, state: &PaxosState<V, B>, feature: Features) -> bool {
+        state
+            .everyone()
+            .into_iter()
+            .all(|peer| self.speaks(state, peer, feature))
+    }
+
+    // whether a proposal for `slot` may go out now rather than wait for earlier slots
+    fn may_propose(&self, state: &PaxosState<V, B>, slot: Slot) -> bool {
+        state.window_open(slot, self.alpha) && state.in_pipeline(slot, self.pipeline)
+    }
+
+    // whether a read can be answered from the applied register without asking anyone
+    fn reads_locally(&self, state: &PaxosState<V, B>) -> bool {
+        let lease = match &state.read_lease {
+            Some(lease) => lease,
+            None => return false,
+        };
+        if let Some(expires) = lease.expires {
+            if self.clock.now(state.ticks) >= expires {
+                return false;
+            }
+        }
+        let leading = match state.durable.lease {
+            Some(leader) => leader.ballot == lease.ballot,
+            None => false,
+        };
+        // anything decided past the applied prefix may already have been acknowledged
+        let next = state.applied.next;
+        let caught_up = next >= lease.floor && state.durable.log.range(next..).next().is_none();
+        leading && caught_up && self.is_quorum(state, next, &lease.grants, true)
+    }
+
+    // lets go of a timed read lease or grant once our clock says it has run out
+    fn expire_leases(&self, state: &mut PaxosState<V, B>) {
+        let now = self.clock.now(state.ticks);
+        let expired = |until: Option<Duration>| until.map_or(false, |until| now >= until);
+        if expired(state.read_lease.as_ref().and_then(|lease| lease.expires)) {
+            state.read_lease = None;
+        }
+        if expired(state.grant_until) {
+            state.read_grant = None;
+            state.grant_until = None;
+        }
+    }
+
+    // applies a change to the durable state, writing it through before anything is sent
+    fn persist(&self, state: &mut PaxosState<V, B>, record: WalRecord<V, B>) {
+        if let Some(wal) = &self.wal {
+            wal.lock()
+                .unwrap()
+                .persist(&record)
+                .expect("unable to write the write-ahead log");
+        }
+        let _ = state.storage.persist(&record);
+        match record {
+            WalRecord::Promise(slot, rid) => state.durable.promise(slot, rid),
+            WalRecord::Accept(slot, rid, command) => state.durable.accept(slot, rid, command),
+            WalRecord::Witness(slot, rid) => state.durable.witness(slot, rid),
+            WalRecord::Round(round) => state.durable.set_round(round),
+            WalRecord::Lease(lease) => state.durable.set_lease(lease),
+        }
+    }
+
+    // whether this actor crashes and restarts once at some point during the run
+    pub fn crashes(mut self, crashes: bool) -> Self {
+        self.crashes = crashes;
+        self
+    }
+
+    // whether this actor never comes up at all: it sends nothing and ignores everything
+    pub fn down(mut self, down: bool) -> Self {
+        self.down = down;
+        self
+    }
+
+    // the servers this actor stops hearing from while a partition lasts, at some point during
+    // the run
+    pub fn partition(mut self, other_side: Vec<Id>) -> Self {
+        self.partition = other_side;
+        self
+    }
+
+    // tags each client put with its session, which only works for clients whose request ids
+    // increase; batching leaves tagged puts alone
+    pub fn sessions(mut self, sessions: bool) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    // whether this actor ignores the protocol and vouches for every round it hears of
+    pub fn byzantine(mut self, byzantine: bool) -> Self {
+        self.byzantine = byzantine;
+        self
+    }
+
+    // a byzantine acceptor promises every round while hiding what it accepted, and claims to
+    // accept everything; nothing in plain Paxos can tell these forgeries from honest replies
+    fn forge(&self, src: Id, msg: &PaxosMsg<V, B>, o: &mut Out<Self>) -> bool {
+        match msg {
+            PaxosMsg::Prepare(request_id, org_sender, slot, rid) => {
+                let msg = PaxosMsg::Promise(*request_id, *org_sender, *slot, *rid, None);
+                o.send(src, RegisterMsg::Internal(msg));
+                true
+            }
+            PaxosMsg::Accept(request_id, org_sender, slot, rid, value) => {
+                let msg = PaxosMsg::Accepted(*request_id, *org_sender, *slot, *rid, value.clone());
+                o.send(src, RegisterMsg::Internal(msg));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // followers hand commands to the leader, everyone else proposes directly
+    fn put(
+        &self,
+        state: &mut PaxosState<V, B>,
+        request_id: u64,
+        client: Id,
+        value: Command<V>,
+        o: &mut Out<Self>,
+    ) {
+        // the cached answer to a request already applied, rather than a second slot for it
+        if let Command::SessionPut(session, _) = &value {
+            if state.applied.has_applied(session) {
+                o.send(client, RegisterMsg::PutOk(request_id));
+                return;
+            }
+        }
+        // a witness could not carry a value through phase 1, so it never proposes
+        if self.is_witness() {
+            let to = match state.durable.lease {
+                Some(lease) => Some(lease.ballot.id()),
+                None => state
+                    .peers
+                    .iter()
+                    .copied()
+                    .find(|id| !self.witnesses.contains(id)),
+            };
+            if let Some(to) = to {
+                let msg = RegisterMsg::Internal(PaxosMsg::Forward(request_id, client, value));
+                o.send(to, msg);
+            }
+            return;
+        }
+        if let Some(lease) = state.durable.lease {
+            if lease.ballot.id() != state.id && state.suspected != Some(lease.ballot) {
+                let msg = RegisterMsg::Internal(PaxosMsg::Forward(request_id, client, value));
+                o.send(lease.ballot.id(), msg);
+                return;
+            }
+        }
+        // a leader handing off proposes nothing new, the target takes the request
+        if let (Some(lease), Some(handoff)) = (state.durable.lease, &mut state.handoff) {
+            if lease.ballot.id() == state.id {
+                if handoff.sent {
+                    let requests = vec![(request_id, client, value)];
+                    let msg = PaxosMsg::Handoff(lease.ballot, state.applied.next, requests);
+                    o.send(handoff.target, RegisterMsg::Internal(msg));
+                } else if !handoff.held.iter().any(|(held, _, _)| *held == request_id) {
+                    handoff.held.push((request_id, client, value));
+                }
+                return;
+            }
+        }
+        if !state.handled.insert(request_id) {
+            return;
+        }
+        self.metrics.proposal_started(state.id, request_id);
+        let batching = self
+            .batch_size
+            .filter(|_| self.negotiated(state, Features::BATCHING));
+        if let (Some(batch_size), Command::Put(put)) = (batching, &value) {
+            let open = state.proposers_where(|_, ctx| {
+                matches!(ctx.phase, Phase::Waiting | Phase::Prepare) && ctx.batch_len() < batch_size
+            });
+            if let Some(ctx) = open.first().and_then(|key| state.proposers.get_mut(key)) {
+                ctx.join(request_id, client, put.clone());
+                return;
+            }
+        }
+        let slot = state.next_slot();
+        self.propose(
+            state,
+            ProposerContext::new(request_id, client, value),
+            slot,
+            o,
+        );
+    }
+
+    // a leader skips phase 1 for the slots its lease covers
+    fn propose(
+        &self,
+        state: &mut PaxosState<V, B>,
+        mut ctx: ProposerContext<V, B>,
+        slot: Slot,
+        o: &mut Out<Self>,
+    ) {
+        if !self.may_propose(state, slot) {
+            ctx.restart(Phase::Waiting);
+            state.proposers.insert((slot, B::new(0, state.id)), ctx);
+            return;
+        }
+        // a lease only speaks for the acceptors that granted it
+        let lease = match state.durable.lease {
+            Some(lease)
+                if state.is_leader()
+                    && slot > lease.slot
+                    && state.peers_for(slot, self.alpha)
+                        == state.peers_for(lease.slot, self.alpha) =>
+            {
+                lease
+            }
+            _ => return self.prepare(state, ctx, slot, o),
+        };
+        ctx.restart(Phase::Accept);
+        let msg = RegisterMsg::Internal(PaxosMsg::Accept(
+            ctx.request_id,
+            ctx.client,
+            slot,
+            lease.ballot,
+            ctx.value.clone(),
+        ));
+        let peers = state.peers_for(slot, self.alpha);
+        self.replicate(state, slot, &peers, &msg, o);
+        o.set_timer(self.timeout.clone());
+        state.proposers.insert((slot, lease.ballot), ctx);
+    }
+
+    // Sends an accept under our lease to every follower with room in its replication window.
+    // The rest get it once they have acknowledged earlier slots, or not at all if the slot is
+    // decided first, in which case they learn it like any other decision.
+    fn replicate(
+        &self,
+        state: &mut PaxosState<V, B>,
+        slot: Slot,
+        peers: &[Id],
+        msg: &<Self as Actor>::Msg,
+        o: &mut Out<Self>,
+    ) {
+        let window = match self.replication_window {
+            Some(window) => window,
+            None => return o.broadcast(peers, msg),
+        };
+        for peer in peers {
+            if *peer != state.id {
+                let unacked = state.unacked.entry(*peer).or_default();
+                if unacked.len() >= window {
+                    continue;
+                }
+                unacked.insert(slot);
+            }
+            o.send(*peer, msg.clone());
+        }
+    }
+
+    // sends a follower the accepts its window held back, lowest slot first, as far as it has room
+    fn resume(&self, state: &mut PaxosState<V, B>, follower: Id, o: &mut Out<Self>) {
+        let ballot = match state.durable.lease {
+            Some(lease) if state.is_leader() => lease.ballot,
+            _ => return,
+        };
+        let held: Vec<(Slot, <Self as Actor>::Msg)> = state
+            .proposers
+            .iter()
+            .filter(|((slot, rid), ctx)| {
+                let roster = state.peers_for(*slot, self.alpha);
+                *rid == ballot
+                    && ctx.phase == Phase::Accept
+                    && roster.contains(&follower)
+                    && !ctx.accepts.ids(&roster).contains(&follower)
+                    && !state
+                        .unacked
+                        .get(&follower)
+                        .map_or(false, |unacked| unacked.contains(slot))
+            })
+            .map(|((slot, rid), ctx)| {
+                let msg = RegisterMsg::Internal(PaxosMsg::Accept(
+                    ctx.request_id,
+                    ctx.client,
+                    *slot,
+                    *rid,
+                    ctx.proposed_value(),
+                ));
+                (*slot, msg)
+            })
+            .collect();
+        for (slot, msg) in held {
+            self.replicate(state, slot, &[follower], &msg, o);
+        }
+    }
+
+    // records a decision, then settles our own proposals for that slot
+    fn learn(
+        &self,
+        state: &mut PaxosState<V, B>,
+        slot: Slot,
+        value: Command<V>,
+        o: &mut Out<Self>,
+    ) {
+        // our proposals for this slot either won, or must move to a later slot
+        let in_slot = state.proposers_where(|(s, _), _| *s == slot);
+        state.durable.log.insert(slot, value.clone());
+        // the promises and accepts for the slot are settled; storage keeps them for a restart,
+        // which forgets the decision
+        state.durable.last_seen.remove(&slot);
+        state.durable.accepted.remove(&slot);
+        tracing::info!(slot, value = ?value, "decided");
+        if let Some(backoff) = &self.backoff {
+            backoff.lock().unwrap().retries = 0;
+        }
+        self.metrics.increment(state.id, Counter::SlotsDecided, 1);
+        state.advance_applied(self.observer.as_ref());
+        if let Some(compact_after) = self.compact_after {
+            if state.applied.next - state.compacted() >= compact_after {
+                let snapshot = state.applied.clone();
+                state.compact(snapshot);
+            }
+        }
+        for key in in_slot {
+            let ctx = match state.proposers.remove(&key) {
+                Some(ctx) => ctx,
+                None => continue,
+            };
+            if ctx.value == value {
+                self.metrics.proposal_decided(state.id, ctx.request_id);
+                o.send(ctx.client, RegisterMsg::PutOk(ctx.request_id));
+                for (request_id, client) in ctx.riders {
+                    self.metrics.proposal_decided(state.id, request_id);
+                    o.send(client, RegisterMsg::PutOk(request_id));
+                }
+            } else {
+                let next = state.next_slot();
+                self.propose(state, ctx, next, o);
+            }
+        }
+
+        self.release_waiting(state, o);
+    }
+
+    // the log may have settled the configuration of slots we are holding back
+    fn release_waiting(&self, state: &mut PaxosState<V, B>, o: &mut Out<Self>) {
+        let ready = state.proposers_where(|(s, _), ctx| {
+            ctx.phase == Phase::Waiting && self.may_propose(state, *s)
+        });
+        for key in ready {
+            if let Some(ctx) = state.proposers.remove(&key) {
+                self.propose(state, ctx, key.0, o);
+            }
+        }
+        if state.proposers.is_empty() {
+            self.idle(state, o);
+            self.hand_off(state, o);
+        }
+    }
+
+    // Once a leader's last proposals are settled, hands the target its round along with the
+    // requests it held back, and tells whoever asked. The target goes on with a higher round,
+    // and nothing of ours is left in any slot for it to compete with.
+    fn hand_off(&self, state: &mut PaxosState<V, B>, o: &mut Out<Self>) {
+        let ballot = match state.durable.lease {
+            Some(lease) if lease.ballot.id() == state.id => lease.ballot,
+            _ => return,
+        };
+        let handoff = match &mut state.handoff {
+            Some(handoff) if !handoff.sent => handoff,
+            _ => return,
+        };
+        handoff.sent = true;
+        let held = std::mem::take(&mut handoff.held);
+        let (target, requester) = (handoff.target, handoff.requester);
+        tracing::info!(target = usize::from(target), "handed off leadership");
+        let upto = state.applied.next;
+        o.send(
+            target,
+            RegisterMsg::Internal(PaxosMsg::Handoff(ballot, upto, held)),
+        );
+        if requester != state.id && requester != target {
+            let msg = PaxosMsg::Handoff(ballot, upto, Vec::new());
+            o.send(requester, RegisterMsg::Internal(msg));
+        }
+    }
+
+    // a handoff overtaken by a change of leader is called off, the requests it held going
+    // wherever they would go now
+    fn drop_handoff(&self, state: &mut PaxosState<V, B>, o: &mut Out<Self>) {
+        if let Some(handoff) = state.handoff.take() {
+            for (request_id, client, command) in handoff.held {
+                self.put(state, request_id, client, command, o);
+            }
+        }
+    }
+
+    // asks every other replica for the decisions past our applied prefix, after we may have
+    // missed some
+    fn catch_up(&self, state: &PaxosState<V, B>, o: &mut Out<Self>) {
+        if self.is_witness() {
+            return;
+        }
+        let msg = RegisterMsg::Internal(PaxosMsg::CatchUpRequest(state.applied.next));
+        o.broadcast(&state.everyone(), &msg);
+    }
+
+    // counts a promise towards one of our proposals in phase 1, `record` taking in what the
+    // acceptor reported, and moves the proposal on to phase 2 once enough have promised
+    fn promised(
+        &self,
+        state: &mut PaxosState<V, B>,
+        src: Id,
+        slot: Slot,
+        rid: B,
+        o: &mut Out<Self>,
+        record: impl FnOnce(&mut ProposerContext<V, B>),
+    ) {
+        self.metrics
+            .increment(state.id, Counter::PromisesReceived, 1);
+        let roster = state.peers_for(slot, self.alpha);
+        let ctx = match state.proposers.get_mut(&(slot, rid)) {
+            Some(ctx) if ctx.phase == Phase::Prepare => ctx,
+            _ => return,
+        };
+        record(ctx);
+        ctx.promises.insert(&roster, src);
+
+        let promises = ctx.promises;
+        let data: BTreeSet<Id> = roster
+            .iter()
+            .copied()
+            .filter(|id| !self.witnesses.contains(id))
+            .collect();
+        let accounted = ctx.accounts_for_witnesses(&roster, &data);
+        let quorum = if self.strategy.majority_promises() {
+            self.is_quorum(state, slot, &promises.ids(&roster), true)
+        } else {
+            !promises.is_empty()
+        };
+        if !quorum || !accounted {
+            return;
+        }
+        let peers = state.peers_for(slot, self.alpha);
+        let ctx = match state.proposers.get_mut(&(slot, rid)) {
+            Some(ctx) => ctx,
+            None => return,
+        };
+        ctx.phase = Phase::Accept;
+        let msg = RegisterMsg::Internal(PaxosMsg::Accept(
+            ctx.request_id,
+            ctx.client,
+            slot,
+            rid,
+            ctx.proposed_value(),
+        ));
+        // later promises for the round are ignored, so stop keeping the earlier
+        ctx.promises.clear();
+        o.broadcast(&peers, &msg);
+
+        // a quorum of promises also makes us the distinguished proposer
+        let newer = match state.durable.lease {
+            Some(lease) => rid > lease.ballot,
+            None => true,
+        };
+        if newer {
+            let lease = Some(Lease { ballot: rid, slot });
+            self.persist(state, WalRecord::Lease(lease));
+            state.unacked.clear();
+            let msg = RegisterMsg::Internal(PaxosMsg::Heartbeat(slot, rid));
+            o.broadcast(&state.everyone(), &msg);
+            if self.read_leases.is_some() {
+                let expires = self.lease_timing.map(|(duration, skew)| {
+                    (self.clock.now(state.ticks) + duration).saturating_sub(skew * 2)
+                });
+                state.read_lease = Some(ReadLease {
+                    ballot: rid,
+                    grants: BTreeSet::new(),
+                    floor: state.accepted_below(),
+                    expires,
+                });
+                let msg = RegisterMsg::Internal(PaxosMsg::LeaseRequest(rid));
+                o.broadcast(&peers, &msg);
+                if expires.is_none() {
+                    let msg = RegisterMsg::Internal(PaxosMsg::LeaseExpired(rid));
+                    o.send(state.id, msg);
+                }
+            }
+        }
+    }
+
+    // Drops proposals in `slot` that the higher round `seen` has outrun. Each goes again with a
+    // round of our own, or with backoff is handed to `seen`'s proposer rather than dueling it.
+    fn abandon(
+        &self,
+        state: &mut PaxosState<V, B>,
+        keys: Vec<(Slot, B)>,
+        slot: Slot,
+        seen: B,
+        o: &mut Out<Self>,
+    ) {
+        state.observe_round(seen);
+        // a higher round elsewhere means our leadership has been superseded
+        if let Some(lease) = state.durable.lease {
+            if lease.ballot < seen {
+                self.persist(state, WalRecord::Lease(None));
+                self.drop_handoff(state, o);
+            }
+        }
+        for key in keys {
+            let ctx = match state.proposers.remove(&key) {
+                Some(ctx) => ctx,
+                None => continue,
+            };
+            if self.backoff.is_none() || seen.id() == state.id {
+                self.prepare(state, ctx, slot, o);
+                continue;
+            }
+            // yield to the higher ballot rather than duel with it
+            for (request_id, client, command) in ctx.requests() {
+                let msg = RegisterMsg::Internal(PaxosMsg::Forward(request_id, client, command));
+                o.send(seen.id(), msg);
+            }
+        }
+        if state.proposers.is_empty() {
+            self.idle(state, o);
+        }
+    }
+
+    // (re)starts phase 1 for one of our proposals in the given slot with a fresh round
+    fn prepare(
+        &self,
+        state: &mut PaxosState<V, B>,
+        ctx: ProposerContext<V, B>,
+        slot: Slot,
+        o: &mut Out<Self>,
+    ) {
+        if self.pre_vote && self.negotiated(state, Features::PRE_VOTE) {
+            self.probe(state, ctx, slot, o);
+        } else {
+            self.start_round(state, ctx, slot, o);
+        }
+    }
+
+    // Asks the acceptors whether they would promise our next round before raising it. A
+    // proposer no quorum would follow, one cut off by a partition or behind a leader the others
+    // still trust, keeps probing with the same round rather than driving rounds up, so it
+    // cannot abort the rounds of others once it is heard again.
+    fn probe(
+        &self,
+        state: &mut PaxosState<V, B>,
+        mut ctx: ProposerContext<V, B>,
+        slot: Slot,
+        o: &mut Out<Self>,
+    ) {
+        let rid = match ballot::next_round(state.durable.round) {
+            Some(round) => B::new(round, state.id),
+            None => return exhausted(slot, &ctx),
+        };
+        ctx.restart(Phase::Probe);
+        let msg = RegisterMsg::Internal(PaxosMsg::Probe(slot, rid));
+        o.broadcast(&state.peers_for(slot, self.alpha), &msg);
+        o.set_timer(self.retry_timeout());
+        state.proposers.insert((slot, rid), ctx);
+    }
+
+    fn start_round(
+        &self,
+        state: &mut PaxosState<V, B>,
+        mut ctx: ProposerContext<V, B>,
+        slot: Slot,
+        o: &mut Out<Self>,
+    ) {
+        let rid = match state.next_round() {
+            Some(rid) => rid,
+            None => return exhausted(slot, &ctx),
+        };
+        self.persist(state, WalRecord::Round(rid.round_num()));
+        tracing::debug!(slot, round = %rid, request_id = ctx.request_id, "preparing");
+        ctx.restart(Phase::Prepare);
+        if !self.witnesses.is_empty() {
+            // we are a data replica too, so we promise our own round and count what we accepted
+            if state
+                .durable
+                .last_seen
+                .get(&slot)
+                .map_or(true, |seen| *seen < rid)
+            {
+                self.persist(state, WalRecord::Promise(slot, rid));
+            }
+            if let Some((accepted, command)) = state.durable.accepted.get(&slot).cloned() {
+                ctx.adopt(accepted, command);
+            }
+        }
+        let msg = RegisterMsg::Internal(PaxosMsg::Prepare(ctx.request_id, ctx.client, slot, rid));
+        let peers = state.peers_for(slot, self.alpha);
+        self.metrics
+            .increment(state.id, Counter::PreparesSent, peers.len() as u64);
+        o.broadcast(&peers, &msg);
+        o.set_timer(self.retry_timeout());
+        state.proposers.insert((slot, rid), ctx);
+    }
+}
+
+// Past the last round of the last epoch a proposer has nothing left that outranks what it has
+// already promised, so it gives up on the proposal rather than reuse a ballot. It still accepts
+// and learns; the client's retry finds another proposer.
+fn exhausted<V: Value, B: Ballot>(slot: Slot, ctx: &ProposerContext<V, B>) {
+    tracing::error!(
+        slot,
+        request_id = ctx.request_id,
+        "rounds exhausted, dropping proposal"
+    );
+}
+
+// the first slot past the decisions a message shows its sender to know of
+fn decided_upto<V, B>(msg: &PaxosMsg<V, B>) -> Option<Slot> {
+    match msg {
+        PaxosMsg::Decided(slot, _, _) => Some(slot + 1),
+        PaxosMsg::CatchUpReply(entries) => entries.last().map(|(slot, _)| slot + 1),
+        PaxosMsg::Snapshot(applied) => Some(applied.next),
+        PaxosMsg::CatchUpRequest(from) => Some(*from),
+        PaxosMsg::Handoff(_, upto, _) => Some(*upto),
+        _ => None,
+    }
+}
+
+// everything a message leads to is traced within the round and client request it belongs to
+fn message_span<V: Value, B: Ballot>(
+    id: Id,
+    msg: &RegisterMsg<u64, V, PaxosMsg<V, B>>,
+) -> tracing::Span {
+    let (round, request_id) = match msg {
+        RegisterMsg::Put(request_id, _) | RegisterMsg::Get(request_id) => (None, Some(*request_id)),
+        RegisterMsg::Internal(internal) => match internal {
+            PaxosMsg::Prepare(request_id, _, _, rid)
+            | PaxosMsg::Promise(request_id, _, _, rid, _)
+            | PaxosMsg::WitnessPromise(request_id, _, _, rid, _)
+            | PaxosMsg::Accept(request_id, _, _, rid, _)
+            | PaxosMsg::Accepted(request_id, _, _, rid, _)
+            | PaxosMsg::Nack(request_id, _, rid) => (Some(*rid), Some(*request_id)),
+            PaxosMsg::Decided(_, rid, _)
+            | PaxosMsg::Expire(_, rid, _)
+            | PaxosMsg::Heartbeat(_, rid)
+            | PaxosMsg::Probe(_, rid)
+            | PaxosMsg::ProbeReply(_, rid, _)
+            | PaxosMsg::LeaseRequest(rid)
+            | PaxosMsg::LeaseGrant(rid, _)
+            | PaxosMsg::LeaseExpired(rid)
+            | PaxosMsg::Suspect(rid)
+            | PaxosMsg::Trust(rid)
+            | PaxosMsg::Handoff(rid, _, _) => (Some(*rid), None),
+            PaxosMsg::Forward(request_id, _, _)
+            | PaxosMsg::Reconfigure(request_id, _)
+            | PaxosMsg::Read(request_id, _)
+            | PaxosMsg::ReadReply(request_id, _, _, _) => (None, Some(*request_id)),
+            PaxosMsg::Crash
+            | PaxosMsg::Split
+            | PaxosMsg::Heal
+            | PaxosMsg::Tick
+            | PaxosMsg::Snapshot(_)
+            | PaxosMsg::CatchUpRequest(_)
+            | PaxosMsg::CatchUpReply(_)
+            | PaxosMsg::TransferLeadership(_)
+            | PaxosMsg::Hello(_)
+            | PaxosMsg::Welcome(_) => (None, None),
+        },
+        _ => (None, None),
+    };
+    let span = tracing::debug_span!(
+        "paxos",
+        replica = usize::from(id),
+        round = tracing::field::Empty,
+        request_id = tracing::field::Empty
+    );
+    if let Some(round) = round {
+        span.record("round", &tracing::field::display(round));
+    }
+    if let Some(request_id) = request_id {
+        span.record("request_id", &request_id);
+    }
+    span
+}
+
+impl<V: Value, B: Ballot> Actor for PaxosActor<V, B> {
+    type Msg = RegisterMsg<u64, V, PaxosMsg<V, B>>;
+    type State = PaxosState<V, B>;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        if self.down {
+            return PaxosState::recover(id, self.peers.clone(), DurableState::new());
+        }
+        if self.crashes {
+            // delivered at an arbitrary point, which is when the crash happens
+            o.send(id, RegisterMsg::Internal(PaxosMsg::Crash));
+        }
+        if !self.partition.is_empty() {
+            // the partition forms whenever this is delivered
+            o.send(id, RegisterMsg::Internal(PaxosMsg::Split));
+        }
+        // the hello below may be lost, and goes again on the timer until answered
+        if self.watch.is_some() || self.features.is_some() {
+            o.set_timer(self.heartbeat_interval());
+        }
+        if self.clock.is_logical() {
+            o.send(id, RegisterMsg::Internal(PaxosMsg::Tick));
+        }
+        if let Some(members) = &self.reconfigure {
+            // also delivered at an arbitrary point, and kept clear of client request ids
+            let request_id = u64::MAX - usize::from(id) as u64;
+            let msg = PaxosMsg::Reconfigure(request_id, members.clone());
+            o.send(id, RegisterMsg::Internal(msg));
+        }
+        if let Some(target) = self.transfer {
+            // delivered at an arbitrary point too, and ignored unless we lead by then
+            o.send(
+                id,
+                RegisterMsg::Internal(PaxosMsg::TransferLeadership(target)),
+            );
+        }
+        if let Some(features) = self.features {
+            let msg = RegisterMsg::Internal(PaxosMsg::Hello(features));
+            o.broadcast(&self.peers, &msg);
+        }
+        let durable = match &self.wal {
+            Some(wal) => wal
+                .lock()
+                .unwrap()
+                .load()
+                .expect("unable to read the write-ahead log"),
+            None => DurableState::new(),
+        };
+        let state = PaxosState::recover(id, self.peers.clone(), durable);
+        if self.wal.is_some() {
+            // a restarted replica has to learn whatever was decided while it was down
+            self.catch_up(&state, o);
+        }
+        state
+    }
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        if self.down {
+            return;
+        }
+        let span = message_span(id, &msg);
+        let _entered = span.enter();
+        tracing::debug!(src = usize::from(src), msg = ?msg, "received");
+        match msg {
+            RegisterMsg::Internal(internal_msg) => {
+                // dropped on arrival, as if the network had lost it
+                if state.split && self.partition.contains(&src) {
+                    return;
+                }
+                if self.byzantine && self.forge(src, &internal_msg, o) {
+                    return;
+                }
+                // a witness keeps no values, so it has nothing to learn and nothing to tell
+                if self.is_witness()
+                    && matches!(
+                        internal_msg,
+                        PaxosMsg::Decided(..)
+                            | PaxosMsg::Snapshot(_)
+                            | PaxosMsg::CatchUpRequest(_)
+                            | PaxosMsg::CatchUpReply(_)
+                            | PaxosMsg::Read(..)
+                    )
+                {
+                    return;
+                }
+                if let Some(upto) = decided_upto(&internal_msg) {
+                    if upto > state.reported {
+                        state.to_mut().reported = upto;
+                    }
+                }
+                // slots compacted away are answered with the snapshot that replaced them, and
+                // decided slots, which keep no acceptor state, with their decision
+                if let PaxosMsg::Prepare(_, _, slot, rid) | PaxosMsg::Accept(_, _, slot, rid, _) =
+                    &internal_msg
+                {
+                    if let Some(snapshot) = &state.durable.snapshot {
+                        if *slot < snapshot.next {
+                            let msg = PaxosMsg::Snapshot(snapshot.clone());
+                            o.send(src, RegisterMsg::Internal(msg));
+                            return;
+                        }
+                    }
+                    if let Some(command) = state.durable.log.get(slot) {
+                        let msg = PaxosMsg::Decided(*slot, *rid, command.clone());
+                        o.send(src, RegisterMsg::Internal(msg));
+                        return;
+                    }
+                }
+                match internal_msg {
+                    // request_id is stateright specific while rid is the round identifier
+                    PaxosMsg::Prepare(request_id, org_sender, slot, rid) => {
+                        let last_seen = state.durable.last_seen.get(&slot).copied();
+                        // a redelivered prepare is promised again rather than nacked
+                        let greater = match last_seen {
+                            Some(val) => rid >= val,
+                            None => true,
+                        };
+                        if state.granted_elsewhere(rid) {
+                            // the proposer retries once the read lease has run out
+                            return;
+                        }
+                        if state.below_lease(slot, rid) {
+                            // point the stale proposer at the leader instead of nacking
+                            if let Some(lease) = state.durable.lease {
+                                let msg = RegisterMsg::Internal(PaxosMsg::Heartbeat(
+                                    lease.slot,
+                                    lease.ballot,
+                                ));
+                                o.send(src, msg);
+                            }
+                        } else if greater {
+                            let state = state.to_mut();
+                            self.persist(state, WalRecord::Promise(slot, rid));
+                            tracing::debug!(slot, "promised");
+                            // the proposer we promised before need not wait for its timeout to
+                            // learn that we have moved on
+                            if let Some(seen) = last_seen {
+                                if seen < rid && seen.id() != rid.id() && seen.id() != state.id {
+                                    let msg = PaxosMsg::Expire(slot, seen, rid);
+                                    o.send(seen.id(), RegisterMsg::Internal(msg));
+                                }
+                            }
+                            if self.is_witness() {
+                                let msg = RegisterMsg::Internal(PaxosMsg::WitnessPromise(
+                                    request_id,
+                                    org_sender,
+                                    slot,
+                                    rid,
+                                    state.durable.witnessed.get(&slot).copied(),
+                                ));
+                                o.send(src, msg);
+                                return;
+                            }
+                            let msg = RegisterMsg::Internal(PaxosMsg::Promise(
+                                request_id,
+                                org_sender,
+                                slot,
+                                rid,
+                                state.durable.accepted.get(&slot).cloned(),
+                            ));
+                            o.send(src, msg);
+                        } else if let Some(seen) = last_seen {
+                            let msg = RegisterMsg::Internal(PaxosMsg::Nack(request_id, slot, seen));
+                            o.send(src, msg);
+                        }
+                    }
+
+                    // request_id is stateright specific while rid is the round identifier
+                    PaxosMsg::Promise(_request_id, _org_sender, slot, rid, prev_accepted) => {
+                        self.promised(state.to_mut(), src, slot, rid, o, |ctx| {
+                            // remember the highest numbered value accepted by any acceptor in the
+                            // quorum
+                            if let Some((prev_rid, prev_value)) = prev_accepted {
+                                ctx.adopt(prev_rid, prev_value);
+                            }
+                        });
+                    }
+                    PaxosMsg::WitnessPromise(_request_id, _org_sender, slot, rid, witnessed) => {
+                        self.promised(state.to_mut(), src, slot, rid, o, |ctx| {
+                            if let Some(witnessed) = witnessed {
+                                ctx.witness(witnessed);
+                            }
+                        });
+                    }
+                    PaxosMsg::Probe(slot, rid) => {
+                        // granted if a prepare would be promised, and we are not following a
+                        // leader we still trust
+                        let promised = state
+                            .durable
+                            .last_seen
+                            .get(&slot)
+                            .map_or(true, |seen| rid > *seen);
+                        let following = match state.durable.lease {
+                            Some(lease) => {
+                                lease.ballot.id() != rid.id()
+                                    && state.suspected != Some(lease.ballot)
+                            }
+                            None => false,
+                        };
+                        let granted = promised && !following && !state.granted_elsewhere(rid);
+                        let msg = PaxosMsg::ProbeReply(slot, rid, granted);
+                        o.send(src, RegisterMsg::Internal(msg));
+                    }
+                    PaxosMsg::ProbeReply(slot, rid, granted) => {
+                        let probing = matches!(
+                            state.proposers.get(&(slot, rid)),
+                            Some(ctx) if ctx.phase == Phase::Probe
+                        );
+                        // a refused probe is tried again on the timeout
+                        if !probing || !granted {
+                            return;
+                        }
+                        let state = state.to_mut();
+                        let roster = state.peers_for(slot, self.alpha);
+                        let grants = match state.proposers.get_mut(&(slot, rid)) {
+                            Some(ctx) => {
+                                ctx.promises.insert(&roster, src);
+                                ctx.promises.ids(&roster)
+                            }
+                            None => return,
+                        };
+                        if !self.is_quorum(state, slot, &grants, true) {
+                            return;
+                        }
+                        if let Some(ctx) = state.proposers.remove(&(slot, rid)) {
+                            self.start_round(state, ctx, slot, o);
+                        }
+                    }
+                    PaxosMsg::Accept(request_id, org_sender, slot, rid, value) => {
+                        // a busy leader's accepts stand in for its heartbeats
+                        let leading = state.durable.lease.map(|lease| lease.ballot) == Some(rid);
+                        if leading && rid.id() != state.id {
+                            self.heard_from(state, rid);
+                        }
+                        if state.below_lease(slot, rid) || state.granted_elsewhere(rid) {
+                            return;
+                        }
+                        if Some(&rid) == state.durable.last_seen.get(&slot)
+                            || state.leader_covers(slot, rid)
+                        {
+                            let state = state.to_mut();
+                            let record = if self.is_witness() {
+                                WalRecord::Witness(slot, rid)
+                            } else {
+                                WalRecord::Accept(slot, rid, value.clone())
+                            };
+                            self.persist(state, record);
+                            tracing::debug!(slot, value = ?value, "accepted");
+                            // only the proposer counts accepts, learners hear about the decision
+                            let msg = RegisterMsg::Internal(PaxosMsg::Accepted(
+                                request_id, org_sender, slot, rid, value,
+                            ));
+                            o.send(src, msg);
+                        }
+                    }
+                    PaxosMsg::Accepted(_request_id, _org_sender, slot, rid, value) => {
+                        // an answer makes room in the follower's window, decided slot or not
+                        let acked = state
+                            .unacked
+                            .get(&src)
+                            .map_or(false, |unacked| unacked.contains(&slot));
+                        if acked {
+                            let state = state.to_mut();
+                            if let Some(unacked) = state.unacked.get_mut(&src) {
+                                unacked.remove(&slot);
+                                if unacked.is_empty() {
+                                    state.unacked.remove(&src);
+                                }
+                            }
+                            self.resume(state, src, o);
+                        }
+                        if state.is_decided(slot) {
+                            return;
+                        }
+                        let state = state.to_mut();
+                        let roster = state.peers_for(slot, self.alpha);
+                        // accepts only count for a round still in phase 2
+                        let ctx = match state.proposers.get_mut(&(slot, rid)) {
+                            Some(ctx) if ctx.phase == Phase::Accept => ctx,
+                            _ => return,
+                        };
+                        ctx.accepts.insert(&roster, src);
+                        let accepts = ctx.accepts;
+
+                        let quorum = if self.strategy.majority_acceptor() {
+                            self.is_quorum(state, slot, &accepts.ids(&roster), false)
+                        } else {
+                            !accepts.is_empty()
+                        };
+                        if quorum {
+                            let msg =
+                                RegisterMsg::Internal(PaxosMsg::Decided(slot, rid, value.clone()));
+                            o.broadcast(&state.everyone(), &msg);
+                            self.learn(state, slot, value, o);
+                        }
+                    }
+                    PaxosMsg::Decided(slot, _rid, value) => {
+                        if state.is_decided(slot) {
+                            return;
+                        }
+                        self.learn(state.to_mut(), slot, value, o);
+                    }
+                    PaxosMsg::Nack(request_id, slot, seen) => {
+                        self.metrics.increment(state.id, Counter::NacksReceived, 1);
+                        // only restart if the nack outranks the round we are currently driving
+                        let stale = state.proposers_where(|(s, rid), ctx| {
+                            ctx.request_id == request_id && *s == slot && *rid < seen
+                        });
+                        if stale.is_empty() {
+                            return;
+                        }
+
+                        self.abandon(state.to_mut(), stale, slot, seen, o);
+                    }
+                    PaxosMsg::Expire(slot, rid, seen) => {
+                        let roster = state.peers_for(slot, self.alpha);
+                        let phase = match state.proposers.get(&(slot, rid)) {
+                            Some(ctx) if matches!(ctx.phase, Phase::Prepare | Phase::Accept) => {
+                                ctx.phase
+                            }
+                            _ => return,
+                        };
+                        let state = state.to_mut();
+                        let ctx = match state.proposers.get_mut(&(slot, rid)) {
+                            Some(ctx) => ctx,
+                            None => return,
+                        };
+                        // what the acceptor told us about the round no longer holds
+                        ctx.expired.insert(&roster, src);
+                        ctx.promises.remove(&roster, src);
+                        ctx.accepts.remove(&roster, src);
+                        let expired = ctx.expired.ids(&roster);
+                        let live: BTreeSet<Id> = roster
+                            .iter()
+                            .copied()
+                            .filter(|id| !expired.contains(id))
+                            .collect();
+                        let phase1 = phase == Phase::Prepare;
+                        let majority = if phase1 {
+                            self.strategy.majority_promises()
+                        } else {
+                            self.strategy.majority_acceptor()
+                        };
+                        // the round stays alive while the acceptors yet to move on could finish it
+                        let possible = if majority {
+                            self.is_quorum(state, slot, &live, phase1)
+                        } else {
+                            !live.is_empty()
+                        };
+                        if !possible {
+                            self.abandon(state, vec![(slot, rid)], slot, seen, o);
+                        }
+                    }
+                    PaxosMsg::Heartbeat(slot, ballot) => {
+                        let (newer, current) = match state.durable.lease {
+                            Some(lease) => (ballot > lease.ballot, ballot == lease.ballot),
+                            None => (true, false),
+                        };
+                        if current && ballot.id() != state.id {
+                            self.heard_from(state, ballot);
+                        }
+                        if !newer {
+                            return;
+                        }
+                        let state = state.to_mut();
+                        self.persist(state, WalRecord::Lease(Some(Lease { ballot, slot })));
+                        state.suspected = None;
+                        self.drop_handoff(state, o);
+                        if ballot.id() == state.id {
+                            return;
+                        }
+                        // the detector starts over with the new leader
+                        if let Some(watch) = &self.watch {
+                            let now = self.clock.now(state.ticks);
+                            let mut watch = watch.lock().unwrap();
+                            watch.detector = watch.spec.build();
+                            watch.detector.heartbeat(now);
+                        }
+                        if self.suspicion {
+                            o.send(state.id, RegisterMsg::Internal(PaxosMsg::Suspect(ballot)));
+                        }
+
+                        // proposals the new lease rules out are handed over to the leader
+                        let stranded =
+                            state.proposers_where(|(s, rid), _| *s >= slot && *rid < ballot);
+                        for key in stranded {
+                            if let Some(ctx) = state.proposers.remove(&key) {
+                                // a batch goes over as its separate requests, the leader may batch
+                                // them differently
+                                for (request_id, client, command) in ctx.requests() {
+                                    let msg = RegisterMsg::Internal(PaxosMsg::Forward(
+                                        request_id, client, command,
+                                    ));
+                                    o.send(ballot.id(), msg);
+                                }
+                            }
+                        }
+                    }
+                    // a replica from before the handshake would not understand either
+                    PaxosMsg::Hello(theirs) => {
+                        if let Some(ours) = self.features {
+                            o.send(src, RegisterMsg::Internal(PaxosMsg::Welcome(ours)));
+                            self.advertised(state, src, theirs);
+                        }
+                    }
+                    PaxosMsg::Welcome(theirs) => {
+                        if self.features.is_some() {
+                            self.advertised(state, src, theirs);
+                        }
+                    }
+                    PaxosMsg::TransferLeadership(target) => {
+                        // only the leader can hand over, and only to a replica it would accept
+                        // from
+                        if !state.is_leader()
+                            || state.handoff.is_some()
+                            || target == state.id
+                            || !state.peers.contains(&target)
+                            || !self.speaks(state, target, Features::HANDOFF)
+                        {
+                            return;
+                        }
+                        tracing::info!(target = usize::from(target), "handing off leadership");
+                        let state = state.to_mut();
+                        state.handoff = Some(Handoff {
+                            target,
+                            requester: src,
+                            held: Vec::new(),
+                            sent: false,
+                        });
+                        if state.proposers.is_empty() {
+                            self.hand_off(state, o);
+                        }
+                    }
+                    PaxosMsg::Handoff(ballot, upto, requests) => {
+                        if ballot.id() == state.id {
+                            return;
+                        }
+                        let state = state.to_mut();
+                        // a handoff overtaken by another leader only passes its requests along
+                        let current = state
+                            .durable
+                            .lease
+                            .map_or(true, |lease| ballot >= lease.ballot);
+                        if current {
+                            state.observe_round(ballot);
+                            if let Some(lease) = state.durable.lease {
+                                if lease.ballot.id() != state.id {
+                                    state.suspected = Some(lease.ballot);
+                                }
+                            }
+                            // the old leader's decisions come first, our proposals follow them
+                            if upto > state.applied.next {
+                                let msg = PaxosMsg::CatchUpRequest(state.applied.next);
+                                o.send(src, RegisterMsg::Internal(msg));
+                            }
+                        }
+                        for (request_id, client, command) in requests {
+                            self.put(state, request_id, client, command, o);
+                        }
+                    }
+                    PaxosMsg::Forward(request_id, client, value) => {
+                        self.put(state.to_mut(), request_id, client, value, o);
+                    }
+                    PaxosMsg::Reconfigure(request_id, members) => {
+                        let command = Command::Reconfigure(members);
+                        self.put(state.to_mut(), request_id, src, command, o);
+                    }
+                    PaxosMsg::Snapshot(snapshot) => {
+                        if snapshot.next <= state.applied.next {
+                            return;
+                        }
+                        let state = state.to_mut();
+                        state.applied = snapshot.clone();
+                        state.compact(snapshot);
+                        state.advance_applied(self.observer.as_ref());
+
+                        // our proposals for covered slots were decided one way or another
+                        let compacted = state.compacted();
+                        let covered = state.proposers_where(|(s, _), _| *s < compacted);
+                        for key in covered {
+                            if let Some(ctx) = state.proposers.remove(&key) {
+                                let next = state.next_slot();
+                                self.propose(state, ctx, next, o);
+                            }
+                        }
+                        self.release_waiting(state, o);
+                    }
+                    PaxosMsg::Crash => {
+                        // a fault the model injects, or anyone could wipe a spawned replica
+                        if src != state.id || !self.crashes {
+                            return;
+                        }
+                        // decided slots were never written through, they have to be learned again
+                        let mut recovered = PaxosState::recover(
+                            state.id,
+                            state.peers.clone(),
+                            state.storage.durable().clone(),
+                        );
+                        // a restarted replica waits out any lease it may have granted
+                        recovered.read_grant = state.read_grant;
+                        recovered.grant_until = state.grant_until;
+                        // and its clock kept running
+                        recovered.ticks = state.ticks;
+                        // and is no better connected than before
+                        recovered.split = state.split;
+                        // nor running a different build from its peers, who would be told the
+                        // same again
+                        recovered.features = state.features.clone();
+                        self.catch_up(&recovered, o);
+                        *state.to_mut() = recovered;
+                    }
+                    PaxosMsg::Split => {
+                        // likewise, or anyone could cut a spawned replica off
+                        if src != state.id || self.partition.is_empty() {
+                            return;
+                        }
+                        state.to_mut().split = true;
+                        // heals at an arbitrary point after the split
+                        o.send(state.id, RegisterMsg::Internal(PaxosMsg::Heal));
+                    }
+                    PaxosMsg::Heal => {
+                        if src != state.id || self.partition.is_empty() {
+                            return;
+                        }
+                        state.to_mut().split = false;
+                        self.catch_up(state, o);
+                    }
+                    PaxosMsg::CatchUpRequest(from) => {
+                        if let Some(snapshot) = &state.durable.snapshot {
+                            if from < snapshot.next {
+                                let msg = PaxosMsg::Snapshot(snapshot.clone());
+                                o.send(src, RegisterMsg::Internal(msg));
+                            }
+                        }
+                        let entries = state.decided_from(from);
+                        if !entries.is_empty() {
+                            o.send(src, RegisterMsg::Internal(PaxosMsg::CatchUpReply(entries)));
+                        }
+                        // the asker is further along, so we may be the one missing decisions
+                        if from > state.applied.next {
+                            let msg = PaxosMsg::CatchUpRequest(state.applied.next);
+                            o.send(src, RegisterMsg::Internal(msg));
+                        }
+                    }
+                    PaxosMsg::CatchUpReply(entries) => {
+                        let full = entries.len() == CATCH_UP_BATCH;
+                        let state = state.to_mut();
+                        for (slot, command) in entries {
+                            if !state.is_decided(slot) {
+                                self.learn(state, slot, command, o);
+                            }
+                        }
+                        if full {
+                            let msg = PaxosMsg::CatchUpRequest(state.applied.next);
+                            o.send(src, RegisterMsg::Internal(msg));
+                        }
+                    }
+                    PaxosMsg::Suspect(ballot) => {
+                        if src != state.id || state.suspected == Some(ballot) {
+                            return;
+                        }
+                        self.suspect(state, ballot);
+                        if state.suspected == Some(ballot) {
+                            // the leader may yet be heard from, at an arbitrary point after
+                            o.send(state.id, RegisterMsg::Internal(PaxosMsg::Trust(ballot)));
+                        }
+                    }
+                    PaxosMsg::Trust(ballot) => {
+                        if src == state.id && state.suspected == Some(ballot) {
+                            state.to_mut().suspected = None;
+                        }
+                    }
+                    PaxosMsg::Read(request_id, _slot) => {
+                        let (slot, decided, accepted) = state.latest_slot();
+                        let msg = RegisterMsg::Internal(PaxosMsg::ReadReply(
+                            request_id, slot, decided, accepted,
+                        ));
+                        o.send(src, msg);
+                    }
+                    PaxosMsg::ReadReply(request_id, slot, decided, accepted) => {
+                        if !state.reads.contains_key(&request_id) {
+                            return;
+                        }
+                        let state = state.to_mut();
+                        let read = match state.reads.get_mut(&request_id) {
+                            Some(read) => read,
+                            None => return,
+                        };
+                        read.replies.insert(src);
+                        read.observe(slot, decided, accepted);
+                        let replies = read.replies.clone();
+
+                        // a phase 1 quorum intersects every quorum that could have decided
+                        let next = state.next_slot();
+                        if self.is_quorum(state, next, &replies, true) {
+                            if let Some(read) = state.reads.remove(&request_id) {
+                                let msg = RegisterMsg::GetOk(request_id, read.value());
+                                o.send(read.client, msg);
+                            }
+                        }
+                    }
+                    PaxosMsg::LeaseRequest(ballot) => {
+                        if state.read_grant == Some(ballot) {
+                            let floor = state.accepted_below();
+                            o.send(
+                                src,
+                                RegisterMsg::Internal(PaxosMsg::LeaseGrant(ballot, floor)),
+                            );
+                            return;
+                        }
+                        let following = match state.durable.lease {
+                            Some(lease) => lease.ballot <= ballot,
+                            None => true,
+                        };
+                        if state.read_grant.is_some() || !following {
+                            return;
+                        }
+                        let state = state.to_mut();
+                        state.read_grant = Some(ballot);
+                        let floor = state.accepted_below();
+                        o.send(
+                            src,
+                            RegisterMsg::Internal(PaxosMsg::LeaseGrant(ballot, floor)),
+                        );
+                        if let Some((duration, _)) = self.lease_timing {
+                            state.grant_until = Some(self.clock.now(state.ticks) + duration);
+                        } else if self.read_leases == Some(ClockSkew::Unbounded) {
+                            // our own clock decides when the grant ends, regardless of the leader's
+                            o.send(
+                                state.id,
+                                RegisterMsg::Internal(PaxosMsg::LeaseExpired(ballot)),
+                            );
+                        }
+                    }
+                    PaxosMsg::LeaseGrant(ballot, floor) => {
+                        let state = state.to_mut();
+                        let lease = match &mut state.read_lease {
+                            Some(lease) if lease.ballot == ballot => lease,
+                            _ => return,
+                        };
+                        lease.grants.insert(src);
+                        lease.floor = lease.floor.max(floor);
+                    }
+                    PaxosMsg::LeaseExpired(ballot) => {
+                        let holder = src == state.id && ballot.id() == state.id;
+                        let grant_ends = state.read_grant == Some(ballot)
+                            && match self.read_leases {
+                                Some(ClockSkew::Bounded) => src == ballot.id(),
+                                _ => src == state.id,
+                            };
+                        if !holder && !grant_ends {
+                            return;
+                        }
+                        let state = state.to_mut();
+                        if grant_ends {
+                            state.read_grant = None;
+                        }
+                        if holder {
+                            if state.read_lease.as_ref().map(|lease| lease.ballot) == Some(ballot) {
+                                state.read_lease = None;
+                            }
+                            // within the skew bound every grantor's clock runs out after ours
+                            if self.read_leases == Some(ClockSkew::Bounded) {
+                                let msg = RegisterMsg::Internal(PaxosMsg::LeaseExpired(ballot));
+                                o.broadcast(&state.everyone(), &msg);
+                            }
+                        }
+                    }
+                    PaxosMsg::Tick => {
+                        if src != state.id || !self.clock.is_logical() {
+                            return;
+                        }
+                        let state = state.to_mut();
+                        state.ticks += 1;
+                        self.expire_leases(state);
+                        // the next tick, unless the network keeps this one around anyway
+                        o.send(state.id, RegisterMsg::Internal(PaxosMsg::Tick));
+                    }
+                }
+            }
+            RegisterMsg::Put(request_id, value) => {
+                let command = if self.sessions {
+                    Command::SessionPut(Session::new(src, request_id), value)
+                } else {
+                    Command::Put(value)
+                };
+                self.put(state.to_mut(), request_id, src, command, o);
+            }
+            RegisterMsg::Get(request_id) => {
+                // a witness has no register to read, the client has to ask another server
+                if self.is_witness() || state.handled.contains(&request_id) {
+                    return;
+                }
+                if self.stale_reads || self.reads_locally(state) {
+                    let state = state.to_mut();
+                    state.handled.insert(request_id);
+                    o.send(
+                        src,
+                        RegisterMsg::GetOk(request_id, state.applied.value.clone()),
+                    );
+                    return;
+                }
+                // without a lease the log may still grow, so the read needs a quorum
+                let state = state.to_mut();
+                state.handled.insert(request_id);
+                let (slot, decided, accepted) = state.latest_slot();
+                state.reads.insert(
+                    request_id,
+                    PendingRead {
+                        client: src,
+                        replies: BTreeSet::new(),
+                        slot,
+                        decided,
+                        highest_accepted: accepted,
+                    },
+                );
+                let peers = state.peers_for(state.next_slot(), self.alpha);
+                o.broadcast(
+                    &peers,
+                    &RegisterMsg::Internal(PaxosMsg::Read(request_id, slot)),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn on_timeout(&self, _: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        if self.down {
+            return;
+        }
+        if self.lease_timing.is_some() && !self.clock.is_logical() {
+            self.expire_leases(state.to_mut());
+        }
+        let unwelcomed = self.unwelcomed(state);
+        if let Some(features) = self.features.filter(|_| !unwelcomed.is_empty()) {
+            let msg = RegisterMsg::Internal(PaxosMsg::Hello(features));
+            o.broadcast(&unwelcomed, &msg);
+        }
+        if let (Some(watch), Some(lease)) = (&self.watch, state.durable.lease) {
+            let handed_off = state.handoff.as_ref().map_or(false, |handoff| handoff.sent);
+            if lease.ballot.id() == state.id {
+                // a leader that has handed off leaves its followers to suspect it
+                if handed_off {
+                    return;
+                }
+                let msg = RegisterMsg::Internal(PaxosMsg::Heartbeat(lease.slot, lease.ballot));
+                o.broadcast(&state.everyone(), &msg);
+            } else {
+                let now = self.clock.now(state.ticks);
+                let suspects = watch.lock().unwrap().detector.suspects(now);
+                if suspects {
+                    self.suspect(state, lease.ballot);
+                }
+            }
+        }
+        if state.proposers.is_empty() {
+            if self.watch.is_some() || !unwelcomed.is_empty() {
+                o.set_timer(self.heartbeat_interval());
+            }
+            return;
+        }
+        // no quorum answered in time, so every outstanding proposal goes again with a higher round
+        let state = state.to_mut();
+        // followers that never answered for decided slots learn them some other way, and the
+        // proposals going again are sent to everyone
+        state.unacked.clear();
+        if let Some(backoff) = &self.backoff {
+            backoff.lock().unwrap().retries += 1;
+        }
+        // held back proposals have nothing in flight yet
+        let outstanding = state.proposers_where(|_, ctx| ctx.phase != Phase::Waiting);
+        for (slot, rid) in outstanding {
+            if let Some(ctx) = state.proposers.remove(&(slot, rid)) {
+                self.prepare(state, ctx, slot, o);
+            }
+        }
+    }
+}
+
+// symmetry reduction renames servers, so everything holding an `Id` has to follow; member
+// lists are kept sorted so renamed configurations compare equal
+fn rewrite_members<S>(ids: &[Id], plan: &RewritePlan<Id, S>) -> Vec<Id> {
+    let mut ids: Vec<Id> = ids.iter().map(|id| plan.rewrite(id)).collect();
+    ids.sort();
+    ids
+}
+
+fn rewrite_set<S>(ids: &BTreeSet<Id>, plan: &RewritePlan<Id, S>) -> BTreeSet<Id> {
+    ids.iter().map(|id| plan.rewrite(id)).collect()
+}
+
+impl<V: Clone> Rewrite<Id> for Command<V> {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        match self {
+            Command::Put(value) => Command::Put(value.clone()),
+            Command::SessionPut(session, value) => {
+                Command::SessionPut(session.rewrite(plan), value.clone())
+            }
+            Command::Batch(values) => Command::Batch(values.clone()),
+            Command::Reconfigure(ids) => Command::Reconfigure(rewrite_members(ids, plan)),
+        }
+    }
+}
+
+impl<V: Clone> Rewrite<Id> for Applied<V> {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        Applied {
+            next: self.next,
+            value: self.value.clone(),
+            members: self
+                .members
+                .as_ref()
+                .map(|(slot, ids)| (*slot, rewrite_members(ids, plan))),
+            sessions: self
+                .sessions
+                .iter()
+                .map(|(client, seq)| (plan.rewrite(client), *seq))
+                .collect(),
+        }
+    }
+}
+
+impl Rewrite<Id> for Session {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        Session {
+            client: plan.rewrite(&self.client),
+            seq: self.seq,
+        }
+    }
+}
+
+impl<B: Ballot> Rewrite<Id> for Lease<B> {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        Lease {
+            ballot: self.ballot.rewrite(plan),
+            slot: self.slot,
+        }
+    }
+}
+
+impl<B: Ballot> Rewrite<Id> for ReadLease<B> {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        ReadLease {
+            ballot: self.ballot.rewrite(plan),
+            grants: rewrite_set(&self.grants, plan),
+            floor: self.floor,
+            expires: self.expires,
+        }
+    }
+}
+
+impl<V: Clone, B: Ballot> Rewrite<Id> for DurableState<V, B> {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        DurableState {
+            round: self.round,
+            last_seen: self
+                .last_seen
+                .iter()
+                .map(|(slot, rid)| (*slot, rid.rewrite(plan)))
+                .collect(),
+            accepted: self
+                .accepted
+                .iter()
+                .map(|(slot, (rid, command))| (*slot, (rid.rewrite(plan), command.rewrite(plan))))
+                .collect(),
+            witnessed: self
+                .witnessed
+                .iter()
+                .map(|(slot, rid)| (*slot, rid.rewrite(plan)))
+                .collect(),
+            log: self
+                .log
+                .iter()
+                .map(|(slot, command)| (*slot, command.rewrite(plan)))
+                .collect(),
+            snapshot: self
+                .snapshot
+                .as_ref()
+                .map(|snapshot| snapshot.rewrite(plan)),
+            lease: self.lease.map(|lease| lease.rewrite(plan)),
+        }
+    }
+}
+
+impl<V: Clone, B: Ballot> ProposerContext<V, B> {
+    // votes are bits in roster order, so renaming has to go through the roster; symmetry
+    // reduction leaves reconfiguration out, which leaves every slot with the initial acceptors
+    fn rewrite<S>(&self, roster: &[Id], renamed: &[Id], plan: &RewritePlan<Id, S>) -> Self {
+        ProposerContext {
+            request_id: self.request_id,
+            client: plan.rewrite(&self.client),
+            value: self.value.rewrite(plan),
+            phase: self.phase,
+            promises: self.promises.rewrite(roster, renamed, plan),
+            adopted: self
+                .adopted
+                .as_ref()
+                .map(|(rid, command)| (rid.rewrite(plan), command.rewrite(plan))),
+            witnessed: self.witnessed.as_ref().map(|rid| rid.rewrite(plan)),
+            accepts: self.accepts.rewrite(roster, renamed, plan),
+            expired: self.expired.rewrite(roster, renamed, plan),
+            riders: self
+                .riders
+                .iter()
+                .map(|(request_id, client)| (*request_id, plan.rewrite(client)))
+                .collect(),
+        }
+    }
+}
+
+impl<V: Clone, B: Ballot> Rewrite<Id> for PendingRead<V, B> {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        PendingRead {
+            client: plan.rewrite(&self.client),
+            replies: rewrite_set(&self.replies, plan),
+            slot: self.slot,
+            decided: self.decided.clone(),
+            highest_accepted: self
+                .highest_accepted
+                .as_ref()
+                .map(|(rid, value)| (rid.rewrite(plan), value.clone())),
+        }
+    }
+}
+
+impl<V: Clone, B: Ballot> Rewrite<Id> for PaxosState<V, B> {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        let peers = rewrite_members(&self.peers, plan);
+        PaxosState {
+            id: plan.rewrite(&self.id),
+            peers: peers.clone(),
+            durable: self.durable.rewrite(plan),
+            storage: self.storage.rewrite(plan),
+            applied: self.applied.rewrite(plan),
+            proposers: self
+                .proposers
+                .iter()
+                .map(|((slot, rid), ctx)| {
+                    let ctx = ctx.rewrite(&self.peers, &peers, plan);
+                    ((*slot, rid.rewrite(plan)), ctx)
+                })
+                .collect(),
+            reads: self
+                .reads
+                .iter()
+                .map(|(request_id, read)| (*request_id, read.rewrite(plan)))
+                .collect(),
+            handled: self.handled.clone(),
+            read_lease: self.read_lease.as_ref().map(|lease| lease.rewrite(plan)),
+            read_grant: self.read_grant.map(|rid| rid.rewrite(plan)),
+            grant_until: self.grant_until,
+            ticks: self.ticks,
+            unacked: self
+                .unacked
+                .iter()
+                .map(|(follower, slots)| (plan.rewrite(follower), slots.clone()))
+                .collect(),
+            split: self.split,
+            suspected: self.suspected.map(|rid| rid.rewrite(plan)),
+            handoff: self.handoff.as_ref().map(|handoff| Handoff {
+                target: plan.rewrite(&handoff.target),
+                requester: plan.rewrite(&handoff.requester),
+                held: rewrite_requests(&handoff.held, plan),
+                sent: handoff.sent,
+            }),
+            features: self
+                .features
+                .iter()
+                .map(|(peer, features)| (plan.rewrite(peer), *features))
+                .collect(),
+            reported: self.reported,
+        }
+    }
+}
+
+fn rewrite_requests<V: Clone, S>(
+    requests: &[(u64, Id, Command<V>)],
+    plan: &RewritePlan<Id, S>,
+) -> Vec<(u64, Id, Command<V>)> {
+    requests
+        .iter()
+        .map(|(request_id, client, command)| {
+            (*request_id, plan.rewrite(client), command.rewrite(plan))
+        })
+        .collect()
+}
+
+impl<V: Clone, B: Ballot> Rewrite<Id> for PaxosMsg<V, B> {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        let rewrite_accepted = |accepted: &Option<(B, Command<V>)>| {
+            accepted
+                .as_ref()
+                .map(|(rid, command)| (rid.rewrite(plan), command.rewrite(plan)))
+        };
+        match self {
+            PaxosMsg::Prepare(request_id, org_sender, slot, rid) => PaxosMsg::Prepare(
+                *request_id,
+                plan.rewrite(org_sender),
+                *slot,
+                rid.rewrite(plan),
+            ),
+            PaxosMsg::Promise(request_id, org_sender, slot, rid, accepted) => PaxosMsg::Promise(
+                *request_id,
+                plan.rewrite(org_sender),
+                *slot,
+                rid.rewrite(plan),
+                rewrite_accepted(accepted),
+            ),
+            PaxosMsg::WitnessPromise(request_id, org_sender, slot, rid, witnessed) => {
+                PaxosMsg::WitnessPromise(
+                    *request_id,
+                    plan.rewrite(org_sender),
+                    *slot,
+                    rid.rewrite(plan),
+                    witnessed.as_ref().map(|witnessed| witnessed.rewrite(plan)),
+                )
+            }
+            PaxosMsg::Accept(request_id, org_sender, slot, rid, command) => PaxosMsg::Accept(
+                *request_id,
+                plan.rewrite(org_sender),
+                *slot,
+                rid.rewrite(plan),
+                command.rewrite(plan),
+            ),
+            PaxosMsg::Accepted(request_id, org_sender, slot, rid, command) => PaxosMsg::Accepted(
+                *request_id,
+                plan.rewrite(org_sender),
+                *slot,
+                rid.rewrite(plan),
+                command.rewrite(plan),
+            ),
+            PaxosMsg::Nack(request_id, slot, rid) => {
+                PaxosMsg::Nack(*request_id, *slot, rid.rewrite(plan))
+            }
+            PaxosMsg::Expire(slot, rid, seen) => {
+                PaxosMsg::Expire(*slot, rid.rewrite(plan), seen.rewrite(plan))
+            }
+            PaxosMsg::Decided(slot, rid, command) => {
+                PaxosMsg::Decided(*slot, rid.rewrite(plan), command.rewrite(plan))
+            }
+            PaxosMsg::Heartbeat(slot, rid) => PaxosMsg::Heartbeat(*slot, rid.rewrite(plan)),
+            PaxosMsg::Forward(request_id, client, command) => {
+                PaxosMsg::Forward(*request_id, plan.rewrite(client), command.rewrite(plan))
+            }
+            PaxosMsg::Reconfigure(request_id, ids) => {
+                PaxosMsg::Reconfigure(*request_id, rewrite_members(ids, plan))
+            }
+            PaxosMsg::Crash => PaxosMsg::Crash,
+            PaxosMsg::Split => PaxosMsg::Split,
+            PaxosMsg::Heal => PaxosMsg::Heal,
+            PaxosMsg::Snapshot(snapshot) => PaxosMsg::Snapshot(snapshot.rewrite(plan)),
+            PaxosMsg::Read(request_id, slot) => PaxosMsg::Read(*request_id, *slot),
+            PaxosMsg::ReadReply(request_id, slot, decided, accepted) => PaxosMsg::ReadReply(
+                *request_id,
+                *slot,
+                decided.clone(),
+                accepted
+                    .as_ref()
+                    .map(|(rid, value)| (rid.rewrite(plan), value.clone())),
+            ),
+            PaxosMsg::LeaseRequest(rid) => PaxosMsg::LeaseRequest(rid.rewrite(plan)),
+            PaxosMsg::LeaseGrant(rid, floor) => PaxosMsg::LeaseGrant(rid.rewrite(plan), *floor),
+            PaxosMsg::LeaseExpired(rid) => PaxosMsg::LeaseExpired(rid.rewrite(plan)),
+            PaxosMsg::Tick => PaxosMsg::Tick,
+            PaxosMsg::Probe(slot, rid) => PaxosMsg::Probe(*slot, rid.rewrite(plan)),
+            PaxosMsg::ProbeReply(slot, rid, granted) => {
+                PaxosMsg::ProbeReply(*slot, rid.rewrite(plan), *granted)
+            }
+            PaxosMsg::Suspect(rid) => PaxosMsg::Suspect(rid.rewrite(plan)),
+            PaxosMsg::Trust(rid) => PaxosMsg::Trust(rid.rewrite(plan)),
+            PaxosMsg::CatchUpRequest(slot) => PaxosMsg::CatchUpRequest(*slot),
+            PaxosMsg::CatchUpReply(entries) => PaxosMsg::CatchUpReply(
+                entries
+                    .iter()
+                    .map(|(slot, command)| (*slot, command.rewrite(plan)))
+                    .collect(),
+            ),
+            PaxosMsg::TransferLeadership(target) => {
+                PaxosMsg::TransferLeadership(plan.rewrite(target))
+            }
+            PaxosMsg::Handoff(rid, slot, requests) => {
+                PaxosMsg::Handoff(rid.rewrite(plan), *slot, rewrite_requests(requests, plan))
+            }
+            PaxosMsg::Hello(features) => PaxosMsg::Hello(*features),
+            PaxosMsg::Welcome(features) => PaxosMsg::Welcome(*features),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::quorum::QuorumSystem;
+use serde::{Deserialize, Serialize};
+use stateright::actor::Id;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+
+// scaffolding for exploring what it takes to survive a byzantine acceptor. signatures stop
+// a faulty acceptor from speaking for anyone else, but not from lying about its own state,
+// so quorums also have to grow to 2f + 1 out of 3f + 1 acceptors for any two of them to
+// share an honest acceptor
+
+// a message together with who vouches for it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Signed<M> {
+    msg: M,
+    signer: Id,
+    signature: u64,
+}
+
+impl<M> Signed<M> {
+    pub fn msg(&self) -> &M {
+        &self.msg
+    }
+
+    pub fn signer(&self) -> Id {
+        self.signer
+    }
+}
+
+// every acceptor's secret, as known to a trusted setup; a keyed hash stands in for real
+// signatures, which is plenty for the model checker but not for a deployment
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyRing {
+    keys: BTreeMap<Id, u64>,
+}
+
+impl KeyRing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(mut self, id: Id, key: u64) -> Self {
+        self.keys.insert(id, key);
+        self
+    }
+
+    fn digest<M: Hash>(key: u64, signer: Id, msg: &M) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        signer.hash(&mut hasher);
+        msg.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn sign<M: Hash>(&self, signer: Id, msg: M) -> Option<Signed<M>> {
+        let key = *self.keys.get(&signer)?;
+        let signature = Self::digest(key, signer, &msg);
+        Some(Signed {
+            msg,
+            signer,
+            signature,
+        })
+    }
+
+    pub fn verify<M: Hash>(&self, signed: &Signed<M>) -> bool {
+        match self.keys.get(&signed.signer) {
+            Some(key) => Self::digest(*key, signed.signer, &signed.msg) == signed.signature,
+            None => false,
+        }
+    }
+
+    // distinct signers whose signatures check out, forged or repeated ones count for nothing
+    pub fn signers<M: Hash>(&self, msgs: &[Signed<M>]) -> BTreeSet<Id> {
+        msgs.iter()
+            .filter(|signed| self.verify(signed))
+            .map(|signed| signed.signer)
+            .collect()
+    }
+}
+
+// 2f + 1 out of 3f + 1, so any two quorums overlap in at least f + 1 acceptors
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ByzantineQuorum {
+    acceptors: usize,
+}
+
+impl ByzantineQuorum {
+    pub fn new(acceptors: usize) -> Self {
+        ByzantineQuorum { acceptors }
+    }
+
+    // how many faulty acceptors the cluster tolerates
+    pub fn faults(&self) -> usize {
+        self.acceptors.saturating_sub(1) / 3
+    }
+}
+
+impl QuorumSystem for ByzantineQuorum {
+    fn is_phase1_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        ids.len() > 2 * self.faults()
+    }
+
+    fn is_phase2_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        ids.len() > 2 * self.faults()
+    }
+}
@@ -0,0 +1,316 @@
+use crate::topology::Topology;
+use serde::{Deserialize, Serialize};
+use stateright::actor::Id;
+use stateright::RewritePlan;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+// Which acceptors answered a round, as bits in the order of a roster, the acceptors of the
+// round's slot. A proposal keeps one of these per phase rather than a set of ids, so a state
+// hashes a word where it hashed a length and every id, and rosters past 64 acceptors are not
+// supported. The roster is not kept alongside, whoever asks has to pass the same one in.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct Votes(u64);
+
+impl Votes {
+    pub const CAPACITY: usize = 64;
+
+    // whether `id` was on the roster, and so counted
+    pub fn insert(&mut self, roster: &[Id], id: Id) -> bool {
+        match roster.iter().position(|member| *member == id) {
+            Some(index) if index < Self::CAPACITY => {
+                self.0 |= 1 << index;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn remove(&mut self, roster: &[Id], id: Id) {
+        if let Some(index) = roster.iter().position(|member| *member == id) {
+            if index < Self::CAPACITY {
+                self.0 &= !(1 << index);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.0 = 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    // the ids behind the bits, for the quorum systems
+    pub fn ids(&self, roster: &[Id]) -> BTreeSet<Id> {
+        roster
+            .iter()
+            .take(Self::CAPACITY)
+            .enumerate()
+            .filter(|(index, _)| self.0 & (1 << index) != 0)
+            .map(|(_, id)| *id)
+            .collect()
+    }
+
+    // the same votes once the roster's ids are renamed, with `renamed` the new roster
+    pub fn rewrite<S>(&self, roster: &[Id], renamed: &[Id], plan: &RewritePlan<Id, S>) -> Self {
+        let mut votes = Votes::default();
+        for id in self.ids(roster) {
+            votes.insert(renamed, plan.rewrite(&id));
+        }
+        votes
+    }
+}
+
+impl fmt::Debug for Votes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let indices = (0..Self::CAPACITY).filter(|index| self.0 & (1 << index) != 0);
+        f.debug_set().entries(indices).finish()
+    }
+}
+
+// decides which sets of acceptors may complete each phase; safety needs every
+// phase 1 quorum to intersect every phase 2 quorum
+pub trait QuorumSystem: Send + Sync {
+    fn is_phase1_quorum(&self, ids: &BTreeSet<Id>) -> bool;
+    fn is_phase2_quorum(&self, ids: &BTreeSet<Id>) -> bool;
+
+    // brute force over every pair of subsets, only meant for model sized clusters
+    fn intersects(&self, acceptors: &[Id]) -> bool {
+        let subsets: Vec<BTreeSet<Id>> = (0..1u64 << acceptors.len())
+            .map(|mask| {
+                acceptors
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| mask & (1 << i) != 0)
+                    .map(|(_, id)| *id)
+                    .collect()
+            })
+            .collect();
+        let phase1: Vec<&BTreeSet<Id>> = subsets
+            .iter()
+            .filter(|s| self.is_phase1_quorum(s))
+            .collect();
+        let phase2: Vec<&BTreeSet<Id>> = subsets
+            .iter()
+            .filter(|s| self.is_phase2_quorum(s))
+            .collect();
+        phase1
+            .iter()
+            .all(|q1| phase2.iter().all(|q2| !q1.is_disjoint(q2)))
+    }
+}
+
+// classic Paxos, any strict majority for both phases
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Majority {
+    acceptors: usize,
+}
+
+impl Majority {
+    pub fn new(acceptors: usize) -> Self {
+        Majority { acceptors }
+    }
+}
+
+impl QuorumSystem for Majority {
+    fn is_phase1_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        ids.len() > self.acceptors / 2
+    }
+
+    fn is_phase2_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        ids.len() > self.acceptors / 2
+    }
+}
+
+// Flexible Paxos, only safe while phase1 + phase2 > number of acceptors
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Flexible {
+    phase1: usize,
+    phase2: usize,
+}
+
+impl Flexible {
+    pub fn new(phase1: usize, phase2: usize) -> Self {
+        Flexible { phase1, phase2 }
+    }
+}
+
+impl QuorumSystem for Flexible {
+    fn is_phase1_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        ids.len() >= self.phase1
+    }
+
+    fn is_phase2_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        ids.len() >= self.phase2
+    }
+}
+
+// acceptors laid out in rows: phase 1 needs a whole row, phase 2 one acceptor from every row
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid {
+    rows: Vec<BTreeSet<Id>>,
+}
+
+impl Grid {
+    pub fn new(rows: Vec<BTreeSet<Id>>) -> Self {
+        Grid { rows }
+    }
+
+    // fills rows of `row_len` acceptors in order, the last row may be short
+    pub fn with_row_len(acceptors: &[Id], row_len: usize) -> Self {
+        let rows = acceptors
+            .chunks(row_len.max(1))
+            .map(|row| row.iter().copied().collect())
+            .collect();
+        Grid { rows }
+    }
+}
+
+impl QuorumSystem for Grid {
+    fn is_phase1_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        self.rows.iter().any(|row| row.is_subset(ids))
+    }
+
+    fn is_phase2_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        self.rows.iter().all(|row| !row.is_disjoint(ids))
+    }
+}
+
+// a majority of the acceptors in each of a majority of regions; any two such quorums share a
+// region and so an acceptor within it, and losing a minority of regions outright still leaves one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Regions {
+    regions: Vec<BTreeSet<Id>>,
+}
+
+impl Regions {
+    pub fn new(acceptors: &[Id], topology: &Topology) -> Self {
+        Regions {
+            regions: topology.partition(acceptors),
+        }
+    }
+
+    fn is_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        let won = self
+            .regions
+            .iter()
+            .filter(|region| region.intersection(ids).count() > region.len() / 2)
+            .count();
+        won > self.regions.len() / 2
+    }
+}
+
+impl QuorumSystem for Regions {
+    fn is_phase1_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        self.is_quorum(ids)
+    }
+
+    fn is_phase2_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        self.is_quorum(ids)
+    }
+}
+
+// One vote per region, cast by the first of its acceptors, and a majority of the votes. The rest
+// of a region's acceptors still replicate but count for nothing, so a region never outvotes
+// another by being bigger and a quorum never waits on a second acceptor in the same region.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegionVoters {
+    voters: BTreeSet<Id>,
+}
+
+impl RegionVoters {
+    pub fn new(acceptors: &[Id], topology: &Topology) -> Self {
+        RegionVoters {
+            voters: topology
+                .partition(acceptors)
+                .iter()
+                .filter_map(|region| region.first().copied())
+                .collect(),
+        }
+    }
+}
+
+impl QuorumSystem for RegionVoters {
+    fn is_phase1_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        self.voters.intersection(ids).count() > self.voters.len() / 2
+    }
+
+    fn is_phase2_quorum(&self, ids: &BTreeSet<Id>) -> bool {
+        self.voters.intersection(ids).count() > self.voters.len() / 2
+    }
+}
+
+// a quorum system described independently of the acceptors it is applied to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QuorumSpec {
+    Majority,
+    Flexible { phase1: usize, phase2: usize },
+    Grid { row_len: usize },
+    Regions,
+    RegionVoters,
+}
+
+impl QuorumSpec {
+    // without a topology every acceptor is its own region, so the region systems are majorities
+    pub fn build(&self, acceptors: &[Id]) -> Box<dyn QuorumSystem> {
+        self.build_in(acceptors, &Topology::default())
+    }
+
+    pub fn build_in(&self, acceptors: &[Id], topology: &Topology) -> Box<dyn QuorumSystem> {
+        match *self {
+            QuorumSpec::Majority => Box::new(Majority::new(acceptors.len())),
+            QuorumSpec::Flexible { phase1, phase2 } => Box::new(Flexible::new(phase1, phase2)),
+            QuorumSpec::Grid { row_len } => Box::new(Grid::with_row_len(acceptors, row_len)),
+            QuorumSpec::Regions => Box::new(Regions::new(acceptors, topology)),
+            QuorumSpec::RegionVoters => Box::new(RegionVoters::new(acceptors, topology)),
+        }
+    }
+}
+
+impl FromStr for QuorumSpec {
+    type Err = String;
+
+    // majority, flexible:<phase1>:<phase2>, grid:<row_len>, regions or one-per-region
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let num = |part: &str| {
+            part.parse::<usize>()
+                .map_err(|e| format!("invalid quorum size {}: {}", part, e))
+        };
+        match parts.as_slice() {
+            ["majority"] => Ok(QuorumSpec::Majority),
+            ["flexible", phase1, phase2] => Ok(QuorumSpec::Flexible {
+                phase1: num(phase1)?,
+                phase2: num(phase2)?,
+            }),
+            ["grid", row_len] => Ok(QuorumSpec::Grid {
+                row_len: num(row_len)?,
+            }),
+            ["regions"] => Ok(QuorumSpec::Regions),
+            ["one-per-region"] => Ok(QuorumSpec::RegionVoters),
+            _ => Err(format!(
+                "unknown quorum system {}, expected majority, flexible:<phase1>:<phase2>, grid:<row_len>, regions or one-per-region",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for QuorumSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuorumSpec::Majority => write!(f, "majority"),
+            QuorumSpec::Flexible { phase1, phase2 } => write!(f, "flexible:{}:{}", phase1, phase2),
+            QuorumSpec::Grid { row_len } => write!(f, "grid:{}", row_len),
+            QuorumSpec::Regions => write!(f, "regions"),
+            QuorumSpec::RegionVoters => write!(f, "one-per-region"),
+        }
+    }
+}
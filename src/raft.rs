@@ -0,0 +1,437 @@
+use crate::paxos::Value;
+use serde::{Deserialize, Serialize};
+use stateright::actor::{register::*, *};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::time::Duration;
+
+pub type Term = u64;
+
+// a client request replicated through the log, reads included so they are linearizable
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Entry<V> {
+    term: Term,
+    request_id: u64,
+    client: Id,
+    // the server the client is waiting on, which is the only one to reply
+    origin: Id,
+    // None is a read
+    op: Option<V>,
+}
+
+impl<V> Entry<V> {
+    pub fn term(&self) -> Term {
+        self.term
+    }
+
+    pub fn op(&self) -> Option<&V> {
+        self.op.as_ref()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum Role {
+    Follower,
+    Candidate {
+        votes: BTreeSet<Id>,
+    },
+    Leader {
+        // log indexes are 1 based, 0 meaning nothing
+        next: BTreeMap<Id, usize>,
+        matched: BTreeMap<Id, usize>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct RaftState<V> {
+    id: Id,
+    term: Term,
+    voted_for: Option<Id>,
+    log: Vec<Entry<V>>,
+    commit: usize,
+    applied: usize,
+    register: V,
+    role: Role,
+    leader: Option<Id>,
+    // requests that arrived before any leader was known
+    pending: Vec<Entry<V>>,
+    // client requests already taken on, so redelivered requests are not served twice
+    handled: BTreeSet<u64>,
+}
+
+impl<V: Value> RaftState<V> {
+    pub fn term(&self) -> Term {
+        self.term
+    }
+
+    pub fn role(&self) -> &Role {
+        &self.role
+    }
+
+    // the entries a majority has stored, which can never be overwritten
+    pub fn committed(&self) -> &[Entry<V>] {
+        &self.log[..self.commit]
+    }
+
+    fn last_log(&self) -> (usize, Term) {
+        (
+            self.log.len(),
+            self.log.last().map_or(0, |entry| entry.term),
+        )
+    }
+
+    fn term_at(&self, index: usize) -> Option<Term> {
+        match index {
+            0 => Some(0),
+            _ => self.log.get(index - 1).map(|entry| entry.term),
+        }
+    }
+
+    // a higher term from anyone turns us back into a follower
+    fn observe_term(&mut self, term: Term) {
+        if term > self.term {
+            self.term = term;
+            self.voted_for = None;
+            self.role = Role::Follower;
+            self.leader = None;
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum RaftMsg<V> {
+    // term, last log index, last log term
+    RequestVote(Term, usize, Term),
+    Vote(Term, bool),
+    // term, previous index, previous term, entries, leader commit
+    Append(Term, usize, Term, Vec<Entry<V>>, usize),
+    // term, success, highest index known to match the leader
+    AppendReply(Term, bool, usize),
+    Forward(Entry<V>),
+}
+
+// Raft behind the same register interface as `PaxosActor`, so both can be checked by one harness
+pub struct RaftActor<V> {
+    peers: Vec<Id>,
+    // followers wait this long for a leader before standing for election
+    election_timeout: Range<Duration>,
+    heartbeat: Range<Duration>,
+    _value: std::marker::PhantomData<V>,
+}
+
+impl<V: Value> RaftActor<V> {
+    pub fn new(peers: Vec<Id>) -> Self {
+        RaftActor {
+            peers,
+            election_timeout: Duration::from_millis(500)..Duration::from_millis(1000),
+            heartbeat: Duration::from_millis(100)..Duration::from_millis(200),
+            _value: std::marker::PhantomData,
+        }
+    }
+
+    pub fn election_timeout(mut self, election_timeout: Range<Duration>) -> Self {
+        self.election_timeout = election_timeout;
+        self
+    }
+
+    pub fn heartbeat(mut self, heartbeat: Range<Duration>) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    // counting ourselves, since peers never include this actor
+    fn is_majority(&self, count: usize) -> bool {
+        count > (self.peers.len() + 1) / 2
+    }
+
+    fn append_for(&self, state: &RaftState<V>, peer: Id) -> RegisterMsg<u64, V, RaftMsg<V>> {
+        let next = match &state.role {
+            Role::Leader { next, .. } => next.get(&peer).copied().unwrap_or(1),
+            _ => state.log.len() + 1,
+        };
+        let prev = next - 1;
+        let prev_term = state.term_at(prev).unwrap_or(0);
+        RegisterMsg::Internal(RaftMsg::Append(
+            state.term,
+            prev,
+            prev_term,
+            state.log[prev..].to_vec(),
+            state.commit,
+        ))
+    }
+
+    fn replicate(&self, state: &RaftState<V>, o: &mut Out<Self>) {
+        for peer in &self.peers {
+            o.send(*peer, self.append_for(state, *peer));
+        }
+    }
+
+    fn become_leader(&self, state: &mut RaftState<V>, o: &mut Out<Self>) {
+        let next_index = state.log.len() + 1;
+        state.role = Role::Leader {
+            next: self.peers.iter().map(|peer| (*peer, next_index)).collect(),
+            matched: self.peers.iter().map(|peer| (*peer, 0)).collect(),
+        };
+        state.leader = Some(state.id);
+        for entry in std::mem::take(&mut state.pending) {
+            self.submit(state, entry, o);
+        }
+        self.replicate(state, o);
+        o.set_timer(self.heartbeat.clone());
+        self.advance_commit(state, o);
+    }
+
+    // appends a request if we lead, otherwise hands it to whoever does
+    fn submit(&self, state: &mut RaftState<V>, mut entry: Entry<V>, o: &mut Out<Self>) {
+        match state.leader {
+            Some(leader) if leader == state.id => {
+                let duplicate = state.log.iter().any(|logged| {
+                    logged.request_id == entry.request_id && logged.client == entry.client
+                });
+                if duplicate {
+                    return;
+                }
+                entry.term = state.term;
+                state.log.push(entry);
+                self.replicate(state, o);
+                self.advance_commit(state, o);
+            }
+            Some(leader) => o.send(leader, RegisterMsg::Internal(RaftMsg::Forward(entry))),
+            None => state.pending.push(entry),
+        }
+    }
+
+    // a leader only counts replicas for entries of its own term
+    fn advance_commit(&self, state: &mut RaftState<V>, o: &mut Out<Self>) {
+        let matched = match &state.role {
+            Role::Leader { matched, .. } => matched,
+            _ => return,
+        };
+        let mut commit = state.commit;
+        for index in (state.commit + 1..=state.log.len()).rev() {
+            let replicas = 1 + matched.values().filter(|m| **m >= index).count();
+            if state.log[index - 1].term == state.term && self.is_majority(replicas) {
+                commit = index;
+                break;
+            }
+        }
+        if commit > state.commit {
+            state.commit = commit;
+            self.apply(state, o);
+            self.replicate(state, o);
+        }
+    }
+
+    fn apply(&self, state: &mut RaftState<V>, o: &mut Out<Self>) {
+        while state.applied < state.commit {
+            let entry = state.log[state.applied].clone();
+            state.applied += 1;
+            if let Some(value) = &entry.op {
+                state.register = value.clone();
+            }
+            if entry.origin != state.id {
+                continue;
+            }
+            match entry.op {
+                Some(_) => o.send(entry.client, RegisterMsg::PutOk(entry.request_id)),
+                None => o.send(
+                    entry.client,
+                    RegisterMsg::GetOk(entry.request_id, state.register.clone()),
+                ),
+            }
+        }
+    }
+
+    fn request(
+        &self,
+        state: &mut Cow<RaftState<V>>,
+        request_id: u64,
+        client: Id,
+        op: Option<V>,
+        o: &mut Out<Self>,
+    ) {
+        if state.handled.contains(&request_id) {
+            return;
+        }
+        let state = state.to_mut();
+        state.handled.insert(request_id);
+        let entry = Entry {
+            term: state.term,
+            request_id,
+            client,
+            origin: state.id,
+            op,
+        };
+        self.submit(state, entry, o);
+    }
+}
+
+impl<V: Value> Actor for RaftActor<V> {
+    type Msg = RegisterMsg<u64, V, RaftMsg<V>>;
+    type State = RaftState<V>;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        o.set_timer(self.election_timeout.clone());
+        RaftState {
+            id,
+            term: 0,
+            voted_for: None,
+            log: Vec::new(),
+            commit: 0,
+            applied: 0,
+            register: V::default(),
+            role: Role::Follower,
+            leader: None,
+            pending: Vec::new(),
+            handled: BTreeSet::new(),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        _: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        let internal_msg = match msg {
+            RegisterMsg::Internal(internal_msg) => internal_msg,
+            RegisterMsg::Put(request_id, value) => {
+                return self.request(state, request_id, src, Some(value), o);
+            }
+            RegisterMsg::Get(request_id) => {
+                return self.request(state, request_id, src, None, o);
+            }
+            _ => return,
+        };
+        match internal_msg {
+            RaftMsg::RequestVote(term, last_index, last_term) => {
+                let state = state.to_mut();
+                state.observe_term(term);
+                let (my_index, my_term) = state.last_log();
+                let up_to_date =
+                    last_term > my_term || (last_term == my_term && last_index >= my_index);
+                let free = match state.voted_for {
+                    Some(voted_for) => voted_for == src,
+                    None => true,
+                };
+                let granted = term == state.term && free && up_to_date;
+                if granted {
+                    state.voted_for = Some(src);
+                    o.set_timer(self.election_timeout.clone());
+                }
+                o.send(
+                    src,
+                    RegisterMsg::Internal(RaftMsg::Vote(state.term, granted)),
+                );
+            }
+            RaftMsg::Vote(term, granted) => {
+                let state = state.to_mut();
+                state.observe_term(term);
+                if term != state.term || !granted {
+                    return;
+                }
+                let votes = match &mut state.role {
+                    Role::Candidate { votes } => votes,
+                    _ => return,
+                };
+                votes.insert(src);
+                if self.is_majority(votes.len()) {
+                    self.become_leader(state, o);
+                }
+            }
+            RaftMsg::Append(term, prev, prev_term, entries, leader_commit) => {
+                let state = state.to_mut();
+                state.observe_term(term);
+                if term < state.term {
+                    let msg = RaftMsg::AppendReply(state.term, false, 0);
+                    o.send(src, RegisterMsg::Internal(msg));
+                    return;
+                }
+
+                // a candidate that hears from the leader of its term gives up
+                state.role = Role::Follower;
+                if state.leader.is_none() {
+                    state.leader = Some(src);
+                    for entry in std::mem::take(&mut state.pending) {
+                        o.send(src, RegisterMsg::Internal(RaftMsg::Forward(entry)));
+                    }
+                }
+                o.set_timer(self.election_timeout.clone());
+
+                if state.term_at(prev) != Some(prev_term) {
+                    let msg = RaftMsg::AppendReply(state.term, false, 0);
+                    o.send(src, RegisterMsg::Internal(msg));
+                    return;
+                }
+                for (offset, entry) in entries.iter().enumerate() {
+                    let index = prev + offset + 1;
+                    match state.term_at(index) {
+                        Some(existing) if existing == entry.term => continue,
+                        Some(_) => state.log.truncate(index - 1),
+                        None => {}
+                    }
+                    state.log.push(entry.clone());
+                }
+                let matched = prev + entries.len();
+                if leader_commit > state.commit {
+                    state.commit = leader_commit.min(matched).max(state.commit);
+                    self.apply(state, o);
+                }
+                let msg = RaftMsg::AppendReply(state.term, true, matched);
+                o.send(src, RegisterMsg::Internal(msg));
+            }
+            RaftMsg::AppendReply(term, success, matched_index) => {
+                let state = state.to_mut();
+                state.observe_term(term);
+                if term != state.term {
+                    return;
+                }
+                let (next, matched) = match &mut state.role {
+                    Role::Leader { next, matched } => (next, matched),
+                    _ => return,
+                };
+                if success {
+                    let matched = matched.entry(src).or_insert(0);
+                    *matched = (*matched).max(matched_index);
+                    next.insert(src, *matched + 1);
+                    self.advance_commit(state, o);
+                } else {
+                    // walk back one entry at a time until the logs agree
+                    let next = next.entry(src).or_insert(1);
+                    *next = (*next - 1).max(1);
+                    o.send(src, self.append_for(state, src));
+                }
+            }
+            RaftMsg::Forward(entry) => {
+                self.submit(state.to_mut(), entry, o);
+            }
+        }
+    }
+
+    fn on_timeout(&self, _: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        if let Role::Leader { .. } = state.role {
+            self.replicate(state, o);
+            o.set_timer(self.heartbeat.clone());
+            return;
+        }
+
+        // no word from a leader, so stand for election in a new term
+        let state = state.to_mut();
+        state.term += 1;
+        state.voted_for = Some(state.id);
+        state.leader = None;
+        state.role = Role::Candidate {
+            votes: [state.id].into_iter().collect(),
+        };
+        let (last_index, last_term) = state.last_log();
+        let msg = RaftMsg::RequestVote(state.term, last_index, last_term);
+        o.broadcast(&self.peers, &RegisterMsg::Internal(msg));
+        o.set_timer(self.election_timeout.clone());
+        if self.is_majority(1) {
+            self.become_leader(state, o);
+        }
+    }
+}
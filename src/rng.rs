@@ -0,0 +1,104 @@
+use std::ops::Range;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Where every random choice outside the exhaustive checkers comes from: proposers' backoff, the
+// timer jitter that staggers elections among spawned replicas, the simulator's scheduling and
+// the random walks. Each is seeded from the command line, and every randomized mode prints its
+// seed when it starts, so a run that went wrong is repeated by running it again with that seed.
+pub trait Rng {
+    fn next_u64(&mut self) -> u64;
+
+    // 0 when there is nothing to pick from, rather than dividing by zero
+    fn below(&mut self, n: usize) -> usize {
+        match n {
+            0 => 0,
+            n => (self.next_u64() % n as u64) as usize,
+        }
+    }
+
+    // uniform in [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // somewhere in the range, its start if it is empty
+    fn within(&mut self, range: Range<Duration>) -> Duration {
+        range.start + range.end.saturating_sub(range.start).mul_f64(self.unit())
+    }
+}
+
+// splitmix64, enough to pick actions reproducibly from a seed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SplitMix(pub u64);
+
+impl Rng for SplitMix {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl SplitMix {
+    // A generator of its own for one part of a run, a replica say, told apart by `stream`.
+    // Parts drawing from a shared generator would see different numbers whenever another part
+    // happened to draw first, and the run would no longer follow from the seed.
+    pub fn fork(&self, stream: u64) -> SplitMix {
+        SplitMix(SplitMix(self.0 ^ stream.wrapping_mul(0x9e37_79b9_7f4a_7c15)).next_u64())
+    }
+}
+
+// the seed given, or one from the clock for a run that did not ask for a particular one
+pub fn seed_or_fresh(seed: Option<u64>) -> u64 {
+    seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_same_seed_draws_the_same_numbers() {
+        let draw = |seed| {
+            let mut rng = SplitMix(seed);
+            (0..16).map(|_| rng.next_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(7), draw(8));
+    }
+
+    #[test]
+    fn forks_are_independent_of_each_other() {
+        let root = SplitMix(7);
+        let mut a = root.fork(1);
+        let mut b = root.fork(2);
+        assert_ne!(a.next_u64(), b.next_u64());
+        // drawing from one fork leaves the other as it was
+        let mut again = root.fork(2);
+        a.next_u64();
+        assert_eq!(b.next_u64(), {
+            again.next_u64();
+            again.next_u64()
+        });
+    }
+
+    #[test]
+    fn draws_stay_within_their_range() {
+        let mut rng = SplitMix(1);
+        let range = Duration::from_millis(500)..Duration::from_millis(1000);
+        for _ in 0..100 {
+            let drawn = rng.within(range.clone());
+            assert!(range.contains(&drawn));
+        }
+        let empty = Duration::from_millis(5)..Duration::from_millis(5);
+        assert_eq!(rng.within(empty), Duration::from_millis(5));
+        assert!((0..100).all(|_| rng.below(3) < 3));
+        assert_eq!(rng.below(0), 0);
+    }
+}
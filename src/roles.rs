@@ -0,0 +1,660 @@
+use crate::ballot;
+use crate::paxos::{Applied, Command, PaxosMsg, RoundIdentifier, Slot, Value};
+use serde::{Deserialize, Serialize};
+use stateright::actor::{register::*, Actor, Command as ActorCommand, Id, Out};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::time::Duration;
+
+// The three roles of Paxos as actors of their own, rather than the one `PaxosActor` that plays
+// all of them, so a cluster can run any number of each. Proposers take client requests and
+// drive them through the acceptors, acceptors only promise and accept, and learners count the
+// accepts they are sent to learn what each slot decided and apply the log. A proposer answers
+// its client once a learner says it has applied the slot, since only learners hold the
+// register. The roles speak the same messages as `PaxosActor`, so clients and transports need
+// nothing new.
+
+fn is_majority(count: usize, of: usize) -> bool {
+    count > of / 2
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum Phase {
+    Prepare,
+    Accept,
+}
+
+// a client request being proposed in one slot, under one round at a time
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Proposal<V> {
+    request_id: u64,
+    client: Id,
+    // the client's put, or for a get an empty batch that orders the read in the log
+    command: Command<V>,
+    get: bool,
+    round: RoundIdentifier,
+    phase: Phase,
+    promises: BTreeSet<Id>,
+    // highest (round, command) the promises reported, which must win over the client's
+    adopted: Option<(RoundIdentifier, Command<V>)>,
+    accepts: BTreeSet<Id>,
+}
+
+impl<V: Value> Proposal<V> {
+    // not prepared yet, under round 0 of the proposer taking it on
+    fn new(proposer: Id, request_id: u64, client: Id, command: Command<V>, get: bool) -> Self {
+        Proposal {
+            request_id,
+            client,
+            command,
+            get,
+            round: RoundIdentifier::new(0, proposer),
+            phase: Phase::Prepare,
+            promises: BTreeSet::new(),
+            adopted: None,
+            accepts: BTreeSet::new(),
+        }
+    }
+
+    // the command phase 2 has to carry
+    fn value(&self) -> Command<V> {
+        match &self.adopted {
+            Some((_, command)) => command.clone(),
+            None => self.command.clone(),
+        }
+    }
+
+    // Whether the round carries the client's own request. Every put writes a value of its own,
+    // so one adopted back from an earlier round is still the client's, but a get only reads at
+    // a slot no acceptor in the quorum had accepted anything in: one that adopted an earlier
+    // read's empty batch could be reading at a slot decided before it was asked.
+    fn carries_own(&self) -> bool {
+        match &self.adopted {
+            Some((_, command)) => !self.get && *command == self.command,
+            None => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct ProposerState<V> {
+    id: Id,
+    // the highest round this proposer has started or been told of
+    round: u64,
+    proposals: BTreeMap<Slot, Proposal<V>>,
+    // slots known to be decided, never proposed in again
+    decided: BTreeSet<Slot>,
+    // decided requests waiting on a learner to apply their slot: the client, whether it asked
+    // for a read, and the slot
+    answering: BTreeMap<u64, (Id, bool, Slot)>,
+    handled: BTreeSet<u64>,
+}
+
+impl<V: Value> ProposerState<V> {
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    // the lowest slot neither known to be decided nor already being proposed in
+    fn next_slot(&self) -> Slot {
+        let mut slot = 0;
+        while self.decided.contains(&slot) || self.proposals.contains_key(&slot) {
+            slot += 1;
+        }
+        slot
+    }
+}
+
+pub struct ProposerActor<V> {
+    acceptors: Vec<Id>,
+    learners: Vec<Id>,
+    timeout: Range<Duration>,
+    _value: PhantomData<V>,
+}
+
+impl<V: Value> ProposerActor<V> {
+    pub fn new(acceptors: Vec<Id>, learners: Vec<Id>) -> Self {
+        ProposerActor {
+            acceptors,
+            learners,
+            timeout: Duration::from_millis(500)..Duration::from_millis(1000),
+            _value: PhantomData,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Range<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn request(&self, state: &mut Cow<ProposerState<V>>, proposal: Proposal<V>, o: &mut Out<Self>) {
+        if state.handled.contains(&proposal.request_id) {
+            return;
+        }
+        let state = state.to_mut();
+        state.handled.insert(proposal.request_id);
+        let slot = state.next_slot();
+        self.prepare(state, slot, proposal, o);
+    }
+
+    // starts phase 1 for the proposal under a round of our own, higher than any we know of
+    fn prepare(
+        &self,
+        state: &mut ProposerState<V>,
+        slot: Slot,
+        mut proposal: Proposal<V>,
+        o: &mut Out<Self>,
+    ) {
+        state.round = match ballot::next_round(state.round) {
+            Some(round) => round,
+            None => return,
+        };
+        proposal.round = RoundIdentifier::new(state.round, state.id);
+        proposal.phase = Phase::Prepare;
+        proposal.promises.clear();
+        proposal.adopted = None;
+        proposal.accepts.clear();
+        let msg = RegisterMsg::Internal(PaxosMsg::Prepare(
+            proposal.request_id,
+            proposal.client,
+            slot,
+            proposal.round,
+        ));
+        o.broadcast(&self.acceptors, &msg);
+        o.set_timer(self.timeout.clone());
+        state.proposals.insert(slot, proposal);
+    }
+
+    fn idle(&self, state: &ProposerState<V>, o: &mut Out<Self>) {
+        if state.proposals.is_empty() && state.answering.is_empty() {
+            o.cancel_timer();
+        }
+    }
+}
+
+impl<V: Value> Actor for ProposerActor<V> {
+    type Msg = RegisterMsg<u64, V, PaxosMsg<V>>;
+    type State = ProposerState<V>;
+
+    fn on_start(&self, id: Id, _o: &mut Out<Self>) -> Self::State {
+        ProposerState {
+            id,
+            round: 0,
+            proposals: BTreeMap::new(),
+            decided: BTreeSet::new(),
+            answering: BTreeMap::new(),
+            handled: BTreeSet::new(),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        let internal_msg = match msg {
+            RegisterMsg::Internal(internal_msg) => internal_msg,
+            RegisterMsg::Put(request_id, value) => {
+                let proposal = Proposal::new(id, request_id, src, Command::Put(value), false);
+                return self.request(state, proposal, o);
+            }
+            RegisterMsg::Get(request_id) => {
+                let command = Command::Batch(Vec::new());
+                let proposal = Proposal::new(id, request_id, src, command, true);
+                return self.request(state, proposal, o);
+            }
+            _ => return,
+        };
+        match internal_msg {
+            PaxosMsg::Promise(_, _, slot, rid, accepted) => {
+                let current = matches!(
+                    state.proposals.get(&slot),
+                    Some(proposal) if proposal.round == rid
+                        && proposal.phase == Phase::Prepare
+                        && !proposal.promises.contains(&src)
+                );
+                if !current {
+                    return;
+                }
+                let proposal = match state.to_mut().proposals.get_mut(&slot) {
+                    Some(proposal) => proposal,
+                    None => return,
+                };
+                proposal.promises.insert(src);
+                if let Some((accepted_rid, command)) = accepted {
+                    let higher = match &proposal.adopted {
+                        Some((seen, _)) => accepted_rid > *seen,
+                        None => true,
+                    };
+                    if higher {
+                        proposal.adopted = Some((accepted_rid, command));
+                    }
+                }
+                if !is_majority(proposal.promises.len(), self.acceptors.len()) {
+                    return;
+                }
+                proposal.phase = Phase::Accept;
+                let msg = RegisterMsg::Internal(PaxosMsg::Accept(
+                    proposal.request_id,
+                    proposal.client,
+                    slot,
+                    rid,
+                    proposal.value(),
+                ));
+                o.broadcast(&self.acceptors, &msg);
+            }
+            PaxosMsg::Accepted(_, _, slot, rid, _) => {
+                let current = matches!(
+                    state.proposals.get(&slot),
+                    Some(proposal) if proposal.round == rid
+                        && proposal.phase == Phase::Accept
+                        && !proposal.accepts.contains(&src)
+                );
+                if !current {
+                    return;
+                }
+                let state = state.to_mut();
+                let majority = match state.proposals.get_mut(&slot) {
+                    Some(proposal) => {
+                        proposal.accepts.insert(src);
+                        is_majority(proposal.accepts.len(), self.acceptors.len())
+                    }
+                    None => return,
+                };
+                if !majority {
+                    return;
+                }
+                let proposal = match state.proposals.remove(&slot) {
+                    Some(proposal) => proposal,
+                    None => return,
+                };
+                state.decided.insert(slot);
+                if proposal.carries_own() {
+                    // answered once a learner has applied the slot
+                    let msg = RegisterMsg::Internal(PaxosMsg::Read(proposal.request_id, slot));
+                    o.broadcast(&self.learners, &msg);
+                    state
+                        .answering
+                        .insert(proposal.request_id, (proposal.client, proposal.get, slot));
+                } else {
+                    // someone else's command took the slot, ours moves on to the next
+                    let next = state.next_slot();
+                    self.prepare(state, next, proposal, o);
+                }
+            }
+            PaxosMsg::Nack(_, slot, seen) => {
+                let outranked = matches!(
+                    state.proposals.get(&slot),
+                    Some(proposal) if proposal.round < seen
+                );
+                if !outranked {
+                    return;
+                }
+                let state = state.to_mut();
+                state.round = state.round.max(seen.round_num());
+                if let Some(proposal) = state.proposals.remove(&slot) {
+                    self.prepare(state, slot, proposal, o);
+                }
+            }
+            PaxosMsg::ReadReply(request_id, _, value, _) => {
+                let (client, get, _) = match state.answering.get(&request_id) {
+                    Some(answering) => *answering,
+                    None => return,
+                };
+                let state = state.to_mut();
+                state.answering.remove(&request_id);
+                if get {
+                    o.send(
+                        client,
+                        RegisterMsg::GetOk(request_id, value.unwrap_or_default()),
+                    );
+                } else {
+                    o.send(client, RegisterMsg::PutOk(request_id));
+                }
+                self.idle(state, o);
+            }
+            _ => {}
+        }
+    }
+
+    // anything may have been lost on the way: rounds start over and learners are asked again
+    fn on_timeout(&self, _: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        if state.proposals.is_empty() && state.answering.is_empty() {
+            return;
+        }
+        let state = state.to_mut();
+        let slots: Vec<Slot> = state.proposals.keys().copied().collect();
+        for slot in slots {
+            if let Some(proposal) = state.proposals.remove(&slot) {
+                self.prepare(state, slot, proposal, o);
+            }
+        }
+        for (request_id, (_, _, slot)) in &state.answering {
+            let msg = RegisterMsg::Internal(PaxosMsg::Read(*request_id, *slot));
+            o.broadcast(&self.learners, &msg);
+        }
+        if !state.answering.is_empty() {
+            o.set_timer(self.timeout.clone());
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct AcceptorState<V> {
+    promised: BTreeMap<Slot, RoundIdentifier>,
+    accepted: BTreeMap<Slot, (RoundIdentifier, Command<V>)>,
+}
+
+impl<V> AcceptorState<V> {
+    pub fn accepted(&self) -> &BTreeMap<Slot, (RoundIdentifier, Command<V>)> {
+        &self.accepted
+    }
+}
+
+// never told what was decided, so keeps its promises and accepts for every slot
+pub struct AcceptorActor<V> {
+    learners: Vec<Id>,
+    _value: PhantomData<V>,
+}
+
+impl<V: Value> AcceptorActor<V> {
+    pub fn new(learners: Vec<Id>) -> Self {
+        AcceptorActor {
+            learners,
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<V: Value> Actor for AcceptorActor<V> {
+    type Msg = RegisterMsg<u64, V, PaxosMsg<V>>;
+    type State = AcceptorState<V>;
+
+    fn on_start(&self, _: Id, _o: &mut Out<Self>) -> Self::State {
+        AcceptorState {
+            promised: BTreeMap::new(),
+            accepted: BTreeMap::new(),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        _: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match msg {
+            RegisterMsg::Internal(PaxosMsg::Prepare(request_id, client, slot, rid)) => {
+                if let Some(seen) = state.promised.get(&slot).filter(|seen| **seen > rid) {
+                    let msg = RegisterMsg::Internal(PaxosMsg::Nack(request_id, slot, *seen));
+                    return o.send(src, msg);
+                }
+                // a redelivered prepare is promised again
+                if state.promised.get(&slot) != Some(&rid) {
+                    state.to_mut().promised.insert(slot, rid);
+                }
+                let msg = RegisterMsg::Internal(PaxosMsg::Promise(
+                    request_id,
+                    client,
+                    slot,
+                    rid,
+                    state.accepted.get(&slot).cloned(),
+                ));
+                o.send(src, msg);
+            }
+            RegisterMsg::Internal(PaxosMsg::Accept(request_id, client, slot, rid, command)) => {
+                if let Some(seen) = state.promised.get(&slot).filter(|seen| **seen > rid) {
+                    let msg = RegisterMsg::Internal(PaxosMsg::Nack(request_id, slot, *seen));
+                    return o.send(src, msg);
+                }
+                let state = state.to_mut();
+                state.promised.insert(slot, rid);
+                state.accepted.insert(slot, (rid, command.clone()));
+                // the proposer counts accepts to move on, the learners to learn the slot
+                let msg = RegisterMsg::Internal(PaxosMsg::Accepted(
+                    request_id, client, slot, rid, command,
+                ));
+                o.send(src, msg.clone());
+                o.broadcast(&self.learners, &msg);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct LearnerState<V> {
+    // the acceptors that accepted each round, until its slot is decided
+    votes: BTreeMap<(Slot, RoundIdentifier), BTreeSet<Id>>,
+    log: BTreeMap<Slot, Command<V>>,
+    applied: Applied<V>,
+    // reads waiting for the log to be applied past their slot, with the proposer asking
+    reads: BTreeSet<(Slot, u64, Id)>,
+}
+
+impl<V> LearnerState<V> {
+    pub fn log(&self) -> &BTreeMap<Slot, Command<V>> {
+        &self.log
+    }
+
+    pub fn applied(&self) -> &Applied<V> {
+        &self.applied
+    }
+}
+
+pub struct LearnerActor<V> {
+    acceptors: Vec<Id>,
+    _value: PhantomData<V>,
+}
+
+impl<V: Value> LearnerActor<V> {
+    pub fn new(acceptors: Vec<Id>) -> Self {
+        LearnerActor {
+            acceptors,
+            _value: PhantomData,
+        }
+    }
+
+    // applies the gap-free prefix of the log, then answers the reads it has caught up with
+    fn apply(&self, state: &mut LearnerState<V>, o: &mut Out<Self>) {
+        while let Some(command) = state.log.get(&state.applied.next()).cloned() {
+            state.applied.apply(&command);
+        }
+        let next = state.applied.next();
+        let ready: Vec<(Slot, u64, Id)> = state
+            .reads
+            .iter()
+            .copied()
+            .filter(|(slot, _, _)| *slot < next)
+            .collect();
+        for (slot, request_id, proposer) in ready {
+            state.reads.remove(&(slot, request_id, proposer));
+            self.reply(state, request_id, proposer, o);
+        }
+    }
+
+    fn reply(&self, state: &LearnerState<V>, request_id: u64, proposer: Id, o: &mut Out<Self>) {
+        let msg = PaxosMsg::ReadReply(
+            request_id,
+            state.applied.next(),
+            Some(state.applied.value().clone()),
+            None,
+        );
+        o.send(proposer, RegisterMsg::Internal(msg));
+    }
+}
+
+impl<V: Value> Actor for LearnerActor<V> {
+    type Msg = RegisterMsg<u64, V, PaxosMsg<V>>;
+    type State = LearnerState<V>;
+
+    fn on_start(&self, _: Id, _o: &mut Out<Self>) -> Self::State {
+        LearnerState {
+            votes: BTreeMap::new(),
+            log: BTreeMap::new(),
+            applied: Applied::new(),
+            reads: BTreeSet::new(),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        _: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match msg {
+            RegisterMsg::Internal(PaxosMsg::Accepted(_, _, slot, rid, command)) => {
+                let counted = state
+                    .votes
+                    .get(&(slot, rid))
+                    .map_or(false, |votes| votes.contains(&src));
+                if !self.acceptors.contains(&src) || state.log.contains_key(&slot) || counted {
+                    return;
+                }
+                let state = state.to_mut();
+                let votes = state.votes.entry((slot, rid)).or_default();
+                votes.insert(src);
+                if !is_majority(votes.len(), self.acceptors.len()) {
+                    return;
+                }
+                state.votes.retain(|(voted, _), _| *voted != slot);
+                state.log.insert(slot, command);
+                self.apply(state, o);
+            }
+            RegisterMsg::Internal(PaxosMsg::Read(request_id, slot)) => {
+                if slot < state.applied.next() {
+                    return self.reply(state, request_id, src, o);
+                }
+                if !state.reads.contains(&(slot, request_id, src)) {
+                    state.to_mut().reads.insert((slot, request_id, src));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// the roles have to share an actor type in the model, and a spawned cluster runs any of them
+pub enum RoleActor<V> {
+    Proposer(ProposerActor<V>),
+    Acceptor(AcceptorActor<V>),
+    Learner(LearnerActor<V>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum RoleActorState<V> {
+    Proposer(ProposerState<V>),
+    Acceptor(AcceptorState<V>),
+    Learner(LearnerState<V>),
+}
+
+impl<V: Value> RoleActor<V> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RoleActor::Proposer(_) => "proposer",
+            RoleActor::Acceptor(_) => "acceptor",
+            RoleActor::Learner(_) => "learner",
+        }
+    }
+
+    // all three speak the same messages, only the type of the output differs
+    fn forward<A>(inner: Out<A>, o: &mut Out<Self>)
+    where
+        A: Actor<Msg = RegisterMsg<u64, V, PaxosMsg<V>>>,
+    {
+        for command in inner {
+            match command {
+                ActorCommand::Send(dst, msg) => o.send(dst, msg),
+                ActorCommand::SetTimer(duration) => o.set_timer(duration),
+                ActorCommand::CancelTimer => o.cancel_timer(),
+            }
+        }
+    }
+}
+
+impl<V: Value> Actor for RoleActor<V> {
+    type Msg = RegisterMsg<u64, V, PaxosMsg<V>>;
+    type State = RoleActorState<V>;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        let mut inner = Out::new();
+        let state = match self {
+            RoleActor::Proposer(proposer) => {
+                RoleActorState::Proposer(proposer.on_start(id, &mut inner))
+            }
+            RoleActor::Acceptor(acceptor) => {
+                RoleActorState::Acceptor(acceptor.on_start(id, &mut inner))
+            }
+            RoleActor::Learner(learner) => {
+                RoleActorState::Learner(learner.on_start(id, &mut inner))
+            }
+        };
+        Self::forward(inner, o);
+        state
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match (self, state.as_ref()) {
+            (RoleActor::Proposer(proposer), RoleActorState::Proposer(proposer_state)) => {
+                let mut proposer_state = Cow::Borrowed(proposer_state);
+                let mut inner = Out::new();
+                proposer.on_msg(id, &mut proposer_state, src, msg, &mut inner);
+                if let Cow::Owned(proposer_state) = proposer_state {
+                    *state = Cow::Owned(RoleActorState::Proposer(proposer_state));
+                }
+                Self::forward(inner, o);
+            }
+            (RoleActor::Acceptor(acceptor), RoleActorState::Acceptor(acceptor_state)) => {
+                let mut acceptor_state = Cow::Borrowed(acceptor_state);
+                let mut inner = Out::new();
+                acceptor.on_msg(id, &mut acceptor_state, src, msg, &mut inner);
+                if let Cow::Owned(acceptor_state) = acceptor_state {
+                    *state = Cow::Owned(RoleActorState::Acceptor(acceptor_state));
+                }
+                Self::forward(inner, o);
+            }
+            (RoleActor::Learner(learner), RoleActorState::Learner(learner_state)) => {
+                let mut learner_state = Cow::Borrowed(learner_state);
+                let mut inner = Out::new();
+                learner.on_msg(id, &mut learner_state, src, msg, &mut inner);
+                if let Cow::Owned(learner_state) = learner_state {
+                    *state = Cow::Owned(RoleActorState::Learner(learner_state));
+                }
+                Self::forward(inner, o);
+            }
+            _ => {}
+        }
+    }
+
+    // only proposers set timers
+    fn on_timeout(&self, id: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        if let (RoleActor::Proposer(proposer), RoleActorState::Proposer(proposer_state)) =
+            (self, state.as_ref())
+        {
+            let mut proposer_state = Cow::Borrowed(proposer_state);
+            let mut inner = Out::new();
+            proposer.on_timeout(id, &mut proposer_state, &mut inner);
+            if let Cow::Owned(proposer_state) = proposer_state {
+                *state = Cow::Owned(RoleActorState::Proposer(proposer_state));
+            }
+            Self::forward(inner, o);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::model::{NetworkMode, Protocol};
+use crate::quorum::QuorumSpec;
+use crate::sim::{AdversarySpec, Latency};
+use crate::topology::Topology;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+// the scenarios in scenarios/, built in so they run by name from anywhere
+const SHIPPED: &[(&str, &str)] = &[
+    ("happy-path", include_str!("../scenarios/happy-path.toml")),
+    (
+        "dueling-proposers",
+        include_str!("../scenarios/dueling-proposers.toml"),
+    ),
+    ("partition", include_str!("../scenarios/partition.toml")),
+    (
+        "crash-recovery",
+        include_str!("../scenarios/crash-recovery.toml"),
+    ),
+    ("livelock", include_str!("../scenarios/livelock.toml")),
+    (
+        "isolated-replica",
+        include_str!("../scenarios/isolated-replica.toml"),
+    ),
+    ("wan", include_str!("../scenarios/wan.toml")),
+    (
+        "rolling-upgrade",
+        include_str!("../scenarios/rolling-upgrade.toml"),
+    ),
+    ("slow-leader", include_str!("../scenarios/slow-leader.toml")),
+    (
+        "region-outage",
+        include_str!("../scenarios/region-outage.toml"),
+    ),
+];
+
+// A model check written down, in TOML. Anything left out keeps the value of the matching
+// command line flag, and an empty `properties` checks all of them.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, deserialize_with = "parsed")]
+    pub protocol: Option<Protocol>,
+    pub servers: Option<usize>,
+    pub clients: Option<usize>,
+    pub puts: Option<usize>,
+    #[serde(default, deserialize_with = "parsed")]
+    pub network: Option<NetworkMode>,
+    pub backoff: Option<bool>,
+    pub max_rounds: Option<u64>,
+    pub max_messages: Option<usize>,
+    #[serde(default, deserialize_with = "parsed")]
+    pub regions: Option<Topology>,
+    #[serde(default, deserialize_with = "parsed")]
+    pub quorum: Option<QuorumSpec>,
+    // as `--batch` and `--upgrade`
+    pub batch: Option<usize>,
+    pub upgrade: Option<usize>,
+    #[serde(default)]
+    pub faults: Faults,
+    #[serde(default)]
+    pub properties: Vec<String>,
+    // how long messages take under `sim`, which a check has no use for
+    #[serde(default, deserialize_with = "parsed")]
+    pub latency: Option<Latency>,
+    #[serde(default)]
+    pub links: Vec<LinkLatency>,
+    // the latency of messages between regions under `sim`, as `--cross-region-latency`
+    #[serde(default, deserialize_with = "parsed")]
+    pub cross_region_latency: Option<Latency>,
+    // who schedules the messages under `sim`
+    #[serde(default, deserialize_with = "parsed")]
+    pub adversary: Option<AdversarySpec>,
+}
+
+// the latency of messages from any of `from` to any of `to` under `sim`, as `--link`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinkLatency {
+    pub from: Vec<usize>,
+    pub to: Vec<usize>,
+    #[serde(deserialize_with = "parsed_value")]
+    pub latency: Latency,
+}
+
+// what goes wrong during the run, as the `--crashes`, `--byzantine`, `--split` and
+// `--region-down` flags
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Faults {
+    pub crashes: Option<usize>,
+    pub byzantine: Option<usize>,
+    pub split: Option<usize>,
+    pub region_down: Option<usize>,
+}
+
+// the options spelled as on the command line
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(D::Error::custom))
+        .transpose()
+}
+
+fn parsed_value<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(D::Error::custom)
+}
+
+impl FromStr for Scenario {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).map_err(|e| e.to_string())
+    }
+}
+
+impl Scenario {
+    // a shipped scenario by name, or else the file at that path
+    pub fn load(name: &str) -> io::Result<Self> {
+        let text = match SHIPPED.iter().find(|(shipped, _)| *shipped == name) {
+            Some((_, text)) => text.to_string(),
+            None => fs::read_to_string(Path::new(name))?,
+        };
+        text.parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+// every shipped scenario by name, or why it does not parse
+pub fn shipped() -> Vec<(&'static str, Result<Scenario, String>)> {
+    SHIPPED
+        .iter()
+        .map(|(name, text)| (*name, text.parse()))
+        .collect()
+}
@@ -0,0 +1,337 @@
+use stateright::actor::{Actor, Command, Id, Out};
+use std::borrow::Cow;
+
+// Drives one actor through a scripted run of incoming messages, for unit tests that pin down
+// how it handles a particular sequence without building a whole model around it. Each step
+// keeps what the actor sent, and `expect_send` claims those messages one at a time:
+//
+//     Script::new(actor)
+//         .recv(Id::from(1), prepare)
+//         .expect_send(Id::from(1), promise)
+//         .expect_quiet();
+//
+// Timers are not modelled, a script only delivers the messages it is given.
+pub struct Script<A: Actor> {
+    actor: A,
+    id: Id,
+    state: A::State,
+    // what the last step sent that no expectation has claimed yet
+    sent: Vec<(Id, A::Msg)>,
+}
+
+impl<A: Actor> Script<A> {
+    // the actor started as id 0
+    pub fn new(actor: A) -> Self {
+        Self::at(Id::from(0), actor)
+    }
+
+    pub fn at(id: Id, actor: A) -> Self {
+        let mut out = Out::new();
+        let state = actor.on_start(id, &mut out);
+        let mut script = Script {
+            actor,
+            id,
+            state,
+            sent: Vec::new(),
+        };
+        script.record(out);
+        script
+    }
+
+    // delivers a message, dropping whatever the step before sent and nobody expected
+    pub fn recv(mut self, src: Id, msg: A::Msg) -> Self {
+        let mut out = Out::new();
+        let mut state = Cow::Borrowed(&self.state);
+        self.actor.on_msg(self.id, &mut state, src, msg, &mut out);
+        let next = match state {
+            Cow::Owned(next) => Some(next),
+            Cow::Borrowed(_) => None,
+        };
+        if let Some(next) = next {
+            self.state = next;
+        }
+        self.record(out);
+        self
+    }
+
+    // the last step sent this message to `dst`, in whatever order among its other sends
+    pub fn expect_send(mut self, dst: Id, msg: A::Msg) -> Self {
+        match self.sent.iter().position(|sent| *sent == (dst, msg.clone())) {
+            Some(i) => {
+                self.sent.remove(i);
+            }
+            None => panic!(
+                "expected {:?} to be sent to {:?}, the last step sent {:?}",
+                msg, dst, self.sent
+            ),
+        }
+        self
+    }
+
+    // the last step sent nothing that has not been expected already
+    pub fn expect_quiet(self) -> Self {
+        assert!(self.sent.is_empty(), "unexpected sends {:?}", self.sent);
+        self
+    }
+
+    // the last step did not send this message to `dst`
+    pub fn expect_no_send(self, dst: Id, msg: A::Msg) -> Self {
+        assert!(
+            !self.sent.contains(&(dst, msg.clone())),
+            "expected {:?} not to be sent to {:?}",
+            msg,
+            dst
+        );
+        self
+    }
+
+    // the actor's state satisfies `check`, described by `what` when it does not
+    pub fn expect_state(self, what: &str, check: impl FnOnce(&A::State) -> bool) -> Self {
+        assert!(check(&self.state), "expected {}, state is {:?}", what, self.state);
+        self
+    }
+
+    fn record(&mut self, out: Out<A>) {
+        self.sent.clear();
+        for command in out {
+            if let Command::Send(dst, msg) = command {
+                self.sent.push((dst, msg));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::paxos::{ClockSkew, Command as PaxosCommand, PaxosActor, PaxosMsg, RoundIdentifier};
+    use stateright::actor::register::RegisterMsg;
+
+    type Msg = RegisterMsg<u64, char, PaxosMsg<char>>;
+
+    fn acceptor() -> Script<PaxosActor<char>> {
+        Script::new(PaxosActor::new(vec![Id::from(1), Id::from(2)]))
+    }
+
+    // an acceptor the model may crash, which it does by sending itself `Crash`
+    fn crashing() -> Script<PaxosActor<char>> {
+        Script::new(PaxosActor::new(vec![Id::from(1), Id::from(2)]).crashes(true))
+    }
+
+    fn rid(round: u64, id: usize) -> RoundIdentifier {
+        RoundIdentifier::new(round, Id::from(id))
+    }
+
+    fn prepare(slot: u64, rid: RoundIdentifier) -> Msg {
+        RegisterMsg::Internal(PaxosMsg::Prepare(7, Id::from(3), slot, rid))
+    }
+
+    fn promise(
+        slot: u64,
+        rid: RoundIdentifier,
+        accepted: Option<(RoundIdentifier, PaxosCommand<char>)>,
+    ) -> Msg {
+        RegisterMsg::Internal(PaxosMsg::Promise(7, Id::from(3), slot, rid, accepted))
+    }
+
+    fn accept(slot: u64, rid: RoundIdentifier, value: char) -> Msg {
+        RegisterMsg::Internal(PaxosMsg::Accept(
+            7,
+            Id::from(3),
+            slot,
+            rid,
+            PaxosCommand::Put(value),
+        ))
+    }
+
+    fn accepted(slot: u64, rid: RoundIdentifier, value: char) -> Msg {
+        RegisterMsg::Internal(PaxosMsg::Accepted(
+            7,
+            Id::from(3),
+            slot,
+            rid,
+            PaxosCommand::Put(value),
+        ))
+    }
+
+    #[test]
+    fn redelivered_prepares_are_promised_again_and_older_ones_nacked() {
+        acceptor()
+            .recv(Id::from(1), prepare(0, rid(2, 1)))
+            .expect_send(Id::from(1), promise(0, rid(2, 1), None))
+            .expect_quiet()
+            .recv(Id::from(1), prepare(0, rid(2, 1)))
+            .expect_send(Id::from(1), promise(0, rid(2, 1), None))
+            .expect_quiet()
+            .recv(Id::from(2), prepare(0, rid(1, 2)))
+            .expect_send(
+                Id::from(2),
+                RegisterMsg::Internal(PaxosMsg::Nack(7, 0, rid(2, 1))),
+            )
+            .expect_quiet()
+            .expect_state("the later round still promised", |state| {
+                state.durable().last_seen(0) == Some(rid(2, 1))
+            });
+    }
+
+    #[test]
+    fn accepts_below_the_promise_are_ignored() {
+        acceptor()
+            .recv(Id::from(1), prepare(0, rid(1, 1)))
+            .expect_send(Id::from(1), promise(0, rid(1, 1), None))
+            .recv(Id::from(2), prepare(0, rid(2, 2)))
+            .expect_send(Id::from(2), promise(0, rid(2, 2), None))
+            // the first proposer hears it has been overtaken rather than waiting out its timeout
+            .expect_send(
+                Id::from(1),
+                RegisterMsg::Internal(PaxosMsg::Expire(0, rid(1, 1), rid(2, 2))),
+            )
+            .expect_quiet()
+            .recv(Id::from(1), accept(0, rid(1, 1), 'A'))
+            .expect_quiet()
+            .expect_state("nothing accepted", |state| {
+                state.durable().accepted(0).is_none()
+            });
+    }
+
+    #[test]
+    fn promises_carry_the_value_accepted_before() {
+        acceptor()
+            .recv(Id::from(1), prepare(0, rid(1, 1)))
+            .expect_send(Id::from(1), promise(0, rid(1, 1), None))
+            .recv(Id::from(1), accept(0, rid(1, 1), 'A'))
+            .expect_send(Id::from(1), accepted(0, rid(1, 1), 'A'))
+            .expect_quiet()
+            .recv(Id::from(2), prepare(0, rid(2, 2)))
+            .expect_send(
+                Id::from(2),
+                promise(0, rid(2, 2), Some((rid(1, 1), PaxosCommand::Put('A')))),
+            )
+            .expect_send(
+                Id::from(1),
+                RegisterMsg::Internal(PaxosMsg::Expire(0, rid(1, 1), rid(2, 2))),
+            )
+            .expect_quiet();
+    }
+
+    #[test]
+    fn a_restarted_leader_runs_phase_1_in_a_new_round() {
+        let mut first = None;
+        let script = crashing()
+            .recv(Id::from(3), RegisterMsg::Put(1, 'A'))
+            .expect_state("a proposal for the put", |state| {
+                first = state.proposals().next();
+                first.is_some()
+            });
+        let (slot, round) = first.unwrap();
+        let promise = RegisterMsg::Internal(PaxosMsg::Promise(1, Id::from(3), slot, round, None));
+        script
+            .recv(Id::from(1), promise.clone())
+            .recv(Id::from(2), promise)
+            .expect_state("leading under the put's round", |state| {
+                state.lease().map(|lease| lease.ballot()) == Some(round)
+            })
+            .recv(Id::from(0), RegisterMsg::Internal(PaxosMsg::Crash))
+            .expect_state("the lease gone with the crash", |state| {
+                state.lease().is_none()
+            })
+            // the same slot is free again after the restart, and must not get a second value
+            // under the round that may already have carried the first to some acceptors
+            .recv(Id::from(4), RegisterMsg::Put(2, 'B'))
+            .expect_state("the next put in a round of its own", |state| {
+                let proposals: Vec<_> = state.proposals().collect();
+                proposals.len() == 1 && proposals[0].1 > round
+            });
+    }
+
+    #[test]
+    fn decisions_peers_report_are_remembered_before_they_are_learned() {
+        let catch_up = |from| RegisterMsg::Internal(PaxosMsg::CatchUpRequest(from));
+        acceptor()
+            // a peer asking from slot 4 has applied everything before it
+            .recv(Id::from(1), catch_up(4))
+            .expect_send(Id::from(1), catch_up(0))
+            .expect_quiet()
+            .expect_state("slot 4 reported, nothing learned", |state| {
+                state.reported() == 4 && state.applied().next() == 0
+            })
+            .recv(
+                Id::from(2),
+                RegisterMsg::Internal(PaxosMsg::Decided(6, rid(1, 2), PaxosCommand::Put('A'))),
+            )
+            .expect_state("slot 7 reported", |state| state.reported() == 7)
+            .recv(Id::from(2), catch_up(5))
+            .expect_state("no report taken back", |state| state.reported() == 7);
+    }
+
+    #[test]
+    fn faults_are_only_taken_from_ourselves_when_set_up_for_them() {
+        let promised = |state: &crate::paxos::PaxosState<char>| {
+            state.durable().last_seen(0) == Some(rid(1, 1))
+        };
+        let crash = RegisterMsg::Internal(PaxosMsg::Crash);
+        let split = RegisterMsg::Internal(PaxosMsg::Split);
+        acceptor()
+            .recv(Id::from(1), prepare(0, rid(1, 1)))
+            .expect_send(Id::from(1), promise(0, rid(1, 1), None))
+            .recv(Id::from(0), crash.clone())
+            .recv(Id::from(0), split.clone())
+            .expect_quiet()
+            .expect_state("no crash or split", |state| {
+                promised(state) && !state.split()
+            });
+        crashing()
+            .recv(Id::from(1), prepare(0, rid(1, 1)))
+            .expect_send(Id::from(1), promise(0, rid(1, 1), None))
+            .recv(Id::from(1), crash.clone())
+            .expect_quiet()
+            .expect_state("no crash from a peer", promised)
+            // a crash from itself restarts it, asking its peers for what it missed
+            .recv(Id::from(0), crash)
+            .expect_send(
+                Id::from(1),
+                RegisterMsg::Internal(PaxosMsg::CatchUpRequest(0)),
+            )
+            .expect_send(
+                Id::from(2),
+                RegisterMsg::Internal(PaxosMsg::CatchUpRequest(0)),
+            )
+            .expect_quiet()
+            .expect_state("the promise kept through the crash", promised);
+    }
+
+    #[test]
+    fn a_restarted_leader_serves_no_reads_under_its_old_lease() {
+        let actor = PaxosActor::new(vec![Id::from(1), Id::from(2)])
+            .crashes(true)
+            .read_leases(Some(ClockSkew::Bounded));
+        let mut first = None;
+        let script = Script::new(actor)
+            .recv(Id::from(3), RegisterMsg::Put(1, 'A'))
+            .expect_state("a proposal for the put", |state| {
+                first = state.proposals().next();
+                first.is_some()
+            });
+        let (slot, round) = first.unwrap();
+        let promise = RegisterMsg::Internal(PaxosMsg::Promise(1, Id::from(3), slot, round, None));
+        let grant = RegisterMsg::Internal(PaxosMsg::LeaseGrant(round, 0));
+        let read = |request_id| RegisterMsg::GetOk(request_id, char::default());
+        script
+            .recv(Id::from(1), promise.clone())
+            .recv(Id::from(2), promise)
+            .recv(Id::from(1), grant.clone())
+            .recv(Id::from(2), grant.clone())
+            // leading under a read lease, it answers reads itself
+            .recv(Id::from(4), RegisterMsg::Get(2))
+            .expect_send(Id::from(4), read(2))
+            .recv(Id::from(0), RegisterMsg::Internal(PaxosMsg::Crash))
+            // grants for the old round still in flight when it comes back
+            .recv(Id::from(1), grant.clone())
+            .recv(Id::from(2), grant)
+            .recv(Id::from(4), RegisterMsg::Get(3))
+            .expect_no_send(Id::from(4), read(3))
+            .expect_state("neither a lease nor a read lease", |state| {
+                state.lease().is_none() && state.read_lease().is_none()
+            });
+    }
+}
@@ -0,0 +1,71 @@
+use crate::rng::{Rng, SplitMix};
+use stateright::actor::Id;
+
+// Splits the keys between independent Paxos groups, each with acceptors and a log of its own,
+// so that commands on keys in different groups never wait on one another. Keys are hashed
+// rather than taken modulo the group count, so neighbouring keys spread out. Linearizability
+// is local: a history is linearizable when each object's is, so groups that are linearizable
+// on their own keys make a linearizable store without coordinating.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShardedPaxos {
+    groups: Vec<Vec<Id>>,
+}
+
+impl ShardedPaxos {
+    pub fn new(groups: Vec<Vec<Id>>) -> Self {
+        assert!(
+            groups.iter().all(|group| !group.is_empty()),
+            "every group needs a replica"
+        );
+        ShardedPaxos { groups }
+    }
+
+    // `groups` groups of `replicas` each, with ids numbered from 0 group after group
+    pub fn contiguous(groups: usize, replicas: usize) -> Self {
+        Self::new(
+            (0..groups)
+                .map(|group| {
+                    (group * replicas..(group + 1) * replicas)
+                        .map(Id::from)
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    pub fn group_count(&self) -> usize {
+        self.groups.len()
+    }
+
+    // the group a key belongs to
+    pub fn shard(&self, key: u64) -> usize {
+        SplitMix(key).below(self.groups.len())
+    }
+
+    pub fn group(&self, shard: usize) -> &[Id] {
+        &self.groups[shard]
+    }
+
+    // the group `id` is a replica in, if any
+    pub fn shard_of(&self, id: Id) -> Option<usize> {
+        self.groups.iter().position(|group| group.contains(&id))
+    }
+
+    // the rest of `id`'s group, which is all it runs Paxos with
+    pub fn peers(&self, id: Id) -> Vec<Id> {
+        match self.shard_of(id) {
+            Some(shard) => self.groups[shard]
+                .iter()
+                .copied()
+                .filter(|peer| *peer != id)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // the replica a client sends a command on `key` to, clients spread over the group by id
+    pub fn route(&self, key: u64, client: Id) -> Id {
+        let group = self.group(self.shard(key));
+        group[usize::from(client) % group.len()]
+    }
+}
@@ -0,0 +1,749 @@
+use crate::checksum;
+use crate::paxos::{Ballot, PaxosMsg};
+use crate::rng::{Rng, SplitMix};
+use crate::topology::Topology;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use stateright::actor::register::RegisterMsg;
+use stateright::actor::{Actor, Command, Id, Out};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::time::Duration;
+
+// a safety property over the actor states alone, checked after every step
+pub type Invariant<S> = (&'static str, fn(&[S]) -> bool);
+
+fn parse_ms(s: &str) -> Result<Duration, String> {
+    s.parse::<u64>()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("expected milliseconds, got {}", s))
+}
+
+fn parse_ids(s: &str) -> Result<Vec<Id>, String> {
+    s.split(',')
+        .map(|id| {
+            id.parse::<usize>()
+                .map(Id::from)
+                .map_err(|_| format!("expected an actor index, got {}", id))
+        })
+        .collect()
+}
+
+// how long a message spends on the wire
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Latency {
+    Fixed(Duration),
+    Uniform(Duration, Duration),
+    // most messages are quick, a few take much longer
+    Exponential(Duration),
+    // The median and the spread of its logarithm, the heavy right tail measured WAN round
+    // trips have: a sigma of 0.5 puts one message in twenty past 2.3 times the median.
+    LogNormal(Duration, f64),
+}
+
+impl Latency {
+    fn sample(&self, rng: &mut SplitMix) -> Duration {
+        match *self {
+            Latency::Fixed(latency) => latency,
+            Latency::Uniform(min, max) => min + (max.saturating_sub(min)).mul_f64(rng.unit()),
+            Latency::Exponential(mean) => mean.mul_f64(-(1.0 - rng.unit()).ln()),
+            Latency::LogNormal(median, sigma) => {
+                // Box-Muller, from two uniform draws to a standard normal one
+                let z = (-2.0 * (1.0 - rng.unit()).ln()).sqrt()
+                    * (2.0 * std::f64::consts::PI * rng.unit()).cos();
+                median.mul_f64((sigma * z).exp())
+            }
+        }
+    }
+}
+
+impl FromStr for Latency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        match parts.as_slice() {
+            ["fixed", ms] => Ok(Latency::Fixed(parse_ms(ms)?)),
+            ["uniform", min, max] => Ok(Latency::Uniform(parse_ms(min)?, parse_ms(max)?)),
+            ["exponential", mean] => Ok(Latency::Exponential(parse_ms(mean)?)),
+            ["lognormal", median, sigma] => match sigma.parse::<f64>() {
+                Ok(sigma) if (0.0..=4.0).contains(&sigma) => {
+                    Ok(Latency::LogNormal(parse_ms(median)?, sigma))
+                }
+                _ => Err(format!("expected a sigma between 0 and 4, got {}", sigma)),
+            },
+            _ => Err(format!(
+                "unknown latency {}, expected fixed:<ms>, uniform:<min_ms>:<max_ms>, exponential:<mean_ms> or lognormal:<median_ms>:<sigma>",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Latency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Latency::Fixed(latency) => write!(f, "fixed:{}", latency.as_millis()),
+            Latency::Uniform(min, max) => {
+                write!(f, "uniform:{}:{}", min.as_millis(), max.as_millis())
+            }
+            Latency::Exponential(mean) => write!(f, "exponential:{}", mean.as_millis()),
+            Latency::LogNormal(median, sigma) => {
+                write!(f, "lognormal:{}:{}", median.as_millis(), sigma)
+            }
+        }
+    }
+}
+
+// messages from any of `from` to any of `to` take `latency` instead of the default, one way only
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    from: Vec<Id>,
+    to: Vec<Id>,
+    latency: Latency,
+}
+
+impl Link {
+    pub fn new(from: Vec<Id>, to: Vec<Id>, latency: Latency) -> Self {
+        Link { from, to, latency }
+    }
+
+    fn carries(&self, src: Id, dst: Id) -> bool {
+        self.from.contains(&src) && self.to.contains(&dst)
+    }
+}
+
+// <id>,<id>,...:<id>,<id>,...:<latency>
+impl FromStr for Link {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.splitn(3, ':').collect();
+        match parts.as_slice() {
+            [from, to, latency] => Ok(Link::new(
+                parse_ids(from)?,
+                parse_ids(to)?,
+                latency.parse()?,
+            )),
+            _ => Err(format!(
+                "unknown link {}, expected <id>,<id>,...:<id>,<id>,...:<latency>",
+                s
+            )),
+        }
+    }
+}
+
+// messages between `group` and everyone else are dropped while the partition lasts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Partition {
+    during: Range<Duration>,
+    group: Vec<Id>,
+}
+
+impl Partition {
+    fn separates(&self, now: Duration, src: Id, dst: Id) -> bool {
+        self.during.contains(&now) && self.group.contains(&src) != self.group.contains(&dst)
+    }
+}
+
+// <from_ms>:<until_ms>:<id>,<id>,...
+impl FromStr for Partition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        match parts.as_slice() {
+            [from, until, group] => Ok(Partition {
+                during: parse_ms(from)?..parse_ms(until)?,
+                group: parse_ids(group)?,
+            }),
+            _ => Err(format!(
+                "unknown partition {}, expected <from_ms>:<until_ms>:<id>,<id>,...",
+                s
+            )),
+        }
+    }
+}
+
+// what an adversary can tell about the messages it schedules
+pub trait Traffic {
+    // phase 1 of a round
+    fn is_prepare(&self) -> bool;
+    // only sent by a proposer leading a round: its accepts, decisions and heartbeats
+    fn is_leading(&self) -> bool;
+}
+
+impl<V, B: Ballot> Traffic for PaxosMsg<V, B> {
+    fn is_prepare(&self) -> bool {
+        matches!(self, PaxosMsg::Prepare(..))
+    }
+
+    fn is_leading(&self) -> bool {
+        matches!(
+            self,
+            PaxosMsg::Accept(..) | PaxosMsg::Decided(..) | PaxosMsg::Heartbeat(..)
+        )
+    }
+}
+
+impl<V, I: Traffic> Traffic for RegisterMsg<u64, V, I> {
+    fn is_prepare(&self) -> bool {
+        matches!(self, RegisterMsg::Internal(internal) if internal.is_prepare())
+    }
+
+    fn is_leading(&self) -> bool {
+        matches!(self, RegisterMsg::Internal(internal) if internal.is_leading())
+    }
+}
+
+// Decides how long each message between two actors spends on the wire, given the latency its
+// link drew for it. Random scheduling takes the draw as it is and can take a long time to line
+// up the orderings a protocol is weakest against; an adversary steers the messages it targets
+// into them on purpose. Messages an actor sends itself are never scheduled.
+pub trait Scheduler<M> {
+    fn latency(&mut self, now: Duration, src: Id, dst: Id, msg: &M, drawn: Duration) -> Duration;
+}
+
+// the link's draw, unchanged
+pub struct Random;
+
+impl<M> Scheduler<M> for Random {
+    fn latency(&mut self, _: Duration, _: Id, _: Id, _: &M, drawn: Duration) -> Duration {
+        drawn
+    }
+}
+
+// holds back everything the current leader sends, the leader being whoever last sent something
+// only a leader sends, so followers suspect it and proposers duel over its rounds
+pub struct SlowLeader {
+    delay: Duration,
+    leader: Option<Id>,
+}
+
+impl<M: Traffic> Scheduler<M> for SlowLeader {
+    fn latency(&mut self, _: Duration, src: Id, _: Id, msg: &M, drawn: Duration) -> Duration {
+        if msg.is_leading() {
+            self.leader = Some(src);
+        }
+        if self.leader == Some(src) {
+            drawn + self.delay
+        } else {
+            drawn
+        }
+    }
+}
+
+// Gathers prepares sent within `hold` of the first one and delivers them in the reverse of the
+// order they were sent, the oldest last, so an acceptor hears of the higher round before the
+// stale one it supersedes. Prepares are the only messages whose draw is ignored.
+pub struct LatePrepares {
+    hold: Duration,
+    // when the current gathering of prepares ends
+    until: Option<Duration>,
+}
+
+impl<M: Traffic> Scheduler<M> for LatePrepares {
+    fn latency(&mut self, now: Duration, _: Id, _: Id, msg: &M, drawn: Duration) -> Duration {
+        if !msg.is_prepare() {
+            return drawn;
+        }
+        let until = match self.until.filter(|until| now < *until) {
+            Some(until) => until,
+            None => *self.until.insert(now + self.hold),
+        };
+        // arrives at 2 * until + hold - now, the later sent the sooner delivered
+        self.hold + (until - now) * 2
+    }
+}
+
+// names the schedulers so the CLI and scenario files can pick one
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AdversarySpec {
+    Random,
+    SlowLeader(Duration),
+    LatePrepares(Duration),
+}
+
+impl AdversarySpec {
+    pub fn build<M: Traffic>(&self) -> Box<dyn Scheduler<M>> {
+        match *self {
+            AdversarySpec::Random => Box::new(Random),
+            AdversarySpec::SlowLeader(delay) => Box::new(SlowLeader {
+                delay,
+                leader: None,
+            }),
+            AdversarySpec::LatePrepares(hold) => Box::new(LatePrepares { hold, until: None }),
+        }
+    }
+}
+
+impl FromStr for AdversarySpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        match parts.as_slice() {
+            ["random"] => Ok(AdversarySpec::Random),
+            ["slow-leader", ms] => Ok(AdversarySpec::SlowLeader(parse_ms(ms)?)),
+            ["late-prepares", ms] => Ok(AdversarySpec::LatePrepares(parse_ms(ms)?)),
+            _ => Err(format!(
+                "unknown adversary {}, expected random, slow-leader:<ms> or late-prepares:<ms>",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AdversarySpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AdversarySpec::Random => write!(f, "random"),
+            AdversarySpec::SlowLeader(delay) => write!(f, "slow-leader:{}", delay.as_millis()),
+            AdversarySpec::LatePrepares(hold) => write!(f, "late-prepares:{}", hold.as_millis()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    seed: u64,
+    steps: usize,
+    latency: Latency,
+    // the first link carrying a message sets its latency, the rest take `latency`
+    links: Vec<Link>,
+    // messages from one region into another take `cross_region` rather than `latency`
+    topology: Topology,
+    cross_region: Option<Latency>,
+    // chance of any message between two actors being lost
+    drop_rate: f64,
+    // chance of a message having a bit flipped on the way, which its checksum should catch
+    corrupt_rate: f64,
+    partitions: Vec<Partition>,
+    adversary: AdversarySpec,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            seed: 0,
+            steps: 1_000_000,
+            latency: Latency::Uniform(Duration::from_millis(1), Duration::from_millis(10)),
+            links: Vec::new(),
+            topology: Topology::default(),
+            cross_region: None,
+            drop_rate: 0.0,
+            corrupt_rate: 0.0,
+            partitions: Vec::new(),
+            adversary: AdversarySpec::Random,
+        }
+    }
+}
+
+impl SimConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    pub fn latency(mut self, latency: Latency) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn link(mut self, link: Link) -> Self {
+        self.links.push(link);
+        self
+    }
+
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn cross_region_latency(mut self, cross_region: Option<Latency>) -> Self {
+        self.cross_region = cross_region;
+        self
+    }
+
+    fn latency_between(&self, src: Id, dst: Id) -> Latency {
+        match self.links.iter().find(|link| link.carries(src, dst)) {
+            Some(link) => link.latency,
+            None => match self.cross_region {
+                Some(latency) if self.topology.crosses(src, dst) => latency,
+                _ => self.latency,
+            },
+        }
+    }
+
+    pub fn drop_rate(mut self, drop_rate: f64) -> Self {
+        self.drop_rate = drop_rate;
+        self
+    }
+
+    pub fn corrupt_rate(mut self, corrupt_rate: f64) -> Self {
+        self.corrupt_rate = corrupt_rate;
+        self
+    }
+
+    pub fn partition(mut self, partition: Partition) -> Self {
+        self.partitions.push(partition);
+        self
+    }
+
+    pub fn adversary(mut self, adversary: AdversarySpec) -> Self {
+        self.adversary = adversary;
+        self
+    }
+}
+
+// what a run did, and the first invariant it broke if any
+#[derive(Clone, Debug)]
+pub struct SimReport {
+    seed: u64,
+    steps: usize,
+    elapsed: Duration,
+    delivered: usize,
+    dropped: usize,
+    // messages with a flipped bit, those their checksum caught and those it let through
+    corrupted: usize,
+    undetected: usize,
+    // time from a client sending a request to it receiving the reply, in completion order
+    latencies: Vec<(Id, Duration)>,
+    // when the last partition healed, and how many requests completed since
+    healed: Option<Duration>,
+    completed_after_heal: usize,
+    pub violation: Option<(&'static str, usize, Duration)>,
+}
+
+impl SimReport {
+    // completed requests per simulated second
+    pub fn throughput(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.latencies.len() as f64 / self.elapsed.as_secs_f64()
+    }
+
+    // messages corrupted in transit that their checksum caught, and those it let through
+    pub fn corrupted(&self) -> (usize, usize) {
+        (self.corrupted, self.undetected)
+    }
+
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        let mut sorted: Vec<Duration> =
+            self.latencies.iter().map(|(_, latency)| *latency).collect();
+        sorted.sort();
+        let last = sorted.len().checked_sub(1)?;
+        sorted.get(((last as f64) * p).round() as usize).copied()
+    }
+}
+
+impl fmt::Display for SimReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Simulated {} steps over {:?} from seed {}: {} messages delivered, {} dropped",
+            self.steps, self.elapsed, self.seed, self.delivered, self.dropped
+        )?;
+        if self.corrupted + self.undetected > 0 {
+            writeln!(
+                f,
+                "Corrupted {} messages in transit, {} caught by their checksum and dropped",
+                self.corrupted + self.undetected,
+                self.corrupted
+            )?;
+        }
+        match (
+            self.percentile(0.5),
+            self.percentile(0.99),
+            self.percentile(1.0),
+        ) {
+            (Some(p50), Some(p99), Some(max)) => writeln!(
+                f,
+                "Completed {} requests, {:.1}/s, latency p50 {:?}, p99 {:?}, max {:?}",
+                self.latencies.len(),
+                self.throughput(),
+                p50,
+                p99,
+                max
+            )?,
+            _ => writeln!(f, "Completed no requests")?,
+        }
+        let mut by_client: BTreeMap<Id, Histogram> = BTreeMap::new();
+        for (client, latency) in &self.latencies {
+            by_client.entry(*client).or_default().record(*latency);
+        }
+        for (client, histogram) in by_client {
+            writeln!(f, "Client {} latency:", client)?;
+            write!(f, "{}", histogram)?;
+        }
+        if let Some(healed) = self.healed {
+            writeln!(
+                f,
+                "Completed {} requests after the last partition healed at {:?}",
+                self.completed_after_heal, healed
+            )?;
+        }
+        match self.violation {
+            Some((name, step, at)) => write!(
+                f,
+                "Broke \"{}\" at step {}, {:?} into the run",
+                name, step, at
+            ),
+            None => write!(f, "Every invariant held"),
+        }
+    }
+}
+
+// request latencies counted in buckets doubling in width: under 1ms, under 2ms, under 4ms, ...
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+    buckets: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn record(&mut self, latency: Duration) {
+        let ms = latency.as_millis() as u64;
+        let bucket = (u64::BITS - ms.leading_zeros()) as usize;
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+    }
+}
+
+// a row per bucket from the first filled to the last, its bar scaled to the fullest
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let first = self.buckets.iter().position(|count| *count > 0);
+        let fullest = self.buckets.iter().copied().max().unwrap_or(0);
+        for (bucket, count) in self.buckets.iter().enumerate().skip(first.unwrap_or(0)) {
+            let bar = (count * 40 + fullest - 1) / fullest.max(1);
+            writeln!(
+                f,
+                "  < {:>6}ms {:<40} {}",
+                1u64 << bucket,
+                "#".repeat(bar),
+                count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+enum Pending<M> {
+    Deliver { src: Id, dst: Id, msg: M },
+    // stale once the actor has set or cancelled its timer since
+    Timeout { id: Id, generation: u64 },
+}
+
+struct Sim<'a, A: Actor> {
+    cfg: &'a SimConfig,
+    rng: SplitMix,
+    now: Duration,
+    // (when, sequence number), the sequence number breaking ties in scheduling order
+    queue: BinaryHeap<Reverse<(Duration, u64)>>,
+    pending: HashMap<u64, Pending<A::Msg>>,
+    scheduled: u64,
+    timers: Vec<u64>,
+    requests: HashMap<(Id, u64), Duration>,
+    scheduler: Box<dyn Scheduler<A::Msg>>,
+    report: SimReport,
+}
+
+impl<'a, A, V, I> Sim<'a, A>
+where
+    A: Actor<Msg = RegisterMsg<u64, V, I>>,
+    A::Msg: Serialize + DeserializeOwned,
+    I: Traffic,
+{
+    fn schedule(&mut self, after: Duration, event: Pending<A::Msg>) {
+        self.queue.push(Reverse((self.now + after, self.scheduled)));
+        self.pending.insert(self.scheduled, event);
+        self.scheduled += 1;
+    }
+
+    fn perform(&mut self, src: Id, out: Out<A>) {
+        for command in out {
+            match command {
+                Command::Send(dst, msg) => {
+                    if let RegisterMsg::Put(request_id, _) | RegisterMsg::Get(request_id) = &msg {
+                        self.requests.entry((src, *request_id)).or_insert(self.now);
+                    }
+                    // a message to itself stands in for something local, which is never lost
+                    let lost = src != dst
+                        && (self.rng.unit() < self.cfg.drop_rate
+                            || self
+                                .cfg
+                                .partitions
+                                .iter()
+                                .any(|partition| partition.separates(self.now, src, dst)));
+                    if lost {
+                        self.report.dropped += 1;
+                        continue;
+                    }
+                    let msg = if src != dst && self.rng.unit() < self.cfg.corrupt_rate {
+                        match self.corrupt(&msg) {
+                            Some(msg) => msg,
+                            None => continue,
+                        }
+                    } else {
+                        msg
+                    };
+                    let mut latency = self.cfg.latency_between(src, dst).sample(&mut self.rng);
+                    if src != dst {
+                        latency = self.scheduler.latency(self.now, src, dst, &msg, latency);
+                    }
+                    self.schedule(latency, Pending::Deliver { src, dst, msg });
+                }
+                Command::SetTimer(range) => {
+                    let timer = &mut self.timers[usize::from(src)];
+                    *timer += 1;
+                    let generation = *timer;
+                    let after = self.rng.within(range);
+                    self.schedule(
+                        after,
+                        Pending::Timeout {
+                            id: src,
+                            generation,
+                        },
+                    );
+                }
+                Command::CancelTimer => self.timers[usize::from(src)] += 1,
+            }
+        }
+    }
+
+    // sends the message through the same checksummed encoding as a TCP frame, flipping one bit
+    // of it, and what comes out if the checksum misses it
+    fn corrupt(&mut self, msg: &A::Msg) -> Option<A::Msg> {
+        let mut frame = checksum::seal(&bincode::serialize(msg).ok()?);
+        let bit = self.rng.below(frame.len() * 8);
+        frame[bit / 8] ^= 1 << (bit % 8);
+        let received =
+            checksum::unseal(&frame).and_then(|payload| bincode::deserialize(payload).ok());
+        match received {
+            Some(_) => self.report.undetected += 1,
+            None => self.report.corrupted += 1,
+        }
+        received
+    }
+}
+
+// Runs the actors, indexed by id as in a model, under a scheduler driven entirely by the seed,
+// so the same seed always gives the same run. Message latency, per link or overall, loss and
+// partitions come from `cfg`, and its adversary may reschedule any message; timers fire
+// somewhere in the range they were set with. Stops after `cfg.steps` deliveries and timeouts,
+// once nothing is left to happen, or as soon as an invariant breaks.
+pub fn run<A, V, I>(actors: &[A], cfg: &SimConfig, invariants: &[Invariant<A::State>]) -> SimReport
+where
+    A: Actor<Msg = RegisterMsg<u64, V, I>>,
+    A::Msg: Serialize + DeserializeOwned,
+    I: Traffic,
+{
+    let mut sim = Sim {
+        cfg,
+        rng: SplitMix(cfg.seed),
+        now: Duration::ZERO,
+        queue: BinaryHeap::new(),
+        pending: HashMap::new(),
+        scheduled: 0,
+        timers: vec![0; actors.len()],
+        requests: HashMap::new(),
+        scheduler: cfg.adversary.build(),
+        report: SimReport {
+            seed: cfg.seed,
+            steps: 0,
+            elapsed: Duration::ZERO,
+            delivered: 0,
+            dropped: 0,
+            corrupted: 0,
+            undetected: 0,
+            latencies: Vec::new(),
+            healed: cfg
+                .partitions
+                .iter()
+                .map(|partition| partition.during.end)
+                .max(),
+            completed_after_heal: 0,
+            violation: None,
+        },
+    };
+    let mut states = Vec::with_capacity(actors.len());
+    for (i, actor) in actors.iter().enumerate() {
+        let mut out = Out::new();
+        states.push(actor.on_start(Id::from(i), &mut out));
+        sim.perform(Id::from(i), out);
+    }
+
+    while sim.report.steps < cfg.steps {
+        let Reverse((at, seq)) = match sim.queue.pop() {
+            Some(next) => next,
+            None => break,
+        };
+        let event = match sim.pending.remove(&seq) {
+            Some(event) => event,
+            None => continue,
+        };
+        sim.now = at;
+        let (id, out) = match event {
+            Pending::Deliver { src, dst, msg } => {
+                let i = usize::from(dst);
+                if i >= actors.len() {
+                    continue;
+                }
+                if let RegisterMsg::PutOk(request_id) | RegisterMsg::GetOk(request_id, _) = &msg {
+                    if let Some(sent) = sim.requests.remove(&(dst, *request_id)) {
+                        sim.report.latencies.push((dst, at - sent));
+                        if sim.report.healed.map_or(false, |healed| at >= healed) {
+                            sim.report.completed_after_heal += 1;
+                        }
+                    }
+                }
+                sim.report.delivered += 1;
+                let mut out = Out::new();
+                let mut state = Cow::Borrowed(&states[i]);
+                actors[i].on_msg(dst, &mut state, src, msg, &mut out);
+                if let Cow::Owned(state) = state {
+                    states[i] = state;
+                }
+                (dst, out)
+            }
+            Pending::Timeout { id, generation } => {
+                let i = usize::from(id);
+                if sim.timers[i] != generation {
+                    continue;
+                }
+                let mut out = Out::new();
+                let mut state = Cow::Borrowed(&states[i]);
+                actors[i].on_timeout(id, &mut state, &mut out);
+                if let Cow::Owned(state) = state {
+                    states[i] = state;
+                }
+                (id, out)
+            }
+        };
+        sim.perform(id, out);
+        sim.report.steps += 1;
+        sim.report.elapsed = sim.now;
+
+        if let Some((name, _)) = invariants.iter().find(|(_, holds)| !holds(&states)) {
+            sim.report.violation = Some((*name, sim.report.steps, sim.now));
+            break;
+        }
+    }
+    sim.report
+}
@@ -0,0 +1,354 @@
+use crate::paxos::{Command, PaxosActor, PaxosMsg, PaxosState, Slot, Value};
+use crate::shard::ShardedPaxos;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use stateright::actor::register::RegisterMsg;
+use stateright::actor::Command as ActorCommand;
+use stateright::actor::*;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+// whatever the replicated log drives, fed every decided command exactly once and in slot order
+pub trait StateMachine: Clone + Debug + Default + Eq + Hash + Serialize + DeserializeOwned {
+    type Command: Value;
+    type Response: Clone + Debug + Eq + Hash + Serialize + DeserializeOwned;
+
+    fn apply(&mut self, command: &Self::Command) -> Self::Response;
+
+    // whether the order two commands run in can make a difference; the default assumes it
+    // always can, so nothing is ever reordered
+    fn conflicts(_a: &Self::Command, _b: &Self::Command) -> bool {
+        true
+    }
+
+    // the part of the state a command touches, which picks the Paxos group ordering it when
+    // the state is sharded; by default everything is in one part
+    fn shard_key(_command: &Self::Command) -> u64 {
+        0
+    }
+}
+
+// a command as it sits in the log, tagged with the request it came from so that a command
+// decided twice is only applied once and the replica the client asked can answer it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Tagged<C> {
+    client: Id,
+    request_id: u64,
+    command: C,
+}
+
+impl<C> Tagged<C> {
+    pub fn command(&self) -> &C {
+        &self.command
+    }
+}
+
+impl<C: Default> Default for Tagged<C> {
+    fn default() -> Self {
+        Tagged {
+            client: Id::from(0),
+            request_id: 0,
+            command: C::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum SmrMsg<C, R> {
+    // client facing
+    Request(u64, C),
+    Response(u64, R),
+    Internal(PaxosMsg<Tagged<C>>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct SmrState<M: StateMachine> {
+    paxos: PaxosState<Tagged<M::Command>>,
+    machine: M,
+    // the next slot to apply
+    next: Slot,
+    applied: BTreeSet<(Id, u64)>,
+    // requests sent to this replica that still need a response
+    waiting: BTreeSet<(Id, u64)>,
+    // every command in the order this replica applied it
+    order: Vec<Tagged<M::Command>>,
+}
+
+impl<M: StateMachine> SmrState<M> {
+    pub fn paxos(&self) -> &PaxosState<Tagged<M::Command>> {
+        &self.paxos
+    }
+
+    pub fn machine(&self) -> &M {
+        &self.machine
+    }
+
+    pub fn next(&self) -> Slot {
+        self.next
+    }
+
+    pub fn order(&self) -> &[Tagged<M::Command>] {
+        &self.order
+    }
+}
+
+// Multi-Paxos orders the commands and the state machine runs them; clients are answered
+// once their command has been applied rather than when it is decided. Compaction has to
+// stay off, since a snapshot only carries the register and not the state machine.
+//
+// With `commutative` set the replicas are a lite take on Generalized Paxos: a decided batch only
+// fixes the order of the commands in it that conflict, and each replica applies the rest in an
+// order of its own. What replicas agree on is then the log up to reordering commands that do
+// not conflict, rather than the exact sequence.
+pub struct SmrActor<M: StateMachine> {
+    paxos: PaxosActor<Tagged<M::Command>>,
+    commutative: bool,
+}
+
+impl<M: StateMachine> SmrActor<M> {
+    pub fn new(paxos: PaxosActor<Tagged<M::Command>>) -> Self {
+        SmrActor {
+            paxos,
+            commutative: false,
+        }
+    }
+
+    pub fn commutative(mut self, commutative: bool) -> Self {
+        self.commutative = commutative;
+        self
+    }
+
+    // an order of the batch that keeps every conflicting pair as decided, picking among the
+    // commands free to go next by replica, so replicas really do apply batches differently
+    fn reorder(id: Id, mut commands: Vec<Tagged<M::Command>>) -> Vec<Tagged<M::Command>> {
+        let mut ordered = Vec::with_capacity(commands.len());
+        while !commands.is_empty() {
+            let free: Vec<usize> = (0..commands.len())
+                .filter(|&i| {
+                    commands[..i]
+                        .iter()
+                        .all(|earlier| !M::conflicts(&earlier.command, &commands[i].command))
+                })
+                .collect();
+            let next = free[usize::from(id) % free.len()];
+            ordered.push(commands.remove(next));
+        }
+        ordered
+    }
+
+    fn forward<A: Actor<Msg = SmrMsg<M::Command, M::Response>>>(
+        paxos_out: Out<PaxosActor<Tagged<M::Command>>>,
+        o: &mut Out<A>,
+    ) {
+        for command in paxos_out {
+            match command {
+                ActorCommand::Send(dst, RegisterMsg::Internal(msg)) => {
+                    o.send(dst, SmrMsg::Internal(msg))
+                }
+                // the paxos acknowledgements only mean decided, clients wait for `apply_decided`
+                ActorCommand::Send(_, _) => {}
+                ActorCommand::SetTimer(duration) => o.set_timer(duration),
+                ActorCommand::CancelTimer => o.cancel_timer(),
+            }
+        }
+    }
+
+    fn apply_decided<A: Actor<Msg = SmrMsg<M::Command, M::Response>>>(
+        &self,
+        id: Id,
+        state: &mut Cow<SmrState<M>>,
+        o: &mut Out<A>,
+    ) {
+        while let Some(entry) = state.paxos.log().get(&state.next) {
+            let entry = entry.clone();
+            let state = state.to_mut();
+            state.next += 1;
+            // reconfigurations only concern paxos itself
+            let commands = match entry {
+                Command::Put(tagged) | Command::SessionPut(_, tagged) => vec![tagged],
+                Command::Batch(tagged) if self.commutative => Self::reorder(id, tagged),
+                Command::Batch(tagged) => tagged,
+                Command::Reconfigure(_) => continue,
+            };
+            for tagged in commands {
+                // a retried proposal can get the same command decided in a second slot
+                if !state.applied.insert((tagged.client, tagged.request_id)) {
+                    continue;
+                }
+                let response = state.machine.apply(&tagged.command);
+                state.order.push(tagged.clone());
+                if state.waiting.remove(&(tagged.client, tagged.request_id)) {
+                    o.send(tagged.client, SmrMsg::Response(tagged.request_id, response));
+                }
+            }
+        }
+    }
+
+    // the handlers take the output of whichever model actor wraps the replica
+    pub(crate) fn on_start<A: Actor<Msg = SmrMsg<M::Command, M::Response>>>(
+        &self,
+        id: Id,
+        o: &mut Out<A>,
+    ) -> SmrState<M> {
+        let mut paxos_out = Out::new();
+        let paxos = self.paxos.on_start(id, &mut paxos_out);
+        Self::forward(paxos_out, o);
+        SmrState {
+            paxos,
+            machine: M::default(),
+            next: 0,
+            applied: BTreeSet::new(),
+            waiting: BTreeSet::new(),
+            order: Vec::new(),
+        }
+    }
+
+    pub(crate) fn on_msg<A: Actor<Msg = SmrMsg<M::Command, M::Response>>>(
+        &self,
+        id: Id,
+        state: &mut Cow<SmrState<M>>,
+        src: Id,
+        msg: SmrMsg<M::Command, M::Response>,
+        o: &mut Out<A>,
+    ) {
+        let paxos_msg = match msg {
+            SmrMsg::Request(request_id, command) => {
+                if !state.applied.contains(&(src, request_id)) {
+                    state.to_mut().waiting.insert((src, request_id));
+                }
+                let tagged = Tagged {
+                    client: src,
+                    request_id,
+                    command,
+                };
+                RegisterMsg::Put(request_id, tagged)
+            }
+            SmrMsg::Internal(msg) => RegisterMsg::Internal(msg),
+            SmrMsg::Response(_, _) => return,
+        };
+        let mut paxos_state = Cow::Borrowed(&state.paxos);
+        let mut paxos_out = Out::new();
+        self.paxos
+            .on_msg(id, &mut paxos_state, src, paxos_msg, &mut paxos_out);
+        if let Cow::Owned(paxos_state) = paxos_state {
+            state.to_mut().paxos = paxos_state;
+        }
+        Self::forward(paxos_out, o);
+        self.apply_decided(id, state, o);
+    }
+
+    pub(crate) fn on_timeout<A: Actor<Msg = SmrMsg<M::Command, M::Response>>>(
+        &self,
+        id: Id,
+        state: &mut Cow<SmrState<M>>,
+        o: &mut Out<A>,
+    ) {
+        let mut paxos_state = Cow::Borrowed(&state.paxos);
+        let mut paxos_out = Out::new();
+        self.paxos.on_timeout(id, &mut paxos_state, &mut paxos_out);
+        if let Cow::Owned(paxos_state) = paxos_state {
+            state.to_mut().paxos = paxos_state;
+        }
+        Self::forward(paxos_out, o);
+        self.apply_decided(id, state, o);
+    }
+}
+
+// clients and servers have to share an actor type in the model, as with `RegisterActor`
+pub enum SmrModelActor<M: StateMachine> {
+    // submits its commands one at a time, each to a server in the group ordering it
+    Client {
+        commands: Vec<M::Command>,
+        shards: ShardedPaxos,
+    },
+    Server(SmrActor<M>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum SmrModelState<M: StateMachine> {
+    Client { awaiting: Option<u64>, sent: usize },
+    Server(SmrState<M>),
+}
+
+impl<M: StateMachine> SmrModelActor<M> {
+    fn send_command(
+        id: Id,
+        commands: &[M::Command],
+        shards: &ShardedPaxos,
+        sent: usize,
+        o: &mut Out<Self>,
+    ) -> Option<u64> {
+        let command = commands.get(sent)?;
+        // unique across clients, since a server deduplicates on it
+        let request_id = ((usize::from(id) as u64) << 32) | sent as u64;
+        let server = shards.route(M::shard_key(command), id);
+        o.send(server, SmrMsg::Request(request_id, command.clone()));
+        Some(request_id)
+    }
+}
+
+impl<M: StateMachine> Actor for SmrModelActor<M> {
+    type Msg = SmrMsg<M::Command, M::Response>;
+    type State = SmrModelState<M>;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        match self {
+            SmrModelActor::Client { commands, shards } => SmrModelState::Client {
+                awaiting: Self::send_command(id, commands, shards, 0, o),
+                sent: 1,
+            },
+            SmrModelActor::Server(server) => SmrModelState::Server(server.on_start(id, o)),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match (self, state.as_ref()) {
+            (
+                SmrModelActor::Client { commands, shards },
+                SmrModelState::Client { awaiting, sent },
+            ) => {
+                let request_id = match msg {
+                    SmrMsg::Response(request_id, _) => request_id,
+                    _ => return,
+                };
+                if *awaiting != Some(request_id) {
+                    return;
+                }
+                let sent = *sent;
+                *state = Cow::Owned(SmrModelState::Client {
+                    awaiting: Self::send_command(id, commands, shards, sent, o),
+                    sent: sent + 1,
+                });
+            }
+            (SmrModelActor::Server(server), SmrModelState::Server(server_state)) => {
+                let mut server_state = Cow::Borrowed(server_state);
+                server.on_msg(id, &mut server_state, src, msg, o);
+                if let Cow::Owned(server_state) = server_state {
+                    *state = Cow::Owned(SmrModelState::Server(server_state));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_timeout(&self, id: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        if let (SmrModelActor::Server(server), SmrModelState::Server(server_state)) =
+            (self, state.as_ref())
+        {
+            let mut server_state = Cow::Borrowed(server_state);
+            server.on_timeout(id, &mut server_state, o);
+            if let Cow::Owned(server_state) = server_state {
+                *state = Cow::Owned(SmrModelState::Server(server_state));
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use crate::checksum::crc32;
+use crate::encryption::{Encryptor, NoEncryption};
+use crate::paxos::{Ballot, Command, DurableState, Lease, RoundIdentifier, Slot, Value};
+use serde::{Deserialize, Serialize};
+use stateright::actor::Id;
+use stateright::{Rewrite, RewritePlan};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+// everything an acceptor has to make durable before it answers; decided slots are not
+// in here, a recovering replica learns them again from its peers
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum WalRecord<V, B = RoundIdentifier> {
+    Promise(Slot, B),
+    Accept(Slot, B, Command<V>),
+    // a witness's vote, which keeps the ballot but not the value
+    Witness(Slot, B),
+    // the proposer's round counter, so a restarted proposer never reuses a round
+    Round(u64),
+    Lease(Option<Lease<B>>),
+}
+
+impl<V: Value, B: Ballot> WalRecord<V, B> {
+    fn replay(self, durable: &mut DurableState<V, B>) {
+        match self {
+            WalRecord::Promise(slot, rid) => durable.promise(slot, rid),
+            WalRecord::Accept(slot, rid, command) => durable.accept(slot, rid, command),
+            WalRecord::Witness(slot, rid) => durable.witness(slot, rid),
+            WalRecord::Round(round) => durable.set_round(round),
+            WalRecord::Lease(lease) => durable.set_lease(lease),
+        }
+    }
+}
+
+pub trait Storage<V: Value, B: Ballot = RoundIdentifier> {
+    fn persist_promise(&mut self, slot: Slot, rid: B) -> io::Result<()>;
+    fn persist_accept(&mut self, slot: Slot, rid: B, command: &Command<V>) -> io::Result<()>;
+    fn persist_witness(&mut self, slot: Slot, rid: B) -> io::Result<()>;
+    fn persist_round(&mut self, round: u64) -> io::Result<()>;
+    fn persist_lease(&mut self, lease: Option<Lease<B>>) -> io::Result<()>;
+    // everything persisted so far, as a freshly restarted actor sees it
+    fn load(&self) -> io::Result<DurableState<V, B>>;
+
+    fn persist(&mut self, record: &WalRecord<V, B>) -> io::Result<()> {
+        match record {
+            WalRecord::Promise(slot, rid) => self.persist_promise(*slot, *rid),
+            WalRecord::Accept(slot, rid, command) => self.persist_accept(*slot, *rid, command),
+            WalRecord::Witness(slot, rid) => self.persist_witness(*slot, *rid),
+            WalRecord::Round(round) => self.persist_round(*round),
+            WalRecord::Lease(lease) => self.persist_lease(*lease),
+        }
+    }
+}
+
+// what the model checker uses, it lives in the actor state so a crash can fall back on it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, PartialOrd, Ord)]
+pub struct MemoryStorage<V, B = RoundIdentifier> {
+    durable: DurableState<V, B>,
+}
+
+impl<V: Value, B: Ballot> MemoryStorage<V, B> {
+    pub fn new(durable: DurableState<V, B>) -> Self {
+        MemoryStorage { durable }
+    }
+
+    pub fn durable(&self) -> &DurableState<V, B> {
+        &self.durable
+    }
+}
+
+impl<V: Clone, B: Ballot> Rewrite<Id> for MemoryStorage<V, B> {
+    fn rewrite<S>(&self, plan: &RewritePlan<Id, S>) -> Self {
+        MemoryStorage {
+            durable: self.durable.rewrite(plan),
+        }
+    }
+}
+
+impl<V: Value, B: Ballot> Default for MemoryStorage<V, B> {
+    fn default() -> Self {
+        Self::new(DurableState::new())
+    }
+}
+
+impl<V: Value, B: Ballot> Storage<V, B> for MemoryStorage<V, B> {
+    fn persist_promise(&mut self, slot: Slot, rid: B) -> io::Result<()> {
+        self.durable.promise(slot, rid);
+        Ok(())
+    }
+
+    fn persist_accept(&mut self, slot: Slot, rid: B, command: &Command<V>) -> io::Result<()> {
+        self.durable.accept(slot, rid, command.clone());
+        Ok(())
+    }
+
+    fn persist_witness(&mut self, slot: Slot, rid: B) -> io::Result<()> {
+        self.durable.witness(slot, rid);
+        Ok(())
+    }
+
+    fn persist_round(&mut self, round: u64) -> io::Result<()> {
+        self.durable.set_round(round);
+        Ok(())
+    }
+
+    fn persist_lease(&mut self, lease: Option<Lease<B>>) -> io::Result<()> {
+        self.durable.set_lease(lease);
+        Ok(())
+    }
+
+    fn load(&self) -> io::Result<DurableState<V, B>> {
+        Ok(self.durable.clone())
+    }
+}
+
+// an append-only file of JSON records, one per line, synced before every reply
+pub struct FileStorage<V, B = RoundIdentifier> {
+    file: File,
+    path: std::path::PathBuf,
+    encryptor: Box<dyn Encryptor>,
+    _value: PhantomData<(V, B)>,
+}
+
+impl<V: Value, B: Ballot> FileStorage<V, B> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())?;
+        Ok(FileStorage {
+            file,
+            path: path.as_ref().to_path_buf(),
+            encryptor: Box::new(NoEncryption),
+            _value: PhantomData,
+        })
+    }
+
+    // records appended from now on are encrypted, the ones already in the log are still read
+    pub fn encryptor(mut self, encryptor: Box<dyn Encryptor>) -> Self {
+        self.encryptor = encryptor;
+        self
+    }
+
+    // One record per line, behind the CRC-32 of what follows it in hex. Encrypted records are
+    // their ciphertext in hex after `enc:`, and the checksum covers that, so a torn write is
+    // told apart from a wrong key.
+    fn append(&mut self, record: WalRecord<V, B>) -> io::Result<()> {
+        let mut payload = serde_json::to_vec(&record)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if self.encryptor.encrypts() {
+            let sealed = self.encryptor.encrypt(&payload)?;
+            payload = b"enc:".to_vec();
+            payload.extend(
+                sealed
+                    .iter()
+                    .flat_map(|b| format!("{:02x}", b).into_bytes()),
+            );
+        }
+        let mut line = format!("{:08x} ", crc32(&payload)).into_bytes();
+        line.extend_from_slice(&payload);
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()
+    }
+
+    // logs written before records carried a checksum are read as they are
+    fn parse(&self, line: &str) -> io::Result<Option<WalRecord<V, B>>> {
+        let payload = match line.split_once(' ') {
+            Some((crc, payload)) if !line.starts_with('{') => match u32::from_str_radix(crc, 16) {
+                Ok(crc) if crc == crc32(payload.as_bytes()) => payload,
+                _ => return Ok(None),
+            },
+            _ => line,
+        };
+        let json = match payload.strip_prefix("enc:") {
+            Some(hex) => {
+                let invalid = || io::Error::new(io::ErrorKind::InvalidData, "bad ciphertext");
+                if !self.encryptor.encrypts() {
+                    let msg = "the log is encrypted and no key was given";
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+                }
+                if hex.len() % 2 != 0 || !hex.is_ascii() {
+                    return Err(invalid());
+                }
+                let sealed = (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+                    .collect::<io::Result<Vec<_>>>()?;
+                self.encryptor.decrypt(&sealed)?
+            }
+            None => payload.as_bytes().to_vec(),
+        };
+        Ok(serde_json::from_slice(&json).ok())
+    }
+}
+
+impl<V: Value, B: Ballot> Storage<V, B> for FileStorage<V, B> {
+    fn persist_promise(&mut self, slot: Slot, rid: B) -> io::Result<()> {
+        self.append(WalRecord::Promise(slot, rid))
+    }
+
+    fn persist_accept(&mut self, slot: Slot, rid: B, command: &Command<V>) -> io::Result<()> {
+        self.append(WalRecord::Accept(slot, rid, command.clone()))
+    }
+
+    fn persist_witness(&mut self, slot: Slot, rid: B) -> io::Result<()> {
+        self.append(WalRecord::Witness(slot, rid))
+    }
+
+    fn persist_round(&mut self, round: u64) -> io::Result<()> {
+        self.append(WalRecord::Round(round))
+    }
+
+    fn persist_lease(&mut self, lease: Option<Lease<B>>) -> io::Result<()> {
+        self.append(WalRecord::Lease(lease))
+    }
+
+    fn load(&self) -> io::Result<DurableState<V, B>> {
+        let mut durable = DurableState::new();
+        let reader = BufReader::new(File::open(&self.path)?);
+        let lines = reader.lines().collect::<io::Result<Vec<_>>>()?;
+        for (number, line) in lines.iter().enumerate() {
+            let record = match self.parse(line)? {
+                Some(record) => record,
+                // a torn final write is the only way the last line can be cut short
+                None if number + 1 == lines.len() => break,
+                // anything earlier was acknowledged, so skipping it could break a promise
+                None => {
+                    let msg = format!("corrupt record on line {}", number + 1);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+                }
+            };
+            record.replay(&mut durable);
+        }
+        Ok(durable)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    // a log of promises for slots 0 to 2, in a file of its own
+    fn written(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("paxos-rs-{}-{}.wal", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut wal = FileStorage::<char>::open(&path).unwrap();
+        for slot in 0..3 {
+            wal.persist_promise(slot, RoundIdentifier::new(1, Id::from(0)))
+                .unwrap();
+        }
+        path
+    }
+
+    // the log with `edit` applied to its lines
+    fn rewrite(path: &Path, edit: impl FnOnce(&mut Vec<String>)) {
+        let mut lines: Vec<String> = fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        edit(&mut lines);
+        fs::write(path, lines.join("\n") + "\n").unwrap();
+    }
+
+    #[test]
+    fn a_torn_last_record_is_dropped() {
+        let path = written("torn");
+        rewrite(&path, |lines| {
+            let last = lines.last_mut().unwrap();
+            last.truncate(last.len() / 2);
+        });
+        let durable = FileStorage::<char>::open(&path).unwrap().load().unwrap();
+        assert!(durable.last_seen(1).is_some());
+        assert_eq!(durable.last_seen(2), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_corrupt_record_before_the_last_is_refused() {
+        let path = written("corrupt");
+        rewrite(&path, |lines| {
+            // a slot number changed on disk, which the checksum no longer covers
+            assert!(lines[1].contains("[1,"), "{}", lines[1]);
+            lines[1] = lines[1].replacen("[1,", "[7,", 1);
+        });
+        let error = FileStorage::<char>::open(&path)
+            .unwrap()
+            .load()
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 2"), "{}", error);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "encryption")]
+    fn encrypted(wal: FileStorage<char>) -> FileStorage<char> {
+        let key = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+        let key = crate::encryption::Key::parse(key).unwrap();
+        wal.encryptor(Box::new(crate::encryption::AesGcm::new(&key)))
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn a_plaintext_log_goes_on_encrypted_once_given_a_key() {
+        let path = written("encrypted");
+        let mut wal = encrypted(FileStorage::open(&path).unwrap());
+        wal.persist_promise(3, RoundIdentifier::new(2, Id::from(0)))
+            .unwrap();
+        let log = fs::read_to_string(&path).unwrap();
+        assert!(log.lines().last().unwrap().contains(" enc:"), "{}", log);
+
+        let durable = encrypted(FileStorage::open(&path).unwrap()).load().unwrap();
+        assert!(durable.last_seen(0).is_some());
+        assert_eq!(
+            durable.last_seen(3),
+            Some(RoundIdentifier::new(2, Id::from(0)))
+        );
+        // without the key the encrypted record cannot be read, and is not taken for torn
+        let error = FileStorage::<char>::open(&path)
+            .unwrap()
+            .load()
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+}
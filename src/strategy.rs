@@ -0,0 +1,98 @@
+use std::fmt;
+use std::str::FromStr;
+
+// strategy to make forward progress on Paxos
+// "majority wins" is not needed for linearizability only for the strict (arguably correct) definition of "consensus".
+// whenever a strategy does not ask for a quorum, a single reply is enough to move on.
+pub trait ForwardStrategy: Send + Sync {
+    // whether phase 2 waits for a quorum of promises
+    fn majority_promises(&self) -> bool;
+    // whether a value is only decided once a quorum has accepted it
+    fn majority_acceptor(&self) -> bool;
+}
+
+// classic Paxos
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MajorityPromises;
+
+impl ForwardStrategy for MajorityPromises {
+    fn majority_promises(&self) -> bool {
+        true
+    }
+
+    fn majority_acceptor(&self) -> bool {
+        true
+    }
+}
+
+// starts phase 2 after the first promise
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AnyPromise;
+
+impl ForwardStrategy for AnyPromise {
+    fn majority_promises(&self) -> bool {
+        false
+    }
+
+    fn majority_acceptor(&self) -> bool {
+        true
+    }
+}
+
+// decides as soon as a single acceptor has accepted
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FastPath;
+
+impl ForwardStrategy for FastPath {
+    fn majority_promises(&self) -> bool {
+        true
+    }
+
+    fn majority_acceptor(&self) -> bool {
+        false
+    }
+}
+
+// names the strategies so models and the CLI can pick one
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StrategySpec {
+    MajorityPromises,
+    AnyPromise,
+    FastPath,
+}
+
+impl StrategySpec {
+    pub fn build(&self) -> Box<dyn ForwardStrategy> {
+        match self {
+            StrategySpec::MajorityPromises => Box::new(MajorityPromises),
+            StrategySpec::AnyPromise => Box::new(AnyPromise),
+            StrategySpec::FastPath => Box::new(FastPath),
+        }
+    }
+}
+
+impl FromStr for StrategySpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "majority" => Ok(StrategySpec::MajorityPromises),
+            "any-promise" => Ok(StrategySpec::AnyPromise),
+            "fast-path" => Ok(StrategySpec::FastPath),
+            _ => Err(format!(
+                "unknown strategy {}, expected majority, any-promise or fast-path",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for StrategySpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StrategySpec::MajorityPromises => write!(f, "majority"),
+            StrategySpec::AnyPromise => write!(f, "any-promise"),
+            StrategySpec::FastPath => write!(f, "fast-path"),
+        }
+    }
+}
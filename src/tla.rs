@@ -0,0 +1,219 @@
+use crate::client::{PaxosModelActor, PaxosModelActorState};
+use crate::paxos::{Command, PaxosMsg, RoundIdentifier, Slot, Value};
+use crate::trace::Step;
+use stateright::actor::register::RegisterMsg;
+use stateright::actor::Id;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Counterexamples of the paxos model written as behaviors of Lamport's single-decree Paxos.tla,
+// one for every slot the path touched, so that TLC can tell whether each step is one the spec
+// allows. Every state is a record of that spec's variables: per acceptor the highest ballot it
+// promised (maxBal), the ballot it last accepted in (maxVBal) and what it accepted (maxVal),
+// then every message sent so far (msgs). Ballots become round * actors + proposer, which keeps
+// their order, and commands become strings. A leader's lease is a promise for every slot from
+// its own on, which the spec has no message for, so under leases 2a messages turn up without
+// the 1b messages behind them.
+
+// one state of a slot's behavior
+#[derive(PartialEq)]
+struct Record {
+    // (acceptor, maxBal, maxVBal, maxVal)
+    acceptors: Vec<(usize, Option<u64>, Option<u64>, Option<String>)>,
+    msgs: BTreeSet<String>,
+}
+
+// TLA+ identifiers are letters, digits and underscores
+fn identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn string<V: Value>(command: &Command<V>) -> String {
+    let text = format!("{}", command);
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn number(ballot: Option<u64>) -> String {
+    ballot.map_or(String::from("-1"), |ballot| ballot.to_string())
+}
+
+// the spec's message for `msg` from `src`, if it is one of the four in `slot`
+fn message<V: Value>(
+    slot: Slot,
+    src: Id,
+    msg: &PaxosMsg<V>,
+    ballot: &impl Fn(RoundIdentifier) -> u64,
+) -> Option<String> {
+    let acc = usize::from(src);
+    match msg {
+        PaxosMsg::Prepare(_, _, s, rid) if *s == slot => {
+            Some(format!("[type |-> \"1a\", bal |-> {}]", ballot(*rid)))
+        }
+        PaxosMsg::Promise(_, _, s, rid, accepted) if *s == slot => {
+            let (mbal, mval) = match accepted {
+                Some((accepted_rid, command)) => (Some(ballot(*accepted_rid)), string(command)),
+                None => (None, String::from("None")),
+            };
+            Some(format!(
+                "[type |-> \"1b\", acc |-> {}, bal |-> {}, mbal |-> {}, mval |-> {}]",
+                acc,
+                ballot(*rid),
+                number(mbal),
+                mval
+            ))
+        }
+        PaxosMsg::Accept(_, _, s, rid, command) if *s == slot => Some(format!(
+            "[type |-> \"2a\", bal |-> {}, val |-> {}]",
+            ballot(*rid),
+            string(command)
+        )),
+        PaxosMsg::Accepted(_, _, s, rid, command) if *s == slot => Some(format!(
+            "[type |-> \"2b\", acc |-> {}, bal |-> {}, val |-> {}]",
+            acc,
+            ballot(*rid),
+            string(command)
+        )),
+        _ => None,
+    }
+}
+
+fn internal<V>(msg: &RegisterMsg<u64, V, PaxosMsg<V>>) -> Option<&PaxosMsg<V>> {
+    match msg {
+        RegisterMsg::Internal(msg) => Some(msg),
+        _ => None,
+    }
+}
+
+// every slot a prepare, promise, accept or accepted along the path was about
+fn slots<V: Value, H>(path: &[Step<PaxosModelActor<V>, H>]) -> BTreeSet<Slot> {
+    path.iter()
+        .flat_map(|(state, _)| state.network.iter())
+        .filter_map(|env| match internal(env.msg)? {
+            PaxosMsg::Prepare(_, _, slot, _)
+            | PaxosMsg::Promise(_, _, slot, _, _)
+            | PaxosMsg::Accept(_, _, slot, _, _)
+            | PaxosMsg::Accepted(_, _, slot, _, _) => Some(*slot),
+            _ => None,
+        })
+        .collect()
+}
+
+// the behavior of `slot` along the path, leaving out steps that did not touch it
+fn behavior<V: Value, H>(slot: Slot, path: &[Step<PaxosModelActor<V>, H>]) -> Vec<Record> {
+    let actors = path
+        .first()
+        .map_or(0, |(state, _)| state.actor_states.len()) as u64;
+    let ballot = |rid: RoundIdentifier| rid.round_num() * actors + usize::from(rid.id()) as u64;
+    let mut records: Vec<Record> = Vec::new();
+    // msgs only ever grows, a delivered message stays sent
+    let mut msgs = BTreeSet::new();
+    for (state, _) in path {
+        msgs.extend(
+            state
+                .network
+                .iter()
+                .filter_map(|env| message(slot, env.src, internal(env.msg)?, &ballot)),
+        );
+        let acceptors = state
+            .actor_states
+            .iter()
+            .enumerate()
+            .filter_map(|(i, actor_state)| match actor_state.as_ref() {
+                PaxosModelActorState::Server(server_state) => {
+                    let durable = server_state.durable();
+                    let leased = server_state
+                        .lease()
+                        .filter(|lease| lease.slot() <= slot)
+                        .map(|lease| lease.ballot());
+                    let promised = durable.last_seen(slot).max(leased).map(ballot);
+                    let accepted = durable.accepted(slot);
+                    Some((
+                        i,
+                        promised,
+                        accepted.map(|(rid, _)| ballot(*rid)),
+                        accepted.map(|(_, command)| string(command)),
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        let record = Record {
+            acceptors,
+            msgs: msgs.clone(),
+        };
+        if records.last() != Some(&record) {
+            records.push(record);
+        }
+    }
+    records
+}
+
+// a function over the acceptors, in TLC's `a :> x @@ b :> y` form
+fn function(entries: impl Iterator<Item = (usize, String)>) -> String {
+    let entries: Vec<String> = entries
+        .map(|(acceptor, value)| format!("{} :> {}", acceptor, value))
+        .collect();
+    format!("({})", entries.join(" @@ "))
+}
+
+fn render(record: &Record) -> String {
+    let max_bal = function(record.acceptors.iter().map(|(i, b, _, _)| (*i, number(*b))));
+    let max_vbal = function(record.acceptors.iter().map(|(i, _, b, _)| (*i, number(*b))));
+    let max_val = function(
+        record
+            .acceptors
+            .iter()
+            .map(|(i, _, _, v)| (*i, v.clone().unwrap_or_else(|| String::from("None")))),
+    );
+    let msgs: Vec<&str> = record.msgs.iter().map(String::as_str).collect();
+    format!(
+        "[maxBal |-> {}, maxVBal |-> {}, maxVal |-> {}, msgs |-> {{{}}}]",
+        max_bal,
+        max_vbal,
+        max_val,
+        msgs.join(", ")
+    )
+}
+
+// a module extending Paxos.tla with a `<property>_slot<N>` sequence of states per
+// counterexample and slot, with Acceptor the server indices
+pub fn to_tla<V: Value, H>(
+    module: &str,
+    counterexamples: &[(&str, Vec<Step<PaxosModelActor<V>, H>>)],
+) -> String {
+    let mut tla = format!(
+        "---- MODULE {} ----\nEXTENDS Paxos, TLC\n",
+        identifier(module)
+    );
+    for (name, path) in counterexamples {
+        for slot in slots(path) {
+            let states: Vec<String> = behavior(slot, path).iter().map(render).collect();
+            tla.push_str(&format!(
+                "\n\\* slot {} of the \"{}\" counterexample\n{}_slot{} == <<\n    {}\n>>\n",
+                slot,
+                name,
+                identifier(name),
+                slot,
+                states.join(",\n    ")
+            ));
+        }
+    }
+    tla.push_str("\n====\n");
+    tla
+}
+
+// writes the counterexamples to `file`, naming the module after it as TLC expects
+pub fn export<V: Value, H>(
+    file: &Path,
+    counterexamples: &[(&str, Vec<Step<PaxosModelActor<V>, H>>)],
+) -> io::Result<()> {
+    let module = file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("Trace");
+    fs::write(file, to_tla(module, counterexamples))
+}
@@ -0,0 +1,351 @@
+use crate::cluster::{ClusterConfig, Node};
+use crate::transport::{Connection, Handshake};
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::server::{ClientCertVerified, ClientCertVerifier};
+use rustls::{
+    Certificate, ClientConfig, ClientConnection, DistinguishedName, PrivateKey, ServerConfig,
+    ServerConnection, ServerName,
+};
+use stateright::actor::Id;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddrV4, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+// a peer that takes longer than this over its half of the handshake is dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Mutually authenticated TLS between the nodes of a cluster config, for the TCP transport.
+// Rather than trusting a certificate authority, each side only accepts the certificates the
+// config lists, so only configured nodes can get a message in, and a node has to send the
+// address its certificate is listed under. The certificate files are checked for changes on
+// every connection, and rotated ones take effect on the next connection without a restart;
+// until every node has reloaded, the certificates loaded before are still accepted.
+pub struct Tls {
+    addr: SocketAddrV4,
+    nodes: Vec<Node>,
+    loaded: Mutex<Loaded>,
+}
+
+struct Loaded {
+    // when each node's certificate, and our own key, were last modified
+    stamps: Vec<Option<SystemTime>>,
+    server: Arc<ServerConfig>,
+    client: Arc<ClientConfig>,
+    pins: Arc<Pins>,
+}
+
+// each node's own certificate, as loaded now and the time before
+struct Pins {
+    certs: Vec<(SocketAddrV4, Certificate)>,
+}
+
+impl Pins {
+    fn node(&self, cert: &Certificate) -> Option<SocketAddrV4> {
+        self.certs
+            .iter()
+            .find(|(_, pinned)| pinned == cert)
+            .map(|(addr, _)| *addr)
+    }
+
+    fn check(&self, cert: &Certificate) -> Result<(), rustls::Error> {
+        match self.node(cert) {
+            Some(_) => Ok(()),
+            None => Err(rustls::Error::General(String::from(
+                "certificate of no configured node",
+            ))),
+        }
+    }
+}
+
+impl ClientCertVerifier for Pins {
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        _: &[Certificate],
+        _: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.check(end_entity)
+            .map(|_| ClientCertVerified::assertion())
+    }
+}
+
+// which node answered is only known once the handshake is over, see `Tls::connect`
+impl ServerCertVerifier for Pins {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _: &[Certificate],
+        _: &ServerName,
+        _: &mut dyn Iterator<Item = &[u8]>,
+        _: &[u8],
+        _: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.check(end_entity)
+            .map(|_| ServerCertVerified::assertion())
+    }
+}
+
+fn invalid_data(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn certs(path: &Path) -> io::Result<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))?;
+    if certs.is_empty() {
+        return Err(invalid_data(format!(
+            "no certificate in {}",
+            path.display()
+        )));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn key(path: &Path) -> io::Result<PrivateKey> {
+    let items = rustls_pemfile::read_all(&mut BufReader::new(File::open(path)?))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| invalid_data(format!("no private key in {}", path.display())))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+impl Tls {
+    // TLS for the node at `addr`, which like every node in `cluster` needs a certificate
+    pub fn new(cluster: &ClusterConfig, addr: SocketAddrV4) -> io::Result<Self> {
+        if !cluster.is_tls() {
+            return Err(invalid_data("every node needs a cert and a key"));
+        }
+        if !cluster.nodes().iter().any(|node| node.address() == addr) {
+            return Err(invalid_data(format!("{} is not a configured node", addr)));
+        }
+        let nodes = cluster.nodes().to_vec();
+        let loaded = Self::load(&nodes, addr, None)?;
+        Ok(Tls {
+            addr,
+            nodes,
+            loaded: Mutex::new(loaded),
+        })
+    }
+
+    fn own(nodes: &[Node], addr: SocketAddrV4) -> (PathBuf, PathBuf) {
+        let node = nodes.iter().find(|node| node.address() == addr).unwrap();
+        (
+            node.cert().unwrap().to_path_buf(),
+            node.key().unwrap().to_path_buf(),
+        )
+    }
+
+    fn stamps(nodes: &[Node], addr: SocketAddrV4) -> Vec<Option<SystemTime>> {
+        let (_, key) = Self::own(nodes, addr);
+        nodes
+            .iter()
+            .filter_map(|node| node.cert())
+            .chain(Some(key.as_path()))
+            .map(modified)
+            .collect()
+    }
+
+    // every file read afresh, still accepting the certificates `previous` did
+    fn load(nodes: &[Node], addr: SocketAddrV4, previous: Option<&Pins>) -> io::Result<Loaded> {
+        let stamps = Self::stamps(nodes, addr);
+        let mut pinned = Vec::new();
+        for node in nodes {
+            let cert = certs(node.cert().unwrap())?.swap_remove(0);
+            pinned.push((node.address(), cert));
+        }
+        if let Some(previous) = previous {
+            for (addr, cert) in &previous.certs {
+                if !pinned.contains(&(*addr, cert.clone())) {
+                    pinned.push((*addr, cert.clone()));
+                }
+            }
+        }
+        // only the certificates loaded now and the time before, not every one ever seen
+        let current = nodes.len();
+        pinned.truncate(current * 2);
+        let pins = Arc::new(Pins { certs: pinned });
+
+        let (cert, key_path) = Self::own(nodes, addr);
+        let (chain, key) = (certs(&cert)?, key(&key_path)?);
+        let server = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(Arc::clone(&pins) as Arc<dyn ClientCertVerifier>)
+            .with_single_cert(chain.clone(), key.clone())
+            .map_err(invalid_data)?;
+        let client = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::clone(&pins) as Arc<dyn ServerCertVerifier>)
+            .with_client_auth_cert(chain, key)
+            .map_err(invalid_data)?;
+        Ok(Loaded {
+            stamps,
+            server: Arc::new(server),
+            client: Arc::new(client),
+            pins,
+        })
+    }
+
+    // the configs to use for a new connection, reloaded if any file changed since; a
+    // certificate caught halfway through being replaced leaves the old ones in place
+    fn current(&self) -> (Arc<ServerConfig>, Arc<ClientConfig>, Arc<Pins>) {
+        let mut loaded = self.loaded.lock().unwrap();
+        if Self::stamps(&self.nodes, self.addr) != loaded.stamps {
+            match Self::load(&self.nodes, self.addr, Some(&loaded.pins)) {
+                Ok(reloaded) => {
+                    tracing::info!("reloaded the TLS certificates");
+                    *loaded = reloaded;
+                }
+                Err(e) => tracing::warn!(error = %e, "keeping the TLS certificates loaded before"),
+            }
+        }
+        (
+            Arc::clone(&loaded.server),
+            Arc::clone(&loaded.client),
+            Arc::clone(&loaded.pins),
+        )
+    }
+}
+
+// the handshake on its own, blocking and bounded in time, before the connection is shared
+fn complete(connection: &mut rustls::Connection, stream: &mut TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    while connection.is_handshaking() {
+        connection.complete_io(stream)?;
+    }
+    stream.set_read_timeout(None)
+}
+
+fn peer(connection: &rustls::Connection, pins: &Pins) -> io::Result<SocketAddrV4> {
+    connection
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .and_then(|cert| pins.node(cert))
+        .ok_or_else(|| invalid_data("the peer showed no configured certificate"))
+}
+
+impl Handshake for Tls {
+    fn accept(&self, mut stream: TcpStream) -> io::Result<(Box<dyn Connection>, Option<Id>)> {
+        let (server, _, pins) = self.current();
+        let connection = ServerConnection::new(server).map_err(invalid_data)?;
+        let mut connection = rustls::Connection::from(connection);
+        complete(&mut connection, &mut stream)?;
+        let addr = peer(&connection, &pins)?;
+        Ok((
+            Box::new(TlsStream::new(stream, connection)),
+            Some(Id::from(addr)),
+        ))
+    }
+
+    fn connect(&self, mut stream: TcpStream, dst: Id) -> io::Result<Box<dyn Connection>> {
+        let (_, client, pins) = self.current();
+        let dst = SocketAddrV4::from(dst);
+        let name = ServerName::IpAddress(IpAddr::V4(*dst.ip()));
+        let connection = ClientConnection::new(client, name).map_err(invalid_data)?;
+        let mut connection = rustls::Connection::from(connection);
+        complete(&mut connection, &mut stream)?;
+        // nodes may share a host, so the certificate is told apart by the port dialled
+        if peer(&connection, &pins)? != dst {
+            return Err(invalid_data(format!(
+                "{} showed another node's certificate",
+                dst
+            )));
+        }
+        Ok(Box::new(TlsStream::new(stream, connection)))
+    }
+}
+
+// One TLS connection behind handles that read and write from different threads. The socket
+// is read without holding the lock, so a reader waiting on its peer never holds up a writer.
+struct TlsStream {
+    stream: TcpStream,
+    connection: Arc<Mutex<rustls::Connection>>,
+}
+
+impl TlsStream {
+    fn new(stream: TcpStream, connection: rustls::Connection) -> Self {
+        TlsStream {
+            stream,
+            connection: Arc::new(Mutex::new(connection)),
+        }
+    }
+
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<rustls::Connection>> {
+        self.connection
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "TLS connection poisoned"))
+    }
+}
+
+// sends whatever the connection has encrypted so far
+fn flush_tls(connection: &mut rustls::Connection, mut stream: &TcpStream) -> io::Result<()> {
+    while connection.wants_write() {
+        connection.write_tls(&mut stream)?;
+    }
+    Ok(())
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            {
+                let mut connection = self.lock()?;
+                match connection.reader().read(buf) {
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    read => return read,
+                }
+            }
+            let mut received = [0; 16 * 1024];
+            let len = self.stream.read(&mut received)?;
+            if len == 0 {
+                return Ok(0);
+            }
+            let mut connection = self.lock()?;
+            let mut rest = &received[..len];
+            while !rest.is_empty() {
+                connection.read_tls(&mut rest)?;
+                connection.process_new_packets().map_err(invalid_data)?;
+            }
+            // alerts and key updates are answered as they come
+            flush_tls(&mut connection, &self.stream)?;
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut connection = self.lock()?;
+        let len = connection.writer().write(buf)?;
+        flush_tls(&mut connection, &self.stream)?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut connection = self.lock()?;
+        connection.writer().flush()?;
+        flush_tls(&mut connection, &self.stream)
+    }
+}
+
+impl Connection for TlsStream {
+    fn try_clone(&self) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(TlsStream {
+            stream: self.stream.try_clone()?,
+            connection: Arc::clone(&self.connection),
+        }))
+    }
+}
@@ -0,0 +1,95 @@
+use stateright::actor::Id;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+// Which region, a datacenter or an availability zone, each replica runs in. Regions are filled
+// with consecutive ids in order, so `2,2,1` puts servers 0 and 1 in region 0, 2 and 3 in region
+// 1 and 4 on its own in region 2. Ids past the last region, clients in a model, are in none.
+// The empty topology puts every replica in a region of its own, which is what a cluster without
+// one behaves like.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Topology {
+    sizes: Vec<usize>,
+}
+
+impl Topology {
+    pub fn new(sizes: Vec<usize>) -> Self {
+        Topology { sizes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    pub fn region_count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn region(&self, id: Id) -> Option<usize> {
+        let mut end = 0;
+        for (region, size) in self.sizes.iter().enumerate() {
+            end += size;
+            if usize::from(id) < end {
+                return Some(region);
+            }
+        }
+        None
+    }
+
+    // the ids in `region`
+    pub fn members(&self, region: usize) -> Vec<Id> {
+        let start: usize = self.sizes.iter().take(region).sum();
+        let size = self.sizes.get(region).copied().unwrap_or(0);
+        (start..start + size).map(Id::from).collect()
+    }
+
+    // `ids` grouped by region in region order, each id outside every region alone in its own
+    // group after them; regions with none of `ids` are left out
+    pub fn partition(&self, ids: &[Id]) -> Vec<BTreeSet<Id>> {
+        let mut groups = vec![BTreeSet::new(); self.sizes.len()];
+        let mut untagged = Vec::new();
+        for id in ids {
+            match self.region(*id) {
+                Some(region) => {
+                    groups[region].insert(*id);
+                }
+                None => untagged.push(BTreeSet::from([*id])),
+            }
+        }
+        groups.retain(|group| !group.is_empty());
+        groups.extend(untagged);
+        groups
+    }
+
+    // whether messages between the two cross from one region into another; ids in no region,
+    // clients say, are placed with links instead
+    pub fn crosses(&self, src: Id, dst: Id) -> bool {
+        matches!((self.region(src), self.region(dst)), (Some(a), Some(b)) if a != b)
+    }
+}
+
+// <size>,<size>,...
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|size| match size.parse::<usize>() {
+                Ok(size) if size > 0 => Ok(size),
+                _ => Err(format!(
+                    "expected a region size of at least 1, got {}",
+                    size
+                )),
+            })
+            .collect::<Result<_, _>>()
+            .map(Topology::new)
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sizes: Vec<String> = self.sizes.iter().map(usize::to_string).collect();
+        write!(f, "{}", sizes.join(","))
+    }
+}
@@ -0,0 +1,318 @@
+use crate::checking::StateGraph;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use stateright::actor::{Actor, ActorModelAction, ActorModelState, Envelope, Id};
+use stateright::Model;
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// a state along a discovered path, with the action taken from it; the last one has none
+pub type Step<A, H> = (
+    ActorModelState<A, H>,
+    Option<ActorModelAction<<A as Actor>::Msg>>,
+);
+
+// property names make for awkward file names
+fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+// an action as `{"deliver": {"src", "dst", "msg"}}`, `{"drop": ..}` or `{"timeout": id}`
+pub fn action_json<M: Debug>(action: &ActorModelAction<M>) -> serde_json::Value {
+    match action {
+        ActorModelAction::Deliver { src, dst, msg } => json!({
+            "deliver": {
+                "src": usize::from(*src),
+                "dst": usize::from(*dst),
+                "msg": format!("{:?}", msg),
+            }
+        }),
+        ActorModelAction::Drop(env) => json!({
+            "drop": {
+                "src": usize::from(env.src),
+                "dst": usize::from(env.dst),
+                "msg": format!("{:?}", env.msg),
+            }
+        }),
+        ActorModelAction::Timeout(id) => json!({ "timeout": usize::from(*id) }),
+    }
+}
+
+// an action as a schedule file holds it, messages and all, so that it can be taken again
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scheduled<M> {
+    Deliver { src: usize, dst: usize, msg: M },
+    Drop { src: usize, dst: usize, msg: M },
+    Timeout(usize),
+}
+
+impl<M: Clone> Scheduled<M> {
+    pub fn new(action: &ActorModelAction<M>) -> Self {
+        match action {
+            ActorModelAction::Deliver { src, dst, msg } => Scheduled::Deliver {
+                src: usize::from(*src),
+                dst: usize::from(*dst),
+                msg: msg.clone(),
+            },
+            ActorModelAction::Drop(env) => Scheduled::Drop {
+                src: usize::from(env.src),
+                dst: usize::from(env.dst),
+                msg: env.msg.clone(),
+            },
+            ActorModelAction::Timeout(id) => Scheduled::Timeout(usize::from(*id)),
+        }
+    }
+}
+
+impl<M> Scheduled<M> {
+    pub fn into_action(self) -> ActorModelAction<M> {
+        match self {
+            Scheduled::Deliver { src, dst, msg } => ActorModelAction::Deliver {
+                src: Id::from(src),
+                dst: Id::from(dst),
+                msg,
+            },
+            Scheduled::Drop { src, dst, msg } => ActorModelAction::Drop(Envelope {
+                src: Id::from(src),
+                dst: Id::from(dst),
+                msg,
+            }),
+            Scheduled::Timeout(id) => ActorModelAction::Timeout(Id::from(id)),
+        }
+    }
+}
+
+// the actions taken along a path, in order
+pub fn schedule<A: Actor, H>(path: &[Step<A, H>]) -> Vec<Scheduled<A::Msg>> {
+    path.iter()
+        .filter_map(|(_, action)| action.as_ref())
+        .map(Scheduled::new)
+        .collect()
+}
+
+pub fn write_schedule<M: Serialize>(file: &Path, schedule: &[Scheduled<M>]) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(schedule)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(file, json)
+}
+
+pub fn read_schedule<M: DeserializeOwned>(file: &Path) -> io::Result<Vec<Scheduled<M>>> {
+    serde_json::from_slice(&fs::read(file)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// every action along the path with the actor states it changed, starting from the initial
+// actor states
+pub fn to_json<A: Actor, H>(name: &str, path: &[Step<A, H>]) -> serde_json::Value {
+    let initial: Vec<String> = match path.first() {
+        Some((state, _)) => state
+            .actor_states
+            .iter()
+            .map(|actor_state| format!("{:?}", actor_state))
+            .collect(),
+        None => Vec::new(),
+    };
+    let steps: Vec<serde_json::Value> = path
+        .windows(2)
+        .filter_map(|pair| {
+            let ((before, action), (after, _)) = (&pair[0], &pair[1]);
+            let changed: Vec<serde_json::Value> = after
+                .actor_states
+                .iter()
+                .enumerate()
+                .filter(|(i, actor_state)| before.actor_states.get(*i) != Some(*actor_state))
+                .map(
+                    |(i, actor_state)| json!({ "actor": i, "state": format!("{:?}", actor_state) }),
+                )
+                .collect();
+            Some(json!({ "action": action_json(action.as_ref()?), "changed": changed }))
+        })
+        .collect();
+    json!({ "property": name, "initial": initial, "steps": steps })
+}
+
+// The path from the first initial state that always takes the action `rank` puts first, the
+// one whose label sorts first among equals, until `rank` lets none through or after
+// `max_steps`. `rank` sees the state the action would be taken in, and actions the model says
+// change nothing are passed over. Hash order plays no part, so a model always gives the same path,
+// which golden flows rely on.
+pub fn ranked_path<A, H, M, K>(
+    model: &M,
+    rank: impl Fn(&ActorModelState<A, H>, &ActorModelAction<A::Msg>) -> Option<K>,
+    max_steps: usize,
+) -> Vec<Step<A, H>>
+where
+    A: Actor,
+    M: Model<State = ActorModelState<A, H>, Action = ActorModelAction<A::Msg>>,
+    K: Ord,
+{
+    let mut path = Vec::new();
+    let mut state = match model.init_states().into_iter().next() {
+        Some(state) => state,
+        None => return path,
+    };
+    // labels of the actions that leave the current state as it is
+    let mut unchanged = Vec::new();
+    while path.len() < max_steps {
+        let mut actions = Vec::new();
+        model.actions(&state, &mut actions);
+        let next = actions
+            .into_iter()
+            .filter_map(|action| {
+                let key = rank(&state, &action)?;
+                let label = action_label(&action);
+                if unchanged.contains(&label) {
+                    return None;
+                }
+                Some((key, label, action))
+            })
+            .min_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        let (label, action) = match next {
+            Some((_, label, action)) => (label, action),
+            None => break,
+        };
+        match model.next_state(&state, action.clone()) {
+            Some(after) => {
+                path.push((state, Some(action)));
+                state = after;
+                unchanged.clear();
+            }
+            None => unchanged.push(label),
+        }
+    }
+    path.push((state, None));
+    path
+}
+
+// the actions along the path, one label a line
+pub fn to_flow<A: Actor, H>(path: &[Step<A, H>]) -> String {
+    path.iter()
+        .filter_map(|(_, action)| action.as_ref())
+        .map(|action| action_label(action) + "\n")
+        .collect()
+}
+
+// the message flow along the path as a mermaid sequence diagram
+pub fn to_mermaid<A: Actor, H>(path: &[Step<A, H>]) -> String {
+    let mut diagram = String::from("sequenceDiagram\n");
+    let actor_count = path
+        .first()
+        .map_or(0, |(state, _)| state.actor_states.len());
+    for i in 0..actor_count {
+        diagram.push_str(&format!("    participant n{} as {}\n", i, i));
+    }
+    // semicolons end a mermaid statement
+    let text = |msg: &dyn Debug| format!("{:?}", msg).replace(';', ",");
+    for (_, action) in path {
+        let line = match action {
+            Some(ActorModelAction::Deliver { src, dst, msg }) => format!(
+                "    n{}->>n{}: {}\n",
+                usize::from(*src),
+                usize::from(*dst),
+                text(msg)
+            ),
+            Some(ActorModelAction::Drop(env)) => format!(
+                "    n{}-xn{}: dropped {}\n",
+                usize::from(env.src),
+                usize::from(env.dst),
+                text(&env.msg)
+            ),
+            Some(ActorModelAction::Timeout(id)) => {
+                format!("    Note over n{}: timeout\n", usize::from(*id))
+            }
+            None => continue,
+        };
+        diagram.push_str(&line);
+    }
+    diagram
+}
+
+// a string as a quoted DOT label, lines and all
+fn dot_label(text: &str) -> String {
+    format!(
+        "\"{}\"",
+        text.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\l")
+    )
+}
+
+// an action on one line, `src->dst msg` for a delivery
+pub fn action_label<M: Debug>(action: &ActorModelAction<M>) -> String {
+    match action {
+        ActorModelAction::Deliver { src, dst, msg } => {
+            format!("{}->{} {:?}", usize::from(*src), usize::from(*dst), msg)
+        }
+        ActorModelAction::Drop(env) => format!(
+            "{}-x{} {:?}",
+            usize::from(env.src),
+            usize::from(env.dst),
+            env.msg
+        ),
+        ActorModelAction::Timeout(id) => format!("timeout {}", usize::from(*id)),
+    }
+}
+
+// The explored state graph as a Graphviz digraph: a node per state listing each actor's state,
+// an edge per action labelled with the message delivered or dropped. Actor states and messages
+// print as their `Debug` form, so summarized paxos ones show as the one line `Display` form.
+// Initial states have a double border.
+pub fn to_dot<A: Actor, H: Debug>(
+    graph: &StateGraph<ActorModelState<A, H>, ActorModelAction<A::Msg>>,
+) -> String {
+    let mut dot = String::from("digraph states {\n    node [shape=box];\n");
+    for (i, state) in graph.states.iter().enumerate() {
+        let text: String = state
+            .actor_states
+            .iter()
+            .enumerate()
+            .map(|(actor, actor_state)| format!("{}: {:?}\n", actor, actor_state))
+            .collect();
+        let border = if i < graph.initial {
+            ", peripheries=2"
+        } else {
+            ""
+        };
+        dot.push_str(&format!(
+            "    s{} [label={}{}];\n",
+            i,
+            dot_label(&text),
+            border
+        ));
+    }
+    for (from, to, action) in &graph.edges {
+        dot.push_str(&format!(
+            "    s{} -> s{} [label={}];\n",
+            from,
+            to,
+            dot_label(&action_label(action))
+        ));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+// writes `<property>.json`, `<property>.mmd` and the schedule to replay, `<property>.schedule.json`,
+// into `dir`
+pub fn export<A, H>(dir: &Path, name: &str, path: &[Step<A, H>]) -> io::Result<()>
+where
+    A: Actor,
+    A::Msg: Serialize,
+{
+    fs::create_dir_all(dir)?;
+    let stem = file_stem(name);
+    let json = serde_json::to_string_pretty(&to_json(name, path))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(dir.join(format!("{}.json", stem)), json)?;
+    fs::write(dir.join(format!("{}.mmd", stem)), to_mermaid(path))?;
+    write_schedule(
+        &dir.join(format!("{}.schedule.json", stem)),
+        &schedule(path),
+    )
+}
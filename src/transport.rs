@@ -0,0 +1,910 @@
+use crate::checksum;
+use crate::codec::{Bincode, Cbor, Json, WireCodec};
+use crate::rng::Rng;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use stateright::actor::{Actor, Command, Id, Out};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, SocketAddrV4, TcpListener, TcpStream, UdpSocket};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how long to wait for a message when no timer is set
+const IDLE: Duration = Duration::from_secs(1);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const MIN_BACKOFF: Duration = Duration::from_millis(50);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+// anything longer is a corrupt length prefix rather than a message
+const MAX_FRAME: usize = 1 << 24;
+const QUEUE_DEPTH: usize = 1024;
+const REQUEST_QUEUE_DEPTH: usize = 256;
+
+// frames dropped for failing their checksum, across every connection in the process
+static CORRUPT_FRAMES: AtomicU64 = AtomicU64::new(0);
+
+pub fn corrupt_frames() -> u64 {
+    CORRUPT_FRAMES.load(Ordering::Relaxed)
+}
+
+// how spawned replicas reach each other
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TransportMode {
+    // one message per datagram, JSON by default
+    Udp,
+    // persistent connections carrying length prefixed frames, bincode by default
+    Tcp,
+}
+
+impl FromStr for TransportMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "udp" => Ok(TransportMode::Udp),
+            "tcp" => Ok(TransportMode::Tcp),
+            _ => Err(format!("unknown transport {}, expected udp or tcp", s)),
+        }
+    }
+}
+
+impl fmt::Display for TransportMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransportMode::Udp => write!(f, "udp"),
+            TransportMode::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+// how messages are laid out in datagrams and frames
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WireFormat {
+    Json,
+    Bincode,
+    Cbor,
+    // versioned envelopes from proto/wire.proto, see src/wire.rs
+    #[cfg(feature = "protobuf")]
+    Protobuf,
+}
+
+impl FromStr for WireFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(WireFormat::Json),
+            "bincode" => Ok(WireFormat::Bincode),
+            "cbor" => Ok(WireFormat::Cbor),
+            #[cfg(feature = "protobuf")]
+            "protobuf" => Ok(WireFormat::Protobuf),
+            _ => Err(format!("unknown wire format {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for WireFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WireFormat::Json => write!(f, "json"),
+            WireFormat::Bincode => write!(f, "bincode"),
+            WireFormat::Cbor => write!(f, "cbor"),
+            #[cfg(feature = "protobuf")]
+            WireFormat::Protobuf => write!(f, "protobuf"),
+        }
+    }
+}
+
+impl TransportMode {
+    // what each transport used before there was a choice, JSON being what stateright sends
+    pub fn default_wire(self) -> WireFormat {
+        match self {
+            TransportMode::Udp => WireFormat::Json,
+            TransportMode::Tcp => WireFormat::Bincode,
+        }
+    }
+}
+
+// messages every wire format can carry; protobuf only knows the ones in src/wire.rs
+#[cfg(not(feature = "protobuf"))]
+pub trait WireMessage: Serialize + DeserializeOwned {}
+
+#[cfg(not(feature = "protobuf"))]
+impl<M: Serialize + DeserializeOwned> WireMessage for M {}
+
+#[cfg(feature = "protobuf")]
+pub trait WireMessage: Serialize + DeserializeOwned + crate::wire::Protobuf {}
+
+#[cfg(feature = "protobuf")]
+impl<M: Serialize + DeserializeOwned + crate::wire::Protobuf> WireMessage for M {}
+
+// turn a message into a frame's payload and back; decoding to `None` drops a message the
+// sender knows this side may not understand
+pub type Encode<M> = fn(&M) -> io::Result<Vec<u8>>;
+pub type Decode<M> = fn(&[u8]) -> io::Result<Option<M>>;
+
+fn decode_some<C: WireCodec, M: DeserializeOwned>(bytes: &[u8]) -> io::Result<Option<M>> {
+    C::decode(bytes).map(Some)
+}
+
+impl WireFormat {
+    // how this format turns `M` into bytes and back
+    pub fn codec<M: WireMessage>(self) -> (Encode<M>, Decode<M>) {
+        match self {
+            WireFormat::Json => (Json::encode::<M>, decode_some::<Json, M>),
+            WireFormat::Bincode => (Bincode::encode::<M>, decode_some::<Bincode, M>),
+            WireFormat::Cbor => (Cbor::encode::<M>, decode_some::<Cbor, M>),
+            #[cfg(feature = "protobuf")]
+            WireFormat::Protobuf => (crate::wire::encode::<M>, crate::wire::decode::<M>),
+        }
+    }
+}
+
+// moves messages between spawned actors, which never see which transport is in use
+pub trait Transport<M> {
+    fn send(&mut self, dst: Id, msg: &M) -> io::Result<()>;
+    // the next message to arrive within `timeout`, if any
+    fn recv(&mut self, timeout: Duration) -> io::Result<Option<(Id, M)>>;
+}
+
+fn invalid_data(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+// one message per datagram; in JSON, the encoding `stateright::actor::spawn` uses, the
+// client binary works against both
+pub struct UdpTransport<M> {
+    socket: UdpSocket,
+    encode: Encode<M>,
+    decode: Decode<M>,
+}
+
+impl<M: WireMessage> UdpTransport<M> {
+    pub fn bind(addr: SocketAddrV4, wire: WireFormat) -> io::Result<Self> {
+        let (encode, decode) = wire.codec();
+        Ok(UdpTransport {
+            socket: UdpSocket::bind(addr)?,
+            encode,
+            decode,
+        })
+    }
+}
+
+impl<M> Transport<M> for UdpTransport<M> {
+    fn send(&mut self, dst: Id, msg: &M) -> io::Result<()> {
+        let bytes = (self.encode)(msg)?;
+        self.socket.send_to(&bytes, SocketAddrV4::from(dst))?;
+        Ok(())
+    }
+
+    fn recv(&mut self, timeout: Duration) -> io::Result<Option<(Id, M)>> {
+        // a zero timeout would block forever
+        let timeout = timeout.max(Duration::from_millis(1));
+        self.socket.set_read_timeout(Some(timeout))?;
+        let mut buf = [0; 65_535];
+        match self.socket.recv_from(&mut buf) {
+            // garbage is dropped like a lost datagram
+            Ok((len, SocketAddr::V4(src))) => Ok((self.decode)(&buf[..len])
+                .ok()
+                .flatten()
+                .map(|msg| (Id::from(src), msg))),
+            Ok(_) => Ok(None),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// a bincode payload behind its CRC-32, behind the length of both as a big endian u32
+pub fn write_frame<T: Serialize>(stream: &mut impl Write, value: &T) -> io::Result<()> {
+    write_payload(stream, &Bincode::encode(value)?)
+}
+
+pub fn write_payload(stream: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    let bytes = checksum::seal(payload);
+    let len = u32::try_from(bytes.len()).map_err(invalid_data)?;
+    stream.write_all(&len.to_be_bytes())?;
+    stream.write_all(&bytes)
+}
+
+pub fn read_frame<T: DeserializeOwned>(stream: &mut impl Read) -> io::Result<T> {
+    read_checked_frame(stream)?.ok_or_else(|| invalid_data("frame failed its checksum"))
+}
+
+// a frame that fails its checksum comes back as `None`, with the stream still lined up on the
+// next frame, since the length prefix is taken on trust
+pub fn read_checked_frame<T: DeserializeOwned>(stream: &mut impl Read) -> io::Result<Option<T>> {
+    match read_payload(stream)? {
+        Some(payload) => bincode::deserialize(&payload)
+            .map(Some)
+            .map_err(invalid_data),
+        None => Ok(None),
+    }
+}
+
+// the payload of the next frame, `None` if it failed its checksum
+pub fn read_payload(stream: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME {
+        return Err(invalid_data(format!("frame of {} bytes", len)));
+    }
+    let mut bytes = vec![0; len];
+    stream.read_exact(&mut bytes)?;
+    Ok(checksum::unseal(&bytes).map(<[u8]>::to_vec))
+}
+
+// a connection to a peer, with the stream underneath or something wrapped around it
+pub trait Connection: Read + Write + Send {
+    // another handle on the same connection, for reading while the first writes
+    fn try_clone(&self) -> io::Result<Box<dyn Connection>>;
+}
+
+impl Connection for TcpStream {
+    fn try_clone(&self) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(TcpStream::try_clone(self)?))
+    }
+}
+
+// what a connection goes through before any frame is sent over it, both ways
+pub trait Handshake: Send + Sync {
+    // the accepted connection, with the peer it proved to be if it proved anything
+    fn accept(&self, stream: TcpStream) -> io::Result<(Box<dyn Connection>, Option<Id>)>;
+    fn connect(&self, stream: TcpStream, dst: Id) -> io::Result<Box<dyn Connection>>;
+}
+
+// frames straight over the stream, taking every peer at its word
+pub struct Plain;
+
+impl Handshake for Plain {
+    fn accept(&self, stream: TcpStream) -> io::Result<(Box<dyn Connection>, Option<Id>)> {
+        Ok((Box::new(stream), None))
+    }
+
+    fn connect(&self, stream: TcpStream, _: Id) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(stream))
+    }
+}
+
+// ends with the connection, the sending side dials again when it next has something to say
+fn read_frames<M>(
+    src: Id,
+    mut stream: impl Read,
+    mailbox: Arc<Mailbox>,
+    inbox: SyncSender<(Id, M)>,
+    decode: Decode<M>,
+) {
+    while let Ok(payload) = read_payload(&mut stream) {
+        let payload = match payload {
+            Some(payload) => payload,
+            // dropped like a lost message, rather than handed on as garbage
+            None => {
+                CORRUPT_FRAMES.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(src = %SocketAddrV4::from(src), "dropped a corrupt frame");
+                continue;
+            }
+        };
+        let msg = match decode(&payload) {
+            Ok(Some(msg)) => msg,
+            Ok(None) => {
+                tracing::debug!(src = %SocketAddrV4::from(src), "ignored a message from a newer peer");
+                continue;
+            }
+            // the frame was whole, so the next one is still lined up and the peer is worth
+            // hearing from again
+            Err(e) => {
+                tracing::warn!(src = %SocketAddrV4::from(src), error = %e, "dropped a message that did not decode");
+                continue;
+            }
+        };
+        if !mailbox.admit(src) {
+            tracing::debug!(src = %SocketAddrV4::from(src), "shed a request, too many are queued");
+            continue;
+        }
+        // blocks once a peer fills the inbox, which stops reading and so slows the peer down
+        if inbox.send((src, msg)).is_err() {
+            return;
+        }
+    }
+}
+
+// when a peer that could not be reached is tried again
+struct Backoff {
+    retry_at: Instant,
+    delay: Duration,
+}
+
+// how full a TCP transport's queues are, for whatever reports on the replica
+#[derive(Debug, Default)]
+pub struct QueueDepths {
+    // messages waiting for the actor, client requests included
+    inbox: AtomicUsize,
+    requests: AtomicUsize,
+    // messages waiting to be written to each peer
+    outgoing: Mutex<BTreeMap<Id, Arc<AtomicUsize>>>,
+    requests_shed: AtomicU64,
+    outgoing_shed: AtomicU64,
+}
+
+impl QueueDepths {
+    pub fn inbox(&self) -> usize {
+        self.inbox.load(Ordering::Relaxed)
+    }
+
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn outgoing(&self) -> Vec<(Id, usize)> {
+        match self.outgoing.lock() {
+            Ok(outgoing) => outgoing
+                .iter()
+                .map(|(peer, depth)| (*peer, depth.load(Ordering::Relaxed)))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn requests_shed(&self) -> u64 {
+        self.requests_shed.load(Ordering::Relaxed)
+    }
+
+    pub fn outgoing_shed(&self) -> u64 {
+        self.outgoing_shed.load(Ordering::Relaxed)
+    }
+}
+
+// How much a TCP transport queues before it sheds. Messages to a peer wait in a queue of their
+// own, so a slow or partitioned peer only holds up what is sent to it, and past `outgoing` of
+// them new ones are dropped like lost messages. Client requests past `requests` are dropped
+// before they reach the inbox, and the client retries. Peers are never shed on the way in;
+// past `inbox` of their messages their connections stop being read, and TCP slows them down.
+// Without `peers` every connection counts as a peer.
+#[derive(Debug)]
+pub struct Mailbox {
+    peers: Option<BTreeSet<Id>>,
+    outgoing: usize,
+    inbox: usize,
+    requests: usize,
+    depths: Arc<QueueDepths>,
+}
+
+impl Default for Mailbox {
+    fn default() -> Self {
+        Mailbox {
+            peers: None,
+            outgoing: QUEUE_DEPTH,
+            inbox: QUEUE_DEPTH,
+            requests: REQUEST_QUEUE_DEPTH,
+            depths: Arc::new(QueueDepths::default()),
+        }
+    }
+}
+
+impl Mailbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // connections from anywhere else are clients
+    pub fn peers(mut self, peers: impl IntoIterator<Item = Id>) -> Self {
+        self.peers = Some(peers.into_iter().collect());
+        self
+    }
+
+    // both the queue to each peer and the inbox
+    pub fn queue_depth(mut self, depth: usize) -> Self {
+        self.outgoing = depth.max(1);
+        self.inbox = depth.max(1);
+        self
+    }
+
+    // at least one, like the other queues, or every client request would be shed
+    pub fn request_depth(mut self, depth: usize) -> Self {
+        self.requests = depth.max(1);
+        self
+    }
+
+    pub fn depths(&self) -> Arc<QueueDepths> {
+        Arc::clone(&self.depths)
+    }
+
+    fn is_peer(&self, id: Id) -> bool {
+        self.peers
+            .as_ref()
+            .map_or(true, |peers| peers.contains(&id))
+    }
+
+    // whether a message from `src` may join the inbox, counting it in if so
+    pub(crate) fn admit(&self, src: Id) -> bool {
+        if !self.is_peer(src)
+            && self.depths.requests.fetch_add(1, Ordering::Relaxed) >= self.requests
+        {
+            self.depths.requests.fetch_sub(1, Ordering::Relaxed);
+            self.depths.requests_shed.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        self.depths.inbox.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    // a message from `src` left the inbox
+    pub(crate) fn received(&self, src: Id) {
+        self.depths.inbox.fetch_sub(1, Ordering::Relaxed);
+        if !self.is_peer(src) {
+            self.depths.requests.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    // Takes over the write half of a connection, which a thread of its own drains. The thread
+    // ends when writing fails, and the next send to `dst` finds the queue closed.
+    fn outgoing(&self, dst: Id, mut stream: Box<dyn Connection>) -> Outgoing {
+        let (queue, pending) = mpsc::sync_channel::<Vec<u8>>(self.outgoing);
+        let depth = Arc::new(AtomicUsize::new(0));
+        if self.is_peer(dst) {
+            if let Ok(mut outgoing) = self.depths.outgoing.lock() {
+                outgoing.insert(dst, Arc::clone(&depth));
+            }
+        }
+        let drained = Arc::clone(&depth);
+        thread::spawn(move || {
+            for payload in pending {
+                drained.fetch_sub(1, Ordering::Relaxed);
+                if write_payload(&mut stream, &payload).is_err() {
+                    return;
+                }
+            }
+        });
+        Outgoing { queue, depth }
+    }
+
+    // queues a payload for a peer's writer, shedding it if the peer already has a full queue
+    fn enqueue(&self, outgoing: &Outgoing, payload: Vec<u8>) -> Result<(), TrySendError<Vec<u8>>> {
+        // counted before it is queued, since the writer may take it off straight away
+        outgoing.depth.fetch_add(1, Ordering::Relaxed);
+        let queued = outgoing.queue.try_send(payload);
+        if queued.is_err() {
+            outgoing.depth.fetch_sub(1, Ordering::Relaxed);
+        }
+        if let Err(TrySendError::Full(_)) = &queued {
+            self.depths.outgoing_shed.fetch_add(1, Ordering::Relaxed);
+        }
+        queued
+    }
+}
+
+// the sending end of a connection's queue
+struct Outgoing {
+    queue: SyncSender<Vec<u8>>,
+    depth: Arc<AtomicUsize>,
+}
+
+// One connection per peer, dialled on the first message to it. The dialling side first sends
+// the address it listens on, as a bincode frame whatever the wire format, so either side can use
+// the connection to answer; clients that do not listen anywhere are answered the same way.
+// A peer that proved who it is during the handshake has to send its own address.
+pub struct TcpTransport<M> {
+    addr: SocketAddrV4,
+    // queues to the write halves by peer, the read halves each have a thread feeding `inbox`
+    connections: Arc<Mutex<HashMap<Id, Outgoing>>>,
+    handshake: Arc<dyn Handshake>,
+    mailbox: Arc<Mailbox>,
+    backoff: HashMap<Id, Backoff>,
+    outbox: SyncSender<(Id, M)>,
+    inbox: Receiver<(Id, M)>,
+    encode: Encode<M>,
+    decode: Decode<M>,
+}
+
+impl<M: WireMessage + Send + 'static> TcpTransport<M> {
+    pub fn bind(addr: SocketAddrV4, wire: WireFormat) -> io::Result<Self> {
+        Self::bind_with(addr, wire, Arc::new(Plain), Mailbox::default())
+    }
+
+    // puts every connection through `handshake` first, both those accepted and those dialled
+    pub fn bind_with(
+        addr: SocketAddrV4,
+        wire: WireFormat,
+        handshake: Arc<dyn Handshake>,
+        mailbox: Mailbox,
+    ) -> io::Result<Self> {
+        let (encode, decode) = wire.codec();
+        let listener = TcpListener::bind(addr)?;
+        let (outbox, inbox) = mpsc::sync_channel(mailbox.inbox + mailbox.requests);
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let mailbox = Arc::new(mailbox);
+
+        let accepted = Arc::clone(&connections);
+        let accepted_outbox = outbox.clone();
+        let accepting = Arc::clone(&handshake);
+        let accepted_mailbox = Arc::clone(&mailbox);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let connections = Arc::clone(&accepted);
+                let outbox = accepted_outbox.clone();
+                let handshake = Arc::clone(&accepting);
+                let mailbox = Arc::clone(&accepted_mailbox);
+                thread::spawn(move || {
+                    let (mut stream, proven) = match handshake.accept(stream) {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            tracing::warn!(error = %e, "refused a connection");
+                            return;
+                        }
+                    };
+                    let src = match read_frame::<SocketAddrV4>(&mut stream) {
+                        Ok(src) => Id::from(src),
+                        Err(_) => return,
+                    };
+                    if proven.map_or(false, |proven| proven != src) {
+                        tracing::warn!(
+                            src = %SocketAddrV4::from(src),
+                            "refused a peer claiming another's address"
+                        );
+                        return;
+                    }
+                    if let (Ok(writer), Ok(mut connections)) =
+                        (stream.try_clone(), connections.lock())
+                    {
+                        connections.insert(src, mailbox.outgoing(src, writer));
+                    }
+                    read_frames(src, stream, mailbox, outbox, decode);
+                });
+            }
+        });
+
+        Ok(TcpTransport {
+            addr,
+            connections,
+            handshake,
+            mailbox,
+            backoff: HashMap::new(),
+            outbox,
+            inbox,
+            encode,
+            decode,
+        })
+    }
+
+    fn dial(&self, dst: Id) -> io::Result<Outgoing> {
+        let addr = SocketAddr::V4(SocketAddrV4::from(dst));
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        let mut stream = self.handshake.connect(stream, dst)?;
+        write_frame(&mut stream, &self.addr)?;
+        let reader = stream.try_clone()?;
+        let outbox = self.outbox.clone();
+        let decode = self.decode;
+        let mailbox = Arc::clone(&self.mailbox);
+        thread::spawn(move || read_frames(dst, reader, mailbox, outbox, decode));
+        Ok(self.mailbox.outgoing(dst, stream))
+    }
+
+    // doubles the wait before `dst` is dialled again, up to `MAX_BACKOFF`
+    fn back_off(&mut self, dst: Id) {
+        let delay = match self.backoff.get(&dst) {
+            Some(backoff) => (backoff.delay * 2).min(MAX_BACKOFF),
+            None => MIN_BACKOFF,
+        };
+        let retry_at = Instant::now() + delay;
+        self.backoff.insert(dst, Backoff { retry_at, delay });
+    }
+}
+
+impl<M: Send + 'static> Transport<M> for TcpTransport<M> {
+    fn send(&mut self, dst: Id, msg: &M) -> io::Result<()> {
+        let connections = Arc::clone(&self.connections);
+        let mut connections = connections
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "connection table poisoned"))?;
+        if !connections.contains_key(&dst) {
+            if let Some(backoff) = self.backoff.get(&dst) {
+                if Instant::now() < backoff.retry_at {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "backing off before dialling again",
+                    ));
+                }
+            }
+            match self.dial(dst) {
+                Ok(outgoing) => {
+                    connections.insert(dst, outgoing);
+                }
+                Err(e) => {
+                    self.back_off(dst);
+                    return Err(e);
+                }
+            }
+        }
+        let outgoing = match connections.get(&dst) {
+            Some(outgoing) => outgoing,
+            None => return Ok(()),
+        };
+        let payload = (self.encode)(msg)?;
+        match self.mailbox.enqueue(outgoing, payload) {
+            Ok(()) => {
+                self.backoff.remove(&dst);
+                Ok(())
+            }
+            Err(TrySendError::Full(_)) => {
+                tracing::debug!(dst = %SocketAddrV4::from(dst), "shed a message, too many are queued");
+                Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "too many messages queued for this peer",
+                ))
+            }
+            // the writer gave up on the connection
+            Err(TrySendError::Disconnected(_)) => {
+                connections.remove(&dst);
+                self.back_off(dst);
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+        }
+    }
+
+    fn recv(&mut self, timeout: Duration) -> io::Result<Option<(Id, M)>> {
+        match self.inbox.recv_timeout(timeout) {
+            Ok((src, msg)) => {
+                self.mailbox.received(src);
+                Ok(Some((src, msg)))
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            // we hold a sender ourselves, so this never happens
+            Err(RecvTimeoutError::Disconnected) => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+}
+
+// Sends one request to a replica from outside the cluster and waits for the reply `done`
+// accepts, returning it with the replica that sent it. Over UDP that may be another replica
+// than the one asked, if the request was forwarded; over TCP the reply has to come back on the
+// same connection.
+pub fn call<M: WireMessage>(
+    mode: TransportMode,
+    wire: WireFormat,
+    server: SocketAddrV4,
+    msg: &M,
+    timeout: Duration,
+    done: impl Fn(&M) -> bool,
+) -> io::Result<(SocketAddrV4, M)> {
+    let timeout = timeout.max(Duration::from_millis(1));
+    let (encode, decode) = wire.codec();
+    match mode {
+        TransportMode::Udp => {
+            // replicas answer whichever address the request came from
+            let socket = UdpSocket::bind("127.0.0.1:0")?;
+            socket.set_read_timeout(Some(timeout))?;
+            socket.send_to(&encode(msg)?, server)?;
+            let mut buf = [0; 65_535];
+            loop {
+                let (len, src) = socket.recv_from(&mut buf)?;
+                let reply = match decode(&buf[..len]) {
+                    Ok(Some(reply)) => reply,
+                    _ => continue,
+                };
+                if let (SocketAddr::V4(src), true) = (src, done(&reply)) {
+                    return Ok((src, reply));
+                }
+            }
+        }
+        TransportMode::Tcp => {
+            let mut stream = TcpStream::connect_timeout(&SocketAddr::V4(server), timeout)?;
+            stream.set_read_timeout(Some(timeout))?;
+            let local = match stream.local_addr()? {
+                SocketAddr::V4(local) => local,
+                SocketAddr::V6(local) => {
+                    let msg = format!("replicas only know IPv4 peers, not {}", local);
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+                }
+            };
+            write_frame(&mut stream, &local)?;
+            write_payload(&mut stream, &encode(msg)?)?;
+            loop {
+                let payload = read_payload(&mut stream)?
+                    .ok_or_else(|| invalid_data("frame failed its checksum"))?;
+                if let Some(reply) = decode(&payload)? {
+                    if done(&reply) {
+                        return Ok((server, reply));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// how many messages it sent. Timers go off somewhere in their range, drawn from `rng`, so
+// replicas started together do not all time out together.
+fn perform<A: Actor, T: Transport<A::Msg>>(
+    out: Out<A>,
+    transport: &mut T,
+    timer: &mut Option<Instant>,
+    rng: &mut impl Rng,
+) -> usize {
+    let mut sent = 0;
+    for command in out {
+        match command {
+            // a message that cannot be sent is lost, which the protocol already copes with
+            Command::Send(dst, msg) => {
+                tracing::debug!(dst = usize::from(dst), msg = ?msg, "sent");
+                sent += 1;
+                let _ = transport.send(dst, &msg);
+            }
+            Command::SetTimer(range) => *timer = Some(Instant::now() + rng.within(range)),
+            Command::CancelTimer => *timer = None,
+        }
+    }
+    sent
+}
+
+// what the runner just had the actor handle, for anything watching a replica from outside
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Start,
+    // from this sender
+    Message(Id),
+    Timeout,
+}
+
+// drives an actor over a transport, in place of `stateright::actor::spawn`, until receiving
+// fails, with its timers jittered by `rng`
+pub fn run<A, T>(actor: A, id: Id, transport: T, rng: impl Rng) -> io::Result<()>
+where
+    A: Actor,
+    T: Transport<A::Msg>,
+{
+    run_observed(actor, id, transport, rng, |_, _, _| {})
+}
+
+// like `run`, handing `observe` the state and the number of messages sent after every event
+pub fn run_observed<A, T>(
+    actor: A,
+    id: Id,
+    mut transport: T,
+    mut rng: impl Rng,
+    mut observe: impl FnMut(Event, &A::State, usize),
+) -> io::Result<()>
+where
+    A: Actor,
+    T: Transport<A::Msg>,
+{
+    // spawned replicas are known by their address rather than the number it packs into
+    let span = tracing::info_span!("replica", addr = %SocketAddrV4::from(id));
+    let _entered = span.enter();
+    let mut out = Out::new();
+    let mut state = Cow::Owned(actor.on_start(id, &mut out));
+    let mut timer = None;
+    let sent = perform(out, &mut transport, &mut timer, &mut rng);
+    observe(Event::Start, &state, sent);
+    loop {
+        let timeout = match timer {
+            Some(at) => at.saturating_duration_since(Instant::now()),
+            None => IDLE,
+        };
+        if let Some((src, msg)) = transport.recv(timeout)? {
+            let mut out = Out::new();
+            actor.on_msg(id, &mut state, src, msg, &mut out);
+            let sent = perform(out, &mut transport, &mut timer, &mut rng);
+            observe(Event::Message(src), &state, sent);
+        }
+        // checked after every message too, so a busy replica still times out
+        if matches!(timer, Some(at) if Instant::now() >= at) {
+            timer = None;
+            let mut out = Out::new();
+            actor.on_timeout(id, &mut state, &mut out);
+            let sent = perform(out, &mut transport, &mut timer, &mut rng);
+            observe(Event::Timeout, &state, sent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PEER: usize = 1;
+    const CLIENT: usize = 9;
+
+    fn mailbox(requests: usize) -> Mailbox {
+        Mailbox::new()
+            .peers([Id::from(PEER)])
+            .request_depth(requests)
+    }
+
+    #[test]
+    fn requests_past_the_queue_are_shed_and_peers_never_are() {
+        let mailbox = mailbox(2);
+        let depths = mailbox.depths();
+        assert!(mailbox.admit(Id::from(CLIENT)));
+        assert!(mailbox.admit(Id::from(CLIENT)));
+        assert!(!mailbox.admit(Id::from(CLIENT)));
+        for _ in 0..10 {
+            assert!(mailbox.admit(Id::from(PEER)));
+        }
+        assert_eq!(depths.requests(), 2);
+        assert_eq!(depths.inbox(), 12);
+        assert_eq!(depths.requests_shed(), 1);
+
+        // a request taken off the inbox makes room for another
+        mailbox.received(Id::from(CLIENT));
+        mailbox.received(Id::from(PEER));
+        assert_eq!(depths.requests(), 1);
+        assert_eq!(depths.inbox(), 10);
+        assert!(mailbox.admit(Id::from(CLIENT)));
+        assert_eq!(depths.requests_shed(), 1);
+    }
+
+    #[test]
+    fn a_request_queue_of_zero_still_takes_a_request() {
+        let mailbox = mailbox(0);
+        assert!(mailbox.admit(Id::from(CLIENT)));
+        assert!(!mailbox.admit(Id::from(CLIENT)));
+        assert_eq!(mailbox.depths().requests_shed(), 1);
+    }
+
+    // one byte a message, and anything else is garbage
+    fn decode(payload: &[u8]) -> io::Result<Option<u8>> {
+        match payload {
+            [byte] => Ok(Some(*byte)),
+            _ => Err(invalid_data("not one byte")),
+        }
+    }
+
+    #[test]
+    fn a_message_that_does_not_decode_leaves_the_connection_open() {
+        let mut stream = Vec::new();
+        for payload in [vec![1], vec![2, 2], vec![3]] {
+            write_payload(&mut stream, &payload).unwrap();
+        }
+        let (inbox, received) = mpsc::sync_channel(3);
+        read_frames(
+            Id::from(PEER),
+            &stream[..],
+            Arc::new(mailbox(1)),
+            inbox,
+            decode,
+        );
+        let received: Vec<u8> = received.try_iter().map(|(_, msg)| msg).collect();
+        assert_eq!(received, vec![1, 3]);
+    }
+
+    #[test]
+    fn messages_to_a_peer_with_a_full_queue_are_shed() {
+        let mailbox = mailbox(1);
+        // a writer that never drains its queue
+        let (queue, pending) = mpsc::sync_channel(2);
+        let outgoing = Outgoing {
+            queue,
+            depth: Arc::new(AtomicUsize::new(0)),
+        };
+        assert!(mailbox.enqueue(&outgoing, vec![1]).is_ok());
+        assert!(mailbox.enqueue(&outgoing, vec![2]).is_ok());
+        assert!(matches!(
+            mailbox.enqueue(&outgoing, vec![3]),
+            Err(TrySendError::Full(_))
+        ));
+        assert_eq!(outgoing.depth.load(Ordering::Relaxed), 2);
+        assert_eq!(mailbox.depths().outgoing_shed(), 1);
+
+        // a writer that gave up is not shedding
+        drop(pending);
+        assert!(matches!(
+            mailbox.enqueue(&outgoing, vec![4]),
+            Err(TrySendError::Disconnected(_))
+        ));
+        assert_eq!(mailbox.depths().outgoing_shed(), 1);
+    }
+}
@@ -0,0 +1,511 @@
+use crate::kv::Key;
+use crate::model::NetworkMode;
+use crate::paxos::PaxosActor;
+use crate::shard::ShardedPaxos;
+use crate::smr::{SmrActor, SmrMsg, SmrState, StateMachine};
+use serde::{Deserialize, Serialize};
+use stateright::actor::*;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+pub type TxnId = u64;
+
+// a change to one account's balance
+pub type Change = (Key, i64);
+
+// what a coordinator asks of one participant shard
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum TxnCommand {
+    // votes on making the transaction's changes to this shard's accounts, holding the accounts
+    // until the outcome if the vote is yes
+    Prepare(TxnId, Vec<Change>),
+    // settles the vote, voting no if the shard has not voted yet
+    Fence(TxnId),
+    Commit(TxnId),
+    Abort(TxnId),
+    Read(Key),
+}
+
+impl Default for TxnCommand {
+    fn default() -> Self {
+        TxnCommand::Read(0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum TxnResponse {
+    Vote(bool),
+    Done,
+    Balance(i64),
+}
+
+// A shard's accounts as the participant side of two-phase commit. Votes and outcomes are
+// decided through the shard's Paxos log like any other command, so a vote once given survives
+// whichever replicas fail, and every replica of the shard gives the same one.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Ledger {
+    balances: BTreeMap<Key, i64>,
+    // each vote, with the changes it holds the accounts for if it is yes
+    votes: BTreeMap<TxnId, Option<Vec<Change>>>,
+    // true for committed, false for aborted
+    outcomes: BTreeMap<TxnId, bool>,
+}
+
+impl Ledger {
+    pub fn balance(&self, key: Key) -> i64 {
+        self.balances.get(&key).copied().unwrap_or_default()
+    }
+
+    pub fn outcome(&self, txn: TxnId) -> Option<bool> {
+        self.outcomes.get(&txn).copied()
+    }
+
+    // accounts a yes vote holds until its transaction's outcome
+    fn held(&self) -> BTreeSet<Key> {
+        self.votes
+            .iter()
+            .filter(|(txn, _)| !self.outcomes.contains_key(txn))
+            .filter_map(|(_, changes)| changes.as_ref())
+            .flatten()
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    fn vote(&self, txn: TxnId) -> TxnResponse {
+        TxnResponse::Vote(matches!(self.votes.get(&txn), Some(Some(_))))
+    }
+}
+
+impl StateMachine for Ledger {
+    type Command = TxnCommand;
+    type Response = TxnResponse;
+
+    fn apply(&mut self, command: &TxnCommand) -> TxnResponse {
+        match command {
+            TxnCommand::Prepare(txn, changes) => {
+                if !self.votes.contains_key(txn) {
+                    let held = self.held();
+                    let free = changes.iter().all(|(key, _)| !held.contains(key));
+                    self.votes
+                        .insert(*txn, Some(changes.clone()).filter(|_| free));
+                }
+                self.vote(*txn)
+            }
+            TxnCommand::Fence(txn) => {
+                self.votes.entry(*txn).or_insert(None);
+                self.vote(*txn)
+            }
+            TxnCommand::Commit(txn) => {
+                if !self.outcomes.contains_key(txn) {
+                    if let Some(Some(changes)) = self.votes.get(txn) {
+                        for (key, amount) in changes {
+                            *self.balances.entry(*key).or_default() += amount;
+                        }
+                        self.outcomes.insert(*txn, true);
+                    }
+                }
+                TxnResponse::Done
+            }
+            TxnCommand::Abort(txn) => {
+                // a shard that never voted must not vote yes later
+                self.votes.entry(*txn).or_insert(None);
+                self.outcomes.entry(*txn).or_insert(false);
+                TxnResponse::Done
+            }
+            TxnCommand::Read(key) => TxnResponse::Balance(self.balance(*key)),
+        }
+    }
+}
+
+// Runs two-phase commit for one transaction across the shards it touches. The coordinator
+// keeps nothing durable itself: the votes are in the shards' logs, so a coordinator that crashes
+// and restarts fences every shard, which fixes any vote not yet given at no, and decides from
+// the votes it gets back. Whoever asks, the votes and so the outcome are the same, which is
+// Paxos Commit with the coordinator's own log left out.
+pub struct Coordinator {
+    shards: ShardedPaxos,
+    txn: TxnId,
+    // the changes for each shard the transaction touches
+    changes: BTreeMap<usize, Vec<Change>>,
+    // whether the coordinator crashes and restarts at some point during the run
+    crashes: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum CoordinatorPhase {
+    Voting,
+    Deciding(bool),
+    Done(bool),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct CoordinatorState {
+    // bumped by each restart, so replies to requests from before it are told apart
+    incarnation: u64,
+    phase: CoordinatorPhase,
+    // the shard each outstanding request went to
+    awaiting: BTreeMap<u64, usize>,
+    votes: BTreeMap<usize, bool>,
+}
+
+impl CoordinatorState {
+    pub fn outcome(&self) -> Option<bool> {
+        match self.phase {
+            CoordinatorPhase::Done(outcome) => Some(outcome),
+            _ => None,
+        }
+    }
+}
+
+impl Coordinator {
+    pub fn new(shards: ShardedPaxos, txn: TxnId, changes: Vec<Change>) -> Self {
+        let mut by_shard: BTreeMap<usize, Vec<Change>> = BTreeMap::new();
+        for (key, amount) in changes {
+            by_shard
+                .entry(shards.shard(u64::from(key)))
+                .or_default()
+                .push((key, amount));
+        }
+        Coordinator {
+            shards,
+            txn,
+            changes: by_shard,
+            crashes: false,
+        }
+    }
+
+    pub fn crashes(mut self, crashes: bool) -> Self {
+        self.crashes = crashes;
+        self
+    }
+
+    // asks one replica of every shard the transaction touches, request ids unique per
+    // incarnation and phase since replicas answer each request id only once
+    fn ask<A: Actor<Msg = SmrMsg<TxnCommand, TxnResponse>>>(
+        &self,
+        id: Id,
+        state: &mut CoordinatorState,
+        step: u64,
+        command: impl Fn(usize) -> TxnCommand,
+        o: &mut Out<A>,
+    ) {
+        state.awaiting.clear();
+        for shard in self.changes.keys() {
+            let request_id = (state.incarnation << 16) | (step << 8) | *shard as u64;
+            let group = self.shards.group(*shard);
+            let replica = group[usize::from(id) % group.len()];
+            o.send(replica, SmrMsg::Request(request_id, command(*shard)));
+            state.awaiting.insert(request_id, *shard);
+        }
+    }
+
+    fn start<A: Actor<Msg = SmrMsg<TxnCommand, TxnResponse>>>(
+        &self,
+        id: Id,
+        incarnation: u64,
+        o: &mut Out<A>,
+    ) -> CoordinatorState {
+        let mut state = CoordinatorState {
+            incarnation,
+            phase: CoordinatorPhase::Voting,
+            awaiting: BTreeMap::new(),
+            votes: BTreeMap::new(),
+        };
+        let txn = self.txn;
+        if incarnation == 0 {
+            self.ask(
+                id,
+                &mut state,
+                0,
+                |shard| TxnCommand::Prepare(txn, self.changes[&shard].clone()),
+                o,
+            );
+        } else {
+            self.ask(id, &mut state, 0, |_| TxnCommand::Fence(txn), o);
+        }
+        state
+    }
+
+    fn on_response<A: Actor<Msg = SmrMsg<TxnCommand, TxnResponse>>>(
+        &self,
+        id: Id,
+        state: &mut Cow<CoordinatorState>,
+        request_id: u64,
+        response: TxnResponse,
+        o: &mut Out<A>,
+    ) {
+        let shard = match state.awaiting.get(&request_id) {
+            Some(shard) => *shard,
+            None => return,
+        };
+        let state = state.to_mut();
+        state.awaiting.remove(&request_id);
+        match (state.phase, response) {
+            (CoordinatorPhase::Voting, TxnResponse::Vote(yes)) => {
+                state.votes.insert(shard, yes);
+                if !state.awaiting.is_empty() {
+                    return;
+                }
+                let commit = state.votes.values().all(|yes| *yes);
+                state.phase = CoordinatorPhase::Deciding(commit);
+                let txn = self.txn;
+                let outcome = move |_: usize| {
+                    if commit {
+                        TxnCommand::Commit(txn)
+                    } else {
+                        TxnCommand::Abort(txn)
+                    }
+                };
+                self.ask(id, state, 1, outcome, o);
+            }
+            (CoordinatorPhase::Deciding(commit), TxnResponse::Done) => {
+                if state.awaiting.is_empty() {
+                    state.phase = CoordinatorPhase::Done(commit);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub enum TxnModelActor {
+    Coordinator(Coordinator),
+    Server(SmrActor<Ledger>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum TxnModelState {
+    Coordinator(CoordinatorState),
+    Server(SmrState<Ledger>),
+}
+
+impl Actor for TxnModelActor {
+    type Msg = SmrMsg<TxnCommand, TxnResponse>;
+    type State = TxnModelState;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        match self {
+            TxnModelActor::Coordinator(coordinator) => {
+                if coordinator.crashes {
+                    // fires at an arbitrary point, which is when the crash happens
+                    o.set_timer(Duration::from_secs(1)..Duration::from_secs(2));
+                }
+                TxnModelState::Coordinator(coordinator.start(id, 0, o))
+            }
+            TxnModelActor::Server(server) => TxnModelState::Server(server.on_start(id, o)),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match (self, state.as_ref()) {
+            (TxnModelActor::Coordinator(coordinator), TxnModelState::Coordinator(inner)) => {
+                let (request_id, response) = match msg {
+                    SmrMsg::Response(request_id, response) => (request_id, response),
+                    _ => return,
+                };
+                let mut inner = Cow::Borrowed(inner);
+                coordinator.on_response(id, &mut inner, request_id, response, o);
+                if let Cow::Owned(inner) = inner {
+                    *state = Cow::Owned(TxnModelState::Coordinator(inner));
+                }
+            }
+            (TxnModelActor::Server(server), TxnModelState::Server(inner)) => {
+                let mut inner = Cow::Borrowed(inner);
+                server.on_msg(id, &mut inner, src, msg, o);
+                if let Cow::Owned(inner) = inner {
+                    *state = Cow::Owned(TxnModelState::Server(inner));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_timeout(&self, id: Id, state: &mut Cow<Self::State>, o: &mut Out<Self>) {
+        match (self, state.as_ref()) {
+            (TxnModelActor::Coordinator(coordinator), TxnModelState::Coordinator(inner)) => {
+                // everything in flight is forgotten, and the restart recovers from the shards
+                let restarted = coordinator.start(id, inner.incarnation + 1, o);
+                *state = Cow::Owned(TxnModelState::Coordinator(restarted));
+            }
+            (TxnModelActor::Server(server), TxnModelState::Server(inner)) => {
+                let mut inner = Cow::Borrowed(inner);
+                server.on_timeout(id, &mut inner, o);
+                if let Cow::Owned(inner) = inner {
+                    *state = Cow::Owned(TxnModelState::Server(inner));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// a transfer between an account on one shard and an account on another, with a coordinator
+// that may crash part way through
+#[derive(Clone)]
+pub struct TxnModelConfig {
+    // replicas in each of the two shards
+    server_count: usize,
+    network: NetworkMode,
+    coordinator_crashes: bool,
+    max_rounds: Option<u64>,
+}
+
+impl Default for TxnModelConfig {
+    fn default() -> Self {
+        TxnModelConfig {
+            server_count: 2,
+            network: NetworkMode::Unordered,
+            coordinator_crashes: true,
+            max_rounds: Some(2),
+        }
+    }
+}
+
+// the transaction the model runs, and how much it moves
+const TRANSFER: TxnId = 1;
+const AMOUNT: i64 = 5;
+
+impl TxnModelConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn server_count(mut self, server_count: usize) -> Self {
+        self.server_count = server_count;
+        self
+    }
+
+    pub fn network(mut self, network: NetworkMode) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn coordinator_crashes(mut self, coordinator_crashes: bool) -> Self {
+        self.coordinator_crashes = coordinator_crashes;
+        self
+    }
+
+    pub fn max_rounds(mut self, max_rounds: Option<u64>) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    fn shards(&self) -> ShardedPaxos {
+        ShardedPaxos::contiguous(2, self.server_count)
+    }
+
+    // an account on each shard, the transfer's source first
+    fn accounts(&self) -> (Key, Key) {
+        let shards = self.shards();
+        let on = |shard| {
+            (0..=Key::MAX)
+                .find(|key| shards.shard(u64::from(*key)) == shard)
+                .unwrap_or(0)
+        };
+        (on(0), on(1))
+    }
+
+    pub fn into_model(self) -> TxnModel {
+        let shards = self.shards();
+        let (from, to) = self.accounts();
+        let coordinator = Coordinator::new(
+            shards.clone(),
+            TRANSFER,
+            vec![(from, -AMOUNT), (to, AMOUNT)],
+        )
+        .crashes(self.coordinator_crashes);
+        ActorModel::new(self.clone(), ())
+            .actors((0..2 * self.server_count).map(|i| {
+                let paxos = PaxosActor::new(shards.peers(Id::from(i)));
+                TxnModelActor::Server(SmrActor::new(paxos))
+            }))
+            .actor(TxnModelActor::Coordinator(coordinator))
+            .duplicating_network(match self.network {
+                NetworkMode::UnorderedDuplicating => DuplicatingNetwork::Yes,
+                _ => DuplicatingNetwork::No,
+            })
+            .lossy_network(match self.network {
+                NetworkMode::Lossy => LossyNetwork::Yes,
+                _ => LossyNetwork::No,
+            })
+            .property(stateright::Expectation::Always, "atomic", |_, state| {
+                // no replica of any shard commits the transfer while one of another aborts it
+                let outcomes: BTreeSet<bool> = ledgers(state)
+                    .filter_map(|ledger| ledger.outcome(TRANSFER))
+                    .collect();
+                outcomes.len() <= 1
+            })
+            .property(
+                stateright::Expectation::Always,
+                "money conserved",
+                |model, state| {
+                    // once every replica has the outcome, whatever left the one account arrived
+                    // in the other; each shard has as many replicas, so their sum is zero
+                    let (from, to) = model.cfg.accounts();
+                    let settled = ledgers(state).all(|ledger| ledger.outcome(TRANSFER).is_some());
+                    !settled
+                        || ledgers(state)
+                            .map(|ledger| ledger.balance(from) + ledger.balance(to))
+                            .sum::<i64>()
+                            == 0
+                },
+            )
+            .property(
+                stateright::Expectation::Sometimes,
+                "committed",
+                |_, state| coordinator_outcome(state) == Some(true),
+            )
+            .property(stateright::Expectation::Sometimes, "aborted", |_, state| {
+                coordinator_outcome(state) == Some(false)
+            })
+            .within_boundary(|cfg, state| {
+                let rounds = cfg.max_rounds.map_or(true, |max| {
+                    state
+                        .actor_states
+                        .iter()
+                        .all(|actor_state| match actor_state.as_ref() {
+                            TxnModelState::Server(server_state) => {
+                                server_state.paxos().durable().round() <= max
+                            }
+                            TxnModelState::Coordinator(_) => true,
+                        })
+                });
+                if !rounds {
+                    crate::model::PRUNED.store(true, Ordering::Relaxed);
+                }
+                rounds
+            })
+    }
+}
+
+// every server's ledger, skipping the coordinator
+fn ledgers(state: &ActorModelState<TxnModelActor, ()>) -> impl Iterator<Item = &Ledger> {
+    state
+        .actor_states
+        .iter()
+        .filter_map(|actor_state| match actor_state.as_ref() {
+            TxnModelState::Server(server_state) => Some(server_state.machine()),
+            _ => None,
+        })
+}
+
+fn coordinator_outcome(state: &ActorModelState<TxnModelActor, ()>) -> Option<bool> {
+    state
+        .actor_states
+        .iter()
+        .find_map(|actor_state| match actor_state.as_ref() {
+            TxnModelState::Coordinator(coordinator) => coordinator.outcome(),
+            _ => None,
+        })
+}
+
+pub type TxnModel = ActorModel<TxnModelActor, TxnModelConfig, ()>;
@@ -0,0 +1,679 @@
+use crate::paxos::{Slot, Value};
+use serde::{Deserialize, Serialize};
+use stateright::actor::{register::*, Actor, Command as ActorCommand, Id, Out};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::marker::PhantomData;
+
+// the ballots the master hands out, one per configuration
+pub type Epoch = u32;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum Op<V> {
+    Put(V),
+    Get,
+    // fills a slot the previous configuration left empty
+    Noop,
+}
+
+// a client request replicated through the log, reads included so they are linearizable
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Entry<V> {
+    request_id: u64,
+    client: Id,
+    op: Op<V>,
+}
+
+impl<V> Entry<V> {
+    pub fn op(&self) -> &Op<V> {
+        &self.op
+    }
+
+    fn is_request(&self, other: &Entry<V>) -> bool {
+        self.request_id == other.request_id && self.client == other.client
+    }
+}
+
+// who replicates the log as of an epoch; the primary writes to every member, so reading from
+// any one of them sees everything that was chosen
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Config {
+    epoch: Epoch,
+    primary: Id,
+    members: Vec<Id>,
+}
+
+impl Config {
+    pub fn new(epoch: Epoch, members: Vec<Id>) -> Self {
+        Config {
+            epoch,
+            primary: members[0],
+            members,
+        }
+    }
+
+    pub fn epoch(&self) -> Epoch {
+        self.epoch
+    }
+
+    pub fn primary(&self) -> Id {
+        self.primary
+    }
+
+    pub fn members(&self) -> &[Id] {
+        &self.members
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum VerticalMsg<V> {
+    // model only: the master moves to its next configuration, delivered at an arbitrary point
+    Reconfigure,
+    // master to the next primary, which takes over from the previous members
+    NewEpoch(Config, Vec<Id>),
+    Prepare(Epoch),
+    Promise(Epoch, BTreeMap<Slot, (Epoch, Entry<V>)>),
+    // the next primary hands the log it read to every new member
+    NewState(Epoch, BTreeMap<Slot, Entry<V>>),
+    StateOk(Epoch),
+    // the next primary to the master, every new member holds the log
+    Complete(Epoch),
+    // master to every replica, old and new, once a configuration takes over
+    Activated(Config),
+    Accept(Epoch, Slot, Entry<V>),
+    Accepted(Epoch, Slot),
+    Commit(Slot, Entry<V>),
+    Forward(Entry<V>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct MasterState {
+    active: Config,
+    // the configuration taking over, until its primary reports the state transfer complete
+    next: Option<Config>,
+}
+
+impl MasterState {
+    pub fn active(&self) -> &Config {
+        &self.active
+    }
+}
+
+// Vertical Paxos' configuration master: it alone picks each epoch's members and primary, so
+// replicas never run phase 1 against one another, only a new primary against the previous
+// members. It is assumed not to fail.
+pub struct ConfigMaster<V> {
+    members: Vec<Id>,
+    // the members to move to at some point during the run
+    reconfigure: Option<Vec<Id>>,
+    _value: PhantomData<V>,
+}
+
+impl<V: Value> ConfigMaster<V> {
+    pub fn new(members: Vec<Id>) -> Self {
+        ConfigMaster {
+            members,
+            reconfigure: None,
+            _value: PhantomData,
+        }
+    }
+
+    pub fn reconfigure(mut self, members: Option<Vec<Id>>) -> Self {
+        self.reconfigure = members;
+        self
+    }
+}
+
+impl<V: Value> Actor for ConfigMaster<V> {
+    type Msg = RegisterMsg<u64, V, VerticalMsg<V>>;
+    type State = MasterState;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        if self.reconfigure.is_some() {
+            o.send(id, RegisterMsg::Internal(VerticalMsg::Reconfigure));
+        }
+        MasterState {
+            active: Config::new(0, self.members.clone()),
+            next: None,
+        }
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match msg {
+            RegisterMsg::Internal(VerticalMsg::Reconfigure) if src == id => {
+                let members = match &self.reconfigure {
+                    Some(members) if state.next.is_none() => members.clone(),
+                    _ => return,
+                };
+                let state = state.to_mut();
+                let next = Config::new(state.active.epoch + 1, members);
+                let msg = VerticalMsg::NewEpoch(next.clone(), state.active.members.clone());
+                o.send(next.primary, RegisterMsg::Internal(msg));
+                state.next = Some(next);
+            }
+            RegisterMsg::Internal(VerticalMsg::Complete(epoch)) => {
+                let next = match &state.next {
+                    Some(next) if next.epoch == epoch && next.primary == src => next.clone(),
+                    _ => return,
+                };
+                // the members dropped have to hear too, they forward what they were holding
+                let mut everyone: BTreeSet<Id> = state.active.members.iter().copied().collect();
+                everyone.extend(next.members.iter().copied());
+                let everyone: Vec<Id> = everyone.into_iter().collect();
+                o.broadcast(
+                    &everyone,
+                    &RegisterMsg::Internal(VerticalMsg::Activated(next.clone())),
+                );
+                let state = state.to_mut();
+                state.active = next;
+                state.next = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+// a new primary's progress towards taking over
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Takeover<V> {
+    config: Config,
+    previous: Vec<Id>,
+    // what the previous configuration may have chosen, once one of its members has answered
+    log: Option<BTreeMap<Slot, Entry<V>>>,
+    // new members holding that log
+    acks: BTreeSet<Id>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct ReplicaState<V> {
+    id: Id,
+    // the highest epoch this replica has heard of from a primary, it accepts nothing older
+    promised: Epoch,
+    // the latest configuration the master activated
+    config: Config,
+    accepted: BTreeMap<Slot, (Epoch, Entry<V>)>,
+    log: BTreeMap<Slot, Entry<V>>,
+    applied: Slot,
+    register: V,
+    // as primary, slots in flight and the members that accepted them
+    proposals: BTreeMap<Slot, (Entry<V>, BTreeSet<Id>)>,
+    next_slot: Slot,
+    // slots whose client this replica answers once they are applied
+    answering: BTreeSet<Slot>,
+    takeover: Option<Takeover<V>>,
+    // requests waiting for the configuration this replica is taking over
+    pending: Vec<Entry<V>>,
+    // client requests already taken on, so redelivered requests are not served twice
+    handled: BTreeSet<(Id, u64)>,
+}
+
+impl<V: Value> ReplicaState<V> {
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn log(&self) -> &BTreeMap<Slot, Entry<V>> {
+        &self.log
+    }
+
+    fn is_primary(&self) -> bool {
+        self.config.primary == self.id && self.takeover.is_none()
+    }
+
+    // whether a request is already somewhere in the log or on its way into it
+    fn knows(&self, entry: &Entry<V>) -> bool {
+        self.log.values().any(|logged| logged.is_request(entry))
+            || self
+                .proposals
+                .values()
+                .any(|(proposed, _)| proposed.is_request(entry))
+            || self.pending.iter().any(|pending| pending.is_request(entry))
+    }
+}
+
+// a replica under Vertical Paxos, in primary-backup style: the primary writes every slot to all
+// members, so a write quorum is the whole configuration and a read quorum any single member.
+// Only a primary taking over reads, from the previous configuration, before it writes what it
+// read to the new one and tells the master.
+pub struct VerticalPaxosActor<V> {
+    master: Id,
+    // the configuration of epoch 0, as the master starts with it
+    members: Vec<Id>,
+    _value: PhantomData<V>,
+}
+
+impl<V: Value> VerticalPaxosActor<V> {
+    pub fn new(master: Id, members: Vec<Id>) -> Self {
+        VerticalPaxosActor {
+            master,
+            members,
+            _value: PhantomData,
+        }
+    }
+
+    fn others(state: &ReplicaState<V>, members: &[Id]) -> Vec<Id> {
+        members
+            .iter()
+            .copied()
+            .filter(|member| *member != state.id)
+            .collect()
+    }
+
+    // proposes a request if we are the primary, otherwise hands it to whoever is
+    fn submit(&self, state: &mut ReplicaState<V>, entry: Entry<V>, o: &mut Out<Self>) {
+        if state.takeover.is_some() {
+            if !state.knows(&entry) {
+                state.pending.push(entry);
+            }
+            return;
+        }
+        if state.config.primary != state.id {
+            let msg = RegisterMsg::Internal(VerticalMsg::Forward(entry));
+            o.send(state.config.primary, msg);
+            return;
+        }
+        if state.knows(&entry) {
+            return;
+        }
+        let slot = state.next_slot;
+        state.next_slot += 1;
+        let epoch = state.config.epoch;
+        state.accepted.insert(slot, (epoch, entry.clone()));
+        state
+            .proposals
+            .insert(slot, (entry.clone(), BTreeSet::from([state.id])));
+        state.answering.insert(slot);
+        let msg = RegisterMsg::Internal(VerticalMsg::Accept(epoch, slot, entry));
+        o.broadcast(&Self::others(state, &state.config.members), &msg);
+        self.decide(state, slot, o);
+    }
+
+    // a slot is chosen once every member has accepted it
+    fn decide(&self, state: &mut ReplicaState<V>, slot: Slot, o: &mut Out<Self>) {
+        let entry = match state.proposals.get(&slot) {
+            Some((entry, acks)) if state.config.members.iter().all(|m| acks.contains(m)) => {
+                entry.clone()
+            }
+            _ => return,
+        };
+        state.proposals.remove(&slot);
+        let msg = RegisterMsg::Internal(VerticalMsg::Commit(slot, entry.clone()));
+        o.broadcast(&Self::others(state, &state.config.members), &msg);
+        state.log.insert(slot, entry);
+        self.apply(state, o);
+    }
+
+    fn apply(&self, state: &mut ReplicaState<V>, o: &mut Out<Self>) {
+        while let Some(entry) = state.log.get(&state.applied).cloned() {
+            let slot = state.applied;
+            state.applied += 1;
+            if let Op::Put(value) = &entry.op {
+                state.register = value.clone();
+            }
+            if !state.answering.remove(&slot) {
+                continue;
+            }
+            match entry.op {
+                Op::Put(_) => o.send(entry.client, RegisterMsg::PutOk(entry.request_id)),
+                Op::Get => o.send(
+                    entry.client,
+                    RegisterMsg::GetOk(entry.request_id, state.register.clone()),
+                ),
+                Op::Noop => {}
+            }
+        }
+    }
+
+    // adopts what a member of the previous configuration accepted, filling the gaps with no-ops,
+    // and writes it to the new members
+    fn adopt(
+        &self,
+        state: &mut ReplicaState<V>,
+        accepted: BTreeMap<Slot, (Epoch, Entry<V>)>,
+        o: &mut Out<Self>,
+    ) {
+        let takeover = match &mut state.takeover {
+            Some(takeover) if takeover.log.is_none() => takeover,
+            _ => return,
+        };
+        let end = accepted.keys().next_back().map_or(0, |slot| slot + 1);
+        let log: BTreeMap<Slot, Entry<V>> = (0..end)
+            .map(|slot| {
+                let entry = match accepted.get(&slot) {
+                    Some((_, entry)) => entry.clone(),
+                    None => Entry {
+                        request_id: 0,
+                        client: state.id,
+                        op: Op::Noop,
+                    },
+                };
+                (slot, entry)
+            })
+            .collect();
+        takeover.log = Some(log.clone());
+        takeover.acks.insert(state.id);
+        let epoch = takeover.config.epoch;
+        let members = takeover.config.members.clone();
+        for (slot, entry) in &log {
+            state.accepted.insert(*slot, (epoch, entry.clone()));
+        }
+        let msg = RegisterMsg::Internal(VerticalMsg::NewState(epoch, log));
+        o.broadcast(&Self::others(state, &members), &msg);
+        self.complete(state, o);
+    }
+
+    // tells the master once every new member holds the log
+    fn complete(&self, state: &ReplicaState<V>, o: &mut Out<Self>) {
+        if let Some(takeover) = &state.takeover {
+            let written = takeover
+                .config
+                .members
+                .iter()
+                .all(|member| takeover.acks.contains(member));
+            if takeover.log.is_some() && written {
+                let msg = VerticalMsg::Complete(takeover.config.epoch);
+                o.send(self.master, RegisterMsg::Internal(msg));
+            }
+        }
+    }
+
+    fn activate(&self, state: &mut ReplicaState<V>, config: Config, o: &mut Out<Self>) {
+        let stranded: Vec<Entry<V>> = std::mem::take(&mut state.proposals)
+            .into_values()
+            .map(|(entry, _)| entry)
+            .chain(std::mem::take(&mut state.pending))
+            .collect();
+        state.answering.clear();
+        state.config = config;
+        state.promised = state.promised.max(state.config.epoch);
+        let takeover = match state.takeover.take() {
+            Some(takeover) if takeover.config.epoch == state.config.epoch => takeover,
+            _ => {
+                // whatever we were proposing goes to the new primary, which drops duplicates
+                for entry in stranded {
+                    self.submit(state, entry, o);
+                }
+                return;
+            }
+        };
+        // everything the takeover read is chosen now, and whatever is not applied yet is answered
+        // again since we cannot tell what the previous primary already answered. Members that
+        // are new to the log hear of every slot.
+        let log = takeover.log.unwrap_or_default();
+        state.next_slot = log.keys().next_back().map_or(0, |slot| slot + 1);
+        let others = Self::others(state, &state.config.members);
+        for (slot, entry) in log {
+            if slot >= state.applied && entry.op != Op::Noop {
+                state.answering.insert(slot);
+            }
+            let msg = RegisterMsg::Internal(VerticalMsg::Commit(slot, entry.clone()));
+            o.broadcast(&others, &msg);
+            state.log.entry(slot).or_insert(entry);
+        }
+        self.apply(state, o);
+        for entry in stranded {
+            self.submit(state, entry, o);
+        }
+    }
+}
+
+impl<V: Value> Actor for VerticalPaxosActor<V> {
+    type Msg = RegisterMsg<u64, V, VerticalMsg<V>>;
+    type State = ReplicaState<V>;
+
+    fn on_start(&self, id: Id, _o: &mut Out<Self>) -> Self::State {
+        ReplicaState {
+            id,
+            promised: 0,
+            config: Config::new(0, self.members.clone()),
+            accepted: BTreeMap::new(),
+            log: BTreeMap::new(),
+            applied: 0,
+            register: V::default(),
+            proposals: BTreeMap::new(),
+            next_slot: 0,
+            answering: BTreeSet::new(),
+            takeover: None,
+            pending: Vec::new(),
+            handled: BTreeSet::new(),
+        }
+    }
+
+    fn on_msg(
+        &self,
+        _: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        let internal_msg = match msg {
+            RegisterMsg::Internal(internal_msg) => internal_msg,
+            RegisterMsg::Put(request_id, value) => {
+                if state.handled.contains(&(src, request_id)) {
+                    return;
+                }
+                let state = state.to_mut();
+                state.handled.insert((src, request_id));
+                let entry = Entry {
+                    request_id,
+                    client: src,
+                    op: Op::Put(value),
+                };
+                return self.submit(state, entry, o);
+            }
+            RegisterMsg::Get(request_id) => {
+                if state.handled.contains(&(src, request_id)) {
+                    return;
+                }
+                let state = state.to_mut();
+                state.handled.insert((src, request_id));
+                let entry = Entry {
+                    request_id,
+                    client: src,
+                    op: Op::Get,
+                };
+                return self.submit(state, entry, o);
+            }
+            _ => return,
+        };
+        match internal_msg {
+            VerticalMsg::NewEpoch(config, previous) => {
+                if src != self.master || config.epoch <= state.promised {
+                    return;
+                }
+                let state = state.to_mut();
+                state.promised = config.epoch;
+                let epoch = config.epoch;
+                state.takeover = Some(Takeover {
+                    config,
+                    previous: previous.clone(),
+                    log: None,
+                    acks: BTreeSet::new(),
+                });
+                // a single member of the previous configuration is a read quorum, ourselves
+                // included
+                if previous.contains(&state.id) {
+                    let accepted = state.accepted.clone();
+                    self.adopt(state, accepted, o);
+                } else {
+                    let msg = RegisterMsg::Internal(VerticalMsg::Prepare(epoch));
+                    o.broadcast(&previous, &msg);
+                }
+            }
+            VerticalMsg::Prepare(epoch) => {
+                if epoch < state.promised {
+                    return;
+                }
+                let state = state.to_mut();
+                state.promised = epoch;
+                let msg = VerticalMsg::Promise(epoch, state.accepted.clone());
+                o.send(src, RegisterMsg::Internal(msg));
+            }
+            VerticalMsg::Promise(epoch, accepted) => {
+                let reading = match &state.takeover {
+                    Some(takeover) => {
+                        takeover.config.epoch == epoch
+                            && takeover.log.is_none()
+                            && takeover.previous.contains(&src)
+                    }
+                    None => false,
+                };
+                if reading {
+                    self.adopt(state.to_mut(), accepted, o);
+                }
+            }
+            VerticalMsg::NewState(epoch, log) => {
+                if epoch < state.promised {
+                    return;
+                }
+                let state = state.to_mut();
+                state.promised = epoch;
+                for (slot, entry) in log {
+                    state.accepted.insert(slot, (epoch, entry));
+                }
+                o.send(src, RegisterMsg::Internal(VerticalMsg::StateOk(epoch)));
+            }
+            VerticalMsg::StateOk(epoch) => {
+                let writing = match &state.takeover {
+                    Some(takeover) => takeover.config.epoch == epoch,
+                    None => false,
+                };
+                if !writing {
+                    return;
+                }
+                let state = state.to_mut();
+                if let Some(takeover) = &mut state.takeover {
+                    takeover.acks.insert(src);
+                }
+                self.complete(state, o);
+            }
+            VerticalMsg::Activated(config) => {
+                if src != self.master || config.epoch <= state.config.epoch {
+                    return;
+                }
+                self.activate(state.to_mut(), config, o);
+            }
+            VerticalMsg::Accept(epoch, slot, entry) => {
+                // a primary the master has since replaced gets nowhere
+                if epoch < state.promised {
+                    return;
+                }
+                let state = state.to_mut();
+                state.promised = epoch;
+                state.accepted.insert(slot, (epoch, entry));
+                o.send(
+                    src,
+                    RegisterMsg::Internal(VerticalMsg::Accepted(epoch, slot)),
+                );
+            }
+            VerticalMsg::Accepted(epoch, slot) => {
+                if !state.is_primary() || epoch != state.config.epoch {
+                    return;
+                }
+                let state = state.to_mut();
+                if let Some((_, acks)) = state.proposals.get_mut(&slot) {
+                    acks.insert(src);
+                }
+                self.decide(state, slot, o);
+            }
+            VerticalMsg::Commit(slot, entry) => {
+                if state.log.contains_key(&slot) {
+                    return;
+                }
+                let state = state.to_mut();
+                state.log.insert(slot, entry);
+                self.apply(state, o);
+            }
+            VerticalMsg::Forward(entry) => {
+                self.submit(state.to_mut(), entry, o);
+            }
+            VerticalMsg::Reconfigure | VerticalMsg::Complete(_) => {}
+        }
+    }
+}
+
+// the master and the replicas have to share an actor type in the model
+pub enum VerticalActor<V> {
+    Master(ConfigMaster<V>),
+    Replica(VerticalPaxosActor<V>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum VerticalActorState<V> {
+    Master(MasterState),
+    Replica(ReplicaState<V>),
+}
+
+impl<V: Value> VerticalActor<V> {
+    // both speak the same messages, only the type of the output differs
+    fn forward<A>(inner: Out<A>, o: &mut Out<Self>)
+    where
+        A: Actor<Msg = RegisterMsg<u64, V, VerticalMsg<V>>>,
+    {
+        for command in inner {
+            match command {
+                ActorCommand::Send(dst, msg) => o.send(dst, msg),
+                ActorCommand::SetTimer(duration) => o.set_timer(duration),
+                ActorCommand::CancelTimer => o.cancel_timer(),
+            }
+        }
+    }
+}
+
+impl<V: Value> Actor for VerticalActor<V> {
+    type Msg = RegisterMsg<u64, V, VerticalMsg<V>>;
+    type State = VerticalActorState<V>;
+
+    fn on_start(&self, id: Id, o: &mut Out<Self>) -> Self::State {
+        let mut inner = Out::new();
+        let state = match self {
+            VerticalActor::Master(master) => {
+                VerticalActorState::Master(master.on_start(id, &mut inner))
+            }
+            VerticalActor::Replica(replica) => {
+                VerticalActorState::Replica(replica.on_start(id, &mut inner))
+            }
+        };
+        Self::forward(inner, o);
+        state
+    }
+
+    fn on_msg(
+        &self,
+        id: Id,
+        state: &mut Cow<Self::State>,
+        src: Id,
+        msg: Self::Msg,
+        o: &mut Out<Self>,
+    ) {
+        match (self, state.as_ref()) {
+            (VerticalActor::Master(master), VerticalActorState::Master(master_state)) => {
+                let mut master_state = Cow::Borrowed(master_state);
+                let mut inner = Out::new();
+                master.on_msg(id, &mut master_state, src, msg, &mut inner);
+                if let Cow::Owned(master_state) = master_state {
+                    *state = Cow::Owned(VerticalActorState::Master(master_state));
+                }
+                Self::forward(inner, o);
+            }
+            (VerticalActor::Replica(replica), VerticalActorState::Replica(replica_state)) => {
+                let mut replica_state = Cow::Borrowed(replica_state);
+                let mut inner = Out::new();
+                replica.on_msg(id, &mut replica_state, src, msg, &mut inner);
+                if let Cow::Owned(replica_state) = replica_state {
+                    *state = Cow::Owned(VerticalActorState::Replica(replica_state));
+                }
+                Self::forward(inner, o);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,663 @@
+use crate::chunking::Chunked;
+use crate::features::Features;
+use crate::paxos::{Applied, Command, PaxosMsg, RoundIdentifier, Session};
+use prost::Message;
+use stateright::actor::register::RegisterMsg;
+use stateright::actor::Id;
+use std::io;
+
+pub mod proto {
+    include!(concat!(env!("OUT_DIR"), "/paxos.wire.rs"));
+}
+
+use proto::{command, envelope, internal};
+
+// the schema version this build writes, see proto/wire.proto for what each one added
+pub const VERSION: u32 = 3;
+// the oldest version still read, envelopes from anything older are turned away
+pub const MIN_VERSION: u32 = 1;
+
+// what spawned replicas send each other, and what clients send them
+pub type ReplicaMsg = RegisterMsg<u64, char, Chunked<PaxosMsg<char>>>;
+pub type ClientMsg = RegisterMsg<u64, char, PaxosMsg<char>>;
+
+// messages that have a protobuf encoding
+pub trait Protobuf: Sized {
+    fn to_envelope(&self) -> proto::Envelope;
+    // `None` for a message or case added in a later version, which is dropped like a lost one
+    fn from_envelope(envelope: proto::Envelope) -> io::Result<Option<Self>>;
+}
+
+pub fn encode<M: Protobuf>(msg: &M) -> io::Result<Vec<u8>> {
+    Ok(msg.to_envelope().encode_to_vec())
+}
+
+// Fields a later version added are skipped by the decoder, so a newer peer is read as far as
+// this version understands it; only envelopes older than `MIN_VERSION` are an error.
+pub fn decode<M: Protobuf>(bytes: &[u8]) -> io::Result<Option<M>> {
+    let envelope = proto::Envelope::decode(bytes).map_err(invalid_data)?;
+    if envelope.version < MIN_VERSION {
+        let msg = format!(
+            "wire version {} is older than {}, the oldest this replica reads",
+            envelope.version, MIN_VERSION
+        );
+        return Err(invalid_data(msg));
+    }
+    if envelope.version > VERSION {
+        tracing::trace!(version = envelope.version, "reading a newer envelope");
+    }
+    M::from_envelope(envelope)
+}
+
+impl Protobuf for ReplicaMsg {
+    fn to_envelope(&self) -> proto::Envelope {
+        register_to_proto(self, |chunked| {
+            let msg = match chunked {
+                Chunked::Whole(msg) => internal::Msg::Whole(paxos_to_proto(msg)),
+                Chunked::Chunk(hash, index, count, data) => internal::Msg::Chunk(proto::Chunk {
+                    hash: *hash,
+                    index: *index,
+                    count: *count,
+                    data: data.clone(),
+                }),
+                Chunked::ChunkAck(hash, index) => internal::Msg::ChunkAck(proto::ChunkAck {
+                    hash: *hash,
+                    index: *index,
+                }),
+            };
+            proto::Internal { msg: Some(msg) }
+        })
+    }
+
+    fn from_envelope(envelope: proto::Envelope) -> io::Result<Option<Self>> {
+        readable(register_from_proto(envelope, |msg| match msg {
+            internal::Msg::Whole(msg) => paxos_from_proto(msg).map(Chunked::Whole),
+            internal::Msg::Chunk(chunk) => Ok(Chunked::Chunk(
+                chunk.hash,
+                chunk.index,
+                chunk.count,
+                chunk.data,
+            )),
+            internal::Msg::ChunkAck(ack) => Ok(Chunked::ChunkAck(ack.hash, ack.index)),
+        }))
+    }
+}
+
+impl Protobuf for ClientMsg {
+    fn to_envelope(&self) -> proto::Envelope {
+        register_to_proto(self, |msg| proto::Internal {
+            msg: Some(internal::Msg::Whole(paxos_to_proto(msg))),
+        })
+    }
+
+    // replicas never send clients their chunks
+    fn from_envelope(envelope: proto::Envelope) -> io::Result<Option<Self>> {
+        readable(register_from_proto(envelope, |msg| match msg {
+            internal::Msg::Whole(msg) => paxos_from_proto(msg),
+            internal::Msg::Chunk(_) | internal::Msg::ChunkAck(_) => Err(Unreadable::Unknown),
+        }))
+    }
+}
+
+// why an envelope did not come out as a message
+enum Unreadable {
+    // something a later version added, which its sender cannot count on being understood
+    Unknown,
+    Malformed(String),
+}
+
+type Decoded<T> = Result<T, Unreadable>;
+
+fn malformed<T>(what: impl Into<String>) -> Decoded<T> {
+    Err(Unreadable::Malformed(what.into()))
+}
+
+fn readable<T>(decoded: Decoded<T>) -> io::Result<Option<T>> {
+    match decoded {
+        Ok(msg) => Ok(Some(msg)),
+        Err(Unreadable::Unknown) => Ok(None),
+        Err(Unreadable::Malformed(what)) => Err(invalid_data(what)),
+    }
+}
+
+fn invalid_data(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn register_to_proto<I>(
+    msg: &RegisterMsg<u64, char, I>,
+    internal: impl FnOnce(&I) -> proto::Internal,
+) -> proto::Envelope {
+    let msg = match msg {
+        RegisterMsg::Put(request_id, value) => envelope::Msg::Put(proto::Put {
+            request_id: *request_id,
+            value: value.to_string(),
+        }),
+        RegisterMsg::Get(request_id) => envelope::Msg::Get(proto::Get {
+            request_id: *request_id,
+        }),
+        RegisterMsg::PutOk(request_id) => envelope::Msg::PutOk(proto::PutOk {
+            request_id: *request_id,
+        }),
+        RegisterMsg::GetOk(request_id, value) => envelope::Msg::GetOk(proto::GetOk {
+            request_id: *request_id,
+            value: value.to_string(),
+        }),
+        RegisterMsg::Internal(msg) => envelope::Msg::Internal(internal(msg)),
+    };
+    proto::Envelope {
+        version: VERSION,
+        msg: Some(msg),
+    }
+}
+
+fn register_from_proto<I>(
+    envelope: proto::Envelope,
+    internal: impl FnOnce(internal::Msg) -> Decoded<I>,
+) -> Decoded<RegisterMsg<u64, char, I>> {
+    match envelope.msg {
+        Some(envelope::Msg::Put(put)) => Ok(RegisterMsg::Put(
+            put.request_id,
+            value_from_proto(&put.value)?,
+        )),
+        Some(envelope::Msg::Get(get)) => Ok(RegisterMsg::Get(get.request_id)),
+        Some(envelope::Msg::PutOk(ok)) => Ok(RegisterMsg::PutOk(ok.request_id)),
+        Some(envelope::Msg::GetOk(ok)) => Ok(RegisterMsg::GetOk(
+            ok.request_id,
+            value_from_proto(&ok.value)?,
+        )),
+        Some(envelope::Msg::Internal(proto::Internal { msg: Some(msg) })) => {
+            internal(msg).map(RegisterMsg::Internal)
+        }
+        Some(envelope::Msg::Internal(_)) | None => Err(Unreadable::Unknown),
+    }
+}
+
+fn value_from_proto(value: &str) -> Decoded<char> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(value), None) => Ok(value),
+        _ => malformed(format!("{:?} is not a single character", value)),
+    }
+}
+
+fn id_to_proto(id: Id) -> u64 {
+    usize::from(id) as u64
+}
+
+fn id_from_proto(id: u64) -> Id {
+    Id::from(id as usize)
+}
+
+fn ids_to_proto(ids: &[Id]) -> Vec<u64> {
+    ids.iter().copied().map(id_to_proto).collect()
+}
+
+fn ids_from_proto(ids: Vec<u64>) -> Vec<Id> {
+    ids.into_iter().map(id_from_proto).collect()
+}
+
+fn round_to_proto(round: &RoundIdentifier) -> Option<proto::Round> {
+    Some(proto::Round {
+        round_num: round.round_num(),
+        id: id_to_proto(round.id()),
+    })
+}
+
+fn round_from_proto(round: Option<proto::Round>) -> Decoded<RoundIdentifier> {
+    match round {
+        Some(round) => Ok(RoundIdentifier::new(
+            round.round_num,
+            id_from_proto(round.id),
+        )),
+        None => malformed("a round is missing"),
+    }
+}
+
+fn command_to_proto(command: &Command<char>) -> Option<proto::Command> {
+    let command = match command {
+        Command::Put(value) => command::Command::Put(value.to_string()),
+        Command::SessionPut(session, value) => command::Command::SessionPut(proto::SessionPut {
+            session: Some(proto::Session {
+                client: id_to_proto(session.client()),
+                seq: session.seq(),
+            }),
+            value: value.to_string(),
+        }),
+        Command::Batch(values) => command::Command::Batch(proto::Values {
+            values: values.iter().map(char::to_string).collect(),
+        }),
+        Command::Reconfigure(ids) => command::Command::Reconfigure(proto::Ids {
+            ids: ids_to_proto(ids),
+        }),
+    };
+    Some(proto::Command {
+        command: Some(command),
+    })
+}
+
+fn command_from_proto(command: Option<proto::Command>) -> Decoded<Command<char>> {
+    let command = match command {
+        Some(command) => command.command,
+        None => return malformed("a command is missing"),
+    };
+    match command {
+        Some(command::Command::Put(value)) => Ok(Command::Put(value_from_proto(&value)?)),
+        Some(command::Command::SessionPut(put)) => {
+            let session = match put.session {
+                Some(session) => Session::new(id_from_proto(session.client), session.seq),
+                None => return malformed("a session put has no session"),
+            };
+            Ok(Command::SessionPut(session, value_from_proto(&put.value)?))
+        }
+        Some(command::Command::Batch(batch)) => batch
+            .values
+            .iter()
+            .map(|value| value_from_proto(value))
+            .collect::<Decoded<_>>()
+            .map(Command::Batch),
+        Some(command::Command::Reconfigure(ids)) => {
+            Ok(Command::Reconfigure(ids_from_proto(ids.ids)))
+        }
+        // a kind of command added later, which this replica could not apply anyway
+        None => Err(Unreadable::Unknown),
+    }
+}
+
+fn vote_to_proto(round: &RoundIdentifier, command: &Command<char>) -> proto::Vote {
+    proto::Vote {
+        round: round_to_proto(round),
+        command: command_to_proto(command),
+    }
+}
+
+fn vote_from_proto(vote: proto::Vote) -> Decoded<(RoundIdentifier, Command<char>)> {
+    Ok((
+        round_from_proto(vote.round)?,
+        command_from_proto(vote.command)?,
+    ))
+}
+
+fn applied_to_proto(applied: &Applied<char>) -> proto::Applied {
+    proto::Applied {
+        next: applied.next(),
+        value: applied.value().to_string(),
+        members: applied.members().map(|(slot, ids)| proto::Members {
+            slot: *slot,
+            ids: ids_to_proto(ids),
+        }),
+        sessions: applied
+            .sessions()
+            .iter()
+            .map(|(client, seq)| (id_to_proto(*client), *seq))
+            .collect(),
+    }
+}
+
+fn applied_from_proto(applied: proto::Applied) -> Decoded<Applied<char>> {
+    Ok(Applied::from_parts(
+        applied.next,
+        value_from_proto(&applied.value)?,
+        applied
+            .members
+            .map(|members| (members.slot, ids_from_proto(members.ids))),
+        applied
+            .sessions
+            .into_iter()
+            .map(|(client, seq)| (id_from_proto(client), seq))
+            .collect(),
+    ))
+}
+
+fn paxos_to_proto(msg: &PaxosMsg<char>) -> proto::Paxos {
+    use proto::paxos::Msg;
+    let msg = match msg {
+        PaxosMsg::Prepare(request_id, client, slot, round) => Msg::Prepare(proto::Prepare {
+            request_id: *request_id,
+            client: id_to_proto(*client),
+            slot: *slot,
+            round: round_to_proto(round),
+        }),
+        PaxosMsg::Promise(request_id, client, slot, round, accepted) => {
+            Msg::Promise(proto::Promise {
+                request_id: *request_id,
+                client: id_to_proto(*client),
+                slot: *slot,
+                round: round_to_proto(round),
+                accepted: accepted
+                    .as_ref()
+                    .map(|(round, command)| vote_to_proto(round, command)),
+            })
+        }
+        PaxosMsg::WitnessPromise(request_id, client, slot, round, witnessed) => {
+            Msg::WitnessPromise(proto::WitnessPromise {
+                request_id: *request_id,
+                client: id_to_proto(*client),
+                slot: *slot,
+                round: round_to_proto(round),
+                witnessed: witnessed.as_ref().and_then(round_to_proto),
+            })
+        }
+        PaxosMsg::Accept(request_id, client, slot, round, command) => Msg::Accept(proto::Accept {
+            request_id: *request_id,
+            client: id_to_proto(*client),
+            slot: *slot,
+            round: round_to_proto(round),
+            command: command_to_proto(command),
+        }),
+        PaxosMsg::Accepted(request_id, client, slot, round, command) => {
+            Msg::Accepted(proto::Accepted {
+                request_id: *request_id,
+                client: id_to_proto(*client),
+                slot: *slot,
+                round: round_to_proto(round),
+                command: command_to_proto(command),
+            })
+        }
+        PaxosMsg::Nack(request_id, slot, round) => Msg::Nack(proto::Nack {
+            request_id: *request_id,
+            slot: *slot,
+            round: round_to_proto(round),
+        }),
+        PaxosMsg::Expire(slot, round, seen) => Msg::Expire(proto::Expire {
+            slot: *slot,
+            round: round_to_proto(round),
+            seen: round_to_proto(seen),
+        }),
+        PaxosMsg::Decided(slot, round, command) => Msg::Decided(proto::Decided {
+            slot: *slot,
+            round: round_to_proto(round),
+            command: command_to_proto(command),
+        }),
+        PaxosMsg::Heartbeat(slot, round) => Msg::Heartbeat(proto::Heartbeat {
+            slot: *slot,
+            round: round_to_proto(round),
+        }),
+        PaxosMsg::Forward(request_id, client, command) => Msg::Forward(proto::Forward {
+            request_id: *request_id,
+            client: id_to_proto(*client),
+            command: command_to_proto(command),
+        }),
+        PaxosMsg::Reconfigure(request_id, members) => Msg::Reconfigure(proto::Reconfigure {
+            request_id: *request_id,
+            members: ids_to_proto(members),
+        }),
+        PaxosMsg::Crash => Msg::Crash(proto::Crash {}),
+        PaxosMsg::Split => Msg::Split(proto::Split {}),
+        PaxosMsg::Heal => Msg::Heal(proto::Heal {}),
+        PaxosMsg::Tick => Msg::Tick(proto::Tick {}),
+        PaxosMsg::Probe(slot, round) => Msg::Probe(proto::Probe {
+            slot: *slot,
+            round: round_to_proto(round),
+        }),
+        PaxosMsg::ProbeReply(slot, round, granted) => Msg::ProbeReply(proto::ProbeReply {
+            slot: *slot,
+            round: round_to_proto(round),
+            granted: *granted,
+        }),
+        PaxosMsg::Snapshot(applied) => Msg::Snapshot(applied_to_proto(applied)),
+        PaxosMsg::Read(request_id, slot) => Msg::Read(proto::Read {
+            request_id: *request_id,
+            slot: *slot,
+        }),
+        PaxosMsg::ReadReply(request_id, slot, decided, accepted) => {
+            Msg::ReadReply(proto::ReadReply {
+                request_id: *request_id,
+                slot: *slot,
+                decided: decided.map(|value| value.to_string()),
+                accepted: accepted.map(|(round, value)| proto::RoundValue {
+                    round: round_to_proto(&round),
+                    value: value.to_string(),
+                }),
+            })
+        }
+        PaxosMsg::LeaseRequest(round) => Msg::LeaseRequest(proto::LeaseRequest {
+            round: round_to_proto(round),
+        }),
+        PaxosMsg::LeaseGrant(round, slot) => Msg::LeaseGrant(proto::LeaseGrant {
+            round: round_to_proto(round),
+            slot: *slot,
+        }),
+        PaxosMsg::LeaseExpired(round) => Msg::LeaseExpired(proto::LeaseExpired {
+            round: round_to_proto(round),
+        }),
+        PaxosMsg::Suspect(round) => Msg::Suspect(proto::Suspect {
+            round: round_to_proto(round),
+        }),
+        PaxosMsg::Trust(round) => Msg::Trust(proto::Trust {
+            round: round_to_proto(round),
+        }),
+        PaxosMsg::CatchUpRequest(slot) => {
+            Msg::CatchUpRequest(proto::CatchUpRequest { slot: *slot })
+        }
+        PaxosMsg::CatchUpReply(entries) => Msg::CatchUpReply(proto::CatchUpReply {
+            entries: entries
+                .iter()
+                .map(|(slot, command)| proto::DecidedEntry {
+                    slot: *slot,
+                    command: command_to_proto(command),
+                })
+                .collect(),
+        }),
+        PaxosMsg::TransferLeadership(target) => {
+            Msg::TransferLeadership(proto::TransferLeadership {
+                target: id_to_proto(*target),
+            })
+        }
+        PaxosMsg::Handoff(round, slot, requests) => Msg::Handoff(proto::Handoff {
+            round: round_to_proto(round),
+            slot: *slot,
+            requests: requests
+                .iter()
+                .map(|(request_id, client, command)| proto::Forward {
+                    request_id: *request_id,
+                    client: id_to_proto(*client),
+                    command: command_to_proto(command),
+                })
+                .collect(),
+        }),
+        PaxosMsg::Hello(features) => Msg::Hello(proto::Hello {
+            features: features.bits(),
+        }),
+        PaxosMsg::Welcome(features) => Msg::Welcome(proto::Welcome {
+            features: features.bits(),
+        }),
+    };
+    proto::Paxos { msg: Some(msg) }
+}
+
+fn paxos_from_proto(msg: proto::Paxos) -> Decoded<PaxosMsg<char>> {
+    use proto::paxos::Msg;
+    let msg = match msg.msg {
+        Some(msg) => msg,
+        None => return Err(Unreadable::Unknown),
+    };
+    Ok(match msg {
+        Msg::Prepare(m) => PaxosMsg::Prepare(
+            m.request_id,
+            id_from_proto(m.client),
+            m.slot,
+            round_from_proto(m.round)?,
+        ),
+        Msg::Promise(m) => PaxosMsg::Promise(
+            m.request_id,
+            id_from_proto(m.client),
+            m.slot,
+            round_from_proto(m.round)?,
+            m.accepted.map(vote_from_proto).transpose()?,
+        ),
+        Msg::WitnessPromise(m) => PaxosMsg::WitnessPromise(
+            m.request_id,
+            id_from_proto(m.client),
+            m.slot,
+            round_from_proto(m.round)?,
+            m.witnessed
+                .map(|round| round_from_proto(Some(round)))
+                .transpose()?,
+        ),
+        Msg::Accept(m) => PaxosMsg::Accept(
+            m.request_id,
+            id_from_proto(m.client),
+            m.slot,
+            round_from_proto(m.round)?,
+            command_from_proto(m.command)?,
+        ),
+        Msg::Accepted(m) => PaxosMsg::Accepted(
+            m.request_id,
+            id_from_proto(m.client),
+            m.slot,
+            round_from_proto(m.round)?,
+            command_from_proto(m.command)?,
+        ),
+        Msg::Nack(m) => PaxosMsg::Nack(m.request_id, m.slot, round_from_proto(m.round)?),
+        Msg::Expire(m) => PaxosMsg::Expire(
+            m.slot,
+            round_from_proto(m.round)?,
+            round_from_proto(m.seen)?,
+        ),
+        Msg::Decided(m) => PaxosMsg::Decided(
+            m.slot,
+            round_from_proto(m.round)?,
+            command_from_proto(m.command)?,
+        ),
+        Msg::Heartbeat(m) => PaxosMsg::Heartbeat(m.slot, round_from_proto(m.round)?),
+        Msg::Forward(m) => PaxosMsg::Forward(
+            m.request_id,
+            id_from_proto(m.client),
+            command_from_proto(m.command)?,
+        ),
+        Msg::Reconfigure(m) => PaxosMsg::Reconfigure(m.request_id, ids_from_proto(m.members)),
+        Msg::Crash(_) => PaxosMsg::Crash,
+        Msg::Split(_) => PaxosMsg::Split,
+        Msg::Heal(_) => PaxosMsg::Heal,
+        Msg::Tick(_) => PaxosMsg::Tick,
+        Msg::Probe(m) => PaxosMsg::Probe(m.slot, round_from_proto(m.round)?),
+        Msg::ProbeReply(m) => PaxosMsg::ProbeReply(m.slot, round_from_proto(m.round)?, m.granted),
+        Msg::Snapshot(applied) => PaxosMsg::Snapshot(applied_from_proto(applied)?),
+        Msg::Read(m) => PaxosMsg::Read(m.request_id, m.slot),
+        Msg::ReadReply(m) => PaxosMsg::ReadReply(
+            m.request_id,
+            m.slot,
+            m.decided.as_deref().map(value_from_proto).transpose()?,
+            match m.accepted {
+                Some(accepted) => Some((
+                    round_from_proto(accepted.round)?,
+                    value_from_proto(&accepted.value)?,
+                )),
+                None => None,
+            },
+        ),
+        Msg::LeaseRequest(m) => PaxosMsg::LeaseRequest(round_from_proto(m.round)?),
+        Msg::LeaseGrant(m) => PaxosMsg::LeaseGrant(round_from_proto(m.round)?, m.slot),
+        Msg::LeaseExpired(m) => PaxosMsg::LeaseExpired(round_from_proto(m.round)?),
+        Msg::Suspect(m) => PaxosMsg::Suspect(round_from_proto(m.round)?),
+        Msg::Trust(m) => PaxosMsg::Trust(round_from_proto(m.round)?),
+        Msg::CatchUpRequest(m) => PaxosMsg::CatchUpRequest(m.slot),
+        Msg::CatchUpReply(m) => PaxosMsg::CatchUpReply(
+            m.entries
+                .into_iter()
+                .map(|entry| Ok((entry.slot, command_from_proto(entry.command)?)))
+                .collect::<Decoded<_>>()?,
+        ),
+        Msg::TransferLeadership(m) => PaxosMsg::TransferLeadership(id_from_proto(m.target)),
+        Msg::Handoff(m) => PaxosMsg::Handoff(
+            round_from_proto(m.round)?,
+            m.slot,
+            m.requests
+                .into_iter()
+                .map(|m| {
+                    Ok((
+                        m.request_id,
+                        id_from_proto(m.client),
+                        command_from_proto(m.command)?,
+                    ))
+                })
+                .collect::<Decoded<_>>()?,
+        ),
+        Msg::Hello(m) => PaxosMsg::Hello(Features::from_bits(m.features)),
+        Msg::Welcome(m) => PaxosMsg::Welcome(Features::from_bits(m.features)),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::paxos::RoundIdentifier;
+
+    // How a version 1 replica reads an envelope: the internal message could only be a whole
+    // paxos message, kept here as its bytes since only whether it is there matters. A oneof of
+    // one case is encoded just like an optional field, so this reads what version 1 did.
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct V1Envelope {
+        #[prost(uint32, tag = "1")]
+        version: u32,
+        #[prost(message, optional, tag = "6")]
+        internal: Option<V1Internal>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct V1Internal {
+        #[prost(bytes = "vec", optional, tag = "1")]
+        whole: Option<Vec<u8>>,
+    }
+
+    fn prepare() -> ReplicaMsg {
+        RegisterMsg::Internal(Chunked::Whole(PaxosMsg::Prepare(
+            7,
+            Id::from(3),
+            2,
+            RoundIdentifier::new(1, Id::from(1)),
+        )))
+    }
+
+    fn chunk() -> ReplicaMsg {
+        RegisterMsg::Internal(Chunked::Chunk(9, 0, 2, vec![1, 2, 3]))
+    }
+
+    #[test]
+    fn version_1_reads_what_it_knows_of_a_newer_envelope() {
+        let bytes = encode(&prepare()).unwrap();
+        let v1 = V1Envelope::decode(bytes.as_slice()).unwrap();
+        assert_eq!(v1.version, VERSION);
+        assert!(v1.internal.and_then(|internal| internal.whole).is_some());
+        // a chunk, which version 2 added, is skipped and leaves nothing it understands
+        let bytes = encode(&chunk()).unwrap();
+        let v1 = V1Envelope::decode(bytes.as_slice()).unwrap();
+        assert_eq!(v1.internal, Some(V1Internal { whole: None }));
+    }
+
+    #[test]
+    fn envelopes_from_older_versions_are_read() {
+        for version in MIN_VERSION..=VERSION {
+            let mut envelope = prepare().to_envelope();
+            envelope.version = version;
+            let decoded: Option<ReplicaMsg> = decode(&envelope.encode_to_vec()).unwrap();
+            assert_eq!(decoded, Some(prepare()), "version {}", version);
+        }
+    }
+
+    #[test]
+    fn fields_and_messages_from_later_versions_are_skipped() {
+        // an envelope from a later version, with a field this one has no number for
+        let mut envelope = prepare().to_envelope();
+        envelope.version = VERSION + 1;
+        let mut bytes = envelope.encode_to_vec();
+        // field 15 as a varint
+        bytes.extend_from_slice(&[15 << 3, 1]);
+        assert_eq!(decode::<ReplicaMsg>(&bytes).unwrap(), Some(prepare()));
+        // an internal message under a case number not yet used, field 9 of `Internal`
+        let bytes = [1 << 3, VERSION as u8 + 1, (6 << 3) | 2, 2, 9 << 3, 1];
+        assert_eq!(decode::<ReplicaMsg>(&bytes).unwrap(), None);
+        // and a chunk to a reader that has none, like a version 1 replica
+        let bytes = encode(&chunk()).unwrap();
+        assert_eq!(decode::<ClientMsg>(&bytes).unwrap(), None);
+    }
+
+    #[test]
+    fn envelopes_older_than_the_oldest_version_read_are_refused() {
+        let mut envelope = prepare().to_envelope();
+        envelope.version = MIN_VERSION - 1;
+        let error = decode::<ReplicaMsg>(&envelope.encode_to_vec()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}